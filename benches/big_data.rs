@@ -0,0 +1,66 @@
+//! Assembling a big data reply from the packets it arrives in
+//!
+//! Run with `cargo bench --bench big_data`
+
+use cole_mine::big_data::{BigDataPacket, BigDataState};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+
+/// Bytes of payload in the synthetic reply
+const PAYLOAD_LEN: usize = 16 * 1024;
+/// Bytes in each packet after the first, the ring's notification size
+const PACKET_LEN: usize = 20;
+
+/// A sleep reply with a `PAYLOAD_LEN` byte payload split into packets
+fn packets() -> Vec<Vec<u8>> {
+    let len = (PAYLOAD_LEN as u16).to_le_bytes();
+    let mut bytes = vec![0xbc, 0x27, len[0], len[1], 0, 0];
+    bytes.extend((0..PAYLOAD_LEN).map(|i| i as u8));
+    bytes.chunks(PACKET_LEN).map(<[u8]>::to_vec).collect()
+}
+
+fn assemble(packets: &[Vec<u8>]) -> BigDataPacket {
+    let mut state = BigDataState::new(&packets[0]).unwrap();
+    for packet in &packets[1..] {
+        state.step(packet).unwrap();
+    }
+    match state {
+        BigDataState::Complete(packet) => packet,
+        BigDataState::Partial { .. } => panic!("reply didn't complete"),
+    }
+}
+
+fn big_data(c: &mut Criterion) {
+    let packets = packets();
+    let mut group = c.benchmark_group("big_data");
+    group.throughput(Throughput::Bytes(PAYLOAD_LEN as u64));
+    group.bench_function("assemble_16k", |b| {
+        b.iter_batched(
+            || packets.clone(),
+            |packets| black_box(assemble(&packets)),
+            BatchSize::SmallInput,
+        )
+    });
+    // only the last packet, which completes the reply
+    let (last, rest) = packets.split_last().unwrap();
+    let partial = || {
+        let mut state = BigDataState::new(&rest[0]).unwrap();
+        for packet in &rest[1..] {
+            state.step(packet).unwrap();
+        }
+        state
+    };
+    group.bench_function("complete_16k", |b| {
+        b.iter_batched(
+            partial,
+            |mut state| {
+                state.step(last).unwrap();
+                black_box(state)
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, big_data);
+criterion_main!(benches);
@@ -1,21 +1,174 @@
 //! Http server
-use std::{fmt::Display, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::Infallible,
+    fmt::Display,
+    ops::Bound,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
 use axum::{
-    extract::{DefaultBodyLimit, Path, Query, State},
-    http::StatusCode,
-    response::Json,
+    body::Body,
+    extract::{
+        rejection::{JsonRejection, QueryRejection},
+        DefaultBodyLimit, FromRef, Path, Query, Request, State,
+    },
+    http::{
+        header::{AUTHORIZATION, CONTENT_DISPOSITION, CONTENT_TYPE, WWW_AUTHENTICATE},
+        HeaderMap, HeaderValue, Method, StatusCode,
+    },
+    middleware::{self, Next},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Json, Response,
+    },
     routing::{get, post},
     Router,
 };
-use fissure::{Database, Ring, RingEvent};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use cole_mine::analysis::{
+    goal_progress, hr_daily_stats, resting_hr, sleep_score, HrConfig, HrDailyStats, SleepScore,
+    SleepScoreConfig,
+};
+use cole_mine::big_data::SleepSession;
+use cole_mine::incoming_messages::CommandReply;
+use fissure::{
+    convert::SyncContext, DailySummary, Database, EventData, EventKind, Mac, Ring, RingEvent,
+    RingPatch, UpsertCounts, UpsertMode,
+};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use tower_http::{limit::RequestBodyLimitLayer, trace::TraceLayer};
+use serde_json::{value::RawValue, Value};
+use time::format_description::well_known::Rfc3339;
+use tokio::sync::broadcast;
+use tokio_stream::{
+    wrappers::{BroadcastStream, ReceiverStream},
+    Stream, StreamExt as _,
+};
+use tower_http::{
+    cors::{AllowOrigin, CorsLayer},
+    limit::RequestBodyLimitLayer,
+    trace::TraceLayer,
+};
 use tracing::Level;
 use tracing_subscriber::FmtSubscriber;
 
-type ResponsePair<T = Value> = (StatusCode, Json<T>);
+/// A handler's JSON response, the payload or `ApiError` is serialized once
+/// when the response is built and the body is written as is
+type ResponsePair<T = Box<RawValue>> = (StatusCode, Json<T>);
+
+/// The largest request body accepted by everything but imports
+const BODY_LIMIT: usize = 65535;
+/// How many imported events are added to the database at a time
+const IMPORT_BATCH_SIZE: usize = 500;
+/// The longest line of an import, an event is far shorter so anything longer
+/// isn't buffered waiting for its newline
+const IMPORT_LINE_LIMIT: usize = BODY_LIMIT;
+/// How many added events are buffered for each stream before a slow one
+/// starts missing them
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+/// How often a stream with nothing to send gets a comment to keep it open
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+#[derive(Clone)]
+struct AppState {
+    db: Database,
+    /// Every event added through the api, after it was committed
+    added: broadcast::Sender<RingEvent>,
+}
+
+impl AppState {
+    fn new(db: Database) -> Self {
+        let (added, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Self { db, added }
+    }
+}
+
+impl FromRef<AppState> for Database {
+    fn from_ref(state: &AppState) -> Self {
+        state.db.clone()
+    }
+}
+
+/// What a token allows, a write token can also read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Scope {
+    Read,
+    Write,
+}
+
+/// The bearer tokens accepted by the api, when there are none every request
+/// is allowed
+#[derive(Debug, Clone, Default)]
+struct Auth {
+    tokens: HashMap<String, Scope>,
+}
+
+impl Auth {
+    /// Tokens from the comma separated `RING_VIEWER_READ_TOKENS` and
+    /// `RING_VIEWER_WRITE_TOKENS`
+    fn from_env() -> Self {
+        let mut ret = Self::default();
+        for (var, scope) in [
+            ("RING_VIEWER_READ_TOKENS", Scope::Read),
+            ("RING_VIEWER_WRITE_TOKENS", Scope::Write),
+        ] {
+            let Ok(tokens) = std::env::var(var) else {
+                continue;
+            };
+            for token in tokens.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+                ret = ret.with_token(token, scope);
+            }
+        }
+        ret
+    }
+
+    /// Accept `token`, a token given both scopes gets the wider one
+    fn with_token(mut self, token: impl Into<String>, scope: Scope) -> Self {
+        let entry = self.tokens.entry(token.into()).or_insert(scope);
+        *entry = (*entry).max(scope);
+        self
+    }
+
+    /// GET and HEAD only need to read, everything else writes
+    fn required_scope(method: &Method) -> Scope {
+        if method == Method::GET || method == Method::HEAD {
+            Scope::Read
+        } else {
+            Scope::Write
+        }
+    }
+}
+
+/// Reject requests without a bearer token allowing their method, a missing or
+/// unknown token is a 401 and one with too narrow a scope a 403
+async fn require_token(auth: State<Auth>, request: Request, next: Next) -> Response {
+    const CTX: &str = "authorization";
+    if auth.tokens.is_empty() {
+        return next.run(request).await;
+    }
+    let token = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let Some(scope) = token.and_then(|token| auth.tokens.get(token)) else {
+        let mut response = err(
+            "missing or invalid bearer token",
+            CTX,
+            StatusCode::UNAUTHORIZED,
+        )
+        .into_response();
+        response
+            .headers_mut()
+            .insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        return response;
+    };
+    if *scope < Auth::required_scope(request.method()) {
+        return err("token is read only", CTX, StatusCode::FORBIDDEN).into_response();
+    }
+    next.run(request).await
+}
 
 #[tokio::main]
 async fn main() {
@@ -28,20 +181,17 @@ async fn main() {
         .unwrap_or_else(|_| PathBuf::from("./data.db"));
     let database = Database::new(&db_path).unwrap();
     // build our application with a route
+    let health = Health::new(database.clone(), db_path);
     let app = Router::new()
         .nest_service("/", tower_http::services::ServeDir::new("assets"))
         .nest_service(
             "/api",
-            Router::new()
-                .route("/rings", get(get_rings))
-                .route("/ring", post(add_ring).put(update_ring))
-                .route("/ring/:id", get(get_ring))
-                .route("/events/:id", post(add_events).get(get_events_for_ring))
-                .with_state(database),
+            with_cors(api(database, Auth::from_env()), &cors_origins_from_env()),
         )
+        // outside of `/api` so probes don't need a token
+        .merge(health_routes(health))
         .layer(TraceLayer::new_for_http())
-        .layer(DefaultBodyLimit::disable())
-        .layer(RequestBodyLimitLayer::new(65535));
+        .layer(DefaultBodyLimit::disable());
 
     let port = std::env::var("RING_VIEWER_PORT")
         .ok()
@@ -54,13 +204,140 @@ async fn main() {
     axum::serve(listener, app).await.unwrap();
 }
 
-fn into_response(value: impl Serialize, status: StatusCode, context: impl Display) -> ResponsePair {
-    let v = match serde_json::to_value(&value) {
-        Ok(v) => v,
-        Err(e) => return err(e, context, None),
+/// The origins from the comma separated `RING_VIEWER_CORS_ORIGINS`, `*`
+/// allows any origin
+fn cors_origins_from_env() -> Vec<String> {
+    std::env::var("RING_VIEWER_CORS_ORIGINS")
+        .map(|origins| {
+            origins
+                .split(',')
+                .map(str::trim)
+                .filter(|o| !o.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Answer preflights and add CORS headers for `origins`, with none only same
+/// origin requests work. Applied outside of the auth layer so preflights,
+/// which never carry a token, aren't rejected
+fn with_cors(router: Router, origins: &[String]) -> Router {
+    if origins.is_empty() {
+        return router;
+    }
+    let allow = if origins.iter().any(|o| o == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(
+            origins
+                .iter()
+                .filter_map(|o| match HeaderValue::from_str(o) {
+                    Ok(origin) => Some(origin),
+                    Err(e) => {
+                        tracing::warn!("ignoring invalid cors origin {o}: {e}");
+                        None
+                    }
+                }),
+        )
+    };
+    router.layer(
+        CorsLayer::new()
+            .allow_origin(allow)
+            .allow_methods([
+                Method::GET,
+                Method::POST,
+                Method::PUT,
+                Method::PATCH,
+                Method::DELETE,
+            ])
+            .allow_headers([AUTHORIZATION, CONTENT_TYPE]),
+    )
+}
+
+fn health_routes(health: Health) -> Router {
+    Router::new()
+        .route("/healthz", get(get_health))
+        .with_state(health)
+}
+
+/// What `/healthz` checks
+#[derive(Clone)]
+struct Health {
+    db: Database,
+    path: PathBuf,
+    started: Instant,
+}
+
+impl Health {
+    fn new(db: Database, path: PathBuf) -> Self {
+        Self {
+            db,
+            path,
+            started: Instant::now(),
+        }
+    }
+
+    /// If the database file can still be read and written
+    fn check_db(&self) -> Result<(), String> {
+        std::fs::File::open(&self.path)
+            .map_err(|e| format!("unable to read {}: {e}", self.path.display()))?;
+        self.db.check().map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HealthReport {
+    /// `ok` or why the database can't be used
+    pub db: String,
+    pub uptime_secs: u64,
+    pub version: String,
+}
+
+/// 200 when the database is usable and 503 otherwise
+async fn get_health(health: State<Health>) -> ResponsePair {
+    let (db, status) = match health.check_db() {
+        Ok(()) => ("ok".to_string(), StatusCode::OK),
+        Err(e) => {
+            tracing::error!("health check failed: {e}");
+            (e, StatusCode::SERVICE_UNAVAILABLE)
+        }
     };
+    into_response(
+        HealthReport {
+            db,
+            uptime_secs: health.started.elapsed().as_secs(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        },
+        status,
+        "get_health",
+    )
+}
 
-    (status, Json(v))
+fn api(database: Database, auth: Auth) -> Router {
+    Router::new()
+        .route("/rings", get(get_rings))
+        .route("/ring", post(add_ring).put(update_ring))
+        .route("/ring/:id", get(get_ring).patch(patch_ring))
+        .route("/events/:id", post(add_events).get(get_events_for_ring))
+        .route("/events/:id/stream", get(stream_events))
+        .route("/events/:id/export.csv", get(export_events))
+        .route("/events/:id/summary", get(get_summary))
+        .route("/events/:id/latest", get(get_latest))
+        .route("/events/:id/goals", get(get_goals))
+        .route("/replies/:id", post(add_replies))
+        .layer(RequestBodyLimitLayer::new(BODY_LIMIT))
+        // streamed a line at a time so it has no limit of its own
+        .route("/events/:id/import", post(import_events))
+        .layer(middleware::from_fn_with_state(auth, require_token))
+        .with_state(AppState::new(database))
+}
+
+fn into_response(value: impl Serialize, status: StatusCode, context: impl Display) -> ResponsePair {
+    match serde_json::value::to_raw_value(&value) {
+        Ok(v) => (status, Json(v)),
+        Err(e) => err(e, context, None),
+    }
 }
 
 fn err(
@@ -73,58 +350,907 @@ fn err(
         ApiError {
             context: context.to_string(),
             error: e.to_string(),
+            code: status.into(),
+            invalid: Vec::new(),
         },
         status,
         "error ctor",
     )
 }
 
+/// Map errors from the database to a 404 when they describe something
+/// missing, a 409 for duplicates, a 400 for dates that can't be stored and
+/// a 500 otherwise
+fn db_err(e: fissure::Error, context: impl Display) -> ResponsePair {
+    let status = match e {
+        fissure::Error::RingNotFound { .. } => StatusCode::NOT_FOUND,
+        fissure::Error::RingAlreadyExists { .. } => StatusCode::CONFLICT,
+        fissure::Error::InvalidDate(_) | fissure::Error::InvalidMac(_) => StatusCode::BAD_REQUEST,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    err(e, context, status)
+}
+
 async fn get_rings(db: State<Database>) -> ResponsePair {
     into_response(db.get_rings(), StatusCode::OK, "get_rings")
 }
 
 async fn get_ring(db: State<Database>, mac: Path<String>) -> ResponsePair {
-    match db.get_ring(&mac.0) {
+    const CTX: &str = "get ring by mac";
+    let mac = match mac.0.parse::<Mac>() {
+        Ok(mac) => mac,
+        Err(e) => return db_err(e, CTX),
+    };
+    match db.get_ring(mac.as_str()) {
         Ok(ring) => into_response(ring, StatusCode::OK, "get_rings"),
-        Err(e) => err(e, "get ring by mac", None),
+        Err(e) => db_err(e, CTX),
     }
 }
 
+/// Replace the ring's mac with the canonical one so it matches its events
+fn canonical_ring(mut ring: Ring) -> fissure::Result<Ring> {
+    ring.mac = ring.mac.parse::<Mac>()?.into();
+    Ok(ring)
+}
+
 async fn add_ring(db: State<Database>, ring: Json<Ring>) -> ResponsePair {
-    match db.add_ring(&ring.0) {
+    match canonical_ring(ring.0).and_then(|ring| db.add_ring(&ring)) {
         Ok(()) => into_response(serde_json::Map::new(), StatusCode::OK, "add_ring"),
-        Err(e) => err(e, "add_ring", None),
+        Err(e) => db_err(e, "add_ring"),
     }
 }
 
 async fn update_ring(db: State<Database>, ring: Json<Ring>) -> ResponsePair {
-    match db.update_ring(&ring.0) {
-        Ok(()) => into_response(serde_json::Map::new(), StatusCode::OK, "add_ring"),
-        Err(e) => err(e, "add_ring", None),
+    match canonical_ring(ring.0).and_then(|ring| db.update_ring(&ring)) {
+        Ok(()) => into_response(serde_json::Map::new(), StatusCode::OK, "update_ring"),
+        Err(e) => db_err(e, "update_ring"),
     }
 }
 
-async fn add_events(db: State<Database>, events: Json<Vec<RingEvent>>) -> ResponsePair {
-    match db.add_events(&events) {
-        Ok(()) => into_response(serde_json::Map::new(), StatusCode::OK, "add_events"),
-        Err(e) => err(e, "add_events", None),
+/// Change only the fields of the ring the body has, responding with the ring
+/// as it's stored afterwards. A `"nickname": null` clears the nickname
+async fn patch_ring(
+    db: State<Database>,
+    mac: Path<String>,
+    patch: Result<Json<RingPatch>, JsonRejection>,
+) -> ResponsePair {
+    const CTX: &str = "patch_ring";
+    let patch = match patch {
+        Ok(patch) => patch.0,
+        Err(e) => return err(e.body_text(), CTX, StatusCode::BAD_REQUEST),
+    };
+    let mac = match mac.0.parse::<Mac>() {
+        Ok(mac) => mac,
+        Err(e) => return db_err(e, CTX),
+    };
+    match db.patch_ring(mac.as_str(), patch) {
+        Ok(ring) => into_response(ring, StatusCode::OK, CTX),
+        Err(e) => db_err(e, CTX),
     }
 }
 
+/// Events from before this are from a ring whose clock was never set
+const EARLIEST_EVENT: time::OffsetDateTime = time::macros::datetime!(2000-01-01 0:00 UTC);
+/// How far ahead of the server's clock an event can be, allowing for a ring's
+/// clock drifting
+const LATEST_EVENT_AHEAD: time::Duration = time::Duration::DAY;
+
+#[derive(Debug, Deserialize)]
+struct AddEventsArgs {
+    /// Add the ring if it doesn't exist instead of responding with a 404
+    #[serde(default)]
+    register: bool,
+}
+
+/// Add events for a ring, the whole request is rejected with a 422 listing
+/// every invalid event if any of them are for another ring or have a date
+/// that couldn't be real
+async fn add_events(
+    state: State<AppState>,
+    mac: Path<String>,
+    args: Result<Query<AddEventsArgs>, QueryRejection>,
+    events: Json<Vec<Value>>,
+) -> ResponsePair {
+    const CTX: &str = "add_events";
+    let args = match args {
+        Ok(args) => args.0,
+        Err(e) => return err(e.body_text(), CTX, StatusCode::BAD_REQUEST),
+    };
+    let mac = match mac.0.parse::<Mac>() {
+        Ok(mac) => mac,
+        Err(e) => return db_err(e, CTX),
+    };
+    let mut valid = Vec::with_capacity(events.len());
+    let mut invalid = Vec::new();
+    for (index, event) in events.0.into_iter().enumerate() {
+        match validate_event(&mac, event) {
+            Ok(event) => valid.push(event),
+            Err(error) => invalid.push(InvalidEvent { index, error }),
+        }
+    }
+    if !invalid.is_empty() {
+        let status = StatusCode::UNPROCESSABLE_ENTITY;
+        let error = ApiError {
+            error: format!("{} invalid events", invalid.len()),
+            context: CTX.to_string(),
+            code: status.into(),
+            invalid,
+        };
+        return into_response(error, status, CTX);
+    }
+    match state.db.get_ring(mac.as_str()) {
+        Ok(_) => {}
+        Err(fissure::Error::RingNotFound { .. }) if args.register => {
+            let ring = Ring {
+                nickname: None,
+                name: mac.to_string(),
+                mac: mac.to_string(),
+            };
+            if let Err(e) = state.db.add_ring(&ring) {
+                return db_err(e, CTX);
+            }
+        }
+        Err(e) => return db_err(e, CTX),
+    }
+    if let Err(e) = state.db.add_events(&valid, UpsertMode::Overwrite) {
+        return db_err(e, CTX);
+    }
+    for event in valid {
+        // an error only means nobody is streaming
+        let _ = state.added.send(event);
+    }
+    into_response(serde_json::Map::new(), StatusCode::OK, CTX)
+}
+
+/// Parse an event for the ring with `mac`, returning why if it's for another
+/// ring or happened outside of `EARLIEST_EVENT` and `LATEST_EVENT_AHEAD` from
+/// now. The event's mac is replaced with the canonical one
+fn validate_event(mac: &Mac, event: Value) -> Result<RingEvent, String> {
+    let mut event: RingEvent = serde_json::from_value(event).map_err(|e| e.to_string())?;
+    let event_mac: Mac = event
+        .mac
+        .parse()
+        .map_err(|e: fissure::Error| e.to_string())?;
+    if event_mac != *mac {
+        return Err(format!("event is for {event_mac} not {mac}"));
+    }
+    event.mac = event_mac.into();
+    let when = time::OffsetDateTime::try_from(event.when).map_err(|e| e.to_string())?;
+    if when < EARLIEST_EVENT {
+        return Err(format!("{when} is before {EARLIEST_EVENT}"));
+    }
+    if when > time::OffsetDateTime::now_utc() + LATEST_EVENT_AHEAD {
+        return Err(format!("{when} is in the future"));
+    }
+    Ok(event)
+}
+
+/// The header a sync can send its time in instead of the `requested_at` query
+const REQUESTED_AT_HEADER: &str = "x-requested-at";
+
+#[derive(Debug, Deserialize)]
+struct AddRepliesArgs {
+    /// When the replies were requested, in the ring's offset
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    requested_at: Option<time::OffsetDateTime>,
+    /// Minutes between the samples of a heart rate reply, 5 if not given
+    heart_rate_interval: Option<u16>,
+}
+
+/// What adding a list of replies did
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AddedReplies {
+    /// What storing the events of each kind did, kinds without any events are
+    /// left out
+    pub counts: BTreeMap<String, UpsertCounts>,
+    /// Each reply that wasn't stored and why
+    pub skipped: Vec<InvalidEvent>,
+}
+
+/// Convert `CommandReply`s captured from a ring into events and store them
+///
+/// The replies only carry the ring's wall time, so the time they were
+/// requested with the ring's offset is required as the `requested_at` query
+/// or the `x-requested-at` header. Replies that couldn't be parsed or don't
+/// describe any events are listed as skipped
+async fn add_replies(
+    state: State<AppState>,
+    mac: Path<String>,
+    args: Result<Query<AddRepliesArgs>, QueryRejection>,
+    headers: HeaderMap,
+    replies: Json<Vec<Value>>,
+) -> ResponsePair {
+    const CTX: &str = "add_replies";
+    let args = match args {
+        Ok(args) => args.0,
+        Err(e) => return err(e.body_text(), CTX, StatusCode::BAD_REQUEST),
+    };
+    let mac = match mac.0.parse::<Mac>() {
+        Ok(mac) => mac,
+        Err(e) => return db_err(e, CTX),
+    };
+    let requested_at = match requested_at(&args, &headers) {
+        Ok(when) => when,
+        Err(e) => return err(e, CTX, StatusCode::BAD_REQUEST),
+    };
+    let mut context = SyncContext::new(requested_at);
+    if let Some(minutes) = args.heart_rate_interval {
+        if minutes == 0 {
+            return err(
+                "heart_rate_interval must be greater than 0",
+                CTX,
+                StatusCode::BAD_REQUEST,
+            );
+        }
+        context = context.heart_rate_interval(Duration::from_secs(u64::from(minutes) * 60));
+    }
+    if let Err(e) = state.db.get_ring(mac.as_str()) {
+        return db_err(e, CTX);
+    }
+    let mut by_kind: BTreeMap<String, Vec<RingEvent>> = BTreeMap::new();
+    let mut skipped = Vec::new();
+    for (index, reply) in replies.0.into_iter().enumerate() {
+        match convert_reply(mac.as_str(), reply, &context) {
+            Ok(events) => {
+                for event in events {
+                    by_kind
+                        .entry(event.kind().to_string())
+                        .or_default()
+                        .push(event);
+                }
+            }
+            Err(error) => skipped.push(InvalidEvent { index, error }),
+        }
+    }
+    let mut counts = BTreeMap::new();
+    for (kind, events) in by_kind {
+        match state.db.add_events(&events, UpsertMode::Overwrite) {
+            Ok(added) => counts.insert(kind, added),
+            Err(e) => return db_err(e, CTX),
+        };
+        for event in events {
+            // an error only means nobody is streaming
+            let _ = state.added.send(event);
+        }
+    }
+    into_response(AddedReplies { counts, skipped }, StatusCode::OK, CTX)
+}
+
+/// When the replies were requested, from the query or else the header
+fn requested_at(
+    args: &AddRepliesArgs,
+    headers: &HeaderMap,
+) -> Result<time::OffsetDateTime, String> {
+    if let Some(when) = args.requested_at {
+        return Ok(when);
+    }
+    let Some(header) = headers.get(REQUESTED_AT_HEADER) else {
+        return Err(format!(
+            "either the requested_at query or the {REQUESTED_AT_HEADER} header is required"
+        ));
+    };
+    header
+        .to_str()
+        .ok()
+        .and_then(|value| time::OffsetDateTime::parse(value, &Rfc3339).ok())
+        .ok_or_else(|| format!("{REQUESTED_AT_HEADER} must be an RFC 3339 date"))
+}
+
+/// The events a `CommandReply` describes, or why it has none
+fn convert_reply(mac: &str, reply: Value, context: &SyncContext) -> Result<Vec<RingEvent>, String> {
+    let command = reply
+        .get("command")
+        .and_then(Value::as_str)
+        .unwrap_or("unknown")
+        .to_string();
+    let reply: CommandReply = serde_json::from_value(reply).map_err(|e| e.to_string())?;
+    if !fissure::convert::is_convertible(&reply) {
+        return Err(format!("{command} replies don't describe any events"));
+    }
+    Ok(fissure::convert::events_from_reply(mac, &reply, context))
+}
+
+/// Server sent events with each event added for the ring from now on, as JSON
+async fn stream_events(
+    state: State<AppState>,
+    mac: Path<String>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ResponsePair> {
+    const CTX: &str = "stream_events";
+    let mac = mac.0.parse::<Mac>().map_err(|e| db_err(e, CTX))?;
+    state
+        .db
+        .get_ring(mac.as_str())
+        .map_err(|e| db_err(e, CTX))?;
+    let stream = BroadcastStream::new(state.added.subscribe()).filter_map(move |added| {
+        let event = match added {
+            Ok(event) if event.mac == mac.as_str() => event,
+            Ok(_) => return None,
+            Err(e) => {
+                tracing::warn!("event stream fell behind: {e}");
+                return Some(Ok(Event::default().comment(e.to_string())));
+            }
+        };
+        match Event::default().json_data(&event) {
+            Ok(sse) => Some(Ok(sse)),
+            Err(e) => {
+                tracing::error!("unable to serialize event: {e}");
+                None
+            }
+        }
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::new().interval(HEARTBEAT_INTERVAL)))
+}
+
+/// The result of an import, lines that failed don't stop the rest from being
+/// imported
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ImportSummary {
+    pub inserted: usize,
+    pub updated: usize,
+    pub failed: Vec<ImportFailure>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportFailure {
+    /// Starting from 1
+    pub line: usize,
+    pub error: String,
+}
+
+/// Add newline delimited JSON events for a ring, read from the body a line at
+/// a time and added in batches
+async fn import_events(state: State<AppState>, mac: Path<String>, body: Body) -> ResponsePair {
+    const CTX: &str = "import_events";
+    let mac = match mac.0.parse::<Mac>() {
+        Ok(mac) => mac,
+        Err(e) => return db_err(e, CTX),
+    };
+    if let Err(e) = state.db.get_ring(mac.as_str()) {
+        return db_err(e, CTX);
+    }
+    let mut summary = ImportSummary::default();
+    let mut batch = Vec::with_capacity(IMPORT_BATCH_SIZE);
+    let mut buf = Vec::new();
+    let mut line_number = 0;
+    let mut body = body.into_data_stream();
+    loop {
+        let chunk = match body.next().await {
+            Some(Ok(chunk)) => chunk,
+            Some(Err(e)) => return err(e, CTX, StatusCode::BAD_REQUEST),
+            None => break,
+        };
+        buf.extend_from_slice(&chunk);
+        while let Some(end) = buf.iter().position(|b| *b == b'\n') {
+            if end > IMPORT_LINE_LIMIT {
+                import_batch(&state, &mut batch, &mut summary);
+                return line_too_long(line_number + 1);
+            }
+            let line: Vec<u8> = buf.drain(..=end).collect();
+            line_number += 1;
+            import_line(&mac, line_number, &line, &mut batch, &mut summary);
+            if batch.len() >= IMPORT_BATCH_SIZE {
+                import_batch(&state, &mut batch, &mut summary);
+            }
+        }
+        if buf.len() > IMPORT_LINE_LIMIT {
+            import_batch(&state, &mut batch, &mut summary);
+            return line_too_long(line_number + 1);
+        }
+    }
+    // the last line doesn't need a trailing newline
+    line_number += 1;
+    import_line(&mac, line_number, &buf, &mut batch, &mut summary);
+    import_batch(&state, &mut batch, &mut summary);
+    into_response(summary, StatusCode::OK, CTX)
+}
+
+/// A 413 for an import line longer than `IMPORT_LINE_LIMIT`, the lines before
+/// it have already been imported
+fn line_too_long(line_number: usize) -> ResponsePair {
+    err(
+        format!(
+            "line {line_number} is longer than {IMPORT_LINE_LIMIT} bytes, the lines before it were imported"
+        ),
+        "import_events",
+        StatusCode::PAYLOAD_TOO_LARGE,
+    )
+}
+
+/// Parse a line of an import into `batch`, recording why in `summary` if it
+/// isn't an event for `mac`. Blank lines are skipped
+fn import_line(
+    mac: &Mac,
+    line_number: usize,
+    line: &[u8],
+    batch: &mut Vec<(usize, RingEvent)>,
+    summary: &mut ImportSummary,
+) {
+    if line.trim_ascii().is_empty() {
+        return;
+    }
+    let event = serde_json::from_slice(line)
+        .map_err(|e| e.to_string())
+        .and_then(|event| validate_event(mac, event));
+    let error = match event {
+        Ok(event) => {
+            batch.push((line_number, event));
+            return;
+        }
+        Err(error) => error,
+    };
+    summary.failed.push(ImportFailure {
+        line: line_number,
+        error,
+    });
+}
+
+/// Add and publish the events in `batch`, if the database rejects them every
+/// line in the batch is recorded as failed
+fn import_batch(
+    state: &AppState,
+    batch: &mut Vec<(usize, RingEvent)>,
+    summary: &mut ImportSummary,
+) {
+    if batch.is_empty() {
+        return;
+    }
+    let (lines, events): (Vec<_>, Vec<_>) = std::mem::take(batch).into_iter().unzip();
+    match state.db.add_events(&events, UpsertMode::Overwrite) {
+        Ok(counts) => {
+            summary.inserted += counts.inserted;
+            summary.updated += counts.updated;
+            for event in events {
+                let _ = state.added.send(event);
+            }
+        }
+        Err(e) => {
+            tracing::error!("import batch failed: {e}");
+            summary
+                .failed
+                .extend(lines.into_iter().map(|line| ImportFailure {
+                    line,
+                    error: e.to_string(),
+                }));
+        }
+    }
+}
+
+/// The page size used when a request doesn't provide a limit
+const DEFAULT_PAGE_SIZE: usize = 1000;
+
+/// Either `date` for a single UTC day or at least one of `start` (inclusive)
+/// and `end` (exclusive) must be provided
 #[derive(Debug, Deserialize)]
 struct EventsArgs {
-    date: time::OffsetDateTime,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    date: Option<time::OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    start: Option<time::OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    end: Option<time::OffsetDateTime>,
+    limit: Option<usize>,
+    /// The `next_cursor` from a previous page
+    cursor: Option<String>,
+    /// Only include events of this kind
+    kind: Option<EventKind>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EventsPage {
+    pub items: Vec<RingEvent>,
+    pub next_cursor: Option<String>,
+    pub total: usize,
+}
+
+fn encode_cursor(offset: usize) -> String {
+    URL_SAFE_NO_PAD.encode(offset.to_string())
+}
+
+fn decode_cursor(cursor: &str) -> Option<usize> {
+    let bytes = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+    std::str::from_utf8(&bytes).ok()?.parse().ok()
 }
 
 async fn get_events_for_ring(
     db: State<Database>,
     mac: Path<String>,
-    args: Query<EventsArgs>,
+    args: Result<Query<EventsArgs>, QueryRejection>,
 ) -> ResponsePair {
-    match db.get_events_for_ring(&mac.0, args.0.date) {
-        Ok(list) => into_response(list, StatusCode::OK, "get_events_for_ring"),
-        Err(e) => err(e, "add_events", None),
+    const CTX: &str = "get_events_for_ring";
+    let args = match args {
+        Ok(args) => args.0,
+        Err(e) => return err(e.body_text(), CTX, StatusCode::BAD_REQUEST),
+    };
+    let offset = match args.cursor.as_deref().map(decode_cursor) {
+        None => 0,
+        Some(Some(offset)) => offset,
+        Some(None) => return err("invalid cursor", CTX, StatusCode::BAD_REQUEST),
+    };
+    let limit = args.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if limit == 0 {
+        return err("limit must be greater than 0", CTX, StatusCode::BAD_REQUEST);
     }
+    let mac = match mac.0.parse::<Mac>() {
+        Ok(mac) => mac,
+        Err(e) => return db_err(e, CTX),
+    };
+    if let Err(e) = db.get_ring(mac.as_str()) {
+        return db_err(e, CTX);
+    }
+    let range = match (args.date, args.start, args.end) {
+        (Some(date), None, None) => {
+            let start = date.date().midnight().assume_utc();
+            (
+                Bound::Included(start),
+                Bound::Excluded(start + time::Duration::DAY),
+            )
+        }
+        (Some(_), _, _) => {
+            return err(
+                "date cannot be combined with start or end",
+                CTX,
+                StatusCode::BAD_REQUEST,
+            )
+        }
+        (None, None, None) => {
+            return err(
+                "either date or at least one of start and end is required",
+                CTX,
+                StatusCode::BAD_REQUEST,
+            )
+        }
+        (None, start, end) => (
+            start.map_or(Bound::Unbounded, Bound::Included),
+            end.map_or(Bound::Unbounded, Bound::Excluded),
+        ),
+    };
+    let events = match args.kind {
+        Some(kind) => db.get_events_of_kind(mac.as_str(), kind, range),
+        None => db.get_events_in_range(mac.as_str(), range),
+    };
+    let events = match events {
+        Ok(events) => events,
+        Err(e) => return db_err(e, CTX),
+    };
+    let total = events.len();
+    let items: Vec<_> = events.into_iter().skip(offset).take(limit).collect();
+    let next = offset + items.len();
+    let next_cursor = (next < total).then(|| encode_cursor(next));
+    into_response(
+        EventsPage {
+            items,
+            next_cursor,
+            total,
+        },
+        StatusCode::OK,
+        CTX,
+    )
+}
+
+/// The most days a single summary request can cover
+const MAX_SUMMARY_DAYS: i64 = 366;
+
+/// The UTC days from `start` until `end`
+#[derive(Debug, Deserialize)]
+struct SummaryArgs {
+    start: time::Date,
+    end: time::Date,
+    /// The highest heart rate the zones are fractions of, without it no time
+    /// in zones is reported
+    max_hr: Option<u8>,
+    /// Minutes between the stored heart rates, 5 if not given
+    heart_rate_interval: Option<u16>,
+}
+
+/// A day's `DailySummary` shaped for charting
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SummaryPoint {
+    pub date: time::Date,
+    pub metrics: DailySummary,
+    /// The day's heart rate stats, resting is taken from the sleep session
+    /// that ended that day when there is one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heart_rate: Option<HrDailyStats>,
+    /// The score of the sleep session that ended that day
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sleep_score: Option<SleepScore>,
+}
+
+/// The summary of each day in the range with any events, oldest first
+async fn get_summary(
+    db: State<Database>,
+    mac: Path<String>,
+    args: Result<Query<SummaryArgs>, QueryRejection>,
+) -> ResponsePair {
+    const CTX: &str = "get_summary";
+    let args = match args {
+        Ok(args) => args.0,
+        Err(e) => return err(e.body_text(), CTX, StatusCode::BAD_REQUEST),
+    };
+    if let Err(e) = check_summary_days(args.start, args.end) {
+        return err(e, CTX, StatusCode::BAD_REQUEST);
+    }
+    let mut config = HrConfig {
+        max_hr: args.max_hr,
+        ..Default::default()
+    };
+    if let Some(minutes) = args.heart_rate_interval {
+        if minutes == 0 {
+            return err(
+                "heart_rate_interval must be greater than 0",
+                CTX,
+                StatusCode::BAD_REQUEST,
+            );
+        }
+        config.interval = Duration::from_secs(u64::from(minutes) * 60);
+    }
+    let mac = match mac.0.parse::<Mac>() {
+        Ok(mac) => mac,
+        Err(e) => return db_err(e, CTX),
+    };
+    if let Err(e) = db.get_ring(mac.as_str()) {
+        return db_err(e, CTX);
+    }
+    let summaries = match db.summaries_in_range(mac.as_str(), args.start..args.end) {
+        Ok(summaries) => summaries,
+        Err(e) => return db_err(e, CTX),
+    };
+    let sessions = match nightly_sleep(&db, mac.as_str(), args.start..args.end) {
+        Ok(sessions) => sessions,
+        Err(e) => return db_err(e, CTX),
+    };
+    let mut heart_rates =
+        match heart_rate_stats(&db, mac.as_str(), args.start..args.end, &config, &sessions) {
+            Ok(stats) => stats,
+            Err(e) => return db_err(e, CTX),
+        };
+    let score_config = SleepScoreConfig::default();
+    let points: Vec<_> = summaries
+        .into_iter()
+        .filter(DailySummary::has_data)
+        .map(|metrics| SummaryPoint {
+            date: metrics.day,
+            heart_rate: heart_rates.remove(&metrics.day),
+            sleep_score: sessions
+                .get(&metrics.day)
+                .map(|session| sleep_score(session, &score_config)),
+            metrics,
+        })
+        .collect();
+    into_response(points, StatusCode::OK, CTX)
+}
+
+/// Reject a range of days that's empty or longer than `MAX_SUMMARY_DAYS`
+fn check_summary_days(start: time::Date, end: time::Date) -> Result<(), String> {
+    let days = (end - start).whole_days();
+    if !(1..=MAX_SUMMARY_DAYS).contains(&days) {
+        return Err(format!(
+            "end must be between 1 and {MAX_SUMMARY_DAYS} days after start"
+        ));
+    }
+    Ok(())
+}
+
+/// The UTC days from `start` until `end` to compare to a step goal
+#[derive(Debug, Deserialize)]
+struct GoalsArgs {
+    start: time::Date,
+    end: time::Date,
+    /// The step goal, the one stored for the ring if not given
+    goal: Option<u32>,
+    /// The day that isn't over yet, the current UTC day if not given
+    today: Option<time::Date>,
+}
+
+/// Each day's steps in the range compared to the step goal, with the streaks
+/// of days the goal was met
+async fn get_goals(
+    db: State<Database>,
+    mac: Path<String>,
+    args: Result<Query<GoalsArgs>, QueryRejection>,
+) -> ResponsePair {
+    const CTX: &str = "get_goals";
+    let args = match args {
+        Ok(args) => args.0,
+        Err(e) => return err(e.body_text(), CTX, StatusCode::BAD_REQUEST),
+    };
+    if let Err(e) = check_summary_days(args.start, args.end) {
+        return err(e, CTX, StatusCode::BAD_REQUEST);
+    }
+    let mac = match mac.0.parse::<Mac>() {
+        Ok(mac) => mac,
+        Err(e) => return db_err(e, CTX),
+    };
+    if let Err(e) = db.get_ring(mac.as_str()) {
+        return db_err(e, CTX);
+    }
+    let stored = match db.get_settings(mac.as_str()) {
+        Ok(settings) => settings.map(|settings| settings.goals.steps),
+        Err(e) => return db_err(e, CTX),
+    };
+    let Some(goal) = args.goal.or(stored.filter(|steps| *steps > 0)) else {
+        return err(
+            "no step goal is stored for the ring, pass one as goal",
+            CTX,
+            StatusCode::BAD_REQUEST,
+        );
+    };
+    let summaries = match db.summaries_in_range(mac.as_str(), args.start..args.end) {
+        Ok(summaries) => summaries,
+        Err(e) => return db_err(e, CTX),
+    };
+    let days: Vec<_> = summaries
+        .iter()
+        .filter_map(|summary| Some((summary.day, summary.steps?)))
+        .collect();
+    let today = args
+        .today
+        .unwrap_or_else(|| time::OffsetDateTime::now_utc().date());
+    into_response(goal_progress(goal, &days, today), StatusCode::OK, CTX)
+}
+
+/// The sleep session that ended on each day in `days`, the night before it
+fn nightly_sleep(
+    db: &Database,
+    mac: &str,
+    days: std::ops::Range<time::Date>,
+) -> fissure::Result<BTreeMap<time::Date, SleepSession>> {
+    let range = days.start.midnight().assume_utc()..days.end.midnight().assume_utc();
+    let mut ret = BTreeMap::new();
+    for record in db.get_sleep_sessions(mac, range)? {
+        let session = SleepSession::try_from(&record)?;
+        if days.contains(&session.end.date()) {
+            ret.insert(session.end.date(), session);
+        }
+    }
+    Ok(ret)
+}
+
+/// The heart rate stats of each day in `days` with any heart rates, resting
+/// rates are from the day's session in `sessions` when it has one
+fn heart_rate_stats(
+    db: &Database,
+    mac: &str,
+    days: std::ops::Range<time::Date>,
+    config: &HrConfig,
+    sessions: &BTreeMap<time::Date, SleepSession>,
+) -> fissure::Result<BTreeMap<time::Date, HrDailyStats>> {
+    let range = days.start.midnight().assume_utc()..days.end.midnight().assume_utc();
+    let mut samples: BTreeMap<time::Date, Vec<(time::OffsetDateTime, u8)>> = BTreeMap::new();
+    for event in db.get_events_of_kind(mac, EventKind::HeartRate, range)? {
+        let EventData::HeartRate(rate) = event.value else {
+            continue;
+        };
+        let when = time::OffsetDateTime::try_from(event.when)?;
+        samples
+            .entry(when.date())
+            .or_default()
+            .push((when, rate.try_into().unwrap_or(u8::MAX)));
+    }
+    Ok(samples
+        .into_iter()
+        .map(|(day, samples)| {
+            let mut stats = hr_daily_stats(&samples, config);
+            if let Some(session) = sessions.get(&day) {
+                stats.resting = resting_hr(&samples, config, Some(session));
+            }
+            (day, stats)
+        })
+        .collect())
+}
+
+/// The most recent event of each kind
+async fn get_latest(db: State<Database>, mac: Path<String>) -> ResponsePair {
+    const CTX: &str = "get_latest";
+    let mac = match mac.0.parse::<Mac>() {
+        Ok(mac) => mac,
+        Err(e) => return db_err(e, CTX),
+    };
+    if let Err(e) = db.get_ring(mac.as_str()) {
+        return db_err(e, CTX);
+    }
+    match db.latest_events(mac.as_str()) {
+        Ok(events) => into_response(events, StatusCode::OK, CTX),
+        Err(e) => db_err(e, CTX),
+    }
+}
+
+/// The first row of an export
+const CSV_HEADER: &str = "when,kind,value,steps,calories,distance,min,max\n";
+/// About how many bytes of an export are sent at a time
+const CSV_CHUNK_SIZE: usize = 8 * 1024;
+
+/// Both ends of the range are required so an export is read a day at a time
+#[derive(Debug, Deserialize)]
+struct ExportArgs {
+    #[serde(with = "time::serde::rfc3339")]
+    start: time::OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    end: time::OffsetDateTime,
+    /// Only include events of this kind
+    kind: Option<EventKind>,
+}
+
+/// The ring's events from `start` until `end` as CSV, streamed as they're read
+/// from the database
+async fn export_events(
+    db: State<Database>,
+    mac: Path<String>,
+    args: Result<Query<ExportArgs>, QueryRejection>,
+) -> Result<Response, ResponsePair> {
+    const CTX: &str = "export_events";
+    let args = args
+        .map_err(|e| err(e.body_text(), CTX, StatusCode::BAD_REQUEST))?
+        .0;
+    let mac = mac.0.parse::<Mac>().map_err(|e| db_err(e, CTX))?;
+    db.get_ring(mac.as_str()).map_err(|e| db_err(e, CTX))?;
+    let (tx, rx) = tokio::sync::mpsc::channel::<fissure::Result<String>>(4);
+    let filename = format!(
+        "attachment; filename=\"{}.csv\"",
+        mac.as_str().replace(':', "")
+    );
+    tokio::task::spawn_blocking(move || {
+        let mut chunk = String::from(CSV_HEADER);
+        for event in db.iter_events(mac.as_str(), args.start..args.end) {
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    tracing::error!("export failed: {e}");
+                    let _ = tx.blocking_send(Err(e));
+                    return;
+                }
+            };
+            if args.kind.is_some_and(|kind| kind != event.kind()) {
+                continue;
+            }
+            csv_row(&event, &mut chunk);
+            if chunk.len() >= CSV_CHUNK_SIZE
+                && tx.blocking_send(Ok(std::mem::take(&mut chunk))).is_err()
+            {
+                // the client went away
+                return;
+            }
+        }
+        let _ = tx.blocking_send(Ok(chunk));
+    });
+    Ok((
+        [
+            (CONTENT_TYPE, "text/csv".to_string()),
+            (CONTENT_DISPOSITION, filename),
+        ],
+        Body::from_stream(ReceiverStream::new(rx)),
+    )
+        .into_response())
+}
+
+/// Append `event` to `out` as a row of an export, the columns that don't
+/// apply to its kind are left empty
+fn csv_row(event: &RingEvent, out: &mut String) {
+    use std::fmt::Write as _;
+    let when = time::OffsetDateTime::try_from(event.when)
+        .ok()
+        .and_then(|when| when.format(&Rfc3339).ok())
+        .unwrap_or_default();
+    let kind = event.kind();
+    let _ = match &event.value {
+        EventData::HeartRate(v)
+        | EventData::Sleep(v)
+        | EventData::Stress(v)
+        | EventData::Oxygen(v) => {
+            writeln!(out, "{when},{kind},{v},,,,,")
+        }
+        EventData::Battery(b) => writeln!(out, "{when},{kind},{},,,,,", b.level),
+        EventData::Activity(a) => writeln!(
+            out,
+            "{when},{kind},,{},{},{},,",
+            a.steps, a.calories, a.distance
+        ),
+        EventData::OxygenRange(r) => writeln!(out, "{when},{kind},,,,,{},{}", r.min, r.max),
+        EventData::SleepSession(s) => {
+            let minutes: u32 = s.stages.iter().map(|stage| u32::from(stage.minutes)).sum();
+            writeln!(out, "{when},{kind},{minutes},,,,,")
+        }
+    };
 }
 
 // fn get_utc_date_parts(date: OffsetDateTime) -> Result<(u16, u8, u8)> {
@@ -139,4 +1265,1412 @@ async fn get_events_for_ring(
 pub struct ApiError {
     pub error: String,
     pub context: String,
+    pub code: ErrorCode,
+    /// Each event that made the request invalid
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub invalid: Vec<InvalidEvent>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InvalidEvent {
+    /// The event's position in the request
+    pub index: usize,
+    pub error: String,
+}
+
+/// A machine readable summary of an `ApiError`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    Conflict,
+    UnprocessableEntity,
+    PayloadTooLarge,
+    Internal,
+}
+
+impl From<StatusCode> for ErrorCode {
+    fn from(status: StatusCode) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED => Self::Unauthorized,
+            StatusCode::FORBIDDEN => Self::Forbidden,
+            StatusCode::NOT_FOUND => Self::NotFound,
+            StatusCode::CONFLICT => Self::Conflict,
+            StatusCode::UNPROCESSABLE_ENTITY => Self::UnprocessableEntity,
+            StatusCode::PAYLOAD_TOO_LARGE => Self::PayloadTooLarge,
+            s if s.is_client_error() => Self::BadRequest,
+            _ => Self::Internal,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{
+            header::{
+                ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+                ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_REQUEST_HEADERS,
+                ACCESS_CONTROL_REQUEST_METHOD, ORIGIN,
+            },
+            Request,
+        },
+    };
+    use cole_mine::analysis::GoalReport;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    const MAC: &str = "00:00:00:00:00:00";
+
+    fn test_api() -> (tempfile::TempDir, Router) {
+        let (dir, db) = test_db();
+        (dir, api(db, Auth::default()))
+    }
+
+    fn test_db() -> (tempfile::TempDir, Database) {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::new(dir.path().join("data.db")).unwrap();
+        db.add_ring(&Ring {
+            nickname: None,
+            name: "ring".to_string(),
+            mac: MAC.to_string(),
+        })
+        .unwrap();
+        (dir, db)
+    }
+
+    async fn send(api: Router, request: Request<Body>) -> (StatusCode, Value) {
+        let response = api.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    async fn get(api: Router, uri: &str) -> (StatusCode, Value) {
+        send(api, Request::get(uri).body(Body::empty()).unwrap()).await
+    }
+
+    /// A payload that counts how many times it's serialized
+    struct Counted<'a>(&'a std::sync::atomic::AtomicUsize);
+
+    impl Serialize for Counted<'_> {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            serde_json::json!({ "items": [1, 2, 3] }).serialize(serializer)
+        }
+    }
+
+    #[tokio::test]
+    async fn payload_serialized_once() {
+        let count = std::sync::atomic::AtomicUsize::new(0);
+        let response = into_response(Counted(&count), StatusCode::OK, "test").into_response();
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(count.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(body, r#"{"items":[1,2,3]}"#);
+    }
+
+    #[tokio::test]
+    async fn get_ring_ok() {
+        let (_dir, api) = test_api();
+        let (status, body) = get(api, &format!("/ring/{MAC}")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["mac"], MAC);
+    }
+
+    #[tokio::test]
+    async fn get_ring_not_found() {
+        let (_dir, api) = test_api();
+        let (status, body) = get(api, "/ring/11:11:11:11:11:11").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], "not_found");
+    }
+
+    #[tokio::test]
+    async fn update_ring_not_found() {
+        let (_dir, api) = test_api();
+        let ring = serde_json::json!({
+            "nickname": null,
+            "name": "other",
+            "mac": "11:11:11:11:11:11",
+        });
+        let request = Request::builder()
+            .method(Method::PUT)
+            .uri("/ring")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(ring.to_string()))
+            .unwrap();
+        let (status, body) = send(api, request).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], "not_found");
+    }
+
+    fn patch(mac: &str, body: Value) -> Request<Body> {
+        Request::builder()
+            .method(Method::PATCH)
+            .uri(format!("/ring/{mac}"))
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn patch_ring_fields() {
+        let (_dir, api) = test_api();
+        let cases = [
+            (serde_json::json!({}), Value::Null, "ring"),
+            (
+                serde_json::json!({"nickname": "lefty"}),
+                "lefty".into(),
+                "ring",
+            ),
+            (
+                serde_json::json!({"name": "R02_1234"}),
+                "lefty".into(),
+                "R02_1234",
+            ),
+            (
+                serde_json::json!({"nickname": "righty", "name": "R02_5678"}),
+                "righty".into(),
+                "R02_5678",
+            ),
+        ];
+        for (body, nickname, name) in cases {
+            let (status, ring) = send(api.clone(), patch(MAC, body.clone())).await;
+            assert_eq!(status, StatusCode::OK, "{body}: {ring}");
+            let expected = serde_json::json!({"nickname": nickname, "name": name, "mac": MAC});
+            assert_eq!(ring, expected, "{body}");
+            let (_, stored) = get(api.clone(), &format!("/ring/{MAC}")).await;
+            assert_eq!(stored, expected, "{body}");
+        }
+    }
+
+    #[tokio::test]
+    async fn patch_ring_null_clears_nickname() {
+        let (_dir, api) = test_api();
+        let body = serde_json::json!({"nickname": "lefty"});
+        send(api.clone(), patch(MAC, body)).await;
+        // any way of writing the mac finds the ring
+        let body = serde_json::json!({"nickname": null});
+        let (status, ring) = send(api.clone(), patch("00-00-00-00-00-00", body)).await;
+        assert_eq!(status, StatusCode::OK, "{ring}");
+        assert_eq!(ring["nickname"], Value::Null);
+        assert_eq!(ring["name"], "ring");
+    }
+
+    #[tokio::test]
+    async fn patch_ring_errors() {
+        let (_dir, api) = test_api();
+        let body = serde_json::json!({"nickname": "lefty"});
+        let (status, error) = send(api.clone(), patch("11:11:11:11:11:11", body)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(error["code"], "not_found");
+        for body in [
+            serde_json::json!({"name": null}),
+            serde_json::json!({"mac": "11:11:11:11:11:11"}),
+            serde_json::json!("lefty"),
+        ] {
+            let (status, error) = send(api.clone(), patch(MAC, body.clone())).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{body}: {error}");
+            assert_eq!(error["context"], "patch_ring");
+        }
+        let (status, _) = send(api.clone(), patch("not-a-mac", serde_json::json!({}))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (_, stored) = get(api, &format!("/ring/{MAC}")).await;
+        assert_eq!(stored["nickname"], Value::Null);
+    }
+
+    #[tokio::test]
+    async fn duplicate_ring_is_conflict() {
+        let (_dir, api) = test_api();
+        let ring = serde_json::json!({
+            "nickname": null,
+            "name": "ring",
+            "mac": MAC,
+        });
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/ring")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(ring.to_string()))
+            .unwrap();
+        let (status, body) = send(api, request).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], "conflict");
+    }
+
+    #[tokio::test]
+    async fn events_ok() {
+        let (_dir, api) = test_api();
+        let (status, body) = get(api, &format!("/events/{MAC}?date=2024-11-27T00:00:00Z")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            serde_json::json!({"items": [], "next_cursor": null, "total": 0})
+        );
+    }
+
+    #[tokio::test]
+    async fn events_require_a_range() {
+        let (_dir, api) = test_api();
+        let (status, body) = get(api, &format!("/events/{MAC}")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "bad_request");
+    }
+
+    #[tokio::test]
+    async fn events_bad_cursor() {
+        let (_dir, api) = test_api();
+        let (status, _) = get(
+            api,
+            &format!("/events/{MAC}?start=2024-11-27T00:00:00Z&cursor=nope!"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn events_walk_cursor() {
+        let (_dir, db) = test_db();
+        let start = time::macros::datetime!(2024-11-29 0:00 UTC);
+        let events: Vec<RingEvent> = (0..72u16)
+            .map(|hour| {
+                let when = start + std::time::Duration::from_secs(u64::from(hour) * 60 * 60);
+                let when = when
+                    .format(&time::format_description::well_known::Rfc3339)
+                    .unwrap();
+                serde_json::from_value(serde_json::json!({
+                    "mac": MAC,
+                    "when": when,
+                    "value": {"type": "HeartRate", "data": 60 + hour},
+                }))
+                .unwrap()
+            })
+            .collect();
+        db.add_events(&events, UpsertMode::Overwrite).unwrap();
+        let api = api(db, Auth::default());
+        let mut cursor: Option<String> = None;
+        let mut seen = Vec::new();
+        loop {
+            let mut uri = format!(
+                "/events/{MAC}?start=2024-11-29T00:00:00Z&end=2024-12-02T00:00:00Z&limit=7"
+            );
+            if let Some(cursor) = cursor.take() {
+                uri.push_str(&format!("&cursor={cursor}"));
+            }
+            let (status, body) = get(api.clone(), &uri).await;
+            assert_eq!(status, StatusCode::OK, "{body}");
+            let page: EventsPage = serde_json::from_value(body).unwrap();
+            assert_eq!(page.total, 72);
+            assert!(page.items.len() <= 7);
+            seen.extend(page.items);
+            let Some(next) = page.next_cursor else {
+                break;
+            };
+            cursor = Some(next);
+        }
+        assert_eq!(seen, events);
+    }
+
+    #[tokio::test]
+    async fn events_bad_date() {
+        let (_dir, api) = test_api();
+        let (status, body) = get(api, &format!("/events/{MAC}?date=yesterday")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "bad_request");
+    }
+
+    #[tokio::test]
+    async fn events_missing_ring() {
+        let (_dir, api) = test_api();
+        let (status, body) = get(api, "/events/11:11:11:11:11:11?date=2024-11-27T00:00:00Z").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], "not_found");
+    }
+
+    #[tokio::test]
+    async fn oxygen_events_round_trip() {
+        let (_dir, api) = test_api();
+        let events = serde_json::json!([
+            {
+                "mac": MAC,
+                "when": "2024-11-27T01:00:00Z",
+                "value": {"type": "Oxygen", "data": 96},
+            },
+            {
+                "mac": MAC,
+                "when": "2024-11-27T02:00:00Z",
+                "value": {"type": "OxygenRange", "data": {"min": 94, "max": 98}},
+            },
+        ]);
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(format!("/events/{MAC}"))
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(events.to_string()))
+            .unwrap();
+        let (status, body) = send(api.clone(), request).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let (status, body) = get(api, &format!("/events/{MAC}?date=2024-11-27T00:00:00Z")).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let values: Vec<_> = body["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["value"].clone())
+            .collect();
+        assert_eq!(
+            values,
+            [events[0]["value"].clone(), events[1]["value"].clone()]
+        );
+    }
+
+    #[tokio::test]
+    async fn events_filtered_by_kind() {
+        let (_dir, api) = test_api();
+        let events = serde_json::json!([
+            {
+                "mac": MAC,
+                "when": "2024-11-27T01:00:00Z",
+                "value": {"type": "HeartRate", "data": 70},
+            },
+            {
+                "mac": MAC,
+                "when": "2024-11-27T02:00:00Z",
+                "value": {"type": "Stress", "data": 30},
+            },
+        ]);
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(format!("/events/{MAC}"))
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(events.to_string()))
+            .unwrap();
+        let (status, body) = send(api.clone(), request).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let (status, body) = get(
+            api.clone(),
+            &format!("/events/{MAC}?date=2024-11-27T00:00:00Z&kind=heartRate"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(body["total"], 1);
+        assert_eq!(body["items"][0]["value"], events[0]["value"]);
+        let (status, _) = get(
+            api,
+            &format!("/events/{MAC}?date=2024-11-27T00:00:00Z&kind=steps"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn stream_missing_ring() {
+        let (_dir, api) = test_api();
+        let (status, body) = get(api, "/events/11:11:11:11:11:11/stream").await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{body}");
+    }
+
+    #[tokio::test]
+    async fn stream_receives_added_events() {
+        let (_dir, api) = test_api();
+        let response = api
+            .clone()
+            .oneshot(
+                Request::get(format!("/events/{MAC}/stream"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/event-stream");
+        let mut body = response.into_body().into_data_stream();
+        let events = serde_json::json!([
+            {
+                "mac": "11:11:11:11:11:11",
+                "when": "2024-11-27T01:00:00.000Z",
+                "value": {"type": "HeartRate", "data": 60},
+            },
+            {
+                "mac": MAC,
+                "when": "2024-11-27T01:00:00.000Z",
+                "value": {"type": "HeartRate", "data": 70},
+            },
+            {
+                "mac": MAC,
+                "when": "2024-11-27T02:00:00.000Z",
+                "value": {"type": "Stress", "data": 30},
+            },
+        ]);
+        for event in events.as_array().unwrap() {
+            let request = Request::builder()
+                .method(Method::POST)
+                .uri(format!(
+                    "/events/{}?register=true",
+                    event["mac"].as_str().unwrap()
+                ))
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(Value::Array(vec![event.clone()]).to_string()))
+                .unwrap();
+            let (status, body_json) = send(api.clone(), request).await;
+            assert_eq!(status, StatusCode::OK, "{body_json}");
+        }
+        let mut received = Vec::new();
+        while received.len() < 2 {
+            let chunk = tokio::time::timeout(Duration::from_secs(5), body.next())
+                .await
+                .expect("timed out waiting for an event")
+                .unwrap()
+                .unwrap();
+            let chunk = String::from_utf8(chunk.to_vec()).unwrap();
+            for data in chunk.lines().filter_map(|l| l.strip_prefix("data: ")) {
+                received.push(serde_json::from_str::<Value>(data).unwrap());
+            }
+        }
+        assert_eq!(received, [events[1].clone(), events[2].clone()]);
+    }
+
+    #[tokio::test]
+    async fn reads_accept_any_mac_form() {
+        let (_dir, api) = test_api();
+        let ring = serde_json::json!({"nickname": null, "name": "r02", "mac": "aa:bb:cc:dd:ee:ff"});
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/ring")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(ring.to_string()))
+            .unwrap();
+        let (status, body) = send(api.clone(), request).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let events = serde_json::json!([{
+            "mac": "aa-bb-cc-dd-ee-ff",
+            "when": "2024-11-27T01:00:00.000Z",
+            "value": {"type": "HeartRate", "data": 60},
+        }]);
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/events/aa-bb-cc-dd-ee-ff")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(events.to_string()))
+            .unwrap();
+        let (status, body) = send(api.clone(), request).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        for mac in ["aa:bb:cc:dd:ee:ff", "AA-BB-CC-DD-EE-FF", "aabbccddeeff"] {
+            let (status, ring) = get(api.clone(), &format!("/ring/{mac}")).await;
+            assert_eq!(status, StatusCode::OK, "{mac}: {ring}");
+            assert_eq!(ring["mac"], "AA:BB:CC:DD:EE:FF");
+            let (_, page) = get(
+                api.clone(),
+                &format!("/events/{mac}?date=2024-11-27T00:00:00Z"),
+            )
+            .await;
+            assert_eq!(page["items"].as_array().unwrap().len(), 1, "{mac}: {page}");
+            let (_, latest) = get(api.clone(), &format!("/events/{mac}/latest")).await;
+            assert_eq!(latest.as_array().unwrap().len(), 1, "{mac}: {latest}");
+            let (status, summary) = get(
+                api.clone(),
+                &format!("/events/{mac}/summary?start=2024-11-27&end=2024-11-28"),
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK, "{mac}: {summary}");
+            assert_eq!(summary.as_array().unwrap().len(), 1, "{mac}: {summary}");
+            let request = Request::get(format!(
+                "/events/{mac}/export.csv?start=2024-11-27T00:00:00Z&end=2024-11-28T00:00:00Z"
+            ))
+            .body(Body::empty())
+            .unwrap();
+            let response = api.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK, "{mac}");
+            let csv = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert_eq!(
+                csv.split(|b| *b == b'\n').filter(|l| !l.is_empty()).count(),
+                2
+            );
+        }
+        let (status, body) = get(api, "/ring/not-a-mac").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+    }
+
+    #[tokio::test]
+    async fn stream_with_another_mac_form() {
+        let (_dir, api) = test_api();
+        // the events are for the same ring however the path writes its mac
+        let response = api
+            .clone()
+            .oneshot(
+                Request::get("/events/00-00-00-00-00-00/stream")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let mut body = response.into_body().into_data_stream();
+        let event = serde_json::json!({
+            "mac": MAC,
+            "when": "2024-11-27T01:00:00.000Z",
+            "value": {"type": "HeartRate", "data": 70},
+        });
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(format!("/events/{MAC}"))
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(Value::Array(vec![event.clone()]).to_string()))
+            .unwrap();
+        let (status, body_json) = send(api, request).await;
+        assert_eq!(status, StatusCode::OK, "{body_json}");
+        let chunk = tokio::time::timeout(Duration::from_secs(5), body.next())
+            .await
+            .expect("timed out waiting for an event")
+            .unwrap()
+            .unwrap();
+        let chunk = String::from_utf8(chunk.to_vec()).unwrap();
+        let data = chunk
+            .lines()
+            .find_map(|l| l.strip_prefix("data: "))
+            .unwrap();
+        assert_eq!(serde_json::from_str::<Value>(data).unwrap(), event);
+    }
+
+    fn import_request(body: String) -> Request<Body> {
+        Request::builder()
+            .method(Method::POST)
+            .uri(format!("/events/{MAC}/import"))
+            .header(CONTENT_TYPE, "application/x-ndjson")
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn import_reports_failed_lines() {
+        let (_dir, api) = test_api();
+        let body = [
+            format!(r#"{{"mac":"{MAC}","when":"2024-11-27T01:00:00Z","value":{{"type":"HeartRate","data":70}}}}"#),
+            "not json".to_string(),
+            r#"{"mac":"11:11:11:11:11:11","when":"2024-11-27T01:00:00Z","value":{"type":"HeartRate","data":70}}"#.to_string(),
+            String::new(),
+            format!(r#"{{"mac":"{MAC}","when":"2024-11-27T02:00:00Z","value":{{"type":"Stress","data":30}}}}"#),
+        ]
+        .join("\n");
+        let (status, body) = send(api.clone(), import_request(body)).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(body["inserted"], 2);
+        assert_eq!(body["updated"], 0);
+        let failed: Vec<_> = body["failed"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|f| f["line"].as_u64().unwrap())
+            .collect();
+        assert_eq!(failed, [2, 3]);
+        let (_, body) = get(api, &format!("/events/{MAC}?date=2024-11-27T00:00:00Z")).await;
+        assert_eq!(body["total"], 2);
+    }
+
+    #[tokio::test]
+    async fn import_is_batched_and_unlimited() {
+        let (_dir, api) = test_api();
+        let start = time::macros::datetime!(2024-11-27 0:00 UTC);
+        let mut lines: Vec<_> = (0..1_200)
+            .map(|i| {
+                let when = start + time::Duration::minutes(i);
+                serde_json::json!({
+                    "mac": MAC,
+                    "when": when.format(&time::format_description::well_known::Rfc3339).unwrap(),
+                    "value": {"type": "HeartRate", "data": 60},
+                })
+                .to_string()
+            })
+            .collect();
+        lines[700] = "{}".to_string();
+        let body = lines.join("\n") + "\n";
+        assert!(body.len() > BODY_LIMIT);
+        let (status, body) = send(api.clone(), import_request(body)).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(body["inserted"], 1_199);
+        assert_eq!(body["failed"][0]["line"], 701);
+        assert_eq!(body["failed"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn import_line_too_long() {
+        let (_dir, api) = test_api();
+        let event = format!(
+            r#"{{"mac":"{MAC}","when":"2024-11-27T01:00:00Z","value":{{"type":"HeartRate","data":70}}}}"#
+        );
+        for body in [
+            // a body without a newline
+            "x".repeat(IMPORT_LINE_LIMIT + 1),
+            format!("{event}\n{}\n{event}", "x".repeat(IMPORT_LINE_LIMIT + 1)),
+        ] {
+            let (status, body) = send(api.clone(), import_request(body)).await;
+            assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE, "{body}");
+            assert_eq!(body["code"], "payload_too_large");
+        }
+        // the line before the long one was imported
+        let (_, body) = get(api, &format!("/events/{MAC}?date=2024-11-27T00:00:00Z")).await;
+        assert_eq!(body["total"], 1);
+    }
+
+    #[tokio::test]
+    async fn import_missing_ring() {
+        let (_dir, api) = test_api();
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/events/11:11:11:11:11:11/import")
+            .body(Body::empty())
+            .unwrap();
+        let (status, _) = send(api, request).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    async fn export(api: Router, query: &str) -> (StatusCode, axum::http::HeaderMap, String) {
+        let request = Request::get(format!("/events/{MAC}/export.csv?{query}"))
+            .body(Body::empty())
+            .unwrap();
+        let response = api.oneshot(request).await.unwrap();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, headers, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    fn export_db() -> (tempfile::TempDir, Router) {
+        let (dir, db) = test_db();
+        let events = serde_json::json!([
+            {
+                "mac": MAC,
+                "when": "2024-11-27T01:00:00Z",
+                "value": {"type": "HeartRate", "data": 70},
+            },
+            {
+                "mac": MAC,
+                "when": "2024-11-27T02:00:00Z",
+                "value": {"type": "Activity", "data": {"steps": 100, "calories": 10.5, "distance": 80}},
+            },
+            {
+                "mac": MAC,
+                "when": "2024-11-28T03:00:00Z",
+                "value": {"type": "OxygenRange", "data": {"min": 94, "max": 98}},
+            },
+            {
+                "mac": MAC,
+                "when": "2024-11-28T04:00:00Z",
+                "value": {"type": "HeartRate", "data": 72},
+            },
+        ]);
+        let events: Vec<RingEvent> = serde_json::from_value(events).unwrap();
+        db.add_events(&events, UpsertMode::Overwrite).unwrap();
+        (dir, api(db, Auth::default()))
+    }
+
+    #[tokio::test]
+    async fn export_csv() {
+        let (_dir, api) = export_db();
+        let (status, headers, body) =
+            export(api, "start=2024-11-27T00:00:00Z&end=2024-11-29T00:00:00Z").await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(headers[CONTENT_TYPE], "text/csv");
+        assert_eq!(
+            headers[CONTENT_DISPOSITION],
+            "attachment; filename=\"000000000000.csv\""
+        );
+        assert_eq!(
+            body,
+            "when,kind,value,steps,calories,distance,min,max\n\
+            2024-11-27T01:00:00Z,heartRate,70,,,,,\n\
+            2024-11-27T02:00:00Z,activity,,100,10.5,80,,\n\
+            2024-11-28T03:00:00Z,oxygen,,,,,94,98\n\
+            2024-11-28T04:00:00Z,heartRate,72,,,,,\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn export_csv_of_kind() {
+        let (_dir, api) = export_db();
+        let (status, _, body) = export(
+            api,
+            "start=2024-11-27T00:00:00Z&end=2024-11-29T00:00:00Z&kind=heartRate",
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(
+            body.lines().collect::<Vec<_>>(),
+            [
+                CSV_HEADER.trim_end(),
+                "2024-11-27T01:00:00Z,heartRate,70,,,,,",
+                "2024-11-28T04:00:00Z,heartRate,72,,,,,",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn export_requires_a_range() {
+        let (_dir, api) = export_db();
+        let (status, _, _) = export(api, "start=2024-11-27T00:00:00Z").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    fn auth_api() -> (tempfile::TempDir, Router) {
+        let (dir, db) = test_db();
+        let auth = Auth::default()
+            .with_token("reader", Scope::Read)
+            .with_token("writer", Scope::Write);
+        (dir, api(db, auth))
+    }
+
+    fn with_token(request: axum::http::request::Builder, token: &str) -> Request<Body> {
+        request
+            .header(AUTHORIZATION, format!("Bearer {token}"))
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from("[]"))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn auth_missing_token() {
+        let (_dir, api) = auth_api();
+        let response = api
+            .oneshot(Request::get("/rings").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.headers()[WWW_AUTHENTICATE], "Bearer");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "unauthorized");
+    }
+
+    #[tokio::test]
+    async fn auth_wrong_token() {
+        let (_dir, api) = auth_api();
+        let (status, body) = send(api, with_token(Request::get("/rings"), "nope")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["code"], "unauthorized");
+    }
+
+    #[tokio::test]
+    async fn auth_read_token_cannot_write() {
+        let (_dir, api) = auth_api();
+        let (status, body) = send(
+            api.clone(),
+            with_token(Request::get(format!("/ring/{MAC}")), "reader"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let request = with_token(Request::post(format!("/events/{MAC}")), "reader");
+        let (status, body) = send(api, request).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["code"], "forbidden");
+    }
+
+    #[tokio::test]
+    async fn auth_write_token() {
+        let (_dir, api) = auth_api();
+        let request = with_token(Request::post(format!("/events/{MAC}")), "writer");
+        let (status, body) = send(api.clone(), request).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let (status, body) = send(api, with_token(Request::get("/rings"), "writer")).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+    }
+
+    fn health_api() -> (tempfile::TempDir, PathBuf, Router) {
+        let (dir, db) = test_db();
+        let path = dir.path().join("data.db");
+        let api = health_routes(Health::new(db, path.clone()));
+        (dir, path, api)
+    }
+
+    #[tokio::test]
+    async fn healthy() {
+        let (_dir, _, api) = health_api();
+        let (status, body) = get(api, "/healthz").await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(body["db"], "ok");
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+        assert!(body["uptime_secs"].is_u64());
+    }
+
+    #[tokio::test]
+    async fn unhealthy_when_the_file_is_gone() {
+        let (_dir, path, api) = health_api();
+        std::fs::remove_file(&path).unwrap();
+        let (status, body) = get(api, "/healthz").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE, "{body}");
+        assert!(body["db"].as_str().unwrap().starts_with("unable to read"));
+    }
+
+    #[tokio::test]
+    async fn health_skips_auth() {
+        let (dir, db) = test_db();
+        let path = dir.path().join("data.db");
+        let auth = Auth::default().with_token("writer", Scope::Write);
+        let app = Router::new()
+            .nest_service("/api", api(db.clone(), auth))
+            .merge(health_routes(Health::new(db, path)));
+        let (status, _) = get(app.clone(), "/healthz").await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = get(app, "/api/rings").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    /// Three days of hourly heart rate and stress, a daily activity total and
+    /// a sleep session on the first night, with nothing on the 28th
+    fn summary_api() -> (tempfile::TempDir, Router) {
+        let (dir, db) = summary_db();
+        (dir, api(db, Auth::default()))
+    }
+
+    /// The database behind `summary_api`
+    fn summary_db() -> (tempfile::TempDir, Database) {
+        let (dir, db) = test_db();
+        let mut events = Vec::new();
+        for day in [27, 29, 30] {
+            for hour in 0..24 {
+                let when = format!("2024-11-{day}T{hour:02}:00:00Z");
+                events.push(serde_json::json!({
+                    "mac": MAC,
+                    "when": when,
+                    "value": {"type": "HeartRate", "data": 55 + (hour * 7 + day) % 40},
+                }));
+                events.push(serde_json::json!({
+                    "mac": MAC,
+                    "when": when,
+                    "value": {"type": "Stress", "data": 20 + hour},
+                }));
+            }
+            events.push(serde_json::json!({
+                "mac": MAC,
+                "when": format!("2024-11-{day}T23:59:00Z"),
+                "value": {"type": "Activity", "data": {"steps": day * 300, "calories": 250.5, "distance": day * 200}},
+            }));
+            events.push(serde_json::json!({
+                "mac": MAC,
+                "when": format!("2024-11-{day}T12:00:00Z"),
+                "value": {"type": "OxygenRange", "data": {"min": 90 + day % 5, "max": 99}},
+            }));
+        }
+        events.push(serde_json::json!({
+            "mac": MAC,
+            "when": "2024-11-27T01:00:00Z",
+            "value": {"type": "SleepSession", "data": {
+                "start": "2024-11-27T01:00:00Z",
+                "end": "2024-11-27T08:00:00Z",
+                "stages": [
+                    {"stage": "light", "minutes": 200},
+                    {"stage": "deep", "minutes": 120},
+                    {"stage": "rem", "minutes": 80},
+                    {"stage": "awake", "minutes": 20},
+                ],
+            }},
+        }));
+        for (hour, level) in [(8, 72), (20, 64)] {
+            events.push(serde_json::json!({
+                "mac": MAC,
+                "when": format!("2024-11-30T{hour:02}:00:00Z"),
+                "value": {"type": "Battery", "data": {"level": level, "charging": false}},
+            }));
+        }
+        let events: Vec<RingEvent> = serde_json::from_value(Value::Array(events)).unwrap();
+        db.add_events(&events, UpsertMode::Overwrite).unwrap();
+        db.add_ring(&Ring {
+            nickname: None,
+            name: "empty".to_string(),
+            mac: "11:11:11:11:11:11".to_string(),
+        })
+        .unwrap();
+        (dir, db)
+    }
+
+    #[tokio::test]
+    async fn summary() {
+        let (_dir, api) = summary_api();
+        let (status, body) = get(
+            api,
+            &format!("/events/{MAC}/summary?start=2024-11-26&end=2024-12-01"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        insta::assert_snapshot!(serde_json::to_string_pretty(&body).unwrap());
+    }
+
+    #[tokio::test]
+    async fn summary_heart_rate() {
+        let (_dir, api) = summary_api();
+        let (status, body) = get(
+            api,
+            &format!(
+                "/events/{MAC}/summary?start=2024-11-26&end=2024-12-01&max_hr=100&heart_rate_interval=60"
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let points: Vec<SummaryPoint> = serde_json::from_value(body).unwrap();
+        let resting: Vec<_> = points
+            .iter()
+            .map(|point| (point.date.day(), point.heart_rate.as_ref().unwrap().resting))
+            .collect();
+        // the 27th's is from 1:00 until 8:00 while asleep, the others' from
+        // the early morning
+        assert_eq!(resting, [(27, Some(56)), (29, Some(58)), (30, Some(59))]);
+        let stats = points[0].heart_rate.as_ref().unwrap();
+        assert_eq!(stats.readings, 24);
+        assert_eq!(stats.zone_minutes.unwrap().iter().sum::<u32>(), 24 * 60);
+        // only the 27th has a night's sleep
+        let scores: Vec<_> = points
+            .iter()
+            .map(|point| point.sleep_score.as_ref().map(|score| score.score))
+            .collect();
+        assert_eq!(scores, [Some(82), None, None]);
+    }
+
+    #[tokio::test]
+    async fn latest() {
+        let (_dir, api) = summary_api();
+        let (status, body) = get(api, &format!("/events/{MAC}/latest")).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        insta::assert_snapshot!(serde_json::to_string_pretty(&body).unwrap());
+    }
+
+    #[tokio::test]
+    async fn summary_and_latest_without_data() {
+        let (_dir, api) = summary_api();
+        let empty = "11:11:11:11:11:11";
+        let (status, body) = get(
+            api.clone(),
+            &format!("/events/{empty}/summary?start=2024-11-26&end=2024-12-01"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(body, serde_json::json!([]));
+        let (status, body) = get(api, &format!("/events/{empty}/latest")).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(body, serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn summary_and_latest_missing_ring() {
+        let (_dir, api) = summary_api();
+        let missing = "22:22:22:22:22:22";
+        let (status, _) = get(
+            api.clone(),
+            &format!("/events/{missing}/summary?start=2024-11-26&end=2024-12-01"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = get(api, &format!("/events/{missing}/latest")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn summary_bad_range() {
+        let (_dir, api) = summary_api();
+        for query in [
+            "start=2024-11-26&end=2024-11-26",
+            "start=2024-11-26",
+            "start=2023-01-01&end=2024-12-01",
+        ] {
+            let (status, body) = get(api.clone(), &format!("/events/{MAC}/summary?{query}")).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{query}: {body}");
+        }
+    }
+
+    #[tokio::test]
+    async fn goals() {
+        let (_dir, api) = summary_api();
+        let (status, body) = get(
+            api.clone(),
+            &format!(
+                "/events/{MAC}/goals?start=2024-11-26&end=2024-12-01&goal=8500&today=2024-12-01"
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let report: GoalReport = serde_json::from_value(body).unwrap();
+        // 8100 steps on the 27th, 8700 on the 29th and 9000 on the 30th
+        let met: Vec<_> = report
+            .days
+            .iter()
+            .map(|day| (day.date.day(), day.met))
+            .collect();
+        assert_eq!(met, [(27, false), (29, true), (30, true)]);
+        assert_eq!(report.current_streak, 2);
+        assert_eq!(report.best_streak, 2);
+        // the 30th isn't over so only the 29th counts
+        let (_, body) = get(
+            api,
+            &format!(
+                "/events/{MAC}/goals?start=2024-11-26&end=2024-12-01&goal=8500&today=2024-11-30"
+            ),
+        )
+        .await;
+        let report: GoalReport = serde_json::from_value(body).unwrap();
+        assert_eq!(report.current_streak, 1);
+        assert!(report.days[2].partial);
+    }
+
+    #[tokio::test]
+    async fn goals_stored_goal() {
+        let (_dir, db) = summary_db();
+        let api = api(db.clone(), Auth::default());
+        let uri = format!("/events/{MAC}/goals?start=2024-11-26&end=2024-12-01&today=2024-12-01");
+        let (status, body) = get(api.clone(), &uri).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+        let mut settings = fissure::RingSettings::new(MAC);
+        settings.goals.steps = 9000;
+        db.set_settings(&settings).unwrap();
+        let (status, body) = get(api.clone(), &uri).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let report: GoalReport = serde_json::from_value(body).unwrap();
+        assert_eq!(report.goal, 9000);
+        assert_eq!(report.current_streak, 1);
+        let (status, body) = get(
+            api,
+            "/events/22:22:22:22:22:22/goals?start=2024-11-26&end=2024-12-01&goal=1",
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{body}");
+    }
+
+    fn cors_api(origins: &[&str]) -> (tempfile::TempDir, Router) {
+        let (dir, db) = test_db();
+        let auth = Auth::default().with_token("reader", Scope::Read);
+        let origins: Vec<_> = origins.iter().map(|o| o.to_string()).collect();
+        (dir, with_cors(api(db, auth), &origins))
+    }
+
+    fn preflight(origin: &str) -> Request<Body> {
+        Request::builder()
+            .method(Method::OPTIONS)
+            .uri(format!("/events/{MAC}"))
+            .header(ORIGIN, origin)
+            .header(ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .header(ACCESS_CONTROL_REQUEST_HEADERS, "authorization,content-type")
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn cors_preflight() {
+        let (_dir, api) = cors_api(&["https://dash.example"]);
+        let response = api
+            .oneshot(preflight("https://dash.example"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_ORIGIN], "https://dash.example");
+        let methods = headers[ACCESS_CONTROL_ALLOW_METHODS].to_str().unwrap();
+        for method in ["GET", "POST", "PUT", "PATCH", "DELETE"] {
+            assert!(methods.contains(method), "{methods}");
+        }
+        let allowed = headers[ACCESS_CONTROL_ALLOW_HEADERS].to_str().unwrap();
+        assert!(allowed.contains("authorization"), "{allowed}");
+        assert!(allowed.contains("content-type"), "{allowed}");
+    }
+
+    #[tokio::test]
+    async fn cors_get_from_allowed_origin() {
+        let (_dir, api) = cors_api(&["https://dash.example"]);
+        let request = Request::get("/rings")
+            .header(ORIGIN, "https://dash.example")
+            .header(AUTHORIZATION, "Bearer reader")
+            .body(Body::empty())
+            .unwrap();
+        let response = api.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://dash.example"
+        );
+    }
+
+    #[tokio::test]
+    async fn cors_disallowed_origin() {
+        let (_dir, api) = cors_api(&["https://dash.example"]);
+        let response = api
+            .clone()
+            .oneshot(preflight("https://evil.example"))
+            .await
+            .unwrap();
+        assert!(response
+            .headers()
+            .get(ACCESS_CONTROL_ALLOW_ORIGIN)
+            .is_none());
+        let request = Request::get("/rings")
+            .header(ORIGIN, "https://evil.example")
+            .header(AUTHORIZATION, "Bearer reader")
+            .body(Body::empty())
+            .unwrap();
+        let response = api.oneshot(request).await.unwrap();
+        assert!(response
+            .headers()
+            .get(ACCESS_CONTROL_ALLOW_ORIGIN)
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn cors_any_origin() {
+        let (_dir, api) = cors_api(&["*"]);
+        let response = api
+            .oneshot(preflight("https://anywhere.example"))
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+    }
+
+    #[tokio::test]
+    async fn no_cors_by_default() {
+        let (_dir, api) = cors_api(&[]);
+        let response = api
+            .oneshot(preflight("https://dash.example"))
+            .await
+            .unwrap();
+        assert!(response
+            .headers()
+            .get(ACCESS_CONTROL_ALLOW_ORIGIN)
+            .is_none());
+    }
+
+    fn post_events(uri: &str, events: &Value) -> Request<Body> {
+        Request::builder()
+            .method(Method::POST)
+            .uri(uri)
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(events.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn add_events_rejects_invalid_events() {
+        let (_dir, api) = test_api();
+        let events = serde_json::json!([
+            {
+                "mac": MAC,
+                "when": "2024-11-27T01:00:00Z",
+                "value": {"type": "HeartRate", "data": 70},
+            },
+            {
+                "mac": "11:11:11:11:11:11",
+                "when": "2024-11-27T01:00:00Z",
+                "value": {"type": "HeartRate", "data": 70},
+            },
+            {
+                "mac": MAC,
+                "when": "1970-01-01T00:00:00Z",
+                "value": {"type": "HeartRate", "data": 70},
+            },
+            {
+                "mac": MAC,
+                "when": "2024-02-30T00:00:00Z",
+                "value": {"type": "HeartRate", "data": 70},
+            },
+            {
+                "mac": MAC,
+                "when": "2999-01-01T00:00:00Z",
+                "value": {"type": "HeartRate", "data": 70},
+            },
+            {
+                "mac": "not a mac",
+                "when": "2024-11-27T01:00:00Z",
+                "value": {"type": "HeartRate", "data": 70},
+            },
+        ]);
+        let (status, body) =
+            send(api.clone(), post_events(&format!("/events/{MAC}"), &events)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{body}");
+        assert_eq!(body["code"], "unprocessable_entity");
+        let indices: Vec<_> = body["invalid"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|invalid| invalid["index"].as_u64().unwrap())
+            .collect();
+        assert_eq!(indices, [1, 2, 3, 4, 5]);
+        // nothing is added when any event is invalid
+        let (_, body) = get(api, &format!("/events/{MAC}?date=2024-11-27T00:00:00Z")).await;
+        assert_eq!(body["total"], 0);
+    }
+
+    #[tokio::test]
+    async fn add_events_normalizes_macs() {
+        let (_dir, api) = test_api();
+        let events = serde_json::json!([{
+            "mac": "aa-bb-cc-dd-ee-ff",
+            "when": "2024-11-27T01:00:00Z",
+            "value": {"type": "HeartRate", "data": 70},
+        }]);
+        let (status, body) = send(
+            api.clone(),
+            post_events("/events/aabbccddeeff?register=true", &events),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let (status, body) = get(api.clone(), "/ring/AA:BB:CC:DD:EE:FF").await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let (_, body) = get(api, "/events/AA:BB:CC:DD:EE:FF?date=2024-11-27T00:00:00Z").await;
+        assert_eq!(body["items"][0]["mac"], "AA:BB:CC:DD:EE:FF");
+    }
+
+    #[tokio::test]
+    async fn add_events_requires_the_ring() {
+        let (_dir, api) = test_api();
+        let events = serde_json::json!([{
+            "mac": "11:11:11:11:11:11",
+            "when": "2024-11-27T01:00:00Z",
+            "value": {"type": "HeartRate", "data": 70},
+        }]);
+        let (status, body) = send(
+            api.clone(),
+            post_events("/events/11:11:11:11:11:11", &events),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{body}");
+        let (status, _) = get(api.clone(), "/ring/11:11:11:11:11:11").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, body) = send(
+            api.clone(),
+            post_events("/events/11:11:11:11:11:11?register=true", &events),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let (status, body) = get(api, "/ring/11:11:11:11:11:11").await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(body["name"], "11:11:11:11:11:11");
+    }
+
+    #[tokio::test]
+    async fn add_events_invalid_path_mac() {
+        let (_dir, api) = test_api();
+        let (status, body) = send(api, post_events("/events/nope", &serde_json::json!([]))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+    }
+    /// A heart rate reply as lode prints it, readings at 1:00, 1:05 and 23:55
+    fn heart_rate_reply() -> Value {
+        let mut rates = vec![0u8; 288];
+        rates[12] = 64;
+        rates[13] = 60;
+        rates[287] = 80;
+        serde_json::json!({
+            "command": "heartRate",
+            "data": {
+                "range": 5,
+                "rates": rates,
+                "date": "2024-11-27 00:00:00.0",
+                "gaps": [],
+            },
+        })
+    }
+
+    #[tokio::test]
+    async fn add_replies_stores_events() {
+        let (_dir, api) = test_api();
+        let replies = serde_json::json!([
+            heart_rate_reply(),
+            {"command": "batteryInfo", "data": {"level": 80, "charging": false}},
+            {"command": "setTime", "data": {"device_time": null}},
+            {"command": "teleport"},
+        ]);
+        let uri = format!("/replies/{MAC}?requested_at=2024-11-27T10:00:00-05:00");
+        let (status, body) = send(api.clone(), post_events(&uri, &replies)).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let added: AddedReplies = serde_json::from_value(body).unwrap();
+        assert_eq!(added.counts.len(), 2);
+        assert_eq!(added.counts["heartRate"].inserted, 3);
+        assert_eq!(added.counts["battery"].inserted, 1);
+        let skipped: Vec<_> = added.skipped.iter().map(|s| s.index).collect();
+        assert_eq!(skipped, [2, 3]);
+        assert!(added.skipped[0].error.contains("setTime"));
+
+        let (_, body) = get(
+            api.clone(),
+            &format!("/events/{MAC}?date=2024-11-27T00:00:00Z&kind=heartRate"),
+        )
+        .await;
+        let page: EventsPage = serde_json::from_value(body).unwrap();
+        let stored: Vec<_> = page
+            .items
+            .iter()
+            .map(|event| {
+                let when = time::OffsetDateTime::try_from(event.when).unwrap();
+                ((when.hour(), when.minute()), event.value.clone())
+            })
+            .collect();
+        assert_eq!(
+            stored,
+            [
+                ((1, 0), EventData::HeartRate(64)),
+                ((1, 5), EventData::HeartRate(60)),
+                ((23, 55), EventData::HeartRate(80)),
+            ]
+        );
+        // the battery is stored at the ring's wall time when it was asked
+        let (_, body) = get(
+            api,
+            &format!("/events/{MAC}?date=2024-11-27T00:00:00Z&kind=battery"),
+        )
+        .await;
+        assert_eq!(body["items"][0]["when"], "2024-11-27T10:00:00.000Z");
+        assert_eq!(
+            body["items"][0]["value"]["data"],
+            serde_json::json!({"level": 80, "charging": false})
+        );
+    }
+
+    #[tokio::test]
+    async fn add_replies_requested_at_header() {
+        let (_dir, api) = test_api();
+        let replies = serde_json::json!([heart_rate_reply()]);
+        let mut request = post_events(&format!("/replies/{MAC}"), &replies);
+        request.headers_mut().insert(
+            REQUESTED_AT_HEADER,
+            HeaderValue::from_static("2024-11-27T10:00:00Z"),
+        );
+        let (status, body) = send(api, request).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(body["counts"]["heartRate"]["inserted"], 3, "{body}");
+    }
+
+    #[tokio::test]
+    async fn add_replies_requires_requested_at() {
+        let (_dir, api) = test_api();
+        let replies = serde_json::json!([heart_rate_reply()]);
+        let (status, _) = send(
+            api.clone(),
+            post_events(&format!("/replies/{MAC}"), &replies),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let mut request = post_events(&format!("/replies/{MAC}"), &replies);
+        request
+            .headers_mut()
+            .insert(REQUESTED_AT_HEADER, HeaderValue::from_static("yesterday"));
+        let (status, _) = send(api, request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn add_replies_requires_the_ring() {
+        let (_dir, api) = test_api();
+        let replies = serde_json::json!([heart_rate_reply()]);
+        let uri = "/replies/11:11:11:11:11:11?requested_at=2024-11-27T10:00:00Z";
+        let (status, _) = send(api, post_events(uri, &replies)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
 }
@@ -0,0 +1,63 @@
+//! Storing a large import with `Database::add_events`
+//!
+//! Run with `cargo bench -p fissure --bench add_events`
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use fissure::{Database, RingEvent, UpsertMode};
+use serde_json::json;
+use tempfile::TempDir;
+use time::{format_description::well_known::Rfc3339, macros::datetime, Duration};
+
+const MAC: &str = "00:00:00:00:00:00";
+/// About a week of minute by minute heart rates
+const EVENTS: usize = 10_000;
+
+fn events() -> Vec<RingEvent> {
+    let start = datetime!(2024-11-20 0:00 UTC);
+    (0..EVENTS)
+        .map(|i| {
+            let when = start + Duration::minutes(i as i64);
+            serde_json::from_value(json!({
+                "mac": MAC,
+                "when": when.format(&Rfc3339).unwrap(),
+                "value": { "type": "HeartRate", "data": 60 + i % 40 },
+            }))
+            .unwrap()
+        })
+        .collect()
+}
+
+fn database() -> (TempDir, Database) {
+    let dir = tempfile::tempdir().unwrap();
+    let db = Database::new(dir.path().join("events.db")).unwrap();
+    (dir, db)
+}
+
+fn add_events(c: &mut Criterion) {
+    let events = events();
+    let mut group = c.benchmark_group("add_events");
+    group.sample_size(10);
+    group.bench_function("empty_10k", |b| {
+        b.iter_batched(
+            database,
+            |(_dir, db)| db.add_events(&events, UpsertMode::Overwrite).unwrap(),
+            BatchSize::PerIteration,
+        )
+    });
+    // every event already stored, as when a sync is repeated
+    group.bench_function("existing_10k", |b| {
+        b.iter_batched(
+            || {
+                let (dir, db) = database();
+                db.add_events(&events, UpsertMode::Overwrite).unwrap();
+                (dir, db)
+            },
+            |(_dir, db)| db.add_events(&events, UpsertMode::Overwrite).unwrap(),
+            BatchSize::PerIteration,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, add_events);
+criterion_main!(benches);
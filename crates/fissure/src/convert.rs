@@ -0,0 +1,665 @@
+//! Conversions from the replies a ring sends into events that can be stored
+//!
+//! Only available with the `cole-mine` feature
+
+use std::time::Duration;
+
+use cole_mine::{
+    big_data::{OxygenData, OxygenMeasurement, SleepData, SleepSession},
+    heart_rate::HeartRate,
+    incoming_messages::{
+        framer::PacketFramer, notification::Notification, CommandReply, PacketParser, RawPacket,
+    },
+    sport_detail::SportDetail,
+    stress::StressData,
+    SleepStage,
+};
+use time::{Date, OffsetDateTime, PrimitiveDateTime};
+
+use crate::{
+    date::DateTime, Database, EventData, RawSyncRecord, Result, RingEvent, SleepSessionRecord,
+    SleepStageKind, SleepStageRecord, SyncCategory, UpsertCounts, UpsertMode,
+};
+
+/// How far apart the heart rate samples in a `HeartRate` reply are unless the
+/// context says otherwise
+pub const HEART_RATE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// What converting a reply needs to know about the sync it came from
+///
+/// Events are stored in the ring's local time, so only the wall time of
+/// `requested_at` is used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyncContext {
+    /// When the reply was requested in the ring's offset, replies that
+    /// describe the ring's current state happened then
+    pub requested_at: OffsetDateTime,
+    /// How far apart the samples of a `HeartRate` reply are
+    pub heart_rate_interval: Duration,
+}
+
+impl SyncContext {
+    pub fn new(requested_at: OffsetDateTime) -> Self {
+        Self {
+            requested_at,
+            heart_rate_interval: HEART_RATE_INTERVAL,
+        }
+    }
+
+    pub fn heart_rate_interval(mut self, interval: Duration) -> Self {
+        self.heart_rate_interval = interval;
+        self
+    }
+
+    /// The ring's day the sync was requested on, replies that count days back
+    /// count from this day
+    pub fn reference_date(&self) -> Date {
+        self.requested_at.date()
+    }
+
+    fn wall_time(&self) -> PrimitiveDateTime {
+        PrimitiveDateTime::new(self.requested_at.date(), self.requested_at.time())
+    }
+}
+
+/// Convert a reply from the ring with `mac` into the events it describes
+///
+/// Replies that don't describe any events, like `SetTime`, produce an empty
+/// list
+pub fn events_from_reply(mac: &str, reply: &CommandReply, context: &SyncContext) -> Vec<RingEvent> {
+    match reply {
+        CommandReply::HeartRate(hr) => heart_rate_events(mac, hr, context.heart_rate_interval),
+        CommandReply::SportDetail(details) => details
+            .iter()
+            .filter_map(|detail| sport_detail_event(mac, detail))
+            .collect(),
+        CommandReply::Stress(stress) => stress_events(mac, stress),
+        CommandReply::Sleep(sleep) => sleep_events(mac, sleep),
+        CommandReply::Oxygen(oxygen) => oxygen_events(mac, oxygen),
+        CommandReply::BatteryInfo { level, charging } => event(
+            mac,
+            context.wall_time(),
+            EventData::battery(*level, *charging),
+        )
+        .into_iter()
+        .collect(),
+        // the notification only has the level, so it's stored as not charging
+        CommandReply::Notification(Notification::Battery(level)) => {
+            event(mac, context.wall_time(), EventData::battery(*level, false))
+                .into_iter()
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// If `reply` is one `events_from_reply` converts, the others never describe
+/// any events
+pub fn is_convertible(reply: &CommandReply) -> bool {
+    matches!(
+        reply,
+        CommandReply::HeartRate(_)
+            | CommandReply::SportDetail(_)
+            | CommandReply::Stress(_)
+            | CommandReply::Sleep(_)
+            | CommandReply::Oxygen(_)
+            | CommandReply::BatteryInfo { .. }
+            | CommandReply::Notification(Notification::Battery(_))
+    )
+}
+
+/// Parse a raw sync again with the current parsers into the events it
+/// describes
+pub fn events_from_raw(record: &RawSyncRecord) -> Result<Vec<RingEvent>> {
+    let captured = PrimitiveDateTime::try_from(record.captured_at)?;
+    // only the wall time is used so the offset doesn't matter
+    let context = SyncContext::new(captured.assume_utc());
+    let mut parser = PacketParser::default();
+    let packets = match record.category {
+        SyncCategory::Sleep | SyncCategory::Oxygen => {
+            parser.expect_big_data(context.reference_date());
+            vec![RawPacket::V2(record.bytes.clone())]
+        }
+        SyncCategory::Stress => {
+            let day = context.reference_date() - time::Duration::days(record.day_offset.into());
+            parser.expect_stress_day(day);
+            uart_packets(&record.bytes)
+        }
+        SyncCategory::Activity | SyncCategory::HeartRate => uart_packets(&record.bytes),
+    };
+    let mut ret = Vec::new();
+    for packet in packets {
+        if let Some(reply) = parser.handle_packet(&packet)? {
+            if answers(record.category, &reply) {
+                ret.extend(events_from_reply(&record.mac, &reply, &context));
+            }
+        }
+    }
+    Ok(ret)
+}
+
+/// Parse every raw `category` sync kept for the ring with `mac` again and
+/// store the events with `mode`, oldest first so newer syncs win
+///
+/// Events a fix moved to a different time are stored alongside the ones the
+/// old parser produced, those have to be deleted separately
+pub fn reprocess(
+    db: &Database,
+    mac: &str,
+    category: SyncCategory,
+    mode: UpsertMode,
+) -> Result<UpsertCounts> {
+    let mut events = Vec::new();
+    for record in db.iter_raw_syncs(mac, category) {
+        events.extend(events_from_raw(&record)?);
+    }
+    db.add_events(&events, mode)
+}
+
+/// Split notifications back into 16 byte packets
+fn uart_packets(bytes: &[u8]) -> Vec<RawPacket> {
+    PacketFramer::default()
+        .push(bytes)
+        .into_iter()
+        .map(RawPacket::Uart)
+        .collect()
+}
+
+/// If `reply` is what syncing `category` asks for
+fn answers(category: SyncCategory, reply: &CommandReply) -> bool {
+    matches!(
+        (category, reply),
+        (SyncCategory::Activity, CommandReply::SportDetail(_))
+            | (SyncCategory::HeartRate, CommandReply::HeartRate(_))
+            | (SyncCategory::Stress, CommandReply::Stress(_))
+            | (SyncCategory::Sleep, CommandReply::Sleep(_))
+            | (SyncCategory::Oxygen, CommandReply::Oxygen(_))
+    )
+}
+
+fn heart_rate_events(mac: &str, hr: &HeartRate, interval: Duration) -> Vec<RingEvent> {
+    let mut when = hr.date;
+    let mut ret = Vec::new();
+    for rate in hr.rates.iter().copied() {
+        if when.date() != hr.date.date() {
+            break;
+        }
+        if rate != 0 {
+            ret.extend(event(mac, when, EventData::heart_rate(rate.into())));
+        }
+        when += interval;
+    }
+    ret
+}
+
+fn sport_detail_event(mac: &str, detail: &SportDetail) -> Option<RingEvent> {
+    let when = detail
+        .date()
+        .and_then(|date| Ok(date.with_time(detail.start_time()?)))
+        .inspect_err(|e| log::warn!("skipping sport detail {detail:?}: {e}"))
+        .ok()?;
+    event(mac, when, detail.into())
+}
+
+fn stress_events(mac: &str, stress: &StressData) -> Vec<RingEvent> {
+    stress
+        .samples()
+        .filter_map(|(when, value)| event(mac, when, EventData::stress(value?.into())))
+        .collect()
+}
+
+fn sleep_events(mac: &str, sleep: &SleepData) -> Vec<RingEvent> {
+    sleep
+        .sessions
+        .iter()
+        .flat_map(|session| {
+            let minutes: u16 = session
+                .stages
+                .iter()
+                .map(|stage| match stage {
+                    SleepStage::Light(m) | SleepStage::Deep(m) | SleepStage::Rem(m) => {
+                        u16::from(*m)
+                    }
+                    SleepStage::Awake(_) => 0,
+                })
+                .sum();
+            let record = SleepSessionRecord::try_from(session)
+                .inspect_err(|e| log::warn!("unable to convert sleep session {session:?}: {e}"))
+                .ok();
+            event(mac, session.start, EventData::sleep(minutes))
+                .into_iter()
+                .chain(
+                    record.and_then(|record| {
+                        event(mac, session.start, EventData::SleepSession(record))
+                    }),
+                )
+        })
+        .collect()
+}
+
+impl TryFrom<&SleepSession> for SleepSessionRecord {
+    type Error = crate::Error;
+
+    fn try_from(session: &SleepSession) -> Result<Self, Self::Error> {
+        Ok(Self {
+            start: session.start.try_into()?,
+            end: session.end.try_into()?,
+            stages: session
+                .stages
+                .iter()
+                .map(|stage| {
+                    let (stage, minutes) = match stage {
+                        SleepStage::Light(m) => (SleepStageKind::Light, m),
+                        SleepStage::Deep(m) => (SleepStageKind::Deep, m),
+                        SleepStage::Rem(m) => (SleepStageKind::Rem, m),
+                        SleepStage::Awake(m) => (SleepStageKind::Awake, m),
+                    };
+                    SleepStageRecord {
+                        stage,
+                        minutes: (*minutes).into(),
+                    }
+                })
+                .collect(),
+        })
+    }
+}
+
+impl TryFrom<&SleepSessionRecord> for SleepSession {
+    type Error = crate::Error;
+
+    /// A stage longer than a `SleepStage` can hold is split into as many
+    /// stages of the same kind as it takes
+    fn try_from(record: &SleepSessionRecord) -> Result<Self, Self::Error> {
+        let mut stages = Vec::with_capacity(record.stages.len());
+        for stage in &record.stages {
+            let mut minutes = stage.minutes;
+            while minutes > 0 {
+                let m = minutes.min(u8::MAX.into());
+                minutes -= m;
+                let m = m as u8;
+                stages.push(match stage.stage {
+                    SleepStageKind::Light => SleepStage::Light(m),
+                    SleepStageKind::Deep => SleepStage::Deep(m),
+                    SleepStageKind::Rem => SleepStage::Rem(m),
+                    SleepStageKind::Awake => SleepStage::Awake(m),
+                });
+            }
+        }
+        Ok(Self {
+            start: record.start.try_into()?,
+            end: record.end.try_into()?,
+            stages,
+        })
+    }
+}
+
+fn oxygen_events(mac: &str, oxygen: &OxygenData) -> Vec<RingEvent> {
+    oxygen
+        .samples
+        .iter()
+        .filter(|sample| sample.max != 0)
+        .filter_map(|sample| event(mac, sample.when, sample.into()))
+        .collect()
+}
+
+fn event(mac: &str, when: PrimitiveDateTime, value: EventData) -> Option<RingEvent> {
+    let when = DateTime::try_from(when)
+        .inspect_err(|e| log::warn!("unable to convert {when} to a DateTime: {e}"))
+        .ok()?;
+    Some(RingEvent::new(mac, when, value))
+}
+
+impl From<&SportDetail> for EventData {
+    fn from(detail: &SportDetail) -> Self {
+        EventData::activity(
+            detail.steps.into(),
+            f64::from(detail.calories) / 1000.0,
+            detail.distance.into(),
+        )
+    }
+}
+
+impl From<SportDetail> for EventData {
+    fn from(detail: SportDetail) -> Self {
+        Self::from(&detail)
+    }
+}
+
+impl From<&OxygenMeasurement> for EventData {
+    fn from(sample: &OxygenMeasurement) -> Self {
+        EventData::oxygen_range(sample.min, sample.max)
+    }
+}
+
+impl From<OxygenMeasurement> for EventData {
+    fn from(sample: OxygenMeasurement) -> Self {
+        Self::from(&sample)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cole_mine::incoming_messages::RealTimeEvent;
+    use cole_mine::DurationExt as _;
+    use time::macros::{date, datetime};
+
+    use super::*;
+
+    const MAC: &str = "00:00:00:00:00:00";
+
+    #[track_caller]
+    fn when(event: &RingEvent) -> PrimitiveDateTime {
+        event.when.try_into().unwrap()
+    }
+
+    fn context() -> SyncContext {
+        SyncContext::new(datetime!(2024-11-27 10:00 -5))
+    }
+
+    #[test]
+    fn heart_rate() {
+        let mut rates = vec![0u8; 300];
+        rates[0] = 60;
+        rates[12] = 70;
+        rates[287] = 80;
+        rates[288] = 90;
+        let reply = CommandReply::HeartRate(HeartRate {
+            range: 5,
+            rates,
+            date: datetime!(2024-11-27 0:00),
+            gaps: Vec::new(),
+        });
+        let events = events_from_reply(MAC, &reply, &context());
+        assert_eq!(events.len(), 3);
+        assert_eq!(when(&events[0]), datetime!(2024-11-27 0:00));
+        assert_eq!(events[0].value, EventData::HeartRate(60));
+        assert_eq!(when(&events[1]), datetime!(2024-11-27 1:00));
+        assert_eq!(events[1].value, EventData::HeartRate(70));
+        assert_eq!(when(&events[2]), datetime!(2024-11-27 23:55));
+        assert_eq!(events[2].value, EventData::HeartRate(80));
+    }
+
+    #[test]
+    fn heart_rate_interval() {
+        let reply = CommandReply::HeartRate(HeartRate {
+            range: 30,
+            rates: vec![60, 0, 70, 80, 90],
+            date: datetime!(2024-11-27 22:30),
+            gaps: Vec::new(),
+        });
+        let context = context().heart_rate_interval(Duration::minutes(30));
+        let events = events_from_reply(MAC, &reply, &context);
+        // the samples past midnight belong to the next day's reply
+        assert_eq!(events.len(), 2);
+        assert_eq!(when(&events[0]), datetime!(2024-11-27 22:30));
+        assert_eq!(events[0].value, EventData::HeartRate(60));
+        assert_eq!(when(&events[1]), datetime!(2024-11-27 23:30));
+        assert_eq!(events[1].value, EventData::HeartRate(70));
+    }
+
+    #[test]
+    fn sport_detail() {
+        let reply = CommandReply::SportDetail(vec![SportDetail {
+            year: 2024,
+            month: 11,
+            day: 27,
+            time_index: 34,
+            calories: 12_500,
+            steps: 1000,
+            distance: 80,
+        }]);
+        let events = events_from_reply(MAC, &reply, &context());
+        assert_eq!(events.len(), 1);
+        assert_eq!(when(&events[0]), datetime!(2024-11-27 8:30));
+        assert_eq!(events[0].value, EventData::activity(1000, 12.5, 80));
+    }
+
+    #[test]
+    fn sport_detail_first_and_last_slot() {
+        let detail = |time_index| SportDetail {
+            year: 2024,
+            month: 2,
+            day: 29,
+            time_index,
+            calories: 0,
+            steps: 10,
+            distance: 8,
+        };
+        // a slot past the end of the day is dropped rather than rolled over
+        let reply = CommandReply::SportDetail(vec![detail(0), detail(95), detail(96)]);
+        let events = events_from_reply(MAC, &reply, &context());
+        assert_eq!(events.len(), 2);
+        assert_eq!(when(&events[0]), datetime!(2024-02-29 0:00));
+        assert_eq!(when(&events[1]), datetime!(2024-02-29 23:45));
+    }
+
+    #[test]
+    fn stress() {
+        let mut measurements = vec![0u8; 48];
+        measurements[1] = 20;
+        measurements[47] = 40;
+        let reply = CommandReply::Stress(StressData {
+            date: date!(2024 - 11 - 27),
+            minutes_apart: 30,
+            readings: measurements
+                .into_iter()
+                .zip(0..)
+                .map(|(value, index)| cole_mine::stress::StressReading {
+                    index,
+                    value: (value != 0).then_some(value),
+                })
+                .collect(),
+        });
+        let events = events_from_reply(MAC, &reply, &context());
+        assert_eq!(events.len(), 2);
+        assert_eq!(when(&events[0]), datetime!(2024-11-27 0:30));
+        assert_eq!(events[0].value, EventData::Stress(20));
+        assert_eq!(when(&events[1]), datetime!(2024-11-27 23:30));
+        assert_eq!(events[1].value, EventData::Stress(40));
+    }
+
+    #[test]
+    fn sleep() {
+        let reply = CommandReply::Sleep(SleepData {
+            sessions: vec![SleepSession {
+                start: datetime!(2024-11-26 23:00),
+                end: datetime!(2024-11-27 7:00),
+                stages: vec![
+                    SleepStage::Light(60),
+                    SleepStage::Awake(15),
+                    SleepStage::Deep(90),
+                    SleepStage::Rem(30),
+                ],
+            }],
+        });
+        let events = events_from_reply(MAC, &reply, &context());
+        assert_eq!(events.len(), 2);
+        assert_eq!(when(&events[0]), datetime!(2024-11-26 23:00));
+        assert_eq!(events[0].value, EventData::Sleep(180));
+        assert_eq!(when(&events[1]), datetime!(2024-11-26 23:00));
+        let EventData::SleepSession(record) = &events[1].value else {
+            panic!("expected a sleep session {:?}", events[1]);
+        };
+        assert_eq!(
+            PrimitiveDateTime::try_from(record.end).unwrap(),
+            datetime!(2024-11-27 7:00)
+        );
+        assert_eq!(
+            record.stages,
+            [
+                SleepStageRecord {
+                    stage: SleepStageKind::Light,
+                    minutes: 60
+                },
+                SleepStageRecord {
+                    stage: SleepStageKind::Awake,
+                    minutes: 15
+                },
+                SleepStageRecord {
+                    stage: SleepStageKind::Deep,
+                    minutes: 90
+                },
+                SleepStageRecord {
+                    stage: SleepStageKind::Rem,
+                    minutes: 30
+                },
+            ]
+        );
+        let CommandReply::Sleep(sleep) = &reply else {
+            unreachable!()
+        };
+        assert_eq!(SleepSession::try_from(record).unwrap(), sleep.sessions[0]);
+    }
+
+    #[test]
+    fn long_sleep_stage() {
+        let record = SleepSessionRecord {
+            start: datetime!(2024-11-26 23:00).try_into().unwrap(),
+            end: datetime!(2024-11-27 7:00).try_into().unwrap(),
+            stages: vec![
+                SleepStageRecord {
+                    stage: SleepStageKind::Deep,
+                    minutes: 600,
+                },
+                SleepStageRecord {
+                    stage: SleepStageKind::Rem,
+                    minutes: 0,
+                },
+            ],
+        };
+        let session = SleepSession::try_from(&record).unwrap();
+        assert_eq!(
+            session.stages,
+            [
+                SleepStage::Deep(255),
+                SleepStage::Deep(255),
+                SleepStage::Deep(90)
+            ]
+        );
+    }
+
+    #[test]
+    fn oxygen() {
+        let reply = CommandReply::Oxygen(OxygenData {
+            samples: vec![
+                cole_mine::big_data::OxygenMeasurement {
+                    min: 0,
+                    max: 0,
+                    when: datetime!(2024-11-27 0:00),
+                },
+                cole_mine::big_data::OxygenMeasurement {
+                    min: 94,
+                    max: 98,
+                    when: datetime!(2024-11-27 1:00),
+                },
+            ],
+        });
+        let events = events_from_reply(MAC, &reply, &context());
+        assert_eq!(events.len(), 1);
+        assert_eq!(when(&events[0]), datetime!(2024-11-27 1:00));
+        assert_eq!(events[0].value, EventData::oxygen_range(94, 98));
+    }
+
+    /// The sleep reply to a sync on 2024-11-27, a night ending the day before
+    /// and a nap that day
+    const SLEEP_CAPTURE: &[u8] = &[
+        0xbc, 0x27, 21, 0, 0x6b, 0xc0, // header
+        3, 1, 10, 0x82, 0x05, 0x00, 0x01, 2, 150, 3, 90, 4, 46, // 23:30-04:16
+        0, 6, 0x0c, 0x03, 0x48, 0x03, 2, 60, // 13:00-14:00
+    ];
+
+    #[test]
+    fn reprocess_updates_in_place() {
+        let db = Database::test().unwrap();
+        let record = RawSyncRecord::new(
+            MAC,
+            datetime!(2024-11-27 10:00),
+            SyncCategory::Sleep,
+            0,
+            SLEEP_CAPTURE.to_vec(),
+        )
+        .unwrap();
+        db.add_raw_sync(&record).unwrap();
+        let fixed = events_from_raw(&record).unwrap();
+        assert_eq!(fixed.len(), 4);
+        assert_eq!(when(&fixed[0]), datetime!(2024-11-25 23:30));
+        assert_eq!(when(&fixed[2]), datetime!(2024-11-27 13:00));
+        // what a parser that misread the stages stored from the same capture
+        let buggy: Vec<_> = fixed
+            .iter()
+            .cloned()
+            .map(|mut event| {
+                match &mut event.value {
+                    EventData::Sleep(minutes) => *minutes += 60,
+                    EventData::SleepSession(session) => session.stages.truncate(1),
+                    _ => {}
+                }
+                event
+            })
+            .collect();
+        db.add_events(&buggy, UpsertMode::Overwrite).unwrap();
+        let counts = reprocess(&db, MAC, SyncCategory::Sleep, UpsertMode::Overwrite).unwrap();
+        assert_eq!((counts.inserted, counts.updated), (0, 4));
+        let stored = db.get_events_in_range(MAC, ..).unwrap();
+        assert_eq!(stored.len(), fixed.len());
+        for event in &fixed {
+            assert!(stored.contains(event), "{event:?} missing from {stored:?}");
+        }
+        // nothing else was kept for the ring
+        assert_eq!(
+            reprocess(&db, MAC, SyncCategory::Oxygen, UpsertMode::Overwrite).unwrap(),
+            UpsertCounts::default()
+        );
+    }
+
+    #[test]
+    fn raw_stress_day() {
+        let mut bytes = Vec::new();
+        for start in [&[55, 0, 3, 30][..], &[55, 1, 0, 0, 20], &[55, 2, 40]] {
+            let mut packet = start.to_vec();
+            packet.resize(15, 0);
+            packet.push(packet.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)));
+            bytes.extend(packet);
+        }
+        let record = RawSyncRecord::new(
+            MAC,
+            datetime!(2024-11-27 10:00),
+            SyncCategory::Stress,
+            1,
+            bytes,
+        )
+        .unwrap();
+        let events = events_from_raw(&record).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(when(&events[0]), datetime!(2024-11-26 0:30));
+        assert_eq!(events[0].value, EventData::Stress(20));
+        assert_eq!(when(&events[1]), datetime!(2024-11-26 6:00));
+        assert_eq!(events[1].value, EventData::Stress(40));
+    }
+
+    #[test]
+    fn battery() {
+        let info = CommandReply::BatteryInfo {
+            level: 50,
+            charging: true,
+        };
+        let notification = CommandReply::Notification(Notification::Battery(49));
+        for (reply, expected) in [
+            (info, EventData::battery(50, true)),
+            (notification, EventData::battery(49, false)),
+        ] {
+            let events = events_from_reply(MAC, &reply, &context());
+            assert_eq!(events.len(), 1);
+            // the ring's wall time, not UTC
+            assert_eq!(when(&events[0]), datetime!(2024-11-27 10:00));
+            assert_eq!(events[0].value, expected);
+        }
+    }
+
+    #[test]
+    fn no_events() {
+        let reply = CommandReply::RealTimeData(RealTimeEvent::HeartRate(72));
+        assert!(!is_convertible(&reply));
+        assert!(events_from_reply(MAC, &reply, &context()).is_empty());
+    }
+}
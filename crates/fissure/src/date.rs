@@ -5,7 +5,7 @@ use serde::{Deserialize, Serialize};
 use structsy::derive::{embedded_queries, PersistentEmbedded};
 use time::{OffsetDateTime, PrimitiveDateTime};
 
-use crate::Result;
+use crate::{Error, Result};
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, PersistentEmbedded, bon::Builder)]
 pub struct DateTime {
@@ -21,6 +21,7 @@ pub struct DateTime {
 }
 
 #[embedded_queries(DateTime)]
+#[allow(dead_code)]
 pub trait DateTimeQuery {
     fn with_year(self, year: u16) -> Self;
     fn with_month(self, month: u8) -> Self;
@@ -32,12 +33,20 @@ pub trait DateTimeQuery {
     fn with_hms(self, hour: u8, minute: u8, second: u8) -> Self;
 }
 
+impl DateTime {
+    /// The year and month as one number that orders the same way, `2024-01` is
+    /// `202401`
+    pub fn month_key(&self) -> u32 {
+        u32::from(self.year) * 100 + u32::from(self.month)
+    }
+}
+
 impl TryFrom<time::OffsetDateTime> for DateTime {
-    type Error = Box<dyn std::error::Error>;
+    type Error = Error;
 
     fn try_from(value: time::OffsetDateTime) -> Result<Self> {
         Ok(Self {
-            year: value.year().try_into()?,
+            year: value.year().try_into().map_err(Error::invalid_date)?,
             month: value.month().into(),
             day: value.day(),
             hour: value.hour(),
@@ -48,11 +57,11 @@ impl TryFrom<time::OffsetDateTime> for DateTime {
 }
 
 impl TryFrom<time::PrimitiveDateTime> for DateTime {
-    type Error = Box<dyn std::error::Error>;
+    type Error = Error;
 
     fn try_from(value: time::PrimitiveDateTime) -> Result<Self> {
         Ok(Self {
-            year: value.year().try_into()?,
+            year: value.year().try_into().map_err(Error::invalid_date)?,
             month: value.month().into(),
             day: value.day(),
             hour: value.hour(),
@@ -63,11 +72,11 @@ impl TryFrom<time::PrimitiveDateTime> for DateTime {
 }
 
 impl TryFrom<time::Date> for DateTime {
-    type Error = Box<dyn std::error::Error>;
+    type Error = Error;
 
     fn try_from(value: time::Date) -> Result<Self> {
         Ok(Self {
-            year: value.year().try_into()?,
+            year: value.year().try_into().map_err(Error::invalid_date)?,
             month: value.month().into(),
             day: value.day(),
             hour: 0,
@@ -78,22 +87,24 @@ impl TryFrom<time::Date> for DateTime {
 }
 
 impl TryFrom<DateTime> for PrimitiveDateTime {
-    type Error = Box<dyn std::error::Error>;
+    type Error = Error;
 
     fn try_from(value: DateTime) -> std::result::Result<Self, Self::Error> {
         Ok(PrimitiveDateTime::new(
             time::Date::from_calendar_date(
-                value.year.try_into()?,
-                value.month.try_into()?,
+                value.year.into(),
+                value.month.try_into().map_err(Error::invalid_date)?,
                 value.day,
-            )?,
-            time::Time::from_hms(value.hour, value.minute, value.second)?,
+            )
+            .map_err(Error::invalid_date)?,
+            time::Time::from_hms(value.hour, value.minute, value.second)
+                .map_err(Error::invalid_date)?,
         ))
     }
 }
 
 impl TryFrom<DateTime> for OffsetDateTime {
-    type Error = Box<dyn std::error::Error>;
+    type Error = Error;
 
     fn try_from(value: DateTime) -> std::result::Result<Self, Self::Error> {
         Ok(PrimitiveDateTime::try_from(value)?.assume_utc())
@@ -102,9 +113,7 @@ impl TryFrom<DateTime> for OffsetDateTime {
 
 impl PartialEq<OffsetDateTime> for DateTime {
     fn eq(&self, other: &OffsetDateTime) -> bool {
-        let Ok(year) = i32::try_from(self.year) else {
-            return false;
-        };
+        let year = i32::from(self.year);
         let Ok(month) = time::Month::try_from(self.month) else {
             return false;
         };
@@ -124,65 +133,96 @@ impl PartialEq<DateTime> for OffsetDateTime {
 }
 
 impl FromStr for DateTime {
-    type Err = Box<dyn std::error::Error>;
+    type Err = Error;
 
+    /// Parse `YYYY-MM-DD` optionally followed by `THH:MM[:SS[.fraction]]` and a
+    /// `Z` or `±HH:MM` suffix, times with an offset are converted to UTC
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
-        let mut dt_parts = s.split('T');
-        let date_part = dt_parts
-            .next()
-            .ok_or_else(|| format!("String too short: `{s}`"))?;
+        let (date_part, time_part) = match s.split_once('T') {
+            Some((date, time)) => (date, Some(time)),
+            None => (s, None),
+        };
         let mut date_parts = date_part.split('-');
-        let year: u16 = date_parts
-            .next()
-            .ok_or_else(|| format!("date part too short: `{s}`"))?
-            .parse()?;
-        let month: u8 = date_parts
-            .next()
-            .ok_or_else(|| format!("date part missing month: `{s}`"))?
-            .parse()?;
-        let day: u8 = date_parts
-            .next()
-            .ok_or_else(|| format!("date part missing day: `{s}`"))?
-            .parse()?;
-        let Some(time_part) = dt_parts.next() else {
-            return Ok(Self {
-                year,
-                month,
-                day,
-                hour: 0,
-                minute: 0,
-                second: 0,
-            });
+        let year: u16 = field(date_parts.next(), "year", s)?;
+        let month: u8 = field(date_parts.next(), "month", s)?;
+        let day: u8 = field(date_parts.next(), "day", s)?;
+        if date_parts.next().is_some() {
+            return Err(Error::invalid_date(format!("trailing date parts: `{s}`")));
+        }
+        let month =
+            time::Month::try_from(month).map_err(|e| Error::invalid_date(format!("{e}: `{s}`")))?;
+        let date = time::Date::from_calendar_date(year.into(), month, day)
+            .map_err(|e| Error::invalid_date(format!("{e}: `{s}`")))?;
+        let Some(time_part) = time_part else {
+            return date.try_into();
         };
-        let mut time_parts = time_part.split(":");
-        let hour = time_parts
-            .next()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or_default();
-        let minute = time_parts
-            .next()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or_default();
-        let second = time_parts
-            .next()
-            .and_then(|s| {
-                let end_idx = s
-                    .find(|ch: char| !ch.is_ascii_digit())
-                    .unwrap_or_else(|| s.len());
-                s.get(..end_idx)?.parse().ok()
-            })
-            .unwrap_or_default();
-        Ok(Self {
-            year,
-            month,
-            day,
-            hour,
-            minute,
-            second,
-        })
+        let (clock, offset) = match time_part.find(['Z', 'z', '+', '-']) {
+            Some(idx) => (&time_part[..idx], parse_offset(&time_part[idx..], s)?),
+            None => (time_part, time::UtcOffset::UTC),
+        };
+        let mut time_parts = clock.split(':');
+        let hour: u8 = field(time_parts.next(), "hour", s)?;
+        let minute: u8 = field(time_parts.next(), "minute", s)?;
+        let second: u8 = match time_parts.next() {
+            // fractions of a second aren't stored
+            Some(second) => field(second.split('.').next(), "second", s)?,
+            None => 0,
+        };
+        if time_parts.next().is_some() {
+            return Err(Error::invalid_date(format!("trailing time parts: `{s}`")));
+        }
+        let time = time::Time::from_hms(hour, minute, second)
+            .map_err(|e| Error::invalid_date(format!("{e}: `{s}`")))?;
+        PrimitiveDateTime::new(date, time)
+            .assume_offset(offset)
+            .to_offset(time::UtcOffset::UTC)
+            .try_into()
     }
 }
 
+/// Parse a required numeric part of a date time string, `s` is the whole
+/// string for error messages
+fn field<T: FromStr>(part: Option<&str>, name: &str, s: &str) -> Result<T> {
+    let part = part
+        .filter(|part| !part.is_empty())
+        .ok_or_else(|| Error::invalid_date(format!("missing {name}: `{s}`")))?;
+    if !part.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(Error::invalid_date(format!(
+            "invalid {name} `{part}`: `{s}`"
+        )));
+    }
+    part.parse()
+        .map_err(|_| Error::invalid_date(format!("invalid {name} `{part}`: `{s}`")))
+}
+
+/// Parse a `Z`, `±HH:MM`, `±HHMM` or `±HH` suffix
+fn parse_offset(suffix: &str, s: &str) -> Result<time::UtcOffset> {
+    if suffix.eq_ignore_ascii_case("z") {
+        return Ok(time::UtcOffset::UTC);
+    }
+    let (sign, rest) = suffix.split_at(1);
+    if sign != "+" && sign != "-" {
+        return Err(Error::invalid_date(format!(
+            "invalid offset `{suffix}`: `{s}`"
+        )));
+    }
+    let (hours, minutes) = match rest.split_once(':') {
+        Some(parts) => parts,
+        None if rest.len() == 4 => rest.split_at(2),
+        None if rest.len() == 2 => (rest, "00"),
+        None => (rest, ""),
+    };
+    let hours: i8 = field(Some(hours), "offset hours", s)?;
+    let minutes: i8 = field(Some(minutes), "offset minutes", s)?;
+    let (hours, minutes) = if sign == "-" {
+        (-hours, -minutes)
+    } else {
+        (hours, minutes)
+    };
+    time::UtcOffset::from_hms(hours, minutes, 0)
+        .map_err(|e| Error::invalid_date(format!("{e}: `{s}`")))
+}
+
 impl fmt::Display for DateTime {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_fmt(format_args!("{:04}-", self.year))?;
@@ -209,15 +249,68 @@ impl<'de> Deserialize<'de> for DateTime {
     where
         D: serde::Deserializer<'de>,
     {
-        time::serde::rfc3339::deserialize(d)?
+        d.deserialize_any(DateTimeVisitor)
+    }
+}
+
+/// Accepts an RFC 3339 string or the tuple format `time` uses for
+/// `OffsetDateTime`, which the web servers wrote before they shared this crate
+struct DateTimeVisitor;
+
+impl<'de> serde::de::Visitor<'de> for DateTimeVisitor {
+    type Value = DateTime;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an RFC 3339 date time string or a legacy date time tuple")
+    }
+
+    fn visit_str<E>(self, v: &str) -> std::result::Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        OffsetDateTime::parse(v, &time::format_description::well_known::Rfc3339)
+            .map_err(E::custom)?
+            .try_into()
+            .map_err(E::custom)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> std::result::Result<Self::Value, A::Error>
+    where
+        A: serde::de::SeqAccess<'de>,
+    {
+        use serde::de::Error;
+        fn next<'de, A, T>(seq: &mut A, name: &str) -> std::result::Result<T, A::Error>
+        where
+            A: serde::de::SeqAccess<'de>,
+            T: Deserialize<'de>,
+        {
+            seq.next_element()?
+                .ok_or_else(|| A::Error::custom(format!("legacy date time missing {name}")))
+        }
+        let year: i32 = next(&mut seq, "year")?;
+        let ordinal: u16 = next(&mut seq, "ordinal")?;
+        let hour: u8 = next(&mut seq, "hour")?;
+        let minute: u8 = next(&mut seq, "minute")?;
+        let second: u8 = next(&mut seq, "second")?;
+        let nanos: u32 = next(&mut seq, "nanosecond")?;
+        let offset_hours: i8 = next(&mut seq, "offset hours")?;
+        let offset_minutes: i8 = next(&mut seq, "offset minutes")?;
+        let offset_seconds: i8 = next(&mut seq, "offset seconds")?;
+        let date = time::Date::from_ordinal_date(year, ordinal).map_err(A::Error::custom)?;
+        let time =
+            time::Time::from_hms_nano(hour, minute, second, nanos).map_err(A::Error::custom)?;
+        let offset = time::UtcOffset::from_hms(offset_hours, offset_minutes, offset_seconds)
+            .map_err(A::Error::custom)?;
+        PrimitiveDateTime::new(date, time)
+            .assume_offset(offset)
+            .to_offset(time::UtcOffset::UTC)
             .try_into()
-            .map_err(|e| serde::de::Error::custom(e))
+            .map_err(A::Error::custom)
     }
 }
 
 impl PartialOrd for DateTime {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        use std::cmp::Ordering;
         Some(self.cmp(other))
     }
 }
@@ -271,11 +364,71 @@ mod tests {
 
     #[test]
     fn convert_revert_many() {
-        (0..=u16::MAX).into_iter().into_par_iter().for_each(|n| {
+        (0..=u16::MAX).into_par_iter().for_each(|n| {
             do_convert_revert((n as i64) * 4);
         });
     }
 
+    #[test]
+    fn malformed_strings_fail() {
+        for s in [
+            "",
+            "2024",
+            "2024-11",
+            "2024-11-",
+            "2024-13-01",
+            "2024-00-01",
+            "2024-11-31",
+            "2023-02-29",
+            "2024-13-45T99:99:99",
+            "2024-11-27-01",
+            "2024-11-27T",
+            "2024-11-27T12",
+            "2024-11-27T24:00:00",
+            "2024-11-27T12:60:00",
+            "2024-11-27T12:00:60",
+            "2024-11-27Tab:00:00",
+            "2024-11-27T12:00:00:00",
+            "2024-11-27T12:00:0x",
+            "2024-11-27T12:00:00+",
+            "2024-11-27T12:00:00+26:00",
+            "2024-11-27T12:00:00+5",
+            "2024-11-27T12:00:00Zjunk",
+            "2024-11-27 12:00:00",
+            "+2024-11-27",
+            "-1-11-27",
+        ] {
+            let e = s.parse::<DateTime>().unwrap_err();
+            assert!(matches!(e, Error::InvalidDate(_)), "{s}: {e:?}");
+        }
+    }
+
+    #[test]
+    fn offsets_are_normalized() {
+        let expected = DateTime::builder()
+            .year(2024)
+            .month(11)
+            .day(27)
+            .hour(17)
+            .minute(30)
+            .build();
+        for s in [
+            "2024-11-27T17:30",
+            "2024-11-27T17:30:00",
+            "2024-11-27T17:30:00.000Z",
+            "2024-11-27T17:30:00z",
+            "2024-11-27T12:30:00-05:00",
+            "2024-11-27T23:00:00+0530",
+            "2024-11-28T03:30:00+10",
+        ] {
+            assert_eq!(s.parse::<DateTime>().unwrap(), expected, "{s}");
+        }
+        assert_eq!(
+            "2024-02-29".parse::<DateTime>().unwrap(),
+            DateTime::builder().year(2024).month(2).day(29).build()
+        );
+    }
+
     #[track_caller]
     fn do_convert_revert(timestamp: i64) {
         let dt = OffsetDateTime::from_unix_timestamp(timestamp).unwrap();
@@ -295,11 +448,27 @@ mod tests {
 
     #[test]
     fn serde_round_trip_many() {
-        (0..=u16::MAX).into_iter().into_par_iter().for_each(|n| {
+        (0..=u16::MAX).into_par_iter().for_each(|n| {
             do_serde_round_trip((n as i64) * 4);
         });
     }
 
+    #[test]
+    fn legacy_tuple() {
+        let dt: DateTime = serde_json::from_str("[2001, 31, 13, 14, 15, 0, -5, 0, 0]").unwrap();
+        assert_eq!(
+            dt,
+            DateTime::builder()
+                .year(2001)
+                .month(1)
+                .day(31)
+                .hour(18)
+                .minute(14)
+                .second(15)
+                .build()
+        );
+    }
+
     #[track_caller]
     fn do_serde_round_trip(timestamp: i64) {
         let dt = OffsetDateTime::from_unix_timestamp(timestamp).unwrap();
@@ -0,0 +1,86 @@
+use std::fmt::{self, Display};
+
+/// Everything that can go wrong reading or writing the database
+///
+/// Converts into a `Box<dyn std::error::Error>` for callers that don't need
+/// to tell the cases apart
+#[derive(Debug)]
+pub enum Error {
+    /// No ring with `mac` is in the database
+    RingNotFound { mac: String },
+    /// A ring with `mac` is already in the database
+    RingAlreadyExists { mac: String },
+    /// The database stores `name` in a layout this version can't read or upgrade
+    Incompatible { name: String },
+    /// A date couldn't be converted to or from the stored format
+    InvalidDate(String),
+    /// A string that isn't a bluetooth address was used as one
+    InvalidMac(String),
+    /// An error from the underlying storage
+    Storage(structsy::StructsyError),
+    /// An error working with the database file directly
+    Io(std::io::Error),
+    /// A stored raw sync couldn't be parsed
+    #[cfg(feature = "cole-mine")]
+    Parse(cole_mine::Error),
+}
+
+impl Error {
+    pub(crate) fn invalid_date(reason: impl Display) -> Self {
+        Self::InvalidDate(reason.to_string())
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RingNotFound { mac } => write!(f, "unable to find ring with {mac}"),
+            Self::RingAlreadyExists { mac } => write!(f, "a ring with {mac} already exists"),
+            Self::Incompatible { name } => write!(
+                f,
+                "the database stores {name} in a layout this version can't read or upgrade, \
+                it was likely created by a newer version"
+            ),
+            Self::InvalidDate(reason) => write!(f, "invalid date: {reason}"),
+            Self::InvalidMac(mac) => write!(f, "invalid mac address: {mac:?}"),
+            Self::Storage(e) => write!(f, "storage error: {e}"),
+            Self::Io(e) => write!(f, "io error: {e}"),
+            #[cfg(feature = "cole-mine")]
+            Self::Parse(e) => write!(f, "failed to parse raw sync: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Storage(e) => Some(e),
+            Self::Io(e) => Some(e),
+            #[cfg(feature = "cole-mine")]
+            Self::Parse(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<structsy::StructsyError> for Error {
+    fn from(value: structsy::StructsyError) -> Self {
+        match value {
+            structsy::StructsyError::StructAlreadyDefined(name) => Self::Incompatible { name },
+            e => Self::Storage(e),
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+#[cfg(feature = "cole-mine")]
+impl From<cole_mine::Error> for Error {
+    fn from(value: cole_mine::Error) -> Self {
+        Self::Parse(value)
+    }
+}
@@ -0,0 +1,354 @@
+//! Events as an Apple Health `export.xml`, the document the Health app writes
+//! when exporting all of its data
+
+use std::{borrow::Cow, io::Write, time::Duration};
+
+use time::{
+    format_description::BorrowedFormatItem, macros::format_description, OffsetDateTime, UtcOffset,
+};
+
+use super::at;
+use crate::{
+    Error, EventData, OxygenRange, Result, Ring, RingEvent, SleepSessionRecord, SleepStageKind,
+};
+
+/// The format Apple Health uses for every date, like `2024-11-27 08:30:00 -0600`
+const DATE_FORMAT: &[BorrowedFormatItem<'static>] = format_description!(
+    "[year]-[month]-[day] [hour]:[minute]:[second] [offset_hour sign:mandatory][offset_minute]"
+);
+/// How long the steps, calories and distance of an `Activity` event cover
+const ACTIVITY_INTERVAL: Duration = Duration::from_secs(15 * 60);
+/// How long the readings of an `OxygenRange` event cover
+const OXYGEN_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+const HEART_RATE: &str = "HKQuantityTypeIdentifierHeartRate";
+const STEP_COUNT: &str = "HKQuantityTypeIdentifierStepCount";
+const DISTANCE: &str = "HKQuantityTypeIdentifierDistanceWalkingRunning";
+const ACTIVE_ENERGY: &str = "HKQuantityTypeIdentifierActiveEnergyBurned";
+const OXYGEN_SATURATION: &str = "HKQuantityTypeIdentifierOxygenSaturation";
+const SLEEP_ANALYSIS: &str = "HKCategoryTypeIdentifierSleepAnalysis";
+const IN_BED: &str = "HKCategoryValueSleepAnalysisInBed";
+
+/// Write `events` from `ring` to `out` as an Apple Health export
+///
+/// Heart rates, steps, distance, calories, blood oxygen and sleep sessions are
+/// exported, stress, battery levels and the daily sleep totals have no Health
+/// equivalent and are skipped. Returns how many records were written
+pub fn write(
+    mut out: impl Write,
+    ring: &Ring,
+    events: impl IntoIterator<Item = RingEvent>,
+    offset: UtcOffset,
+    exported: OffsetDateTime,
+) -> Result<usize> {
+    let source = Source::new(ring, format_date(exported)?);
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(out, r#"<HealthData locale="en_US">"#)?;
+    writeln!(out, r#" <ExportDate value="{}"/>"#, source.created)?;
+    let mut written = 0;
+    for event in events {
+        for record in records(&event, offset)? {
+            source.write(&mut out, &record)?;
+            written += 1;
+        }
+    }
+    writeln!(out, "</HealthData>")?;
+    out.flush()?;
+    Ok(written)
+}
+
+/// The attributes every record shares
+struct Source {
+    name: String,
+    device: String,
+    created: String,
+}
+
+impl Source {
+    fn new(ring: &Ring, created: String) -> Self {
+        Self {
+            name: escape(ring.nickname.as_deref().unwrap_or(&ring.name)).into_owned(),
+            device: escape(&format!(
+                "<<HKDevice>, name:{}, identifier:{}>",
+                ring.name, ring.mac
+            ))
+            .into_owned(),
+            created,
+        }
+    }
+
+    fn write(&self, out: &mut impl Write, record: &Record) -> Result {
+        write!(
+            out,
+            r#" <Record type="{}" sourceName="{}" device="{}""#,
+            record.kind, self.name, self.device
+        )?;
+        if let Some(unit) = record.unit {
+            write!(out, r#" unit="{unit}""#)?;
+        }
+        write!(
+            out,
+            r#" creationDate="{}" startDate="{}" endDate="{}" value="{}""#,
+            self.created, record.start, record.end, record.value
+        )?;
+        if record.metadata.is_empty() {
+            writeln!(out, "/>")?;
+            return Ok(());
+        }
+        writeln!(out, ">")?;
+        for (key, value) in &record.metadata {
+            writeln!(out, r#"  <MetadataEntry key="{key}" value="{value}"/>"#)?;
+        }
+        writeln!(out, " </Record>")?;
+        Ok(())
+    }
+}
+
+/// A single Health sample with its dates already formatted
+struct Record {
+    kind: &'static str,
+    /// `None` for category samples, which have a named value instead
+    unit: Option<&'static str>,
+    start: String,
+    end: String,
+    value: String,
+    metadata: Vec<(&'static str, String)>,
+}
+
+impl Record {
+    fn quantity(
+        kind: &'static str,
+        unit: &'static str,
+        start: OffsetDateTime,
+        end: OffsetDateTime,
+        value: impl ToString,
+    ) -> Result<Self> {
+        Ok(Self {
+            kind,
+            unit: Some(unit),
+            start: format_date(start)?,
+            end: format_date(end)?,
+            value: value.to_string(),
+            metadata: Vec::new(),
+        })
+    }
+
+    fn sleep(start: OffsetDateTime, end: OffsetDateTime, value: &'static str) -> Result<Self> {
+        Ok(Self {
+            kind: SLEEP_ANALYSIS,
+            unit: None,
+            start: format_date(start)?,
+            end: format_date(end)?,
+            value: value.to_string(),
+            metadata: Vec::new(),
+        })
+    }
+}
+
+/// The records that describe `event`, empty for events Health has no type for
+fn records(event: &RingEvent, offset: UtcOffset) -> Result<Vec<Record>> {
+    let when = at(event.when, offset)?;
+    Ok(match &event.value {
+        EventData::HeartRate(rate) => {
+            vec![Record::quantity(HEART_RATE, "count/min", when, when, rate)?]
+        }
+        EventData::Activity(activity) => {
+            let end = when + ACTIVITY_INTERVAL;
+            let mut ret = Vec::new();
+            if activity.steps > 0 {
+                ret.push(Record::quantity(
+                    STEP_COUNT,
+                    "count",
+                    when,
+                    end,
+                    activity.steps,
+                )?);
+            }
+            // the ring reports distance in meters
+            if activity.distance > 0 {
+                ret.push(Record::quantity(
+                    DISTANCE,
+                    "m",
+                    when,
+                    end,
+                    activity.distance,
+                )?);
+            }
+            if activity.calories > 0.0 {
+                ret.push(Record::quantity(
+                    ACTIVE_ENERGY,
+                    "kcal",
+                    when,
+                    end,
+                    activity.calories,
+                )?);
+            }
+            ret
+        }
+        EventData::Oxygen(percent) => vec![Record::quantity(
+            OXYGEN_SATURATION,
+            "%",
+            when,
+            when,
+            fraction(f64::from(*percent)),
+        )?],
+        EventData::OxygenRange(range) => vec![oxygen_range(range, when)?],
+        EventData::SleepSession(session) => sleep_session(session, offset)?,
+        EventData::Sleep(_) | EventData::Stress(_) | EventData::Battery(_) => Vec::new(),
+    })
+}
+
+/// Health stores oxygen saturation as a fraction, a range is exported as its
+/// midpoint with the ends kept as metadata
+fn oxygen_range(range: &OxygenRange, when: OffsetDateTime) -> Result<Record> {
+    let midpoint = (f64::from(range.min) + f64::from(range.max)) / 2.0;
+    let mut record = Record::quantity(
+        OXYGEN_SATURATION,
+        "%",
+        when,
+        when + OXYGEN_INTERVAL,
+        fraction(midpoint),
+    )?;
+    record.metadata = vec![
+        ("Minimum", fraction(range.min.into()).to_string()),
+        ("Maximum", fraction(range.max.into()).to_string()),
+    ];
+    Ok(record)
+}
+
+/// The whole session as time in bed followed by each stage back to back from
+/// the start of the session
+fn sleep_session(session: &SleepSessionRecord, offset: UtcOffset) -> Result<Vec<Record>> {
+    let start = at(session.start, offset)?;
+    let mut ret = vec![Record::sleep(start, at(session.end, offset)?, IN_BED)?];
+    let mut stage_start = start;
+    for stage in &session.stages {
+        let stage_end = stage_start + Duration::from_secs(u64::from(stage.minutes) * 60);
+        let value = match stage.stage {
+            SleepStageKind::Light => "HKCategoryValueSleepAnalysisAsleepCore",
+            SleepStageKind::Deep => "HKCategoryValueSleepAnalysisAsleepDeep",
+            SleepStageKind::Rem => "HKCategoryValueSleepAnalysisAsleepREM",
+            SleepStageKind::Awake => "HKCategoryValueSleepAnalysisAwake",
+        };
+        ret.push(Record::sleep(stage_start, stage_end, value)?);
+        stage_start = stage_end;
+    }
+    Ok(ret)
+}
+
+fn fraction(percent: f64) -> f64 {
+    percent / 100.0
+}
+
+fn format_date(when: OffsetDateTime) -> Result<String> {
+    when.format(DATE_FORMAT).map_err(Error::invalid_date)
+}
+
+/// Escape `value` for use in an attribute
+fn escape(value: &str) -> Cow<'_, str> {
+    if !value.contains(['&', '<', '>', '"', '\'']) {
+        return Cow::Borrowed(value);
+    }
+    let mut ret = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => ret.push_str("&amp;"),
+            '<' => ret.push_str("&lt;"),
+            '>' => ret.push_str("&gt;"),
+            '"' => ret.push_str("&quot;"),
+            '\'' => ret.push_str("&apos;"),
+            c => ret.push(c),
+        }
+    }
+    Cow::Owned(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use time::{
+        macros::{datetime, offset},
+        PrimitiveDateTime,
+    };
+
+    use super::*;
+    use crate::{date::DateTime, SleepStageRecord};
+
+    const MAC: &str = "00:00:00:00:00:00";
+
+    fn ring() -> Ring {
+        Ring {
+            nickname: Some("Mine & Yours".to_string()),
+            name: "R02_0000".to_string(),
+            mac: MAC.to_string(),
+        }
+    }
+
+    fn event(when: PrimitiveDateTime, value: EventData) -> RingEvent {
+        RingEvent::new(MAC, DateTime::try_from(when).unwrap(), value)
+    }
+
+    fn export(events: Vec<RingEvent>) -> (usize, String) {
+        let mut out = Vec::new();
+        let written = write(
+            &mut out,
+            &ring(),
+            events,
+            offset!(-6),
+            datetime!(2024-11-28 9:00 -6),
+        )
+        .unwrap();
+        (written, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn synthetic_day() {
+        let session = SleepSessionRecord {
+            start: DateTime::try_from(datetime!(2024-11-26 23:00)).unwrap(),
+            end: DateTime::try_from(datetime!(2024-11-27 0:15)).unwrap(),
+            stages: vec![
+                SleepStageRecord {
+                    stage: SleepStageKind::Light,
+                    minutes: 30,
+                },
+                SleepStageRecord {
+                    stage: SleepStageKind::Deep,
+                    minutes: 20,
+                },
+                SleepStageRecord {
+                    stage: SleepStageKind::Rem,
+                    minutes: 10,
+                },
+                SleepStageRecord {
+                    stage: SleepStageKind::Awake,
+                    minutes: 15,
+                },
+            ],
+        };
+        let (written, xml) = export(vec![
+            event(
+                datetime!(2024-11-26 23:00),
+                EventData::SleepSession(session),
+            ),
+            event(datetime!(2024-11-27 0:00), EventData::sleep(75)),
+            event(datetime!(2024-11-27 8:00), EventData::heart_rate(62)),
+            event(datetime!(2024-11-27 8:05), EventData::heart_rate(71)),
+            event(
+                datetime!(2024-11-27 8:30),
+                EventData::activity(1204, 52.5, 830),
+            ),
+            event(datetime!(2024-11-27 8:45), EventData::activity(0, 0.0, 0)),
+            event(datetime!(2024-11-27 9:00), EventData::oxygen_range(95, 98)),
+            event(datetime!(2024-11-27 10:00), EventData::oxygen(97)),
+            event(datetime!(2024-11-27 10:00), EventData::stress(30)),
+        ]);
+        assert_eq!(written, 12);
+        insta::assert_snapshot!(xml);
+    }
+
+    #[test]
+    fn no_events() {
+        let (written, xml) = export(Vec::new());
+        assert_eq!(written, 0);
+        insta::assert_snapshot!(xml);
+    }
+}
@@ -0,0 +1,195 @@
+//! Events as InfluxDB line protocol, one measurement per kind of event tagged
+//! with the ring's mac and name
+//!
+//! ```text
+//! heart_rate,mac=00:00:00:00:00:00,ring=My\ Ring value=72 1732716000000000000
+//! ```
+//!
+//! Every field is written as a float and timestamps are nanoseconds since the
+//! unix epoch in UTC
+
+use std::{borrow::Cow, io::Write};
+
+use time::UtcOffset;
+
+use super::at;
+use crate::{EventData, Result, Ring, RingEvent, SleepStageKind};
+
+/// Write `events` from `ring` to `out`, one point per line
+///
+/// Returns how many points were written
+pub fn write(
+    mut out: impl Write,
+    ring: &Ring,
+    events: impl IntoIterator<Item = RingEvent>,
+    offset: UtcOffset,
+) -> Result<usize> {
+    let mut written = 0;
+    for event in events {
+        for line in points(ring, &event, offset)? {
+            writeln!(out, "{line}")?;
+            written += 1;
+        }
+    }
+    out.flush()?;
+    Ok(written)
+}
+
+/// The lines describing `event`
+///
+/// A sleep session is a `sleep_stage` point for each stage, starting when the
+/// stage did with its `duration` in seconds
+pub fn points(ring: &Ring, event: &RingEvent, offset: UtcOffset) -> Result<Vec<String>> {
+    let tags = format!(
+        "mac={},ring={}",
+        escape(&ring.mac),
+        escape(ring.nickname.as_deref().unwrap_or(&ring.name))
+    );
+    let timestamp = at(event.when, offset)?.unix_timestamp_nanos();
+    let point =
+        |measurement: &str, fields: String| format!("{measurement},{tags} {fields} {timestamp}");
+    Ok(match &event.value {
+        EventData::HeartRate(value) => vec![point("heart_rate", format!("value={value}"))],
+        EventData::Sleep(minutes) => vec![point("sleep", format!("minutes={minutes}"))],
+        EventData::Stress(value) => vec![point("stress", format!("value={value}"))],
+        EventData::Oxygen(value) => vec![point("oxygen", format!("value={value}"))],
+        EventData::OxygenRange(range) => vec![point(
+            "oxygen",
+            format!("min={},max={}", range.min, range.max),
+        )],
+        EventData::Activity(activity) => vec![point(
+            "activity",
+            format!(
+                "steps={},calories={},distance={}",
+                activity.steps, activity.calories, activity.distance
+            ),
+        )],
+        EventData::Battery(battery) => vec![point(
+            "battery",
+            format!("level={},charging={}", battery.level, battery.charging),
+        )],
+        EventData::SleepSession(session) => {
+            let mut start = at(session.start, offset)?;
+            let mut ret = Vec::with_capacity(session.stages.len());
+            for stage in &session.stages {
+                let name = match stage.stage {
+                    SleepStageKind::Light => "light",
+                    SleepStageKind::Deep => "deep",
+                    SleepStageKind::Rem => "rem",
+                    SleepStageKind::Awake => "awake",
+                };
+                let seconds = u32::from(stage.minutes) * 60;
+                ret.push(format!(
+                    "sleep_stage,{tags},stage={name} duration={seconds} {}",
+                    start.unix_timestamp_nanos()
+                ));
+                start += time::Duration::seconds(seconds.into());
+            }
+            ret
+        }
+    })
+}
+
+/// Escape `value` for use as a tag value
+pub fn escape(value: &str) -> Cow<'_, str> {
+    if !value.contains([',', '=', ' ', '\\']) {
+        return Cow::Borrowed(value);
+    }
+    let mut ret = String::with_capacity(value.len() + 2);
+    for c in value.chars() {
+        if matches!(c, ',' | '=' | ' ' | '\\') {
+            ret.push('\\');
+        }
+        ret.push(c);
+    }
+    Cow::Owned(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use time::{
+        macros::{datetime, offset},
+        PrimitiveDateTime,
+    };
+
+    use super::*;
+    use crate::{date::DateTime, SleepSessionRecord, SleepStageRecord};
+
+    const MAC: &str = "00:00:00:00:00:00";
+
+    fn ring(nickname: Option<&str>) -> Ring {
+        Ring {
+            nickname: nickname.map(ToString::to_string),
+            name: "R02_0000".to_string(),
+            mac: MAC.to_string(),
+        }
+    }
+
+    fn event(when: PrimitiveDateTime, value: EventData) -> RingEvent {
+        RingEvent::new(MAC, DateTime::try_from(when).unwrap(), value)
+    }
+
+    #[test]
+    fn escapes_tag_values() {
+        assert_eq!(escape("R02_0000"), "R02_0000");
+        assert_eq!(escape("My Ring"), r"My\ Ring");
+        assert_eq!(escape(r"a,b=c\d"), r"a\,b\=c\\d");
+        let lines = points(
+            &ring(Some("Left hand, mostly")),
+            &event(datetime!(2024-11-27 8:00), EventData::heart_rate(72)),
+            offset!(UTC),
+        )
+        .unwrap();
+        assert_eq!(
+            lines,
+            [
+                r"heart_rate,mac=00:00:00:00:00:00,ring=Left\ hand\,\ mostly value=72 1732694400000000000"
+            ]
+        );
+    }
+
+    #[test]
+    fn synthetic_day() {
+        let session = SleepSessionRecord {
+            start: DateTime::try_from(datetime!(2024-11-26 23:00)).unwrap(),
+            end: DateTime::try_from(datetime!(2024-11-27 0:15)).unwrap(),
+            stages: vec![
+                SleepStageRecord {
+                    stage: SleepStageKind::Light,
+                    minutes: 30,
+                },
+                SleepStageRecord {
+                    stage: SleepStageKind::Deep,
+                    minutes: 20,
+                },
+                SleepStageRecord {
+                    stage: SleepStageKind::Rem,
+                    minutes: 10,
+                },
+                SleepStageRecord {
+                    stage: SleepStageKind::Awake,
+                    minutes: 15,
+                },
+            ],
+        };
+        let events = vec![
+            event(
+                datetime!(2024-11-26 23:00),
+                EventData::SleepSession(session),
+            ),
+            event(datetime!(2024-11-27 0:00), EventData::sleep(75)),
+            event(datetime!(2024-11-27 8:00), EventData::heart_rate(62)),
+            event(
+                datetime!(2024-11-27 8:30),
+                EventData::activity(1204, 52.5, 830),
+            ),
+            event(datetime!(2024-11-27 9:00), EventData::oxygen_range(95, 98)),
+            event(datetime!(2024-11-27 10:00), EventData::oxygen(97)),
+            event(datetime!(2024-11-27 10:00), EventData::stress(30)),
+        ];
+        let mut out = Vec::new();
+        let written = write(&mut out, &ring(None), events, offset!(-6)).unwrap();
+        assert_eq!(written, 10);
+        insta::assert_snapshot!(String::from_utf8(out).unwrap());
+    }
+}
@@ -0,0 +1,16 @@
+//! Writing stored events in formats other apps can import
+//!
+//! Events are stored in the ring's local time without an offset, so each
+//! format is given the offset they were recorded in
+
+use time::{OffsetDateTime, PrimitiveDateTime, UtcOffset};
+
+use crate::{date::DateTime, Result};
+
+pub mod apple_health;
+pub mod influx;
+
+/// `when` as recorded in `offset`
+fn at(when: DateTime, offset: UtcOffset) -> Result<OffsetDateTime> {
+    Ok(PrimitiveDateTime::try_from(when)?.assume_offset(offset))
+}
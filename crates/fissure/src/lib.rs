@@ -1,28 +1,48 @@
 //! Database Abstractions
 //! 
 
-use std::{ops::RangeBounds, path::Path};
+use std::{
+    collections::BTreeMap,
+    ops::{Bound, Range, RangeBounds},
+    path::Path,
+};
 
 use date::DateTime;
 use serde::{Deserialize, Serialize};
 use structsy::{
     derive::queries,
-    Filter, Operators, Structsy, StructsyTx,
+    Filter, Operators, OwnedSytx, Ref, Snapshot, Structsy, StructsyTx,
 };
-use time::OffsetDateTime;
-use crate::date::DateTimeQuery;
+use time::{Date, OffsetDateTime, PrimitiveDateTime, UtcOffset};
 
+#[cfg(feature = "cole-mine")]
+pub mod convert;
 mod date;
+mod error;
+pub mod export;
+mod mac;
+mod migrations;
+mod raw;
+mod settings;
+mod summary;
+
+pub use error::Error;
+pub use mac::Mac;
+pub use raw::RawSyncRecord;
+pub use settings::{Goals, LastSync, RingSettings, SyncCategory};
+pub use summary::{DailySummary, SleepStageMinutes};
 
-type Result<T = (), E = Box<dyn std::error::Error>> = std::result::Result<T, E>;
+pub type Result<T = (), E = Error> = std::result::Result<T, E>;
+
+/// The longest sleep session `Database::get_sleep_sessions` looks back for
+const MAX_SLEEP_SESSION: time::Duration = time::Duration::DAY;
 
 #[derive(Clone)]
 pub struct Database(Structsy);
 
 impl Database {
     pub fn new(path: impl AsRef<Path>) -> Result<Self> {
-        let inner =
-            Structsy::open(path.as_ref()).map_err(|e| format!("Error opening database: {e}"))?;
+        let inner = migrations::open(path.as_ref())?;
         let ret = Self(inner);
         ret.init()?;
         Ok(ret)
@@ -39,6 +59,14 @@ impl Database {
     fn init(&self) -> Result {
         self.0.define::<Ring>()?;
         self.0.define::<RingEvent>()?;
+        self.0.define::<RingSettings>()?;
+        self.0.define::<RawSyncRecord>()?;
+        Ok(())
+    }
+
+    /// Run an empty transaction, a cheap way to check the database is usable
+    pub fn check(&self) -> Result {
+        self.0.begin()?.commit()?;
         Ok(())
     }
 
@@ -47,23 +75,57 @@ impl Database {
     }
 
     pub fn get_ring(&self, mac: &str) -> Result<Ring> {
-        let (_, ret) = self
-            .0
-            .query()
-            .with_mac(mac)
-            .fetch()
-            .next()
-            .ok_or_else(|| format!("unable to find ring with {mac}"))?;
+        let (_, ret) =
+            self.0
+                .query()
+                .with_mac(mac)
+                .fetch()
+                .next()
+                .ok_or_else(|| Error::RingNotFound {
+                    mac: mac.to_string(),
+                })?;
         Ok(ret)
     }
 
+    /// Add a ring, failing with `Error::RingAlreadyExists` if one with the same
+    /// mac is already in the database
     pub fn add_ring(&self, ring: &Ring) -> Result {
         let mut tx = self.0.begin()?;
+        if tx
+            .query::<Ring>()
+            .with_mac(&ring.mac)
+            .fetch()
+            .next()
+            .is_some()
+        {
+            return Err(Error::RingAlreadyExists {
+                mac: ring.mac.clone(),
+            });
+        }
         tx.insert(ring)?;
         tx.commit()?;
         Ok(())
     }
 
+    /// Add a ring or replace the one with the same mac, returning `true` if
+    /// the ring was added
+    pub fn add_or_update_ring(&self, ring: &Ring) -> Result<bool> {
+        let mut tx = self.0.begin()?;
+        let existing = tx.query::<Ring>().with_mac(&ring.mac).fetch().next();
+        let added = match existing {
+            Some((id, _)) => {
+                tx.update(&id, ring)?;
+                false
+            }
+            None => {
+                tx.insert(ring)?;
+                true
+            }
+        };
+        tx.commit()?;
+        Ok(added)
+    }
+
     pub fn update_ring(&self, ring: &Ring) -> Result {
         let mut tx = self.0.begin()?;
         let db = tx
@@ -71,59 +133,718 @@ impl Database {
             .with_mac(&ring.mac)
             .fetch()
             .next()
-            .ok_or_else(|| format!("unable to find ring with {}", ring.mac))?;
+            .ok_or_else(|| Error::RingNotFound {
+                mac: ring.mac.clone(),
+            })?;
         tx.update(&db.0, ring)?;
         tx.commit()?;
         Ok(())
     }
 
+    /// Change the fields of the ring with `mac` that `patch` sets, leaving the
+    /// rest as they are, and return the ring as it's stored afterwards
+    ///
+    /// The ring is read and written in one transaction so a change made to
+    /// another field at the same time isn't lost
+    pub fn patch_ring(&self, mac: &str, patch: RingPatch) -> Result<Ring> {
+        let mut tx = self.0.begin()?;
+        let (id, mut ring) = tx
+            .query::<Ring>()
+            .with_mac(mac)
+            .fetch()
+            .next()
+            .ok_or_else(|| Error::RingNotFound {
+                mac: mac.to_string(),
+            })?;
+        patch.apply(&mut ring);
+        tx.update(&id, &ring)?;
+        tx.commit()?;
+        Ok(ring)
+    }
+
+    /// Remove the ring with `mac` and its settings, returning `false` if there
+    /// was no such ring
+    ///
+    /// If `cascade` is true all of the ring's events and raw syncs are removed
+    /// as well
+    pub fn delete_ring(&self, mac: &str, cascade: bool) -> Result<bool> {
+        let mut tx = self.0.begin()?;
+        let Some((id, _)) = tx.query::<Ring>().with_mac(mac).fetch().next() else {
+            return Ok(false);
+        };
+        tx.delete(&id)?;
+        let settings = tx
+            .query::<RingSettings>()
+            .with_settings_mac(mac)
+            .fetch()
+            .next();
+        if let Some((id, _)) = settings {
+            tx.delete(&id)?;
+        }
+        if cascade {
+            let events: Vec<_> = tx
+                .query::<RingEvent>()
+                .with_ring_mac(mac)
+                .fetch()
+                .map(|(id, _)| id)
+                .collect();
+            for id in events {
+                tx.delete(&id)?;
+            }
+            let raw: Vec<_> = tx
+                .query::<RawSyncRecord>()
+                .with_raw_mac(mac)
+                .fetch()
+                .map(|(id, _)| id)
+                .collect();
+            for id in raw {
+                tx.delete(&id)?;
+            }
+        }
+        tx.commit()?;
+        Ok(true)
+    }
+
+    /// The settings stored for the ring with `mac`, `None` if none have been
+    /// stored yet
+    pub fn get_settings(&self, mac: &str) -> Result<Option<RingSettings>> {
+        Ok(self
+            .0
+            .query::<RingSettings>()
+            .with_settings_mac(mac)
+            .fetch()
+            .next()
+            .map(|(_, settings)| settings))
+    }
+
+    /// Store `settings`, replacing any already stored for the same ring
+    pub fn set_settings(&self, settings: &RingSettings) -> Result {
+        let mut tx = self.0.begin()?;
+        if tx
+            .query::<Ring>()
+            .with_mac(&settings.mac)
+            .fetch()
+            .next()
+            .is_none()
+        {
+            return Err(Error::RingNotFound {
+                mac: settings.mac.clone(),
+            });
+        }
+        let existing = tx
+            .query::<RingSettings>()
+            .with_settings_mac(&settings.mac)
+            .fetch()
+            .next();
+        match existing {
+            Some((id, _)) => tx.update(&id, settings)?,
+            None => {
+                tx.insert(settings)?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Record that `category` was synced from the ring with `mac` at `when`,
+    /// leaving the other categories and settings as they were
+    pub fn update_last_sync(
+        &self,
+        mac: &str,
+        category: SyncCategory,
+        when: OffsetDateTime,
+    ) -> Result {
+        let when = DateTime::try_from(when.to_offset(UtcOffset::UTC))?;
+        let mut tx = self.0.begin()?;
+        if tx.query::<Ring>().with_mac(mac).fetch().next().is_none() {
+            return Err(Error::RingNotFound {
+                mac: mac.to_string(),
+            });
+        }
+        let existing = tx
+            .query::<RingSettings>()
+            .with_settings_mac(mac)
+            .fetch()
+            .next();
+        match existing {
+            Some((id, mut settings)) => {
+                settings.set_last_sync(category, when);
+                tx.update(&id, &settings)?;
+            }
+            None => {
+                let mut settings = RingSettings::new(mac);
+                settings.set_last_sync(category, when);
+                tx.insert(&settings)?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Remove the ring's events in `range` or all of them when `range` is `None`,
+    /// returning how many were removed
+    pub fn delete_events(&self, mac: &str, range: Option<Range<OffsetDateTime>>) -> Result<usize> {
+        let range = match range {
+            Some(range) => convert_range(&range)?,
+            None => (Bound::Unbounded, Bound::Unbounded),
+        };
+        let mut tx = self.0.begin()?;
+        let events = ring_events(mac, range, |filter| tx.into_iter(filter).collect());
+        for (id, _) in events.iter() {
+            tx.delete(id)?;
+        }
+        tx.commit()?;
+        Ok(events.len())
+    }
+
+    /// Remove every ring's events from before `older_than`, keeping those of
+    /// `keep_kinds` however old they are, returning how many were removed
+    ///
+    /// Freed space is reused for new events but the file doesn't shrink, see
+    /// `Database::compact_into`
+    pub fn prune_events(
+        &self,
+        older_than: OffsetDateTime,
+        keep_kinds: &[EventKind],
+    ) -> Result<usize> {
+        let range = convert_range(&(..older_than))?;
+        let last = DateTime::try_from(older_than.to_offset(UtcOffset::UTC))?.month_key();
+        // every ring's months up to the one `older_than` is in
+        let months = ..format!("{:06}/", next_month(last));
+        let mut tx = self.0.begin()?;
+        let events: Vec<_> = tx
+            .query::<RingEvent>()
+            .between_ring_month(months)
+            .and(|and| and.between_time(range))
+            .fetch()
+            .filter(|(_, event)| !keep_kinds.contains(&event.kind()))
+            .map(|(id, _)| id)
+            .collect();
+        for id in events.iter() {
+            tx.delete(id)?;
+        }
+        tx.commit()?;
+        Ok(events.len())
+    }
+
+    /// Copy everything into a new database at `path`, which only takes up as
+    /// much space as the data it holds
+    ///
+    /// structsy can't give space back to the file system, so this is how a
+    /// database shrinks after pruning. Fails if `path` already holds a database
+    pub fn compact_into(&self, path: impl AsRef<Path>) -> Result {
+        let path = path.as_ref();
+        if path.exists() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("{} already exists", path.display()),
+            )
+            .into());
+        }
+        let dest = Self::new(path)?;
+        let mut tx = dest.0.begin()?;
+        for (_, ring) in self.0.query::<Ring>().fetch() {
+            tx.insert(&ring)?;
+        }
+        for (_, settings) in self.0.query::<RingSettings>().fetch() {
+            tx.insert(&settings)?;
+        }
+        for (_, record) in self.0.query::<RawSyncRecord>().fetch() {
+            tx.insert(&record)?;
+        }
+        tx.commit()?;
+        let events: Vec<_> = self
+            .0
+            .query::<RingEvent>()
+            .fetch()
+            .map(|(_, e)| e)
+            .collect();
+        // one transaction per chunk, large transactions on a cluster index are slow
+        for chunk in events.chunks(1_000) {
+            let mut tx = dest.0.begin()?;
+            for event in chunk {
+                tx.insert(event)?;
+            }
+            tx.commit()?;
+        }
+        Ok(())
+    }
+
+    /// Keep the bytes of a sync reply so it can be parsed again later, see
+    /// `convert::reprocess`
+    pub fn add_raw_sync(&self, record: &RawSyncRecord) -> Result {
+        let mut tx = self.0.begin()?;
+        tx.insert(record)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// The raw `category` replies kept for the ring with `mac`, in the order
+    /// they were captured
+    pub fn iter_raw_syncs(
+        &self,
+        mac: &str,
+        category: SyncCategory,
+    ) -> impl Iterator<Item = RawSyncRecord> {
+        let mut records: Vec<_> = self
+            .0
+            .query::<RawSyncRecord>()
+            .with_raw_mac(mac)
+            .and(|and| and.with_raw_kind(category as u8))
+            .into_iter()
+            .map(|(_, record)| record)
+            .collect();
+        records.sort_by_key(|record| record.captured_at);
+        records.into_iter()
+    }
+
     pub fn get_events_for_ring(&self, mac: &str, when: OffsetDateTime) -> Result<Vec<RingEvent>> {
         let min = when.date().midnight().assume_utc();
         let max = min
             .date()
             .next_day()
-            .ok_or_else(|| format!("Missing next day {min}"))?
+            .ok_or_else(|| Error::invalid_date(format!("Missing next day {min}")))?
             .midnight()
             .assume_utc();
-        let min = DateTime::try_from(min)?;
-        let max = DateTime::try_from(max)?;
+        self.get_events_in_range(mac, min..max)
+    }
+
+    /// Get all of the events for a ring in `range`, ordered by when they happened
+    pub fn get_events_in_range(
+        &self,
+        mac: &str,
+        range: impl RangeBounds<OffsetDateTime>,
+    ) -> Result<Vec<RingEvent>> {
+        let range = convert_range(&range)?;
+        let snapshot = self.0.snapshot()?;
+        Ok(
+            ring_events(mac, range, |filter| snapshot.fetch(filter).collect())
+                .into_iter()
+                .map(|(_, event)| event)
+                .collect(),
+        )
+    }
+
+    /// Get the ring's events of `kind` in `range`, ordered by when they happened
+    pub fn get_events_of_kind(
+        &self,
+        mac: &str,
+        kind: EventKind,
+        range: impl RangeBounds<OffsetDateTime>,
+    ) -> Result<Vec<RingEvent>> {
+        let range = convert_range(&range)?;
         let q = self
             .0
             .query::<RingEvent>()
             .with_ring_mac(mac)
-            .and(|and| and.between_time(min..max));
+            .and(|and| and.with_kind(kind as u8).between_time(range));
+        let mut ret: Vec<_> = q.into_iter().map(|(_, event)| event).collect();
+        ret.sort_by_key(|event| event.when);
+        Ok(ret)
+    }
 
-        Ok(q.into_iter().map(|(_, event)| event).collect())
+    /// Lazily walk the ring's events in `range`, ordered by when they happened
+    ///
+    /// Events are read a UTC month at a time from a single snapshot, so only one
+    /// month is held in memory and writes made while iterating aren't seen
+    pub fn iter_events(
+        &self,
+        mac: &str,
+        range: Range<OffsetDateTime>,
+    ) -> impl Iterator<Item = Result<RingEvent>> + '_ {
+        let mac = mac.to_string();
+        let mut snapshot: Option<Snapshot> = None;
+        EventIter::new(range, move |window| {
+            let snapshot = match snapshot.as_ref() {
+                Some(snapshot) => snapshot,
+                None => snapshot.insert(self.0.snapshot()?),
+            };
+            events_in_window(snapshot, &mac, window)
+        })
     }
 
-    pub fn add_events(&self, events: &[RingEvent]) -> Result<()> {
-        let mut tx = self.0.begin()?;
+    /// Call `f` with each of the ring's events in `range`, ordered by when they
+    /// happened, all read from a single snapshot
+    pub fn for_each_event(
+        &self,
+        mac: &str,
+        range: Range<OffsetDateTime>,
+        mut f: impl FnMut(RingEvent),
+    ) -> Result {
+        for event in self.iter_events(mac, range) {
+            f(event?);
+        }
+        Ok(())
+    }
+
+    /// Get the ring's sleep sessions that overlap `range`, ordered by when they
+    /// started
+    ///
+    /// A session that crosses midnight is returned for both days
+    pub fn get_sleep_sessions(
+        &self,
+        mac: &str,
+        range: Range<OffsetDateTime>,
+    ) -> Result<Vec<SleepSessionRecord>> {
+        let start = DateTime::try_from(range.start.to_offset(UtcOffset::UTC))?;
+        // sessions are stored by when they started so look back far enough to
+        // find the ones that started before the range but are still going
+        let events = self.get_events_in_range(mac, range.start - MAX_SLEEP_SESSION..range.end)?;
+        Ok(events
+            .into_iter()
+            .filter_map(|event| match event.value {
+                EventData::SleepSession(session) if session.end > start => Some(session),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// The ring's battery levels in `range`, ordered by when they were read
+    pub fn battery_history(
+        &self,
+        mac: &str,
+        range: impl RangeBounds<OffsetDateTime>,
+    ) -> Result<Vec<(OffsetDateTime, BatteryLevel)>> {
+        self.get_events_of_kind(mac, EventKind::Battery, range)?
+            .into_iter()
+            .filter_map(|event| match event.value {
+                EventData::Battery(level) => Some((event.when, level)),
+                _ => None,
+            })
+            .map(|(when, level)| Ok((OffsetDateTime::try_from(when)?, level)))
+            .collect()
+    }
 
-        for event in events {
-            let existing = tx
+    /// The ring's most recent event of each kind, in the order of
+    /// `EventKind::ALL`, kinds without any events are left out
+    pub fn latest_events(&self, mac: &str) -> Result<Vec<RingEvent>> {
+        let snapshot = self.0.snapshot()?;
+        let mut ret = Vec::new();
+        for kind in EventKind::ALL {
+            let latest = snapshot
                 .query::<RingEvent>()
-                .with_ring_mac(&event.mac)
-                .and(|and| {
-                    let filter = Filter::<DateTime>::new()
-                        .with_ymd(event.when.year, event.when.month, event.when.day)
-                        .with_hms(event.when.hour, event.when.minute, event.when.second);
-                    and.with_when(filter)
-                })
+                .with_ring_mac(mac)
+                .and(|and| and.with_kind(kind as u8))
                 .into_iter()
-                .filter(|(_r, e)| {
-                    std::mem::discriminant(&e.value) == std::mem::discriminant(&event.value)
-                })
-                .next();
-            if let Some((r, _e)) = existing {
-                println!("found matching event\n{event:?}\n{_e:?}");
-                tx.update(&r, event)?;
-            } else {
-                tx.insert(event)?;
+                .map(|(_, event)| event)
+                .max_by_key(|event| event.when);
+            ret.extend(latest);
+        }
+        Ok(ret)
+    }
+
+    /// Aggregate the ring's events for the UTC `day`
+    pub fn daily_summary(&self, mac: &str, day: Date) -> Result<DailySummary> {
+        let events = self.get_events_for_ring(mac, day.midnight().assume_utc())?;
+        Ok(DailySummary::from_events(day, &events))
+    }
+
+    /// Aggregate the ring's events for each UTC day in `range`, days without any
+    /// events are included with every value `None`
+    pub fn summaries_in_range(&self, mac: &str, range: Range<Date>) -> Result<Vec<DailySummary>> {
+        let events = self.get_events_in_range(
+            mac,
+            range.start.midnight().assume_utc()..range.end.midnight().assume_utc(),
+        )?;
+        let mut events = events.iter().peekable();
+        let mut ret = Vec::new();
+        let mut day = range.start;
+        while day < range.end {
+            let mut todays = Vec::new();
+            while let Some(event) = events.peek() {
+                if PrimitiveDateTime::try_from(event.when)?.date() != day {
+                    break;
+                }
+                todays.extend(events.next());
             }
+            ret.push(DailySummary::from_events(day, todays));
+            day = day
+                .next_day()
+                .ok_or_else(|| Error::invalid_date(format!("Missing next day {day}")))?;
         }
+        Ok(ret)
+    }
+
+    /// Store `events`, see `UpsertMode` for how an event matching one already
+    /// stored is handled
+    ///
+    /// The stored events each ring has over the span of `events` are read once
+    /// and matched in memory, all of `events` are stored in one transaction
+    pub fn add_events(&self, events: &[RingEvent], mode: UpsertMode) -> Result<UpsertCounts> {
+        let mut tx = self.0.begin()?;
+        let counts = upsert_events(&mut tx, events, mode)?;
         tx.commit()?;
-        Ok(())
+        Ok(counts)
+    }
+
+    /// Like `add_events` but committing every `chunk_size` events, a failure
+    /// only rolls back the chunk it happened in and the chunks before it stay
+    /// stored
+    pub fn add_events_chunked(
+        &self,
+        events: &[RingEvent],
+        mode: UpsertMode,
+        chunk_size: usize,
+    ) -> Result<UpsertCounts> {
+        let mut counts = UpsertCounts::default();
+        for chunk in events.chunks(chunk_size.max(1)) {
+            let added = self.add_events(chunk, mode)?;
+            counts.inserted += added.inserted;
+            counts.updated += added.updated;
+            counts.skipped += added.skipped;
+        }
+        Ok(counts)
+    }
+}
+
+/// Stored events by ring, time and kind
+type StoredEvents = BTreeMap<(String, DateTime, u8), Vec<(Ref<RingEvent>, RingEvent)>>;
+
+fn upsert_events(
+    tx: &mut OwnedSytx,
+    events: &[RingEvent],
+    mode: UpsertMode,
+) -> Result<UpsertCounts> {
+    let mut spans: BTreeMap<&str, (DateTime, DateTime)> = BTreeMap::new();
+    for event in events {
+        spans
+            .entry(&event.mac)
+            .and_modify(|(start, end)| {
+                *start = (*start).min(event.when);
+                *end = (*end).max(event.when);
+            })
+            .or_insert((event.when, event.when));
+    }
+    let mut stored = StoredEvents::new();
+    for (mac, (start, end)) in spans {
+        let range = (Bound::Included(start), Bound::Included(end));
+        for (r, e) in ring_events(mac, range, |filter| tx.into_iter(filter).collect()) {
+            stored
+                .entry((e.mac.clone(), e.when, e.kind))
+                .or_default()
+                .push((r, e));
+        }
+    }
+
+    let mut counts = UpsertCounts::default();
+    for event in events {
+        let kind = event.kind() as u8;
+        // the value and time may have been changed since the event was created
+        let event = RingEvent {
+            kind,
+            ring_month: ring_month(&event.mac, event.when.month_key()),
+            ..event.clone()
+        };
+        let matching = stored
+            .entry((event.mac.clone(), event.when, kind))
+            .or_default();
+        let existing = matching.iter_mut().find(|(_r, e)| match mode {
+            UpsertMode::InsertDuplicate => *e == event,
+            UpsertMode::Skip | UpsertMode::Overwrite => true,
+        });
+        match (existing, mode) {
+            (None, _) => {
+                let r = tx.insert(&event)?;
+                matching.push((r, event));
+                counts.inserted += 1;
+            }
+            (Some((r, e)), UpsertMode::Overwrite) => {
+                log::debug!("overwriting matching event\n{e:?}\n{event:?}");
+                tx.update(r, &event)?;
+                *e = event;
+                counts.updated += 1;
+            }
+            (Some((_, e)), UpsertMode::Skip | UpsertMode::InsertDuplicate) => {
+                log::debug!("skipping matching event\n{e:?}\n{event:?}");
+                counts.skipped += 1;
+            }
+        }
+    }
+    Ok(counts)
+}
+
+/// Reads a range of events a window at a time with `fetch`, which returns the
+/// window's events sorted by when they happened
+struct EventIter<F> {
+    fetch: F,
+    /// Where the next window starts
+    next: OffsetDateTime,
+    end: OffsetDateTime,
+    current: std::vec::IntoIter<RingEvent>,
+}
+
+impl<F> EventIter<F>
+where
+    F: FnMut(Range<OffsetDateTime>) -> Result<Vec<RingEvent>>,
+{
+    fn new(range: Range<OffsetDateTime>, fetch: F) -> Self {
+        Self {
+            fetch,
+            next: range.start,
+            end: range.end,
+            current: Vec::new().into_iter(),
+        }
+    }
+}
+
+impl<F> Iterator for EventIter<F>
+where
+    F: FnMut(Range<OffsetDateTime>) -> Result<Vec<RingEvent>>,
+{
+    type Item = Result<RingEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.current.next() {
+                return Some(Ok(event));
+            }
+            if self.next >= self.end {
+                return None;
+            }
+            let window = self.next..next_month_start(self.next).min(self.end);
+            self.next = window.end;
+            match (self.fetch)(window) {
+                Ok(events) => self.current = events.into_iter(),
+                Err(e) => {
+                    // nothing after a failed window can be trusted to be in order
+                    self.next = self.end;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+/// Midnight UTC on the first of the month after `when`, where the window
+/// `EventIter` reads after the one `when` is in starts
+fn next_month_start(when: OffsetDateTime) -> OffsetDateTime {
+    let when = when.to_offset(UtcOffset::UTC);
+    let (year, month) = match when.month() {
+        time::Month::December => (when.year() + 1, time::Month::January),
+        month => (when.year(), month.next()),
+    };
+    Date::from_calendar_date(year, month, 1).map_or(PrimitiveDateTime::MAX.assume_utc(), |first| {
+        first.midnight().assume_utc()
+    })
+}
+
+fn events_in_window(
+    snapshot: &Snapshot,
+    mac: &str,
+    window: Range<OffsetDateTime>,
+) -> Result<Vec<RingEvent>> {
+    let range = convert_range(&window)?;
+    Ok(
+        ring_events(mac, range, |filter| snapshot.fetch(filter).collect())
+            .into_iter()
+            .map(|(_, event)| event)
+            .collect(),
+    )
+}
+
+/// The ring's events in `range` ordered by when they happened, `fetch` reads
+/// each filter from a transaction or snapshot
+///
+/// structsy reads a query through a single index, so the ring's events are
+/// read a month at a time from the `ring_month` index and only the events of
+/// the months `range` touches are read. Without both ends of the range every
+/// event the ring has is read instead
+fn ring_events(
+    mac: &str,
+    range: (Bound<DateTime>, Bound<DateTime>),
+    fetch: impl FnMut(Filter<RingEvent>) -> Vec<(Ref<RingEvent>, RingEvent)>,
+) -> Vec<(Ref<RingEvent>, RingEvent)> {
+    let filters: Vec<_> = match range {
+        (
+            Bound::Included(start) | Bound::Excluded(start),
+            Bound::Included(end) | Bound::Excluded(end),
+        ) => {
+            let mut last = end.month_key();
+            // midnight on the first is only in its month when it's included
+            let first_instant = (end.day, end.hour, end.minute, end.second) == (1, 0, 0, 0);
+            if first_instant && matches!(range.1, Bound::Excluded(_)) {
+                last = previous_month(last);
+            }
+            months(start.month_key(), last)
+                .map(|month| Filter::new().with_ring_month(&ring_month(mac, month)))
+                .collect()
+        }
+        _ => vec![Filter::new().with_ring_mac(mac)],
+    };
+    let mut ret: Vec<_> = filters
+        .into_iter()
+        .flat_map(fetch)
+        .filter(|(_, event)| range.contains(&event.when))
+        .collect();
+    ret.sort_by_key(|(_, event)| event.when);
+    ret
+}
+
+/// The `RingEvent::ring_month` of the ring's events in `month`, a
+/// `DateTime::month_key`
+fn ring_month(mac: &str, month: u32) -> String {
+    format!("{month:06}/{mac}")
+}
+
+/// Every `DateTime::month_key` from `first` to `last`
+fn months(first: u32, last: u32) -> impl Iterator<Item = u32> {
+    std::iter::successors(Some(first), |&month| Some(next_month(month)))
+        .take_while(move |&month| month <= last)
+}
+
+/// The `DateTime::month_key` after `month`
+fn next_month(month: u32) -> u32 {
+    if month % 100 == 12 {
+        month + 89
+    } else {
+        month + 1
+    }
+}
+
+/// The `DateTime::month_key` before `month`
+fn previous_month(month: u32) -> u32 {
+    if month % 100 == 1 {
+        month - 89
+    } else {
+        month - 1
+    }
+}
+
+/// What `Database::add_events` did with the events it was given
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpsertCounts {
+    pub inserted: usize,
+    pub updated: usize,
+    pub skipped: usize,
+}
+
+/// Convert a range of `OffsetDateTime`s into a range of UTC `DateTime`s
+fn convert_range(
+    range: &impl RangeBounds<OffsetDateTime>,
+) -> Result<(Bound<DateTime>, Bound<DateTime>)> {
+    fn convert(bound: Bound<&OffsetDateTime>) -> Result<Bound<DateTime>> {
+        Ok(match bound {
+            Bound::Included(when) => Bound::Included(when.to_offset(UtcOffset::UTC).try_into()?),
+            Bound::Excluded(when) => Bound::Excluded(when.to_offset(UtcOffset::UTC).try_into()?),
+            Bound::Unbounded => Bound::Unbounded,
+        })
     }
+    Ok((convert(range.start_bound())?, convert(range.end_bound())?))
+}
+
+/// How `Database::add_events` treats an event when one of the same kind
+/// already exists for the ring at exactly the same time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UpsertMode {
+    /// Keep the existing event and drop the new one
+    Skip,
+    /// Replace the existing event with the new one
+    #[default]
+    Overwrite,
+    /// Keep both events, only dropping the new one if it is identical
+    /// to an existing event
+    InsertDuplicate,
 }
 
 #[derive(Debug, structsy::derive::Persistent, Serialize, Deserialize, PartialEq)]
@@ -134,6 +855,41 @@ pub struct Ring {
     pub mac: String,
 }
 
+/// The fields of a `Ring` for `Database::patch_ring` to change, `None` leaves
+/// a field as it is
+///
+/// A nickname of `Some(None)` clears it. When deserializing, a missing
+/// `nickname` is `None` and `"nickname": null` is `Some(None)`
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RingPatch {
+    #[serde(default, deserialize_with = "present")]
+    pub nickname: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    pub name: Option<String>,
+}
+
+impl RingPatch {
+    fn apply(self, ring: &mut Ring) {
+        if let Some(nickname) = self.nickname {
+            ring.nickname = nickname;
+        }
+        if let Some(name) = self.name {
+            ring.name = name;
+        }
+    }
+}
+
+/// Deserialize a field that's there as `Some`, a missing one is left to
+/// `#[serde(default)]`, so `null` is only accepted if `T` accepts it
+fn present<'de, D, T>(deserializer: D) -> std::result::Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
 #[queries(Ring)]
 trait FindRingByMac {
     // here is our condition method, to notice that the name of the parameter has to be exactly the same of the struct field.
@@ -142,12 +898,13 @@ trait FindRingByMac {
 
 #[derive(
     Debug,
+    Clone,
     structsy::derive::Persistent,
     Serialize,
     Deserialize,
-    PartialEq,
     bon::Builder,
 )]
+#[serde(from = "RingEventFields")]
 pub struct RingEvent {
     #[builder(into)]
     #[index(mode = "cluster")]
@@ -155,20 +912,159 @@ pub struct RingEvent {
     #[builder(into)]
     pub when: DateTime,
     pub value: EventData,
+    /// The `EventKind` of `value`, stored so events can be queried by kind
+    #[serde(skip)]
+    #[builder(skip = EventKind::from(&value) as u8)]
+    #[index(mode = "cluster")]
+    kind: u8,
+    /// The UTC month of `when` and the mac, like `202401/AA:BB:CC:DD:EE:FF`,
+    /// stored so a ring's events can be read from an index a month at a time
+    ///
+    /// persy fails to rebalance an index of more than one page when many of
+    /// its values are deleted at once, keying by month keeps this one to a
+    /// page until the rings have 128 months of events between them
+    #[serde(skip)]
+    #[builder(skip = ring_month(&mac, when.month_key()))]
+    #[index(mode = "cluster")]
+    ring_month: String,
+}
+
+impl RingEvent {
+    pub fn new(mac: impl Into<String>, when: impl Into<DateTime>, value: EventData) -> Self {
+        let kind = EventKind::from(&value) as u8;
+        let mac = mac.into();
+        let when = when.into();
+        Self {
+            ring_month: ring_month(&mac, when.month_key()),
+            mac,
+            when,
+            value,
+            kind,
+        }
+    }
+
+    pub fn kind(&self) -> EventKind {
+        EventKind::from(&self.value)
+    }
+}
+
+/// Two events are equal when they describe the same thing, the stored kind and
+/// ring month are derived from the value, time and mac
+impl PartialEq for RingEvent {
+    fn eq(&self, other: &Self) -> bool {
+        self.mac == other.mac && self.when == other.when && self.value == other.value
+    }
+}
+
+/// The fields of a `RingEvent` that are serialized, the kind and ring
+/// month are filled in from the value, time and mac
+#[derive(Deserialize)]
+struct RingEventFields {
+    mac: String,
+    when: DateTime,
+    value: EventData,
+}
+
+impl From<RingEventFields> for RingEvent {
+    fn from(fields: RingEventFields) -> Self {
+        Self::new(fields.mac, fields.when, fields.value)
+    }
+}
+
+/// The kind of measurement an `EventData` holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[repr(u8)]
+pub enum EventKind {
+    HeartRate = 1,
+    Sleep = 2,
+    Stress = 3,
+    /// Both single values and ranges, so a range replaces a single value
+    Oxygen = 4,
+    Activity = 5,
+    SleepSession = 6,
+    Battery = 7,
+}
+
+impl EventKind {
+    pub const ALL: [Self; 7] = [
+        Self::HeartRate,
+        Self::Sleep,
+        Self::Stress,
+        Self::Oxygen,
+        Self::Activity,
+        Self::SleepSession,
+        Self::Battery,
+    ];
+
+    /// The name used when serializing, `heartRate` for `HeartRate`
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::HeartRate => "heartRate",
+            Self::Sleep => "sleep",
+            Self::Stress => "stress",
+            Self::Oxygen => "oxygen",
+            Self::Activity => "activity",
+            Self::SleepSession => "sleepSession",
+            Self::Battery => "battery",
+        }
+    }
+}
+
+impl std::fmt::Display for EventKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for EventKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = Self::ALL.iter().map(|kind| kind.name()).collect();
+                format!(
+                    "unknown event kind {s}, expected one of {}",
+                    names.join(", ")
+                )
+            })
+    }
 }
 
-#[derive(Debug, structsy::derive::PersistentEmbedded, Serialize, Deserialize, PartialEq)]
+impl From<&EventData> for EventKind {
+    fn from(value: &EventData) -> Self {
+        match value {
+            EventData::HeartRate(_) => Self::HeartRate,
+            EventData::Sleep(_) => Self::Sleep,
+            EventData::Stress(_) => Self::Stress,
+            EventData::Oxygen(_) | EventData::OxygenRange(_) => Self::Oxygen,
+            EventData::Activity(_) => Self::Activity,
+            EventData::SleepSession(_) => Self::SleepSession,
+            EventData::Battery(_) => Self::Battery,
+        }
+    }
+}
+
+#[derive(Debug, Clone, structsy::derive::PersistentEmbedded, Serialize, Deserialize, PartialEq)]
 #[serde(tag = "type", content = "data")]
 pub enum EventData {
     HeartRate(u16),
     Sleep(u16),
     Stress(u16),
+    /// A single blood oxygen value, no longer produced but still read from
+    /// older databases
     Oxygen(u16),
     Activity(Activity),
+    SleepSession(SleepSessionRecord),
+    OxygenRange(OxygenRange),
+    Battery(BatteryLevel),
 }
 
 impl EventData {
-    pub fn activity(steps: u8, calories: f64, distance: u8) -> Self {
+    pub fn activity(steps: u32, calories: f64, distance: u32) -> Self {
         EventData::Activity(Activity {
             steps,
             calories,
@@ -178,6 +1074,9 @@ impl EventData {
     pub fn oxygen(value: u16) -> Self {
         EventData::Oxygen(value)
     }
+    pub fn oxygen_range(min: u8, max: u8) -> Self {
+        EventData::OxygenRange(OxygenRange { min, max })
+    }
     pub fn sleep(value: u16) -> Self {
         EventData::Sleep(value)
     }
@@ -187,27 +1086,84 @@ impl EventData {
     pub fn heart_rate(value: u16) -> Self {
         EventData::HeartRate(value)
     }
+    pub fn battery(level: u8, charging: bool) -> Self {
+        EventData::Battery(BatteryLevel { level, charging })
+    }
 }
 
-#[derive(Debug, structsy::derive::PersistentEmbedded, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, structsy::derive::PersistentEmbedded, Serialize, Deserialize, PartialEq)]
 pub struct Activity {
-    pub steps: u8,
+    pub steps: u32,
     pub calories: f64,
-    pub distance: u8,
+    pub distance: u32,
+}
+
+/// The battery's charge as a percentage and if the ring was on the charger
+#[derive(Debug, Clone, structsy::derive::PersistentEmbedded, Serialize, Deserialize, PartialEq)]
+pub struct BatteryLevel {
+    pub level: u8,
+    pub charging: bool,
+}
+
+/// The lowest and highest blood oxygen percentage over an hour
+#[derive(Debug, Clone, structsy::derive::PersistentEmbedded, Serialize, Deserialize, PartialEq)]
+pub struct OxygenRange {
+    pub min: u8,
+    pub max: u8,
+}
+
+/// A single sleep session with how long was spent in each stage, stored with
+/// the session's start as the event's time
+#[derive(Debug, Clone, structsy::derive::PersistentEmbedded, Serialize, Deserialize, PartialEq)]
+pub struct SleepSessionRecord {
+    pub start: DateTime,
+    pub end: DateTime,
+    /// Each stage in the order the ring reported them
+    pub stages: Vec<SleepStageRecord>,
+}
+
+#[derive(Debug, Clone, structsy::derive::PersistentEmbedded, Serialize, Deserialize, PartialEq)]
+pub struct SleepStageRecord {
+    pub stage: SleepStageKind,
+    pub minutes: u16,
+}
+
+#[derive(
+    Debug, Clone, Copy, structsy::derive::PersistentEmbedded, Serialize, Deserialize, PartialEq, Eq,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum SleepStageKind {
+    Light,
+    Deep,
+    Rem,
+    Awake,
+}
+
+#[queries(RingSettings)]
+trait FindSettingsByMac {
+    fn with_settings_mac(self, mac: &str) -> Self;
+}
+
+#[queries(RawSyncRecord)]
+trait FindRawByMac {
+    fn with_raw_mac(self, mac: &str) -> Self;
+    fn with_raw_kind(self, kind: u8) -> Self;
 }
 
 #[queries(RingEvent)]
 trait FindEventByMac {
     fn with_ring_mac(self, mac: &str) -> Self;
-    fn with_when(self, when: Filter<DateTime>) -> Self;
+    fn with_kind(self, kind: u8) -> Self;
     fn between_time<R: RangeBounds<DateTime>>(self, when: R) -> Self;
+    fn with_ring_month(self, ring_month: &str) -> Self;
+    fn between_ring_month<R: RangeBounds<String>>(self, ring_month: R) -> Self;
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{sync::atomic::AtomicUsize, time::Duration};
+    use std::time::Duration;
 
-    use time::{Date, Month, Time};
+    use time::{Date, Time};
 
     use super::*;
 
@@ -248,12 +1204,211 @@ mod tests {
     }
 
     #[test]
-    fn serde_events() {
-        let events = [
+    fn double_insert_ring() {
+        let db = Database::test().unwrap();
+        db.add_ring(&ring(MAC)).unwrap();
+        let e = db.add_ring(&ring(MAC)).unwrap_err();
+        assert!(
+            matches!(&e, Error::RingAlreadyExists { mac } if mac == MAC),
+            "{e:?}"
+        );
+        assert_eq!(db.get_rings(), [ring(MAC)]);
+    }
+
+    #[test]
+    fn upsert_ring_creates() {
+        let db = Database::test().unwrap();
+        assert!(db.add_or_update_ring(&ring(MAC)).unwrap());
+        assert_eq!(db.get_rings(), [ring(MAC)]);
+    }
+
+    #[test]
+    fn upsert_ring_updates_nickname() {
+        let db = Database::test().unwrap();
+        db.add_ring(&ring(MAC)).unwrap();
+        let nicknamed = Ring {
+            nickname: Some("lefty".to_string()),
+            ..ring(MAC)
+        };
+        assert!(!db.add_or_update_ring(&nicknamed).unwrap());
+        assert_eq!(db.get_rings(), [nicknamed]);
+    }
+
+    #[test]
+    fn patch_ring() {
+        let db = Database::test().unwrap();
+        let lefty = Ring {
+            nickname: Some("lefty".to_string()),
+            ..ring(MAC)
+        };
+        db.add_ring(&lefty).unwrap();
+        db.add_ring(&ring(MAC2)).unwrap();
+        let patched = db.patch_ring(MAC, RingPatch::default()).unwrap();
+        assert_eq!(patched, lefty);
+        let name_only = RingPatch {
+            name: Some("R02_1234".to_string()),
+            ..Default::default()
+        };
+        let patched = db.patch_ring(MAC, name_only).unwrap();
+        assert_eq!(patched.nickname.as_deref(), Some("lefty"));
+        assert_eq!(patched.name, "R02_1234");
+        let nickname_only = RingPatch {
+            nickname: Some(Some("righty".to_string())),
+            ..Default::default()
+        };
+        let patched = db.patch_ring(MAC, nickname_only).unwrap();
+        assert_eq!(patched.nickname.as_deref(), Some("righty"));
+        assert_eq!(patched.name, "R02_1234");
+        let both = RingPatch {
+            nickname: Some(Some("lefty".to_string())),
+            name: Some("R02_5678".to_string()),
+        };
+        let patched = db.patch_ring(MAC, both).unwrap();
+        assert_eq!(patched.nickname.as_deref(), Some("lefty"));
+        assert_eq!(patched.name, "R02_5678");
+        let cleared = RingPatch {
+            nickname: Some(None),
+            ..Default::default()
+        };
+        let patched = db.patch_ring(MAC, cleared).unwrap();
+        assert_eq!(patched.nickname, None);
+        assert_eq!(patched.name, "R02_5678");
+        assert_eq!(db.get_ring(MAC).unwrap(), patched);
+        // the other ring is untouched
+        assert_eq!(db.get_ring(MAC2).unwrap(), ring(MAC2));
+    }
+
+    #[test]
+    fn patch_missing_ring_is_not_found() {
+        let db = Database::test().unwrap();
+        let e = db.patch_ring(MAC, RingPatch::default()).unwrap_err();
+        assert!(
+            matches!(&e, Error::RingNotFound { mac } if mac == MAC),
+            "{e:?}"
+        );
+    }
+
+    #[test]
+    fn ring_patch_missing_or_null() {
+        let patch = |json: &str| serde_json::from_str::<RingPatch>(json);
+        assert_eq!(patch("{}").unwrap(), RingPatch::default());
+        assert_eq!(patch(r#"{"nickname": null}"#).unwrap().nickname, Some(None));
+        assert_eq!(
+            patch(r#"{"nickname": "lefty"}"#).unwrap().nickname,
+            Some(Some("lefty".to_string()))
+        );
+        assert_eq!(
+            patch(r#"{"name": "R02"}"#).unwrap(),
+            RingPatch {
+                nickname: None,
+                name: Some("R02".to_string()),
+            }
+        );
+        // a ring always has a name and its mac can't change
+        assert!(patch(r#"{"name": null}"#).is_err());
+        assert!(patch(r#"{"mac": "00:00:00:00:00:01"}"#).is_err());
+    }
+
+    #[test]
+    fn settings_round_trip() {
+        let db = Database::test().unwrap();
+        db.add_ring(&ring(MAC)).unwrap();
+        assert_eq!(db.get_settings(MAC).unwrap(), None);
+        let mut settings = RingSettings {
+            heart_rate_enabled: true,
+            heart_rate_interval: 30,
+            oxygen_auto: true,
+            goals: Goals {
+                steps: 10_000,
+                calories: 500,
+                distance: 8,
+            },
+            ..RingSettings::new(MAC)
+        };
+        db.set_settings(&settings).unwrap();
+        assert_eq!(db.get_settings(MAC).unwrap().as_ref(), Some(&settings));
+        settings.stress_auto = true;
+        db.set_settings(&settings).unwrap();
+        assert_eq!(db.get_settings(MAC).unwrap(), Some(settings));
+        let e = db.set_settings(&RingSettings::new(MAC2)).unwrap_err();
+        assert!(
+            matches!(&e, Error::RingNotFound { mac } if mac == MAC2),
+            "{e:?}"
+        );
+    }
+
+    #[test]
+    fn last_sync_categories_are_independent() {
+        let db = Database::test().unwrap();
+        db.add_ring(&ring(MAC)).unwrap();
+        let first = time::macros::datetime!(2024-11-27 8:00 UTC);
+        let second = time::macros::datetime!(2024-11-28 8:00 -5);
+        db.update_last_sync(MAC, SyncCategory::HeartRate, first)
+            .unwrap();
+        db.update_last_sync(MAC, SyncCategory::Sleep, first)
+            .unwrap();
+        db.update_last_sync(MAC, SyncCategory::HeartRate, second)
+            .unwrap();
+        let settings = db.get_settings(MAC).unwrap().unwrap();
+        assert_eq!(settings.last_sync(SyncCategory::HeartRate), Some(second));
+        assert_eq!(settings.last_sync(SyncCategory::Sleep), Some(first));
+        assert_eq!(settings.last_sync(SyncCategory::Activity), None);
+        // the rest of the settings are left alone
+        let settings = RingSettings {
+            heart_rate_interval: 5,
+            ..settings
+        };
+        db.set_settings(&settings).unwrap();
+        db.update_last_sync(MAC, SyncCategory::Activity, first)
+            .unwrap();
+        let settings = db.get_settings(MAC).unwrap().unwrap();
+        assert_eq!(settings.heart_rate_interval, 5);
+        assert_eq!(settings.last_sync.len(), 3);
+        assert!(db
+            .update_last_sync(MAC2, SyncCategory::Activity, first)
+            .is_err());
+    }
+
+    #[test]
+    fn delete_ring_removes_settings() {
+        let db = Database::test().unwrap();
+        db.add_ring(&ring(MAC)).unwrap();
+        db.set_settings(&RingSettings::new(MAC)).unwrap();
+        db.delete_ring(MAC, false).unwrap();
+        assert_eq!(db.get_settings(MAC).unwrap(), None);
+    }
+
+    #[test]
+    fn get_missing_ring_is_not_found() {
+        let db = Database::test().unwrap();
+        let e = db.get_ring(MAC).unwrap_err();
+        assert!(
+            matches!(&e, Error::RingNotFound { mac } if mac == MAC),
+            "{e:?}"
+        );
+    }
+
+    #[test]
+    fn update_missing_ring_is_not_found() {
+        let db = Database::test().unwrap();
+        db.add_ring(&ring(MAC2)).unwrap();
+        let e = db.update_ring(&ring(MAC)).unwrap_err();
+        assert!(
+            matches!(&e, Error::RingNotFound { mac } if mac == MAC),
+            "{e:?}"
+        );
+        // still usable by callers that only want a boxed error
+        let e: Box<dyn std::error::Error> = e.into();
+        assert_eq!(e.to_string(), format!("unable to find ring with {MAC}"));
+    }
+
+    #[test]
+    fn serde_events() {
+        let events = [
             RingEvent::builder()
                 .mac(MAC)
                 .when(DateTime::builder().year(2001).month(1).day(31).build())
-                .value(EventData::activity(11, 222.0, 88))
+                .value(EventData::activity(5_000, 222.0, 3_800))
                 .build(),
             RingEvent::builder()
                 .mac(MAC)
@@ -275,6 +1430,11 @@ mod tests {
                 .when(DateTime::builder().year(2001).month(1).day(31).build())
                 .value(EventData::Stress(0))
                 .build(),
+            RingEvent::builder()
+                .mac(MAC)
+                .when(DateTime::builder().year(2001).month(1).day(31).build())
+                .value(EventData::oxygen_range(94, 98))
+                .build(),
         ];
         let json = serde_json::to_string_pretty(&events).unwrap();
         let back: Vec<RingEvent> = serde_json::from_str(&json).unwrap();
@@ -282,6 +1442,105 @@ mod tests {
         insta::assert_snapshot!(json);
     }
 
+    #[test]
+    fn large_activity_round_trip() {
+        let db = Database::test().unwrap();
+        let event = event_at(9, EventData::activity(5_000, 250.5, 4_200));
+        db.add_events(std::slice::from_ref(&event), UpsertMode::Overwrite)
+            .unwrap();
+        assert_eq!(db.get_events_in_range(MAC, ..).unwrap(), [event]);
+    }
+
+    fn at(month: u8, day: u8, hour: u8) -> DateTime {
+        DateTime::builder()
+            .year(2001)
+            .month(month)
+            .day(day)
+            .hour(hour)
+            .build()
+    }
+
+    fn sleep_session(start: DateTime, end: DateTime) -> RingEvent {
+        RingEvent::builder()
+            .mac(MAC)
+            .when(start)
+            .value(EventData::SleepSession(SleepSessionRecord {
+                start,
+                end,
+                stages: vec![
+                    SleepStageRecord {
+                        stage: SleepStageKind::Light,
+                        minutes: 60,
+                    },
+                    SleepStageRecord {
+                        stage: SleepStageKind::Awake,
+                        minutes: 15,
+                    },
+                    SleepStageRecord {
+                        stage: SleepStageKind::Deep,
+                        minutes: 90,
+                    },
+                    SleepStageRecord {
+                        stage: SleepStageKind::Rem,
+                        minutes: 30,
+                    },
+                ],
+            }))
+            .build()
+    }
+
+    #[test]
+    fn serde_sleep_session() {
+        let event = sleep_session(at(1, 30, 23), at(1, 31, 7));
+        let json = serde_json::to_string_pretty(&event).unwrap();
+        let back: RingEvent = serde_json::from_str(&json).unwrap();
+        assert_eq!(event, back);
+        insta::assert_snapshot!(json);
+    }
+
+    #[test]
+    fn sleep_sessions_cross_midnight() {
+        let db = Database::test().unwrap();
+        let nap = sleep_session(at(1, 31, 14), at(1, 31, 15));
+        let night = sleep_session(at(1, 31, 23), at(2, 1, 7));
+        db.add_events(
+            &[night, nap, event_at(23, EventData::sleep(180))],
+            UpsertMode::Overwrite,
+        )
+        .unwrap();
+        let day = |d: u8| {
+            let start = Date::from_calendar_date(2001, time::Month::January, 31)
+                .unwrap()
+                .midnight()
+                .assume_utc()
+                + Duration::from_secs(u64::from(d) * 24 * 60 * 60);
+            start..start + Duration::from_secs(24 * 60 * 60)
+        };
+        let starts = |sessions: Vec<SleepSessionRecord>| -> Vec<u8> {
+            sessions.iter().map(|s| s.start.hour).collect()
+        };
+        assert_eq!(
+            starts(db.get_sleep_sessions(MAC, day(0)).unwrap()),
+            [14, 23]
+        );
+        assert_eq!(starts(db.get_sleep_sessions(MAC, day(1)).unwrap()), [23]);
+        assert!(db.get_sleep_sessions(MAC, day(2)).unwrap().is_empty());
+        assert!(db.get_sleep_sessions(MAC2, day(0)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn legacy_events() {
+        let events: Vec<RingEvent> =
+            serde_json::from_str(include_str!("fixtures/legacy_events.json")).unwrap();
+        assert_eq!(events.len(), 5);
+        let when = DateTime::builder().year(2001).month(1).day(31).build();
+        assert!(events.iter().all(|e| e.when == when && e.mac == MAC));
+        assert_eq!(events[4].value, EventData::Stress(0));
+        let db = Database::test().unwrap();
+        db.add_events(&events, UpsertMode::InsertDuplicate).unwrap();
+        assert_eq!(db.get_events_in_range(MAC, ..).unwrap(), events);
+    }
+
     #[test]
     fn no_data_loss() {
         let db = Database::test().unwrap();
@@ -291,24 +1550,779 @@ mod tests {
             Time::from_hms(0, 0, 0).unwrap(),
         );
         for i in 0..48 {
-            events.push(RingEvent {
-                mac: MAC.to_string(),
-                when: time.try_into().unwrap(),
-                value: super::EventData::Stress(i),
-            });
+            events.push(RingEvent::new(
+                MAC,
+                DateTime::try_from(time).unwrap(),
+                super::EventData::Stress(i),
+            ));
             time += Duration::from_secs(60 * 60);
         }
 
-        db.add_events(&events).unwrap();
+        db.add_events(&events, UpsertMode::Overwrite).unwrap();
         let from_db: Vec<_> =
             db.0.query::<RingEvent>()
                 .fetch()
-                .into_iter()
                 .map(|(_, e)| e)
                 .collect();
         assert_eq!(from_db, events)
     }
 
+    fn event_at(hour: u8, value: EventData) -> RingEvent {
+        RingEvent::builder()
+            .mac(MAC)
+            .when(
+                DateTime::builder()
+                    .year(2001)
+                    .month(1)
+                    .day(31)
+                    .hour(hour)
+                    .build(),
+            )
+            .value(value)
+            .build()
+    }
+
+    fn all_events(db: &Database) -> Vec<RingEvent> {
+        db.0.query::<RingEvent>().fetch().map(|(_, e)| e).collect()
+    }
+
+    #[test]
+    fn same_kind_same_time_overwrite() {
+        let db = Database::test().unwrap();
+        db.add_events(
+            &[event_at(1, EventData::heart_rate(70))],
+            UpsertMode::Overwrite,
+        )
+        .unwrap();
+        db.add_events(
+            &[event_at(1, EventData::heart_rate(85))],
+            UpsertMode::Overwrite,
+        )
+        .unwrap();
+        assert_eq!(all_events(&db), [event_at(1, EventData::heart_rate(85))]);
+    }
+
+    #[test]
+    fn same_kind_same_time_skip() {
+        let db = Database::test().unwrap();
+        db.add_events(&[event_at(1, EventData::heart_rate(70))], UpsertMode::Skip)
+            .unwrap();
+        let counts = db
+            .add_events(&[event_at(1, EventData::heart_rate(85))], UpsertMode::Skip)
+            .unwrap();
+        assert_eq!(counts.skipped, 1);
+        assert_eq!(all_events(&db), [event_at(1, EventData::heart_rate(70))]);
+    }
+
+    #[test]
+    fn same_kind_same_time_insert_duplicate() {
+        let db = Database::test().unwrap();
+        let events = [
+            event_at(1, EventData::heart_rate(70)),
+            event_at(1, EventData::heart_rate(85)),
+        ];
+        db.add_events(&events, UpsertMode::InsertDuplicate).unwrap();
+        db.add_events(&events, UpsertMode::InsertDuplicate).unwrap();
+        assert_eq!(all_events(&db), events);
+    }
+
+    #[test]
+    fn same_kind_same_day_different_time() {
+        let db = Database::test().unwrap();
+        // midnight used to match every event on the same day
+        let events = [
+            event_at(0, EventData::heart_rate(70)),
+            event_at(1, EventData::heart_rate(85)),
+            event_at(13, EventData::heart_rate(60)),
+        ];
+        for mode in [UpsertMode::Skip, UpsertMode::Overwrite] {
+            let db2 = Database::test().unwrap();
+            for event in events.iter() {
+                db2.add_events(std::slice::from_ref(event), mode).unwrap();
+            }
+            assert_eq!(all_events(&db2), events, "{mode:?}");
+        }
+        db.add_events(&events, UpsertMode::Overwrite).unwrap();
+        assert_eq!(all_events(&db), events);
+    }
+
+    #[test]
+    fn different_kind_same_time() {
+        let db = Database::test().unwrap();
+        let events = [
+            event_at(1, EventData::heart_rate(70)),
+            event_at(1, EventData::stress(20)),
+        ];
+        db.add_events(&events, UpsertMode::Overwrite).unwrap();
+        assert_eq!(all_events(&db), events);
+    }
+
+    /// Stored events from an earlier sync and a new batch overlapping them,
+    /// repeating some of its own events and covering a second ring
+    fn overlapping_batch() -> (Vec<RingEvent>, Vec<RingEvent>) {
+        let other = |hour, value| RingEvent {
+            mac: MAC2.to_string(),
+            ..event_at(hour, value)
+        };
+        let stored = vec![
+            event_at(1, EventData::heart_rate(70)),
+            event_at(2, EventData::heart_rate(71)),
+            event_at(2, EventData::stress(20)),
+        ];
+        let batch = vec![
+            event_at(1, EventData::heart_rate(70)),
+            event_at(2, EventData::heart_rate(90)),
+            event_at(3, EventData::heart_rate(72)),
+            event_at(3, EventData::heart_rate(73)),
+            other(2, EventData::heart_rate(60)),
+            event_at(0, EventData::stress(10)),
+        ];
+        (stored, batch)
+    }
+
+    fn sorted(mut events: Vec<RingEvent>) -> Vec<RingEvent> {
+        events.sort_by(|a, b| {
+            (&a.mac, a.when, a.kind)
+                .cmp(&(&b.mac, b.when, b.kind))
+                .then_with(|| format!("{:?}", a.value).cmp(&format!("{:?}", b.value)))
+        });
+        events
+    }
+
+    #[test]
+    fn batch_dedup() {
+        let other = |hour, value| RingEvent {
+            mac: MAC2.to_string(),
+            ..event_at(hour, value)
+        };
+        let expected = [
+            (
+                UpsertMode::Skip,
+                UpsertCounts {
+                    inserted: 3,
+                    updated: 0,
+                    skipped: 3,
+                },
+                vec![
+                    event_at(0, EventData::stress(10)),
+                    event_at(1, EventData::heart_rate(70)),
+                    event_at(2, EventData::heart_rate(71)),
+                    event_at(2, EventData::stress(20)),
+                    event_at(3, EventData::heart_rate(72)),
+                    other(2, EventData::heart_rate(60)),
+                ],
+            ),
+            (
+                UpsertMode::Overwrite,
+                UpsertCounts {
+                    inserted: 3,
+                    updated: 3,
+                    skipped: 0,
+                },
+                vec![
+                    event_at(0, EventData::stress(10)),
+                    event_at(1, EventData::heart_rate(70)),
+                    event_at(2, EventData::heart_rate(90)),
+                    event_at(2, EventData::stress(20)),
+                    event_at(3, EventData::heart_rate(73)),
+                    other(2, EventData::heart_rate(60)),
+                ],
+            ),
+            (
+                UpsertMode::InsertDuplicate,
+                UpsertCounts {
+                    inserted: 5,
+                    updated: 0,
+                    skipped: 1,
+                },
+                vec![
+                    event_at(0, EventData::stress(10)),
+                    event_at(1, EventData::heart_rate(70)),
+                    event_at(2, EventData::heart_rate(71)),
+                    event_at(2, EventData::heart_rate(90)),
+                    event_at(2, EventData::stress(20)),
+                    event_at(3, EventData::heart_rate(72)),
+                    event_at(3, EventData::heart_rate(73)),
+                    other(2, EventData::heart_rate(60)),
+                ],
+            ),
+        ];
+        for (mode, counts, events) in expected {
+            let (stored, batch) = overlapping_batch();
+            let db = Database::test().unwrap();
+            db.add_events(&stored, UpsertMode::InsertDuplicate).unwrap();
+            assert_eq!(db.add_events(&batch, mode).unwrap(), counts, "{mode:?}");
+            assert_eq!(sorted(all_events(&db)), sorted(events.clone()), "{mode:?}");
+            // the same batch one event at a time
+            let db = Database::test().unwrap();
+            db.add_events(&stored, UpsertMode::InsertDuplicate).unwrap();
+            for event in &batch {
+                db.add_events(std::slice::from_ref(event), mode).unwrap();
+            }
+            assert_eq!(sorted(all_events(&db)), sorted(events.clone()), "{mode:?}");
+            // and committed a few at a time
+            let db = Database::test().unwrap();
+            db.add_events(&stored, UpsertMode::InsertDuplicate).unwrap();
+            assert_eq!(
+                db.add_events_chunked(&batch, mode, 4).unwrap(),
+                counts,
+                "{mode:?}"
+            );
+            assert_eq!(sorted(all_events(&db)), sorted(events), "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn oxygen_range_replaces_single_value() {
+        let db = Database::test().unwrap();
+        db.add_events(&[event_at(1, EventData::oxygen(96))], UpsertMode::Overwrite)
+            .unwrap();
+        let range = event_at(1, EventData::oxygen_range(94, 98));
+        let counts = db
+            .add_events(std::slice::from_ref(&range), UpsertMode::Overwrite)
+            .unwrap();
+        assert_eq!(counts.updated, 1);
+        assert_eq!(all_events(&db), [range]);
+    }
+
+    #[test]
+    fn events_of_kind() {
+        let db = Database::test().unwrap();
+        let events = [
+            event_at(1, EventData::heart_rate(70)),
+            event_at(2, EventData::oxygen(96)),
+            event_at(3, EventData::stress(20)),
+            event_at(4, EventData::oxygen_range(94, 98)),
+        ];
+        db.add_events(&events, UpsertMode::Overwrite).unwrap();
+        let oxygen = db.get_events_of_kind(MAC, EventKind::Oxygen, ..).unwrap();
+        assert_eq!(oxygen, [events[1].clone(), events[3].clone()]);
+        assert!(db
+            .get_events_of_kind(MAC, EventKind::Sleep, ..)
+            .unwrap()
+            .is_empty());
+    }
+
+    /// A heart rate event every minute from the start of 2024, inserted newest
+    /// first so reading them in order relies on sorting
+    /// `count` events for `MAC`, one every `minutes` from the start of 2024
+    fn every(db: &Database, minutes: u64, count: u32) -> Vec<RingEvent> {
+        let start = time::macros::datetime!(2024-01-01 0:00 UTC);
+        let events: Vec<_> = (0..count)
+            .map(|i| {
+                RingEvent::new(
+                    MAC,
+                    DateTime::try_from(start + Duration::from_secs(u64::from(i) * minutes * 60))
+                        .unwrap(),
+                    EventData::heart_rate(60 + (i % 40) as u16),
+                )
+            })
+            .collect();
+        // a single transaction gets much slower as the ring's index entry grows
+        for chunk in events.rchunks(1000) {
+            let mut tx = db.0.begin().unwrap();
+            for event in chunk.iter().rev() {
+                tx.insert(event).unwrap();
+            }
+            tx.commit().unwrap();
+        }
+        events
+    }
+
+    #[test]
+    fn iter_events_is_lazy() {
+        let db = Database::test().unwrap();
+        // every 15 minutes for 100 days
+        let events = every(&db, 15, 100 * 24 * 4);
+        let start = time::macros::datetime!(2024-01-01 0:00 UTC);
+        // another ring with an event in the first window and one the next month
+        let other: Vec<_> = [
+            start + time::Duration::HOUR,
+            start + time::Duration::days(40),
+        ]
+        .into_iter()
+        .map(|when| {
+            RingEvent::new(
+                MAC2,
+                DateTime::try_from(when).unwrap(),
+                EventData::heart_rate(90),
+            )
+        })
+        .collect();
+        db.add_events(&other, UpsertMode::Overwrite).unwrap();
+        let snapshot = db.0.snapshot().unwrap();
+        let fetches = std::cell::Cell::new(0);
+        let rows = std::cell::Cell::new(0);
+        let first = EventIter::new(start..start + time::Duration::days(100), |window| {
+            fetches.set(fetches.get() + 1);
+            // every row read from the database, before any are left out
+            let events = ring_events(MAC, convert_range(&window)?, |filter| {
+                snapshot
+                    .fetch(filter)
+                    .inspect(|_| rows.set(rows.get() + 1))
+                    .collect()
+            });
+            Ok(events.into_iter().map(|(_, event)| event).collect())
+        })
+        .take(10)
+        .collect::<Result<Vec<_>>>()
+        .unwrap();
+        assert_eq!(first, events[..10]);
+        assert_eq!(fetches.get(), 1);
+        // only the ring's january is read, not its later months or the other ring
+        assert_eq!(rows.get(), 31 * 24 * 4);
+    }
+
+    #[test]
+    fn iter_events_in_order() {
+        let db = Database::test().unwrap();
+        let events = every(&db, 1, 5 * 24 * 60);
+        let start = time::macros::datetime!(2024-01-01 12:30 UTC);
+        // 11:00 UTC
+        let end = time::macros::datetime!(2024-01-04 6:00 -5);
+        let expected = &events[12 * 60 + 30..3 * 24 * 60 + 11 * 60];
+        let iterated = db
+            .iter_events(MAC, start..end)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(iterated, expected);
+        assert_eq!(iterated, db.get_events_in_range(MAC, start..end).unwrap());
+        let mut visited = Vec::new();
+        db.for_each_event(MAC, start..end, |event| visited.push(event))
+            .unwrap();
+        assert_eq!(visited, expected);
+    }
+
+    /// One event every 6 hours from December through February, inserted
+    /// newest first
+    fn three_months() -> Vec<RingEvent> {
+        let start = OffsetDateTime::new_utc(
+            Date::from_calendar_date(2000, time::Month::December, 1).unwrap(),
+            Time::MIDNIGHT,
+        );
+        let end = OffsetDateTime::new_utc(
+            Date::from_calendar_date(2001, time::Month::March, 1).unwrap(),
+            Time::MIDNIGHT,
+        );
+        let mut events = Vec::new();
+        let mut time = start;
+        while time < end {
+            events.push(RingEvent::new(
+                MAC,
+                DateTime::try_from(time).unwrap(),
+                EventData::HeartRate(time.hour().into()),
+            ));
+            time += Duration::from_secs(6 * 60 * 60);
+        }
+        events.reverse();
+        events
+    }
+
+    #[test]
+    fn range_crosses_month() {
+        let db = Database::test().unwrap();
+        let events = three_months();
+        db.add_events(&events, UpsertMode::Overwrite).unwrap();
+        let start = OffsetDateTime::new_utc(
+            Date::from_calendar_date(2001, time::Month::January, 25).unwrap(),
+            Time::MIDNIGHT,
+        );
+        let end = start + Duration::from_secs(14 * 24 * 60 * 60);
+        let from_db = db.get_events_in_range(MAC, start..end).unwrap();
+        assert_eq!(from_db.len(), 14 * 4);
+        assert_eq!(
+            from_db.first().unwrap().when,
+            DateTime::try_from(start).unwrap()
+        );
+        assert_eq!(
+            from_db.last().unwrap().when,
+            DateTime::try_from(end - Duration::from_secs(6 * 60 * 60)).unwrap()
+        );
+        assert!(from_db.windows(2).all(|w| w[0].when < w[1].when));
+    }
+
+    #[test]
+    fn range_unbounded_start() {
+        let db = Database::test().unwrap();
+        let mut events = three_months();
+        db.add_events(&events, UpsertMode::Overwrite).unwrap();
+        let end = OffsetDateTime::new_utc(
+            Date::from_calendar_date(2001, time::Month::January, 1).unwrap(),
+            Time::MIDNIGHT,
+        );
+        let from_db = db.get_events_in_range(MAC, ..=end).unwrap();
+        events.reverse();
+        let expected: Vec<_> = events
+            .into_iter()
+            .filter(|e| e.when <= DateTime::try_from(end).unwrap())
+            .collect();
+        assert_eq!(from_db.len(), 31 * 4 + 1);
+        assert_eq!(from_db, expected);
+    }
+
+    #[test]
+    fn range_unbounded_end() {
+        let db = Database::test().unwrap();
+        db.add_events(&three_months(), UpsertMode::Overwrite)
+            .unwrap();
+        db.add_events(
+            &[RingEvent::builder()
+                .mac(MAC2)
+                .when(DateTime::builder().year(2001).month(2).day(1).build())
+                .value(EventData::heart_rate(1))
+                .build()],
+            UpsertMode::Overwrite,
+        )
+        .unwrap();
+        let start = OffsetDateTime::new_utc(
+            Date::from_calendar_date(2001, time::Month::February, 1).unwrap(),
+            Time::MIDNIGHT,
+        );
+        let from_db = db.get_events_in_range(MAC, start..).unwrap();
+        assert_eq!(from_db.len(), 28 * 4);
+        assert!(from_db.iter().all(|e| e.mac == MAC));
+    }
+
+    fn ring(mac: &str) -> Ring {
+        Ring {
+            mac: mac.to_string(),
+            nickname: None,
+            name: mac.to_string(),
+        }
+    }
+
+    fn add_two_rings(db: &Database) -> Vec<RingEvent> {
+        db.add_ring(&ring(MAC)).unwrap();
+        db.add_ring(&ring(MAC2)).unwrap();
+        db.add_events(&three_months(), UpsertMode::Overwrite)
+            .unwrap();
+        let other: Vec<_> = three_months()
+            .into_iter()
+            .map(|mut e| {
+                e.mac = MAC2.to_string();
+                e
+            })
+            .collect();
+        db.add_events(&other, UpsertMode::Overwrite).unwrap();
+        db.get_events_in_range(MAC2, ..).unwrap()
+    }
+
+    #[test]
+    fn delete_ring_cascade() {
+        let db = Database::test().unwrap();
+        let other = add_two_rings(&db);
+        assert!(db.delete_ring(MAC, true).unwrap());
+        assert!(db.get_ring(MAC).is_err());
+        assert!(db.get_events_in_range(MAC, ..).unwrap().is_empty());
+        assert_eq!(db.get_rings(), [ring(MAC2)]);
+        assert_eq!(db.get_events_in_range(MAC2, ..).unwrap(), other);
+    }
+
+    #[test]
+    fn raw_syncs_by_ring_and_category() {
+        let db = Database::test().unwrap();
+        let raw = |mac: &str, hour: u8, category, byte: u8| {
+            let when = time::macros::datetime!(2024-11-27 0:00)
+                .replace_hour(hour)
+                .unwrap();
+            RawSyncRecord::new(mac, when, category, 0, vec![byte]).unwrap()
+        };
+        let later = raw(MAC, 12, SyncCategory::Sleep, 2);
+        let earlier = raw(MAC, 8, SyncCategory::Sleep, 1);
+        for record in [
+            &later,
+            &raw(MAC, 9, SyncCategory::Oxygen, 3),
+            &earlier,
+            &raw(MAC2, 10, SyncCategory::Sleep, 4),
+        ] {
+            db.add_raw_sync(record).unwrap();
+        }
+        let sleep: Vec<_> = db.iter_raw_syncs(MAC, SyncCategory::Sleep).collect();
+        assert_eq!(sleep, [earlier, later]);
+        assert_eq!(db.iter_raw_syncs(MAC, SyncCategory::Stress).count(), 0);
+        add_two_rings(&db);
+        assert!(db.delete_ring(MAC, true).unwrap());
+        assert_eq!(db.iter_raw_syncs(MAC, SyncCategory::Oxygen).count(), 0);
+        assert_eq!(db.iter_raw_syncs(MAC2, SyncCategory::Sleep).count(), 1);
+    }
+
+    #[test]
+    fn delete_ring_no_cascade() {
+        let db = Database::test().unwrap();
+        add_two_rings(&db);
+        assert!(db.delete_ring(MAC, false).unwrap());
+        assert!(db.get_ring(MAC).is_err());
+        assert_eq!(
+            db.get_events_in_range(MAC, ..).unwrap().len(),
+            three_months().len()
+        );
+    }
+
+    #[test]
+    fn delete_missing_ring() {
+        let db = Database::test().unwrap();
+        add_two_rings(&db);
+        assert!(!db.delete_ring("11:11:11:11:11:11", true).unwrap());
+        assert_eq!(db.get_rings().len(), 2);
+    }
+
+    #[test]
+    fn delete_events_in_range() {
+        let db = Database::test().unwrap();
+        let other = add_two_rings(&db);
+        let start = OffsetDateTime::new_utc(
+            Date::from_calendar_date(2001, time::Month::January, 1).unwrap(),
+            Time::MIDNIGHT,
+        );
+        let end = OffsetDateTime::new_utc(
+            Date::from_calendar_date(2001, time::Month::February, 1).unwrap(),
+            Time::MIDNIGHT,
+        );
+        assert_eq!(db.delete_events(MAC, Some(start..end)).unwrap(), 31 * 4);
+        assert!(db.get_events_in_range(MAC, start..end).unwrap().is_empty());
+        assert_eq!(
+            db.get_events_in_range(MAC, ..).unwrap().len(),
+            three_months().len() - 31 * 4
+        );
+        assert_eq!(db.get_events_in_range(MAC2, ..).unwrap(), other);
+    }
+
+    #[test]
+    fn delete_all_events() {
+        let db = Database::test().unwrap();
+        let other = add_two_rings(&db);
+        assert_eq!(db.delete_events(MAC, None).unwrap(), three_months().len());
+        assert!(db.get_events_in_range(MAC, ..).unwrap().is_empty());
+        assert_eq!(db.get_events_in_range(MAC2, ..).unwrap(), other);
+        assert_eq!(db.delete_events("11:11:11:11:11:11", None).unwrap(), 0);
+    }
+
+    #[test]
+    fn prune_keeps_newer_events_and_kinds() {
+        let db = Database::test().unwrap();
+        add_two_rings(&db);
+        let cutoff = OffsetDateTime::new_utc(
+            Date::from_calendar_date(2001, time::Month::January, 1).unwrap(),
+            Time::MIDNIGHT,
+        );
+        let sleep = event_at(1, EventData::Sleep(480));
+        let old_sleep = RingEvent::new(
+            MAC,
+            DateTime::try_from(cutoff - Duration::from_secs(60 * 60)).unwrap(),
+            EventData::Sleep(420),
+        );
+        db.add_events(std::slice::from_ref(&old_sleep), UpsertMode::Overwrite)
+            .unwrap();
+        let newer = db.get_events_in_range(MAC, cutoff..).unwrap();
+        let removed = db.prune_events(cutoff, &[EventKind::Sleep]).unwrap();
+        assert_eq!(removed, 31 * 4 * 2);
+        let mut expected = vec![old_sleep];
+        expected.extend(newer);
+        assert_eq!(db.get_events_in_range(MAC, ..).unwrap(), expected);
+        assert!(db.get_events_in_range(MAC2, ..cutoff).unwrap().is_empty());
+        // without keeping sleep only the old session is left to remove
+        assert_eq!(db.prune_events(cutoff, &[]).unwrap(), 1);
+        db.add_events(std::slice::from_ref(&sleep), UpsertMode::Overwrite)
+            .unwrap();
+        assert_eq!(
+            db.get_events_of_kind(MAC, EventKind::Sleep, ..).unwrap(),
+            [sleep]
+        );
+    }
+
+    #[test]
+    fn compact_copies_everything() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::new(dir.path().join("fissure.db")).unwrap();
+        add_two_rings(&db);
+        db.update_last_sync(MAC, SyncCategory::Sleep, OffsetDateTime::UNIX_EPOCH)
+            .unwrap();
+        let path = dir.path().join("compacted.db");
+        db.compact_into(&path).unwrap();
+        let compacted = Database::new(&path).unwrap();
+        assert_eq!(compacted.get_rings(), db.get_rings());
+        assert_eq!(
+            compacted.get_settings(MAC).unwrap(),
+            db.get_settings(MAC).unwrap()
+        );
+        for mac in [MAC, MAC2] {
+            assert_eq!(
+                compacted.get_events_in_range(mac, ..).unwrap(),
+                db.get_events_in_range(mac, ..).unwrap()
+            );
+        }
+        assert!(db.compact_into(&path).is_err());
+    }
+
+    /// A heart rate every 5 minutes and an activity event every hour
+    fn synthetic_day(day: Date) -> Vec<RingEvent> {
+        let start = day.midnight().assume_utc();
+        let mut events = Vec::new();
+        for i in 0..288u16 {
+            let when = start + Duration::from_secs(u64::from(i) * 5 * 60);
+            events.push(RingEvent::new(
+                MAC,
+                DateTime::try_from(when).unwrap(),
+                EventData::heart_rate(60 + i % 40),
+            ));
+        }
+        for hour in 8..12u8 {
+            let when = start + Duration::from_secs(u64::from(hour) * 60 * 60 + 1);
+            events.push(RingEvent::new(
+                MAC,
+                DateTime::try_from(when).unwrap(),
+                EventData::activity(100 + u32::from(hour), 10.5, hour.into()),
+            ));
+        }
+        for (hour, min, max) in [(2u64, 94, 98), (3, 92, 97)] {
+            let when = start + Duration::from_secs(hour * 60 * 60);
+            events.push(RingEvent::new(
+                MAC,
+                DateTime::try_from(when).unwrap(),
+                EventData::oxygen_range(min, max),
+            ));
+        }
+        events
+    }
+
+    #[test]
+    fn daily_summary() {
+        let db = Database::test().unwrap();
+        let day = Date::from_calendar_date(2001, time::Month::January, 31).unwrap();
+        db.add_events(&synthetic_day(day), UpsertMode::Overwrite)
+            .unwrap();
+        db.add_events(
+            &synthetic_day(day.next_day().unwrap()),
+            UpsertMode::Overwrite,
+        )
+        .unwrap();
+        let summary = db.daily_summary(MAC, day).unwrap();
+        // 0..40 repeated 7 times then 0..8
+        let total: u32 = (0..288u32).map(|i| 60 + i % 40).sum();
+        assert_eq!(
+            summary,
+            DailySummary {
+                day,
+                heart_rate_min: Some(60),
+                heart_rate_max: Some(99),
+                heart_rate_avg: Some(total as f64 / 288.0),
+                steps: Some(108 + 109 + 110 + 111),
+                calories: Some(42.0),
+                distance: Some(8 + 9 + 10 + 11),
+                sleep_minutes: None,
+                sleep_stages: None,
+                stress_avg: None,
+                oxygen_avg: Some(95.25),
+                oxygen_min: Some(92),
+                oxygen_max: Some(98),
+            }
+        );
+    }
+
+    #[test]
+    fn daily_summary_no_data() {
+        let db = Database::test().unwrap();
+        let day = Date::from_calendar_date(2001, time::Month::January, 31).unwrap();
+        let summary = db.daily_summary(MAC, day).unwrap();
+        assert_eq!(summary.heart_rate_min, None);
+        assert_eq!(summary.heart_rate_avg, None);
+        assert_eq!(summary.steps, None);
+        assert_eq!(summary.calories, None);
+        assert!(!summary.has_data());
+    }
+
+    #[test]
+    fn daily_summary_sleep_stages() {
+        let db = Database::test().unwrap();
+        db.add_events(
+            &[
+                sleep_session(at(1, 31, 1), at(1, 31, 7)),
+                sleep_session(at(1, 31, 14), at(1, 31, 15)),
+            ],
+            UpsertMode::Overwrite,
+        )
+        .unwrap();
+        let day = Date::from_calendar_date(2001, time::Month::January, 31).unwrap();
+        let summary = db.daily_summary(MAC, day).unwrap();
+        assert!(summary.has_data());
+        assert_eq!(
+            summary.sleep_stages,
+            Some(SleepStageMinutes {
+                light: 120,
+                deep: 180,
+                rem: 60,
+                awake: 30,
+            })
+        );
+    }
+
+    #[test]
+    fn latest_event_of_each_kind() {
+        let db = Database::test().unwrap();
+        assert!(db.latest_events(MAC).unwrap().is_empty());
+        let events = [
+            event_at(3, EventData::heart_rate(70)),
+            event_at(1, EventData::stress(20)),
+            event_at(5, EventData::heart_rate(72)),
+            event_at(2, EventData::stress(25)),
+            event_at(4, EventData::heart_rate(71)),
+        ];
+        db.add_events(&events, UpsertMode::Overwrite).unwrap();
+        assert_eq!(
+            db.latest_events(MAC).unwrap(),
+            [events[2].clone(), events[3].clone()]
+        );
+        assert!(db.latest_events(MAC2).unwrap().is_empty());
+    }
+
+    #[test]
+    fn battery_history() {
+        let db = Database::test().unwrap();
+        let events = [
+            event_at(3, EventData::battery(60, true)),
+            event_at(1, EventData::battery(80, false)),
+            event_at(2, EventData::heart_rate(70)),
+            event_at(5, EventData::battery(55, false)),
+        ];
+        db.add_events(&events, UpsertMode::Overwrite).unwrap();
+        let day = Date::from_calendar_date(2001, time::Month::January, 31).unwrap();
+        let at = |hour| day.with_hms(hour, 0, 0).unwrap().assume_utc();
+        let levels = |range| -> Vec<_> {
+            db.battery_history(MAC, range)
+                .unwrap()
+                .into_iter()
+                .map(|(when, level)| (when, level.level, level.charging))
+                .collect()
+        };
+        assert_eq!(
+            levels(at(0)..at(23)),
+            [(at(1), 80, false), (at(3), 60, true), (at(5), 55, false)]
+        );
+        assert_eq!(levels(at(2)..at(4)), [(at(3), 60, true)]);
+        assert!(db.battery_history(MAC2, ..).unwrap().is_empty());
+    }
+
+    #[test]
+    fn summaries_in_range() {
+        let db = Database::test().unwrap();
+        let first = Date::from_calendar_date(2001, time::Month::January, 30).unwrap();
+        let second = first.next_day().unwrap();
+        let third = second.next_day().unwrap();
+        db.add_events(&synthetic_day(first), UpsertMode::Overwrite)
+            .unwrap();
+        db.add_events(&synthetic_day(third), UpsertMode::Overwrite)
+            .unwrap();
+        let summaries = db
+            .summaries_in_range(MAC, first..third.next_day().unwrap())
+            .unwrap();
+        assert_eq!(summaries.len(), 3);
+        assert_eq!(summaries[0], db.daily_summary(MAC, first).unwrap());
+        assert_eq!(summaries[1].day, second);
+        assert_eq!(summaries[1].heart_rate_max, None);
+        assert_eq!(summaries[2], db.daily_summary(MAC, third).unwrap());
+        assert_eq!(summaries[2].heart_rate_max, Some(99));
+    }
+
     #[test]
     fn time_search_works() {
         // const MAC: &str = "00:00:00:00:00:00";
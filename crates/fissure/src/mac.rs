@@ -0,0 +1,101 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// A bluetooth address in the format rings are stored with, six upper case hex
+/// pairs separated by colons like `A1:B2:C3:D4:E5:F6`
+///
+/// Parsing also accepts lower case and pairs separated by `-` or nothing
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Mac(String);
+
+impl Mac {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for Mac {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidMac(s.to_string());
+        let digits: String = if s.len() == 12 {
+            s.to_string()
+        } else {
+            let parts: Vec<_> = s.split(if s.contains('-') { '-' } else { ':' }).collect();
+            if parts.len() != 6 || parts.iter().any(|part| part.len() != 2) {
+                return Err(invalid());
+            }
+            parts.concat()
+        };
+        if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let digits = digits.to_ascii_uppercase();
+        let pairs: Vec<_> = (0..12).step_by(2).map(|i| &digits[i..i + 2]).collect();
+        Ok(Self(pairs.join(":")))
+    }
+}
+
+impl TryFrom<String> for Mac {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Mac> for String {
+    fn from(value: Mac) -> Self {
+        value.0
+    }
+}
+
+impl AsRef<str> for Mac {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Mac {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonical_format() {
+        for s in [
+            "a1:b2:c3:d4:e5:f6",
+            "A1:B2:C3:D4:E5:F6",
+            "a1-b2-c3-d4-e5-f6",
+            "a1b2c3d4e5f6",
+        ] {
+            assert_eq!(s.parse::<Mac>().unwrap().as_str(), "A1:B2:C3:D4:E5:F6");
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        for s in [
+            "",
+            "a1:b2:c3:d4:e5",
+            "a1:b2:c3:d4:e5:f6:07",
+            "a1:b2:c3:d4:e5:g6",
+            "a1b:2:c3:d4:e5:f6",
+            "a1:b2-c3:d4:e5:f6",
+            "a1b2c3d4e5f",
+            "+1b2c3d4e5f6",
+        ] {
+            assert!(matches!(s.parse::<Mac>(), Err(Error::InvalidMac(_))), "{s}");
+        }
+    }
+}
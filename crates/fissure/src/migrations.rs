@@ -0,0 +1,782 @@
+//! Older layouts of the stored types and how to upgrade them
+//!
+//! structsy identifies a stored type by its name, so each old layout lives in its
+//! own module using the same names as the current types. Every old layout is
+//! upgraded straight to the current one
+
+use std::{collections::BTreeMap, path::Path};
+
+use structsy::{internal::Persistent, Ref, Structsy, StructsyTx};
+
+use crate::{
+    Activity, EventData, Mac, RawSyncRecord, Result, Ring, RingEvent, RingSettings, UpsertMode,
+};
+
+/// Before activity steps and distance were widened from `u8` to `u32`
+pub(crate) mod v0 {
+    use crate::date::DateTime;
+
+    #[derive(Debug, structsy::derive::Persistent)]
+    pub struct RingEvent {
+        #[index(mode = "cluster")]
+        pub mac: String,
+        pub when: DateTime,
+        pub value: EventData,
+    }
+
+    #[derive(Debug, structsy::derive::PersistentEmbedded)]
+    pub enum EventData {
+        HeartRate(u16),
+        Sleep(u16),
+        Stress(u16),
+        Oxygen(u16),
+        Activity(Activity),
+    }
+
+    #[derive(Debug, structsy::derive::PersistentEmbedded)]
+    pub struct Activity {
+        pub steps: u8,
+        pub calories: f64,
+        pub distance: u8,
+    }
+}
+
+/// Before sleep sessions were stored
+pub(crate) mod v1 {
+    use crate::{date::DateTime, Activity};
+
+    #[derive(Debug, structsy::derive::Persistent)]
+    pub struct RingEvent {
+        #[index(mode = "cluster")]
+        pub mac: String,
+        pub when: DateTime,
+        pub value: EventData,
+    }
+
+    #[derive(Debug, structsy::derive::PersistentEmbedded)]
+    pub enum EventData {
+        HeartRate(u16),
+        Sleep(u16),
+        Stress(u16),
+        Oxygen(u16),
+        Activity(Activity),
+    }
+}
+
+/// Before oxygen was stored as a min/max pair
+pub(crate) mod v2 {
+    use crate::{date::DateTime, Activity, SleepSessionRecord};
+
+    #[derive(Debug, structsy::derive::Persistent)]
+    pub struct RingEvent {
+        #[index(mode = "cluster")]
+        pub mac: String,
+        pub when: DateTime,
+        pub value: EventData,
+    }
+
+    #[derive(Debug, structsy::derive::PersistentEmbedded)]
+    pub enum EventData {
+        HeartRate(u16),
+        Sleep(u16),
+        Stress(u16),
+        Oxygen(u16),
+        Activity(Activity),
+        SleepSession(SleepSessionRecord),
+    }
+}
+
+/// Before events stored their kind
+pub(crate) mod v3 {
+    pub use super::v4::EventData;
+    use crate::date::DateTime;
+
+    #[derive(Debug, structsy::derive::Persistent)]
+    pub struct RingEvent {
+        #[index(mode = "cluster")]
+        pub mac: String,
+        pub when: DateTime,
+        pub value: EventData,
+    }
+}
+
+/// Before battery levels were stored
+pub(crate) mod v4 {
+    use crate::{date::DateTime, Activity, OxygenRange, SleepSessionRecord};
+
+    #[derive(Debug, structsy::derive::Persistent)]
+    pub struct RingEvent {
+        #[index(mode = "cluster")]
+        pub mac: String,
+        pub when: DateTime,
+        pub value: EventData,
+        #[index(mode = "cluster")]
+        pub kind: u8,
+    }
+
+    #[derive(Debug, structsy::derive::PersistentEmbedded)]
+    pub enum EventData {
+        HeartRate(u16),
+        Sleep(u16),
+        Stress(u16),
+        Oxygen(u16),
+        Activity(Activity),
+        SleepSession(SleepSessionRecord),
+        OxygenRange(OxygenRange),
+    }
+
+    impl From<EventData> for crate::EventData {
+        fn from(value: EventData) -> Self {
+            match value {
+                EventData::HeartRate(v) => Self::HeartRate(v),
+                EventData::Sleep(v) => Self::Sleep(v),
+                EventData::Stress(v) => Self::Stress(v),
+                EventData::Oxygen(v) => Self::Oxygen(v),
+                EventData::Activity(a) => Self::Activity(a),
+                EventData::SleepSession(s) => Self::SleepSession(s),
+                EventData::OxygenRange(r) => Self::OxygenRange(r),
+            }
+        }
+    }
+}
+
+/// Before battery levels stored if the ring was charging
+pub(crate) mod v5 {
+    use crate::{date::DateTime, Activity, OxygenRange, SleepSessionRecord};
+
+    #[derive(Debug, structsy::derive::Persistent)]
+    pub struct RingEvent {
+        #[index(mode = "cluster")]
+        pub mac: String,
+        pub when: DateTime,
+        pub value: EventData,
+        #[index(mode = "cluster")]
+        pub kind: u8,
+    }
+
+    #[derive(Debug, structsy::derive::PersistentEmbedded)]
+    pub enum EventData {
+        HeartRate(u16),
+        Sleep(u16),
+        Stress(u16),
+        Oxygen(u16),
+        Activity(Activity),
+        SleepSession(SleepSessionRecord),
+        OxygenRange(OxygenRange),
+        Battery(u8),
+    }
+}
+
+/// Before events stored the month they happened in to index
+pub(crate) mod v6 {
+    use crate::{date::DateTime, EventData};
+
+    #[derive(Debug, structsy::derive::Persistent)]
+    pub struct RingEvent {
+        #[index(mode = "cluster")]
+        pub mac: String,
+        pub when: DateTime,
+        pub value: EventData,
+        #[index(mode = "cluster")]
+        pub kind: u8,
+    }
+}
+
+/// Before events stored the ring's mac with the month they happened in
+pub(crate) mod v7 {
+    use crate::{date::DateTime, EventData};
+
+    #[derive(Debug, structsy::derive::Persistent)]
+    pub struct RingEvent {
+        #[index(mode = "cluster")]
+        pub mac: String,
+        pub when: DateTime,
+        pub value: EventData,
+        #[index(mode = "cluster")]
+        pub kind: u8,
+        #[index(mode = "cluster")]
+        pub month: u32,
+    }
+}
+
+impl From<v0::RingEvent> for RingEvent {
+    fn from(event: v0::RingEvent) -> Self {
+        let value = match event.value {
+            v0::EventData::HeartRate(v) => EventData::HeartRate(v),
+            v0::EventData::Sleep(v) => EventData::Sleep(v),
+            v0::EventData::Stress(v) => EventData::Stress(v),
+            v0::EventData::Oxygen(v) => EventData::Oxygen(v),
+            v0::EventData::Activity(a) => EventData::Activity(Activity {
+                steps: a.steps.into(),
+                calories: a.calories,
+                distance: a.distance.into(),
+            }),
+        };
+        Self::new(event.mac, event.when, value)
+    }
+}
+
+impl From<v1::RingEvent> for RingEvent {
+    fn from(event: v1::RingEvent) -> Self {
+        let value = match event.value {
+            v1::EventData::HeartRate(v) => EventData::HeartRate(v),
+            v1::EventData::Sleep(v) => EventData::Sleep(v),
+            v1::EventData::Stress(v) => EventData::Stress(v),
+            v1::EventData::Oxygen(v) => EventData::Oxygen(v),
+            v1::EventData::Activity(a) => EventData::Activity(a),
+        };
+        Self::new(event.mac, event.when, value)
+    }
+}
+
+impl From<v2::RingEvent> for RingEvent {
+    fn from(event: v2::RingEvent) -> Self {
+        let value = match event.value {
+            v2::EventData::HeartRate(v) => EventData::HeartRate(v),
+            v2::EventData::Sleep(v) => EventData::Sleep(v),
+            v2::EventData::Stress(v) => EventData::Stress(v),
+            v2::EventData::Oxygen(v) => EventData::Oxygen(v),
+            v2::EventData::Activity(a) => EventData::Activity(a),
+            v2::EventData::SleepSession(s) => EventData::SleepSession(s),
+        };
+        Self::new(event.mac, event.when, value)
+    }
+}
+
+impl From<v3::RingEvent> for RingEvent {
+    fn from(event: v3::RingEvent) -> Self {
+        Self::new(event.mac, event.when, event.value.into())
+    }
+}
+
+impl From<v4::RingEvent> for RingEvent {
+    fn from(event: v4::RingEvent) -> Self {
+        Self::new(event.mac, event.when, event.value.into())
+    }
+}
+
+impl From<v5::RingEvent> for RingEvent {
+    fn from(event: v5::RingEvent) -> Self {
+        let value = match event.value {
+            v5::EventData::HeartRate(v) => EventData::HeartRate(v),
+            v5::EventData::Sleep(v) => EventData::Sleep(v),
+            v5::EventData::Stress(v) => EventData::Stress(v),
+            v5::EventData::Oxygen(v) => EventData::Oxygen(v),
+            v5::EventData::Activity(a) => EventData::Activity(a),
+            v5::EventData::SleepSession(s) => EventData::SleepSession(s),
+            v5::EventData::OxygenRange(r) => EventData::OxygenRange(r),
+            // whether it was charging wasn't kept
+            v5::EventData::Battery(level) => EventData::battery(level, false),
+        };
+        Self::new(event.mac, event.when, value)
+    }
+}
+
+impl From<v6::RingEvent> for RingEvent {
+    fn from(event: v6::RingEvent) -> Self {
+        Self::new(event.mac, event.when, event.value)
+    }
+}
+
+impl From<v7::RingEvent> for RingEvent {
+    fn from(event: v7::RingEvent) -> Self {
+        Self::new(event.mac, event.when, event.value)
+    }
+}
+
+/// Open the database at `path`, upgrading any events stored in an older layout
+/// and any mac stored before macs were canonicalized
+pub(crate) fn open(path: &Path) -> Result<Structsy> {
+    let db = Structsy::open(path)?;
+    if is_stored::<v0::RingEvent>(&db)? {
+        rebuild::<v0::RingEvent>(path, &db)?;
+    } else if is_stored::<v1::RingEvent>(&db)? {
+        rebuild::<v1::RingEvent>(path, &db)?;
+    } else if is_stored::<v2::RingEvent>(&db)? {
+        rebuild::<v2::RingEvent>(path, &db)?;
+    } else if is_stored::<v3::RingEvent>(&db)? {
+        rebuild::<v3::RingEvent>(path, &db)?;
+    } else if is_stored::<v4::RingEvent>(&db)? {
+        rebuild::<v4::RingEvent>(path, &db)?;
+    } else if is_stored::<v5::RingEvent>(&db)? {
+        rebuild::<v5::RingEvent>(path, &db)?;
+    } else if is_stored::<v6::RingEvent>(&db)? {
+        rebuild::<v6::RingEvent>(path, &db)?;
+    } else if is_stored::<v7::RingEvent>(&db)? {
+        rebuild::<v7::RingEvent>(path, &db)?;
+    }
+    if !db.is_defined::<CanonicalMacs>()? {
+        canonicalize_macs(&db)?;
+        db.define::<CanonicalMacs>()?;
+    }
+    Ok(db)
+}
+
+/// Defined once the macs stored before `Mac` was used to parse them have been
+/// rewritten in its canonical form, nothing is ever stored as it
+#[derive(Debug, structsy::derive::Persistent)]
+struct CanonicalMacs {}
+
+/// Rewrite every stored mac that isn't canonical
+///
+/// Rings and settings stored under more than one form of the same mac are
+/// merged into the one already canonical, or the first one found. Events that
+/// end up matching one already stored for the same kind and time are dropped
+fn canonicalize_macs(db: &Structsy) -> Result {
+    let mut tx = db.begin()?;
+    if db.is_defined::<Ring>()? {
+        for (mac, mut rings) in by_canonical_mac(db.scan::<Ring>()?, |ring| &ring.mac) {
+            let (kept_ref, mut kept) = rings.remove(0);
+            for (r, ring) in rings {
+                kept.nickname = kept.nickname.or(ring.nickname);
+                tx.delete(&r)?;
+            }
+            kept.mac = mac;
+            tx.update(&kept_ref, &kept)?;
+        }
+    }
+    if db.is_defined::<RingSettings>()? {
+        for (mac, mut settings) in
+            by_canonical_mac(db.scan::<RingSettings>()?, |settings| &settings.mac)
+        {
+            let (kept_ref, mut kept) = settings.remove(0);
+            for (r, _) in settings {
+                tx.delete(&r)?;
+            }
+            kept.mac = mac;
+            tx.update(&kept_ref, &kept)?;
+        }
+    }
+    if db.is_defined::<RawSyncRecord>()? {
+        for (mac, records) in by_canonical_mac(db.scan::<RawSyncRecord>()?, |record| &record.mac) {
+            for (r, mut record) in records {
+                record.mac.clone_from(&mac);
+                tx.update(&r, &record)?;
+            }
+        }
+    }
+    tx.commit()?;
+    if db.is_defined::<RingEvent>()? {
+        let events: Vec<_> = by_canonical_mac(db.scan::<RingEvent>()?, |event| &event.mac)
+            .into_iter()
+            .flat_map(|(mac, events)| {
+                events.into_iter().filter_map(move |(r, event)| {
+                    // the canonical events stay where they are
+                    (event.mac != mac).then(|| {
+                        (
+                            r,
+                            RingEvent {
+                                mac: mac.clone(),
+                                ..event
+                            },
+                        )
+                    })
+                })
+            })
+            .collect();
+        // one transaction per chunk, large transactions on a cluster index are slow
+        for chunk in events.chunks(1_000) {
+            let mut tx = db.begin()?;
+            for (r, _) in chunk {
+                tx.delete(r)?;
+            }
+            let events: Vec<_> = chunk.iter().map(|(_, event)| event.clone()).collect();
+            crate::upsert_events(&mut tx, &events, UpsertMode::Skip)?;
+            tx.commit()?;
+        }
+    }
+    Ok(())
+}
+
+/// The records stored under a mac that isn't canonical, grouped by the
+/// canonical form of the mac
+///
+/// A group starts with the record already stored under the canonical mac when
+/// there is one, macs that can't be parsed are left as they are
+fn by_canonical_mac<T>(
+    records: impl Iterator<Item = (Ref<T>, T)>,
+    mac: impl Fn(&T) -> &String,
+) -> BTreeMap<String, Vec<(Ref<T>, T)>> {
+    let mut groups: BTreeMap<String, Vec<(Ref<T>, T)>> = BTreeMap::new();
+    for (r, record) in records {
+        let canonical = match mac(&record).parse::<Mac>() {
+            Ok(canonical) => String::from(canonical),
+            Err(e) => {
+                log::warn!("leaving a stored mac as it is: {e}");
+                continue;
+            }
+        };
+        let group = groups.entry(canonical.clone()).or_default();
+        if *mac(&record) == canonical {
+            group.insert(0, (r, record));
+        } else {
+            group.push((r, record));
+        }
+    }
+    groups.retain(|canonical, group| group.iter().any(|(_, record)| mac(record) != canonical));
+    groups
+}
+
+/// Replace every event stored as `T` with the current layout
+///
+/// structsy's own migrations rewrite records in place but never create the
+/// segments for a newly indexed field, so the events are read out, the old
+/// definition dropped and the events inserted again. A copy of the database is
+/// kept next to it until that finishes
+fn rebuild<T>(path: &Path, db: &Structsy) -> Result
+where
+    T: Persistent,
+    RingEvent: From<T>,
+{
+    log::info!("upgrading stored events to the current layout");
+    let backup = path.with_extension("upgrade-backup");
+    std::fs::copy(path, &backup)?;
+    let events: Vec<RingEvent> = db.scan::<T>()?.map(|(_, e)| e.into()).collect();
+    db.undefine::<T>()?;
+    db.define::<RingEvent>()?;
+    // one transaction per chunk, large transactions on a cluster index are slow
+    for chunk in events.chunks(1_000) {
+        let mut tx = db.begin()?;
+        for event in chunk {
+            tx.insert(event)?;
+        }
+        tx.commit()?;
+    }
+    std::fs::remove_file(&backup)?;
+    Ok(())
+}
+
+/// If the database holds `T` in exactly the layout `T` describes
+fn is_stored<T: Persistent>(db: &Structsy) -> Result<bool> {
+    let desc = T::get_description();
+    Ok(db.list_defined()?.any(|defined| defined == desc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{date::DateTime, Database, EventKind};
+
+    #[test]
+    fn v0_events_are_upgraded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fissure.db");
+        let when = DateTime::builder().year(2001).month(1).day(31).build();
+        {
+            let db = Structsy::open(&path).unwrap();
+            db.define::<v0::RingEvent>().unwrap();
+            let mut tx = db.begin().unwrap();
+            for value in [
+                v0::EventData::Activity(v0::Activity {
+                    steps: 250,
+                    calories: 12.5,
+                    distance: 180,
+                }),
+                v0::EventData::HeartRate(70),
+            ] {
+                tx.insert(&v0::RingEvent {
+                    mac: "00:00:00:00:00:00".to_string(),
+                    when,
+                    value,
+                })
+                .unwrap();
+            }
+            tx.commit().unwrap();
+        }
+        let db = Database::new(&path).unwrap();
+        let events = db.get_events_in_range("00:00:00:00:00:00", ..).unwrap();
+        let values: Vec<_> = events.into_iter().map(|e| e.value).collect();
+        assert_eq!(
+            values,
+            [
+                EventData::activity(250, 12.5, 180),
+                EventData::heart_rate(70)
+            ]
+        );
+        drop(db);
+        // opening again finds nothing left to upgrade
+        Database::new(&path).unwrap();
+    }
+
+    #[test]
+    fn v1_events_are_upgraded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fissure.db");
+        let when = DateTime::builder().year(2001).month(1).day(31).build();
+        {
+            let db = Structsy::open(&path).unwrap();
+            db.define::<v1::RingEvent>().unwrap();
+            let mut tx = db.begin().unwrap();
+            tx.insert(&v1::RingEvent {
+                mac: "00:00:00:00:00:00".to_string(),
+                when,
+                value: v1::EventData::Activity(Activity {
+                    steps: 5_000,
+                    calories: 12.5,
+                    distance: 4_000,
+                }),
+            })
+            .unwrap();
+            tx.commit().unwrap();
+        }
+        let db = Database::new(&path).unwrap();
+        let events = db.get_events_in_range("00:00:00:00:00:00", ..).unwrap();
+        assert_eq!(events[0].value, EventData::activity(5_000, 12.5, 4_000));
+    }
+
+    #[test]
+    fn v2_events_are_upgraded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fissure.db");
+        let when = DateTime::builder().year(2001).month(1).day(31).build();
+        {
+            let db = Structsy::open(&path).unwrap();
+            db.define::<v2::RingEvent>().unwrap();
+            let mut tx = db.begin().unwrap();
+            tx.insert(&v2::RingEvent {
+                mac: "00:00:00:00:00:00".to_string(),
+                when,
+                value: v2::EventData::Oxygen(96),
+            })
+            .unwrap();
+            tx.commit().unwrap();
+        }
+        let db = Database::new(&path).unwrap();
+        let events = db.get_events_in_range("00:00:00:00:00:00", ..).unwrap();
+        assert_eq!(events[0].value, EventData::oxygen(96));
+    }
+
+    #[test]
+    fn v3_events_are_upgraded_with_their_kind() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fissure.db");
+        let when = DateTime::builder().year(2001).month(1).day(31).build();
+        {
+            let db = Structsy::open(&path).unwrap();
+            db.define::<v3::RingEvent>().unwrap();
+            let mut tx = db.begin().unwrap();
+            for value in [v3::EventData::HeartRate(70), v3::EventData::Oxygen(96)] {
+                tx.insert(&v3::RingEvent {
+                    mac: "00:00:00:00:00:00".to_string(),
+                    when,
+                    value,
+                })
+                .unwrap();
+            }
+            tx.commit().unwrap();
+        }
+        let db = Database::new(&path).unwrap();
+        let events = db
+            .get_events_of_kind("00:00:00:00:00:00", EventKind::Oxygen, ..)
+            .unwrap();
+        assert_eq!(
+            events,
+            [RingEvent::new(
+                "00:00:00:00:00:00",
+                when,
+                EventData::oxygen(96)
+            )]
+        );
+        drop(db);
+        assert!(!path.with_extension("upgrade-backup").exists());
+        // opening again finds nothing left to upgrade
+        Database::new(&path).unwrap();
+    }
+    #[test]
+    fn v4_events_are_upgraded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fissure.db");
+        let when = DateTime::builder().year(2001).month(1).day(31).build();
+        {
+            let db = Structsy::open(&path).unwrap();
+            db.define::<v4::RingEvent>().unwrap();
+            let mut tx = db.begin().unwrap();
+            tx.insert(&v4::RingEvent {
+                mac: "00:00:00:00:00:00".to_string(),
+                when,
+                value: v4::EventData::OxygenRange(crate::OxygenRange { min: 94, max: 98 }),
+                kind: EventKind::Oxygen as u8,
+            })
+            .unwrap();
+            tx.commit().unwrap();
+        }
+        let db = Database::new(&path).unwrap();
+        let events = db
+            .get_events_of_kind("00:00:00:00:00:00", EventKind::Oxygen, ..)
+            .unwrap();
+        assert_eq!(events[0].value, EventData::oxygen_range(94, 98));
+    }
+
+    #[test]
+    fn v5_events_are_upgraded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fissure.db");
+        let when = DateTime::builder().year(2001).month(1).day(31).build();
+        {
+            let db = Structsy::open(&path).unwrap();
+            db.define::<v5::RingEvent>().unwrap();
+            let mut tx = db.begin().unwrap();
+            for value in [v5::EventData::Battery(64), v5::EventData::HeartRate(70)] {
+                tx.insert(&v5::RingEvent {
+                    mac: "00:00:00:00:00:00".to_string(),
+                    when,
+                    kind: match value {
+                        v5::EventData::Battery(_) => EventKind::Battery as u8,
+                        _ => EventKind::HeartRate as u8,
+                    },
+                    value,
+                })
+                .unwrap();
+            }
+            tx.commit().unwrap();
+        }
+        let db = Database::new(&path).unwrap();
+        let events = db
+            .get_events_of_kind("00:00:00:00:00:00", EventKind::Battery, ..)
+            .unwrap();
+        assert_eq!(events[0].value, EventData::battery(64, false));
+        drop(db);
+        // opening again finds nothing left to upgrade
+        Database::new(&path).unwrap();
+    }
+
+    #[test]
+    fn v6_events_are_upgraded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fissure.db");
+        let at = |day| DateTime::builder().year(2001).month(1).day(day).build();
+        {
+            let db = Structsy::open(&path).unwrap();
+            db.define::<v6::RingEvent>().unwrap();
+            let mut tx = db.begin().unwrap();
+            for day in [1, 2, 3] {
+                tx.insert(&v6::RingEvent {
+                    mac: "00:00:00:00:00:00".to_string(),
+                    when: at(day),
+                    value: EventData::HeartRate(70),
+                    kind: EventKind::HeartRate as u8,
+                })
+                .unwrap();
+            }
+            tx.commit().unwrap();
+        }
+        let db = Database::new(&path).unwrap();
+        let start = time::macros::datetime!(2001-01-02 0:00 UTC);
+        let events = db
+            .iter_events("00:00:00:00:00:00", start..start + time::Duration::DAY)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            events,
+            [RingEvent::new(
+                "00:00:00:00:00:00",
+                at(2),
+                EventData::HeartRate(70)
+            )]
+        );
+    }
+
+    #[test]
+    fn v7_events_are_upgraded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fissure.db");
+        let at = |month| DateTime::builder().year(2001).month(month).day(1).build();
+        {
+            let db = Structsy::open(&path).unwrap();
+            db.define::<v7::RingEvent>().unwrap();
+            let mut tx = db.begin().unwrap();
+            for month in [1, 2, 3] {
+                tx.insert(&v7::RingEvent {
+                    mac: "00:00:00:00:00:00".to_string(),
+                    when: at(month),
+                    value: EventData::HeartRate(70),
+                    kind: EventKind::HeartRate as u8,
+                    month: 200100 + u32::from(month),
+                })
+                .unwrap();
+            }
+            tx.commit().unwrap();
+        }
+        let db = Database::new(&path).unwrap();
+        let start = time::macros::datetime!(2001-02-01 0:00 UTC);
+        let events = db
+            .iter_events("00:00:00:00:00:00", start..start + time::Duration::DAY)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            events,
+            [RingEvent::new(
+                "00:00:00:00:00:00",
+                at(2),
+                EventData::HeartRate(70)
+            )]
+        );
+    }
+
+    #[test]
+    fn stored_macs_are_canonicalized() {
+        const MAC: &str = "AA:BB:CC:DD:EE:FF";
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fissure.db");
+        let at = |hour| {
+            DateTime::builder()
+                .year(2024)
+                .month(1)
+                .day(1)
+                .hour(hour)
+                .build()
+        };
+        {
+            let db = Structsy::open(&path).unwrap();
+            db.define::<Ring>().unwrap();
+            db.define::<RingEvent>().unwrap();
+            db.define::<RingSettings>().unwrap();
+            let mut tx = db.begin().unwrap();
+            for (nickname, mac) in [
+                (None, "aa:bb:cc:dd:ee:ff"),
+                (Some("lefty"), "AA-BB-CC-DD-EE-FF"),
+            ] {
+                tx.insert(&Ring {
+                    nickname: nickname.map(String::from),
+                    name: "R02_0000".to_string(),
+                    mac: mac.to_string(),
+                })
+                .unwrap();
+            }
+            tx.insert(&RingSettings::new("aabbccddeeff")).unwrap();
+            for (mac, hour, value) in [
+                (MAC, 1, EventData::HeartRate(70)),
+                ("aa:bb:cc:dd:ee:ff", 1, EventData::HeartRate(71)),
+                ("aa:bb:cc:dd:ee:ff", 2, EventData::HeartRate(72)),
+                ("aa-bb-cc-dd-ee-ff", 2, EventData::HeartRate(72)),
+                ("aa-bb-cc-dd-ee-ff", 3, EventData::HeartRate(73)),
+                ("not a mac", 3, EventData::HeartRate(74)),
+            ] {
+                tx.insert(&RingEvent::new(mac, at(hour), value)).unwrap();
+            }
+            tx.commit().unwrap();
+        }
+        let db = Database::new(&path).unwrap();
+        assert_eq!(
+            db.get_rings(),
+            [Ring {
+                nickname: Some("lefty".to_string()),
+                name: "R02_0000".to_string(),
+                mac: MAC.to_string(),
+            }]
+        );
+        assert_eq!(db.get_settings(MAC).unwrap(), Some(RingSettings::new(MAC)));
+        let events = db.get_events_in_range(MAC, ..).unwrap();
+        let expected = [
+            RingEvent::new(MAC, at(1), EventData::HeartRate(70)),
+            RingEvent::new(MAC, at(2), EventData::HeartRate(72)),
+            RingEvent::new(MAC, at(3), EventData::HeartRate(73)),
+        ];
+        assert_eq!(events, expected);
+        assert!(db
+            .get_events_in_range("aa:bb:cc:dd:ee:ff", ..)
+            .unwrap()
+            .is_empty());
+        assert_eq!(db.get_events_in_range("not a mac", ..).unwrap().len(), 1);
+    }
+}
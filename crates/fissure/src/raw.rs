@@ -0,0 +1,44 @@
+use time::PrimitiveDateTime;
+
+use crate::{date::DateTime, Result, SyncCategory};
+
+/// The bytes of a sync reply as the ring sent them, kept so the reply can be
+/// parsed again once a parser bug is fixed
+///
+/// Big data replies (sleep and oxygen) are the v2 notifications back to back,
+/// the rest are the uart notifications back to back
+#[derive(Debug, Clone, PartialEq, structsy::derive::Persistent)]
+pub struct RawSyncRecord {
+    #[index(mode = "cluster")]
+    pub mac: String,
+    /// When the sync was requested in the ring's local time, replies that
+    /// count days back count from this day
+    pub captured_at: DateTime,
+    pub category: SyncCategory,
+    /// How many days back from `captured_at` the request asked for, only the
+    /// stress reply doesn't say which day it describes
+    pub day_offset: u8,
+    pub bytes: Vec<u8>,
+    /// `category` stored so records can be queried by it
+    #[index(mode = "cluster")]
+    kind: u8,
+}
+
+impl RawSyncRecord {
+    pub fn new(
+        mac: impl Into<String>,
+        captured_at: PrimitiveDateTime,
+        category: SyncCategory,
+        day_offset: u8,
+        bytes: Vec<u8>,
+    ) -> Result<Self> {
+        Ok(Self {
+            mac: mac.into(),
+            captured_at: captured_at.try_into()?,
+            category,
+            day_offset,
+            bytes,
+            kind: category as u8,
+        })
+    }
+}
@@ -0,0 +1,103 @@
+use std::fmt::{self, Display};
+
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::date::DateTime;
+
+/// The configuration last read from or written to a ring, and when each kind
+/// of data was last synced from it
+#[derive(
+    Debug, Clone, Default, PartialEq, structsy::derive::Persistent, Serialize, Deserialize,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct RingSettings {
+    #[index(mode = "exclusive")]
+    pub mac: String,
+    pub heart_rate_enabled: bool,
+    /// Minutes between automatic heart rate readings
+    pub heart_rate_interval: u8,
+    pub oxygen_auto: bool,
+    pub stress_auto: bool,
+    pub goals: Goals,
+    /// When each category was last synced, categories never synced are missing
+    pub last_sync: Vec<LastSync>,
+}
+
+impl RingSettings {
+    /// The default settings for the ring with `mac`
+    pub fn new(mac: impl Into<String>) -> Self {
+        Self {
+            mac: mac.into(),
+            ..Default::default()
+        }
+    }
+
+    /// When `category` was last synced, `None` if it never was
+    pub fn last_sync(&self, category: SyncCategory) -> Option<OffsetDateTime> {
+        self.last_sync
+            .iter()
+            .find(|sync| sync.category == category)
+            .and_then(|sync| sync.when.try_into().ok())
+    }
+
+    pub(crate) fn set_last_sync(&mut self, category: SyncCategory, when: DateTime) {
+        match self
+            .last_sync
+            .iter_mut()
+            .find(|sync| sync.category == category)
+        {
+            Some(sync) => sync.when = when,
+            None => self.last_sync.push(LastSync { category, when }),
+        }
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    structsy::derive::PersistentEmbedded,
+    Serialize,
+    Deserialize,
+)]
+pub struct Goals {
+    pub steps: u32,
+    pub calories: u32,
+    pub distance: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, structsy::derive::PersistentEmbedded, Serialize, Deserialize)]
+pub struct LastSync {
+    pub category: SyncCategory,
+    pub when: DateTime,
+}
+
+/// The kinds of data synced from a ring separately
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, structsy::derive::PersistentEmbedded, Serialize, Deserialize,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum SyncCategory {
+    Activity,
+    HeartRate,
+    Stress,
+    Sleep,
+    Oxygen,
+}
+
+impl Display for SyncCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Activity => "activity",
+            Self::HeartRate => "heart rate",
+            Self::Stress => "stress",
+            Self::Sleep => "sleep",
+            Self::Oxygen => "oxygen",
+        };
+        f.write_str(s)
+    }
+}
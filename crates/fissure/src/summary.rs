@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+use time::Date;
+
+use crate::{EventData, RingEvent, SleepStageKind};
+
+/// Aggregated values for a single day of events
+///
+/// Each value is `None` when there were no events of that kind that day
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailySummary {
+    pub day: Date,
+    pub heart_rate_min: Option<u16>,
+    pub heart_rate_max: Option<u16>,
+    pub heart_rate_avg: Option<f64>,
+    pub steps: Option<u32>,
+    pub calories: Option<f64>,
+    pub distance: Option<u32>,
+    pub sleep_minutes: Option<u32>,
+    /// Minutes in each stage of the sleep sessions that started that day
+    pub sleep_stages: Option<SleepStageMinutes>,
+    pub stress_avg: Option<f64>,
+    pub oxygen_avg: Option<f64>,
+    /// The lowest oxygen reading, only events with a range contribute
+    pub oxygen_min: Option<u8>,
+    /// The highest oxygen reading, only events with a range contribute
+    pub oxygen_max: Option<u8>,
+}
+
+impl DailySummary {
+    /// Summarize `events`, which are all expected to have happened on `day`
+    pub fn from_events<'a>(day: Date, events: impl IntoIterator<Item = &'a RingEvent>) -> Self {
+        let mut heart_rate = Average::default();
+        let mut heart_rate_min: Option<u16> = None;
+        let mut heart_rate_max: Option<u16> = None;
+        let mut stress = Average::default();
+        let mut oxygen = Average::default();
+        let mut oxygen_min: Option<u8> = None;
+        let mut oxygen_max: Option<u8> = None;
+        let mut steps: Option<u32> = None;
+        let mut calories: Option<f64> = None;
+        let mut distance: Option<u32> = None;
+        let mut sleep_minutes: Option<u32> = None;
+        let mut sleep_stages: Option<SleepStageMinutes> = None;
+        for event in events {
+            match &event.value {
+                EventData::HeartRate(value) => {
+                    heart_rate.add(*value);
+                    heart_rate_min = Some(heart_rate_min.map_or(*value, |v| v.min(*value)));
+                    heart_rate_max = Some(heart_rate_max.map_or(*value, |v| v.max(*value)));
+                }
+                EventData::Sleep(minutes) => {
+                    *sleep_minutes.get_or_insert(0) += u32::from(*minutes);
+                }
+                EventData::Stress(value) => stress.add(*value),
+                EventData::Oxygen(value) => oxygen.add(*value),
+                EventData::OxygenRange(range) => {
+                    // both ends count so each hour is averaged at its midpoint
+                    oxygen.add(range.min.into());
+                    oxygen.add(range.max.into());
+                    oxygen_min = Some(oxygen_min.map_or(range.min, |v| v.min(range.min)));
+                    oxygen_max = Some(oxygen_max.map_or(range.max, |v| v.max(range.max)));
+                }
+                EventData::Activity(activity) => {
+                    *steps.get_or_insert(0) += activity.steps;
+                    *calories.get_or_insert(0.0) += activity.calories;
+                    *distance.get_or_insert(0) += activity.distance;
+                }
+                // the total is also stored as a `Sleep` event
+                EventData::SleepSession(session) => {
+                    let stages = sleep_stages.get_or_insert_with(Default::default);
+                    for stage in &session.stages {
+                        let minutes = u32::from(stage.minutes);
+                        match stage.stage {
+                            SleepStageKind::Light => stages.light += minutes,
+                            SleepStageKind::Deep => stages.deep += minutes,
+                            SleepStageKind::Rem => stages.rem += minutes,
+                            SleepStageKind::Awake => stages.awake += minutes,
+                        }
+                    }
+                }
+                EventData::Battery(_) => {}
+            }
+        }
+        Self {
+            day,
+            heart_rate_min,
+            heart_rate_max,
+            heart_rate_avg: heart_rate.get(),
+            steps,
+            calories,
+            distance,
+            sleep_minutes,
+            sleep_stages,
+            stress_avg: stress.get(),
+            oxygen_avg: oxygen.get(),
+            oxygen_min,
+            oxygen_max,
+        }
+    }
+
+    /// If there were any events that day
+    pub fn has_data(&self) -> bool {
+        self.heart_rate_avg.is_some()
+            || self.steps.is_some()
+            || self.sleep_minutes.is_some()
+            || self.sleep_stages.is_some()
+            || self.stress_avg.is_some()
+            || self.oxygen_avg.is_some()
+    }
+}
+
+/// Minutes spent in each sleep stage
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SleepStageMinutes {
+    pub light: u32,
+    pub deep: u32,
+    pub rem: u32,
+    pub awake: u32,
+}
+
+#[derive(Default)]
+struct Average {
+    total: u64,
+    count: u64,
+}
+
+impl Average {
+    fn add(&mut self, value: u16) {
+        self.total += u64::from(value);
+        self.count += 1;
+    }
+
+    fn get(&self) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        Some(self.total as f64 / self.count as f64)
+    }
+}
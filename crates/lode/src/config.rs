@@ -0,0 +1,415 @@
+//! The config file, `$XDG_CONFIG_HOME/lode/config.toml` or
+//! `~/.config/lode/config.toml` unless `--config` points somewhere else
+//!
+//! ```toml
+//! default-device = "work-ring"
+//! format = "json"
+//! units = "metric"
+//!
+//! [aliases]
+//! work-ring = "A1:B2:C3:D4:E5:F6"
+//!
+//! [listen]
+//! find-rings = 30
+//!
+//! [push]
+//! url = "https://rings.example.com"
+//! token = "a-write-token"
+//!
+//! [influx]
+//! url = "http://localhost:8086"
+//! org = "home"
+//! bucket = "rings"
+//! token = "an-influx-token"
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::output::{Format, UnitSystem};
+
+#[derive(Debug)]
+pub enum Error {
+    /// The config file exists but couldn't be read
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The config file isn't valid TOML or has unexpected values
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    Write {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Serialize(toml::ser::Error),
+    /// No device was given and the config doesn't have a default
+    NoDevice,
+    /// An alias that would be mistaken for something else
+    InvalidAlias(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read { path, source } => {
+                write!(f, "failed to read config {}: {source}", path.display())
+            }
+            Self::Parse { path, source } => {
+                write!(f, "invalid config {}: {source}", path.display())
+            }
+            Self::Write { path, source } => {
+                write!(f, "failed to write config {}: {source}", path.display())
+            }
+            Self::Serialize(e) => write!(f, "failed to serialize config: {e}"),
+            Self::NoDevice => write!(
+                f,
+                "no device given and no default device, pass one or set it with `lode config set-default`"
+            ),
+            Self::InvalidAlias(alias) => write!(
+                f,
+                "invalid alias {alias:?}, aliases can't be empty or look like a mac address"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Read { source, .. } | Self::Write { source, .. } => Some(source),
+            Self::Parse { source, .. } => Some(source),
+            Self::Serialize(e) => Some(e),
+            Self::NoDevice | Self::InvalidAlias(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    /// The device used when a command isn't given one, may be an alias
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_device: Option<String>,
+    /// Used when `--format` isn't passed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<Format>,
+    /// Used when `--units` isn't passed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub units: Option<UnitSystem>,
+    /// Names that can be used in place of a device's address or name
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "ListenSeconds::is_empty")]
+    pub listen: ListenSeconds,
+    #[serde(skip_serializing_if = "PushConfig::is_empty")]
+    pub push: PushConfig,
+    #[serde(skip_serializing_if = "InfluxConfig::is_empty")]
+    pub influx: InfluxConfig,
+}
+
+/// How long commands wait when they aren't given `--listen`
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ListenSeconds {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub find_rings: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listen: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw: Option<u64>,
+}
+
+impl ListenSeconds {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// The conveyor server `push` and `sync --push` upload to
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct PushConfig {
+    /// Used when a url isn't passed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Sent as a bearer token, needs the write scope if the server checks them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl PushConfig {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// The InfluxDB `sync --push-influx` writes to
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct InfluxConfig {
+    /// Used when a url isn't passed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub org: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bucket: Option<String>,
+    /// An API token that can write to `bucket`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl InfluxConfig {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Config {
+    /// Where the config is read from without `--config`, `None` if there is no
+    /// home directory to look in
+    pub fn default_path() -> Option<PathBuf> {
+        let dir = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| {
+                let home = std::env::var_os("HOME").filter(|home| !home.is_empty())?;
+                Some(PathBuf::from(home).join(".config"))
+            })?;
+        Some(dir.join("lode").join("config.toml"))
+    }
+
+    /// Read the config at `path`, failing if it doesn't exist
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let text = std::fs::read_to_string(path).map_err(|source| Error::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::parse(path, &text)
+    }
+
+    /// Read the config at `path`, an empty config if it doesn't exist
+    pub fn load_or_default(path: &Path) -> Result<Self, Error> {
+        match Self::load(path) {
+            Err(Error::Read { source, .. }) if source.kind() == std::io::ErrorKind::NotFound => {
+                Ok(Self::default())
+            }
+            ret => ret,
+        }
+    }
+
+    /// Parse the contents of a config file, `path` is only used for errors
+    pub fn parse(path: &Path, text: &str) -> Result<Self, Error> {
+        toml::from_str(text).map_err(|source| Error::Parse {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    pub fn to_toml(&self) -> Result<String, Error> {
+        toml::to_string_pretty(self).map_err(Error::Serialize)
+    }
+
+    /// Write the config to `path`, creating its directory if needed
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let text = self.to_toml()?;
+        let write = |source| Error::Write {
+            path: path.to_path_buf(),
+            source,
+        };
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(write)?;
+        }
+        std::fs::write(path, text).map_err(write)
+    }
+
+    /// Add or replace an alias for `device`
+    pub fn add_alias(&mut self, alias: &str, device: &str) -> Result<(), Error> {
+        if alias.trim().is_empty() || looks_like_mac(alias) {
+            return Err(Error::InvalidAlias(alias.to_string()));
+        }
+        self.aliases.insert(alias.to_string(), device.to_string());
+        Ok(())
+    }
+
+    /// The address or name to connect to for `id`, following an alias if it is
+    /// one, or the default device if `id` is `None`
+    pub fn resolve<'a>(&'a self, id: Option<&'a str>) -> Result<&'a str, Error> {
+        let id = id
+            .or(self.default_device.as_deref())
+            .ok_or(Error::NoDevice)?;
+        Ok(self.aliases.get(id).map_or(id, String::as_str))
+    }
+}
+
+fn looks_like_mac(s: &str) -> bool {
+    cole_mine::BDAddr::from_str_delim(s).is_ok() || cole_mine::BDAddr::from_str_no_delim(s).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<Config, Error> {
+        Config::parse(Path::new("config.toml"), text)
+    }
+
+    #[test]
+    fn parse_full() {
+        let config = parse(
+            r#"
+            default-device = "work-ring"
+            format = "json"
+            units = "metric"
+
+            [aliases]
+            work-ring = "A1:B2:C3:D4:E5:F6"
+            home = "R02_1234"
+
+            [listen]
+            find-rings = 30
+            listen = 60
+
+            [push]
+            url = "https://rings.example.com"
+            token = "a-write-token"
+
+            [influx]
+            url = "http://localhost:8086"
+            org = "home"
+            bucket = "rings"
+            token = "an-influx-token"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.default_device.as_deref(), Some("work-ring"));
+        assert_eq!(config.format, Some(Format::Json));
+        assert_eq!(config.units, Some(UnitSystem::Metric));
+        assert_eq!(config.aliases["work-ring"], "A1:B2:C3:D4:E5:F6");
+        assert_eq!(config.aliases["home"], "R02_1234");
+        assert_eq!(
+            config.listen,
+            ListenSeconds {
+                find_rings: Some(30),
+                listen: Some(60),
+                raw: None,
+            }
+        );
+        assert_eq!(
+            config.push,
+            PushConfig {
+                url: Some("https://rings.example.com".to_string()),
+                token: Some("a-write-token".to_string()),
+            }
+        );
+        assert_eq!(
+            config.influx,
+            InfluxConfig {
+                url: Some("http://localhost:8086".to_string()),
+                org: Some("home".to_string()),
+                bucket: Some("rings".to_string()),
+                token: Some("an-influx-token".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_empty() {
+        assert_eq!(parse("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn parse_corrupt() {
+        for text in [
+            "default-device = ",
+            "format = \"yaml\"",
+            "aliases = 1",
+            "[listen]\nfind-rings = -1",
+            "unknown = true",
+        ] {
+            let e = parse(text).unwrap_err();
+            assert!(matches!(e, Error::Parse { .. }), "{text}: {e:?}");
+            assert!(
+                e.to_string().starts_with("invalid config config.toml"),
+                "{e}"
+            );
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut config = Config {
+            default_device: Some("work-ring".to_string()),
+            format: Some(Format::Text),
+            units: Some(UnitSystem::Imperial),
+            ..Default::default()
+        };
+        config.add_alias("work-ring", "A1:B2:C3:D4:E5:F6").unwrap();
+        config.listen.raw = Some(10);
+        config.push.url = Some("http://localhost:3000".to_string());
+        assert_eq!(parse(&config.to_toml().unwrap()).unwrap(), config);
+    }
+
+    #[test]
+    fn resolve_alias() {
+        let mut config = Config::default();
+        config.add_alias("work-ring", "A1:B2:C3:D4:E5:F6").unwrap();
+        assert_eq!(
+            config.resolve(Some("work-ring")).unwrap(),
+            "A1:B2:C3:D4:E5:F6"
+        );
+        assert_eq!(config.resolve(Some("R02_1234")).unwrap(), "R02_1234");
+    }
+
+    #[test]
+    fn resolve_default() {
+        let mut config = Config::default();
+        assert!(matches!(config.resolve(None), Err(Error::NoDevice)));
+        config.default_device = Some("R02_1234".to_string());
+        assert_eq!(config.resolve(None).unwrap(), "R02_1234");
+        assert_eq!(config.resolve(Some("R02_5678")).unwrap(), "R02_5678");
+        config.add_alias("work-ring", "A1:B2:C3:D4:E5:F6").unwrap();
+        config.default_device = Some("work-ring".to_string());
+        assert_eq!(config.resolve(None).unwrap(), "A1:B2:C3:D4:E5:F6");
+    }
+
+    #[test]
+    fn invalid_alias() {
+        let mut config = Config::default();
+        for alias in ["", " ", "A1:B2:C3:D4:E5:F6", "a1b2c3d4e5f6"] {
+            assert!(
+                matches!(
+                    config.add_alias(alias, "R02_1234"),
+                    Err(Error::InvalidAlias(_))
+                ),
+                "{alias:?}"
+            );
+        }
+        assert!(config.aliases.is_empty());
+    }
+
+    #[test]
+    fn load_missing() {
+        let dir = std::env::temp_dir().join(format!("lode-config-{}", std::process::id()));
+        let path = dir.join("missing.toml");
+        assert!(matches!(Config::load(&path), Err(Error::Read { .. })));
+        assert_eq!(Config::load_or_default(&path).unwrap(), Config::default());
+    }
+
+    #[test]
+    fn save_creates_directory() {
+        let dir = std::env::temp_dir().join(format!("lode-config-save-{}", std::process::id()));
+        let path = dir.join("lode").join("config.toml");
+        let mut config = Config::default();
+        config.add_alias("work-ring", "A1:B2:C3:D4:E5:F6").unwrap();
+        config.save(&path).unwrap();
+        assert_eq!(Config::load(&path).unwrap(), config);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,285 @@
+//! The codes lode exits with so scripts can tell a ring that is out of range
+//! (worth retrying later) from a bad invocation (not worth retrying)
+
+use std::error::Error as StdError;
+use std::fmt;
+
+use crate::config;
+
+/// Listed in `--help`, keep in sync with `ExitCode`
+pub const HELP: &str = "\
+Exit codes:
+  0  success
+  1  any other failure
+  2  invalid arguments or config
+  3  ring not found or the scan timed out
+  4  failed to connect to the ring
+  5  the ring didn't reply in time
+  6  the ring sent something that couldn't be parsed
+  7  the ring replied with an error";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ExitCode {
+    Failure = 1,
+    Usage = 2,
+    NotFound = 3,
+    Connect = 4,
+    Timeout = 5,
+    Protocol = 6,
+    Device = 7,
+}
+
+/// Failures lode raises itself that need their own exit code
+#[derive(Debug)]
+pub enum Error {
+    /// The arguments can't be used together or describe something unsupported
+    Usage(String),
+    /// Finding the ring and looking up its characteristics failed
+    Scan(cole_mine::Error),
+    /// Subscribing to the ring's notifications failed
+    Connect(cole_mine::Error),
+    /// The ring didn't send the reply to `what`
+    NoReply(&'static str),
+    /// The ring reported an error code during a real time reading
+    RealTime(u8),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Usage(message) => f.write_str(message),
+            Self::Scan(_) => write!(f, "failed to find the ring"),
+            Self::Connect(_) => write!(f, "failed to connect to the ring"),
+            Self::NoReply(what) => write!(f, "no reply to {what}"),
+            Self::RealTime(1) => write!(f, "ring not on finger"),
+            Self::RealTime(2) => write!(f, "ring is charging"),
+            Self::RealTime(code) => write!(f, "ring reported real time error code {code}"),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::Scan(e) | Self::Connect(e) => Some(e),
+            Self::Usage(_) | Self::NoReply(_) | Self::RealTime(_) => None,
+        }
+    }
+}
+
+impl Error {
+    pub fn usage(message: impl Into<String>) -> Self {
+        Self::Usage(message.into())
+    }
+}
+
+/// The code to exit with for `e`, decided by the outermost error in its chain
+/// that lode knows about
+pub fn code(e: &(dyn StdError + 'static)) -> ExitCode {
+    std::iter::successors(Some(e), |&e| e.source())
+        .find_map(known_code)
+        .unwrap_or(ExitCode::Failure)
+}
+
+fn known_code(e: &(dyn StdError + 'static)) -> Option<ExitCode> {
+    if let Some(e) = e.downcast_ref::<Error>() {
+        return Some(match e {
+            Error::Usage(_) => ExitCode::Usage,
+            Error::Scan(cole_mine::Error::DeviceNotFound | cole_mine::Error::Timeout) => {
+                ExitCode::NotFound
+            }
+            Error::Scan(_) | Error::Connect(_) => ExitCode::Connect,
+            Error::NoReply(_) => ExitCode::Timeout,
+            Error::RealTime(_) => ExitCode::Device,
+        });
+    }
+    if let Some(e) = e.downcast_ref::<config::Error>() {
+        return Some(match e {
+            config::Error::Read { .. }
+            | config::Error::Parse { .. }
+            | config::Error::NoDevice
+            | config::Error::InvalidAlias(_) => ExitCode::Usage,
+            config::Error::Write { .. } | config::Error::Serialize(_) => ExitCode::Failure,
+        });
+    }
+    let e = e.downcast_ref::<cole_mine::Error>()?;
+    use cole_mine::Error as E;
+    match e {
+        E::DeviceNotFound => Some(ExitCode::NotFound),
+        E::ServiceMissing { .. }
+        | E::CharacteristicMissing { .. }
+        | E::NotConnected
+        | E::ConnectionLost { .. } => Some(ExitCode::Connect),
+        E::Timeout => Some(ExitCode::Timeout),
+        E::PacketParse { .. }
+        | E::PacketLength { .. }
+        | E::MissingPackets { .. }
+        | E::DayCount { .. }
+        | E::Crc { .. }
+        | E::Checksum { .. }
+        | E::UnexpectedReply(_)
+        | E::InvalidCapture { .. }
+        | E::InvalidDate { .. }
+        | E::InvalidTimeIndex(_) => Some(ExitCode::Protocol),
+        E::CommandTooLong { .. }
+        | E::InvalidHrInterval(_)
+        | E::GoalTooLarge(_)
+        | E::InvalidPhoneName(_)
+        | E::DurationOverflow { .. } => Some(ExitCode::Usage),
+        E::RealTime(_) => Some(ExitCode::Device),
+        E::Ble(_) | E::Io(_) => None,
+    }
+}
+
+/// `e` and its sources on one line, separated by `: `, skipping sources whose
+/// message is already part of the one before it
+pub fn cause_chain(e: &(dyn StdError + 'static)) -> String {
+    let mut ret = e.to_string();
+    let mut last = ret.clone();
+    for source in std::iter::successors(e.source(), |&e| e.source()) {
+        let message = source.to_string();
+        if !last.contains(&message) {
+            ret.push_str(": ");
+            ret.push_str(&message);
+        }
+        last = message;
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn boxed(e: impl StdError + 'static) -> Box<dyn StdError> {
+        Box::new(e)
+    }
+
+    #[test]
+    fn library_errors() {
+        for (e, expected) in [
+            (cole_mine::Error::DeviceNotFound, ExitCode::NotFound),
+            (cole_mine::Error::Timeout, ExitCode::Timeout),
+            (cole_mine::Error::NotConnected, ExitCode::Connect),
+            (
+                cole_mine::Error::ConnectionLost { attempts: 3 },
+                ExitCode::Connect,
+            ),
+            (
+                cole_mine::Error::PacketLength {
+                    command: Some(0x73),
+                    length: 3,
+                    expected: 16,
+                },
+                ExitCode::Protocol,
+            ),
+            (
+                cole_mine::Error::MissingPackets {
+                    kind: cole_mine::PacketKind::Sleep,
+                    missing: 2,
+                },
+                ExitCode::Protocol,
+            ),
+            (
+                cole_mine::Error::CommandTooLong {
+                    length: 16,
+                    max: 15,
+                },
+                ExitCode::Usage,
+            ),
+            (cole_mine::Error::InvalidHrInterval(7), ExitCode::Usage),
+            (cole_mine::Error::GoalTooLarge(0x100_0000), ExitCode::Usage),
+            (
+                cole_mine::Error::InvalidPhoneName("é".to_string()),
+                ExitCode::Usage,
+            ),
+            (cole_mine::Error::RealTime(1), ExitCode::Device),
+            (
+                cole_mine::Error::Io(std::io::ErrorKind::Other.into()),
+                ExitCode::Failure,
+            ),
+        ] {
+            assert_eq!(code(boxed(e).as_ref()), expected);
+        }
+    }
+
+    #[test]
+    fn scan_errors() {
+        assert_eq!(
+            code(&Error::Scan(cole_mine::Error::DeviceNotFound)),
+            ExitCode::NotFound
+        );
+        assert_eq!(
+            code(&Error::Scan(cole_mine::Error::Timeout)),
+            ExitCode::NotFound
+        );
+        assert_eq!(
+            code(&Error::Scan(cole_mine::Error::NotConnected)),
+            ExitCode::Connect
+        );
+    }
+
+    #[test]
+    fn outermost_known_error_wins() {
+        // a timeout while subscribing is a connect failure, not a reply timeout
+        assert_eq!(
+            code(&Error::Connect(cole_mine::Error::Timeout)),
+            ExitCode::Connect
+        );
+    }
+
+    #[test]
+    fn lode_errors() {
+        assert_eq!(code(&Error::usage("bad")), ExitCode::Usage);
+        assert_eq!(code(&Error::NoReply("goals")), ExitCode::Timeout);
+        assert_eq!(code(&Error::RealTime(2)), ExitCode::Device);
+    }
+
+    #[test]
+    fn config_errors() {
+        assert_eq!(code(&config::Error::NoDevice), ExitCode::Usage);
+        assert_eq!(
+            code(&config::Error::InvalidAlias(String::new())),
+            ExitCode::Usage
+        );
+        assert_eq!(
+            code(&config::Error::Write {
+                path: "config.toml".into(),
+                source: std::io::ErrorKind::PermissionDenied.into(),
+            }),
+            ExitCode::Failure
+        );
+    }
+
+    #[test]
+    fn unknown_errors() {
+        let e: Box<dyn StdError> = "something else".into();
+        assert_eq!(code(e.as_ref()), ExitCode::Failure);
+    }
+
+    #[test]
+    fn help_lists_every_code() {
+        for code in [
+            ExitCode::Failure,
+            ExitCode::Usage,
+            ExitCode::NotFound,
+            ExitCode::Connect,
+            ExitCode::Timeout,
+            ExitCode::Protocol,
+            ExitCode::Device,
+        ] {
+            assert!(HELP.contains(&format!("\n  {}  ", code as u8)), "{code:?}");
+        }
+    }
+
+    #[test]
+    fn cause_chain_one_line() {
+        assert_eq!(
+            cause_chain(&Error::Connect(cole_mine::Error::Timeout)),
+            "failed to connect to the ring: Timed out"
+        );
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "gone");
+        assert_eq!(cause_chain(&cole_mine::Error::Io(io)), "IO error: gone");
+    }
+}
@@ -0,0 +1,231 @@
+//! Writing events to InfluxDB with the v2 `POST /api/v2/write` endpoint
+//!
+//! Points are rendered by `fissure::export::influx` and sent in batches with
+//! nanosecond precision
+
+use reqwest::Response;
+use serde::Deserialize;
+
+use crate::output::InfluxWritten;
+use crate::push::Error;
+
+/// Lines per request, the batch size InfluxDB recommends
+const BATCH_SIZE: usize = 5000;
+
+/// The parts of an InfluxDB error response needed to report it
+#[derive(Debug, Deserialize)]
+struct InfluxError {
+    message: String,
+}
+
+pub struct InfluxWriter {
+    client: reqwest::Client,
+    base_url: String,
+    org: String,
+    bucket: String,
+    token: Option<String>,
+    batch_size: usize,
+}
+
+impl InfluxWriter {
+    /// Write to `bucket` in `org` on the server at `base_url`, authorizing
+    /// with `token` if there is one
+    pub fn new(base_url: &str, org: String, bucket: String, token: Option<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            org,
+            bucket,
+            token,
+            batch_size: BATCH_SIZE,
+        }
+    }
+
+    /// Send `lines` of line protocol, stopping at the first batch the server
+    /// rejects
+    pub async fn write(&self, lines: &[String]) -> Result<InfluxWritten, Error> {
+        let url = format!("{}/api/v2/write", self.base_url);
+        let mut ret = InfluxWritten {
+            url: self.base_url.clone(),
+            bucket: self.bucket.clone(),
+            points: 0,
+            batches: 0,
+        };
+        for (batch, body) in batches(lines, self.batch_size) {
+            let mut request = self
+                .client
+                .post(&url)
+                .query(&[
+                    ("org", self.org.as_str()),
+                    ("bucket", self.bucket.as_str()),
+                    ("precision", "ns"),
+                ])
+                .header("content-type", "text/plain; charset=utf-8")
+                .body(body);
+            if let Some(token) = &self.token {
+                request = request.header("authorization", format!("Token {token}"));
+            }
+            let response = request.send().await?;
+            if !response.status().is_success() {
+                return Err(status_error(&url, response).await);
+            }
+            ret.points += batch;
+            ret.batches += 1;
+        }
+        Ok(ret)
+    }
+}
+
+/// `lines` joined into request bodies of at most `size` lines, along with how
+/// many lines each has
+fn batches(lines: &[String], size: usize) -> impl Iterator<Item = (usize, String)> + '_ {
+    lines
+        .chunks(size)
+        .map(|chunk| (chunk.len(), chunk.join("\n")))
+}
+
+/// Describe an error response, using the `message` from the body if the server
+/// sent one
+async fn status_error(url: &str, response: Response) -> Error {
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    let message = serde_json::from_str::<InfluxError>(&body)
+        .map(|e| e.message)
+        .unwrap_or(body);
+    Error::Status {
+        url: url.to_string(),
+        status,
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use axum::{
+        extract::{OriginalUri, State},
+        http::HeaderMap,
+        routing::post,
+        Json, Router,
+    };
+    use reqwest::StatusCode;
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    /// A request the test server received
+    #[derive(Debug, Clone)]
+    struct Received {
+        uri: String,
+        authorization: Option<String>,
+        body: String,
+    }
+
+    #[derive(Clone)]
+    struct Server {
+        received: Arc<Mutex<Vec<Received>>>,
+        status: u16,
+    }
+
+    async fn record(
+        server: State<Server>,
+        uri: OriginalUri,
+        headers: HeaderMap,
+        body: String,
+    ) -> (axum::http::StatusCode, Json<Value>) {
+        server.received.lock().unwrap().push(Received {
+            uri: uri.0.to_string(),
+            authorization: headers
+                .get("authorization")
+                .and_then(|value| value.to_str().ok())
+                .map(ToString::to_string),
+            body,
+        });
+        (
+            axum::http::StatusCode::from_u16(server.status).unwrap(),
+            Json(json!({ "code": "invalid", "message": "unable to parse points" })),
+        )
+    }
+
+    /// Serve a write endpoint responding with `status` on a random local port
+    async fn serve(status: u16) -> (Server, String) {
+        let server = Server {
+            received: Arc::default(),
+            status,
+        };
+        let router = Router::new()
+            .route("/api/v2/write", post(record))
+            .with_state(server.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        (server, format!("http://{addr}/"))
+    }
+
+    fn writer(url: &str, batch_size: usize) -> InfluxWriter {
+        let mut ret = InfluxWriter::new(
+            url,
+            "home".to_string(),
+            "rings".to_string(),
+            Some("secret".to_string()),
+        );
+        ret.batch_size = batch_size;
+        ret
+    }
+
+    fn lines(count: usize) -> Vec<String> {
+        (0..count)
+            .map(|i| format!("heart_rate,mac=A1:B2:C3:D4:E5:F6 value=6{i} {i}000000000"))
+            .collect()
+    }
+
+    #[test]
+    fn chunks_lines() {
+        let lines = lines(5);
+        let chunks: Vec<_> = batches(&lines, 2).collect();
+        assert_eq!(
+            chunks,
+            [
+                (2, format!("{}\n{}", lines[0], lines[1])),
+                (2, format!("{}\n{}", lines[2], lines[3])),
+                (1, lines[4].clone()),
+            ]
+        );
+        assert_eq!(batches(&lines, 5).count(), 1);
+        assert_eq!(batches(&[], 5).count(), 0);
+    }
+
+    #[tokio::test]
+    async fn writes_batches() {
+        let (server, url) = serve(204).await;
+        let written = writer(&url, 2).write(&lines(5)).await.unwrap();
+        assert_eq!((written.points, written.batches), (5, 3));
+        let received = server.received.lock().unwrap().clone();
+        assert_eq!(received.len(), 3);
+        for request in &received {
+            assert_eq!(
+                request.uri,
+                "/api/v2/write?org=home&bucket=rings&precision=ns"
+            );
+            assert_eq!(request.authorization.as_deref(), Some("Token secret"));
+        }
+        let sent: Vec<_> = received
+            .iter()
+            .flat_map(|request| request.body.lines().map(ToString::to_string))
+            .collect();
+        assert_eq!(sent, lines(5));
+    }
+
+    #[tokio::test]
+    async fn stops_at_rejected_batch() {
+        let (server, url) = serve(400).await;
+        let e = writer(&url, 2).write(&lines(5)).await.unwrap_err();
+        assert!(
+            matches!(&e, Error::Status { status, message, .. }
+                if *status == StatusCode::BAD_REQUEST && message == "unable to parse points"),
+            "{e:?}"
+        );
+        assert_eq!(server.received.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,1684 @@
+//! Printing the results of each subcommand, either as text for people or as a
+//! single JSON document for scripts
+//!
+//! Logs always go to stderr so stdout only ever holds the output below
+
+use std::fmt::Write as _;
+use std::ops::RangeInclusive;
+use std::sync::OnceLock;
+
+use cole_mine::analysis::{
+    hr_daily_stats, sleep_score, GoalReport, HrConfig, HrDailyStats, SleepScore, SleepScoreConfig,
+};
+use cole_mine::big_data::{
+    OxygenData, OxygenMeasurement, SleepData, SleepSession, SleepStage, SleepTotals,
+};
+use cole_mine::client::{BatteryInfo, Command, DeviceDetails, HeartRateSettings};
+use cole_mine::heart_rate::{HeartRate, HeartRateSummary};
+use cole_mine::incoming_messages::notification::{DataName, Notification};
+use cole_mine::incoming_messages::{CommandReply, RealTimeEvent};
+use cole_mine::preferences::{Hand, Preference, PreferenceKey, TimeFormat, Units};
+use cole_mine::sport_detail::{SportDetail, SLOT_DURATION};
+use cole_mine::stress::StressData;
+use cole_mine::units::Meters;
+use cole_mine::DurationExt as _;
+use fissure::{SyncCategory, UpsertCounts};
+use serde::Serialize;
+use time::format_description::well_known::Rfc3339;
+use time::macros::format_description;
+use time::{Date, Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::exit::{self, ExitCode};
+use crate::Result;
+
+/// The output format, set once from the command line
+static FORMAT: OnceLock<Format> = OnceLock::new();
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum, Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// Human readable text
+    #[default]
+    Text,
+    /// One JSON document per command
+    Json,
+}
+
+impl Format {
+    pub fn init(format: Format) {
+        FORMAT.get_or_init(|| format);
+    }
+
+    pub fn current() -> Format {
+        FORMAT.get().copied().unwrap_or_default()
+    }
+}
+
+/// The units distances are printed in, set once from the command line
+static UNITS: OnceLock<UnitSystem> = OnceLock::new();
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum, Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum UnitSystem {
+    /// Meters and kilometers
+    Metric,
+    /// Feet and miles
+    #[default]
+    Imperial,
+}
+
+impl UnitSystem {
+    pub fn init(units: UnitSystem) {
+        UNITS.get_or_init(|| units);
+    }
+
+    pub fn current() -> UnitSystem {
+        UNITS.get().copied().unwrap_or_default()
+    }
+
+    /// `meters` in the larger unit once there's at least one of it
+    fn distance(self, meters: Meters) -> String {
+        match self {
+            Self::Metric if meters.kilometers() >= 1.0 => {
+                format!("{:>8.2}km", meters.kilometers())
+            }
+            Self::Metric => format!("{:>8.2}m", meters.0),
+            Self::Imperial if meters.miles() >= 1.0 => format!("{:>8.2}mi", meters.miles()),
+            Self::Imperial => format!("{:>8.2}ft", meters.feet()),
+        }
+    }
+}
+
+/// The result of a subcommand, serialized as is for `--format json`
+pub trait Report: Serialize {
+    /// The text printed for `--format text`, each line ending in a newline
+    fn text(&self) -> Result<String>;
+}
+
+/// Render `report` in `format`
+pub fn render<R: Report + ?Sized>(report: &R, format: Format) -> Result<String> {
+    match format {
+        Format::Text => report.text(),
+        Format::Json => Ok(format!("{}\n", serde_json::to_string_pretty(report)?)),
+    }
+}
+
+/// Print `report` to stdout in the format from the command line
+pub fn emit<R: Report + ?Sized>(report: &R) -> Result {
+    print!("{}", render(report, Format::current())?);
+    Ok(())
+}
+
+/// Print `report` to stdout as it arrives, for commands that run until they're
+/// stopped, JSON is printed as one object per line
+pub fn emit_line<R: Report + ?Sized>(report: &R) -> Result {
+    match Format::current() {
+        Format::Text => print!("{}", report.text()?),
+        Format::Json => println!("{}", serde_json::to_string(report)?),
+    }
+    Ok(())
+}
+
+/// The object printed in place of a result when a command fails
+pub fn error_json(error: &(dyn std::error::Error + 'static), code: ExitCode) -> String {
+    serde_json::json!({ "error": exit::cause_chain(error), "code": code as u8 }).to_string()
+}
+
+/// The results of a command that arrive over time
+///
+/// Text is printed as each item is pushed, JSON is collected and printed as an
+/// array by `finish`
+pub struct Items<T> {
+    format: Format,
+    items: Vec<T>,
+}
+
+impl<T: Report> Items<T> {
+    pub fn new() -> Self {
+        Self {
+            format: Format::current(),
+            items: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, item: T) -> Result {
+        match self.format {
+            Format::Text => print!("{}", item.text()?),
+            Format::Json => self.items.push(item),
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result {
+        if self.format == Format::Json {
+            emit(&self.items)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: Report> Report for [T] {
+    fn text(&self) -> Result<String> {
+        self.iter().map(Report::text).collect()
+    }
+}
+
+impl<T: Report> Report for Vec<T> {
+    fn text(&self) -> Result<String> {
+        self.as_slice().text()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Adapter {
+    pub index: usize,
+    pub info: String,
+    pub state: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+pub struct Adapters(pub Vec<Adapter>);
+
+impl Report for Adapters {
+    fn text(&self) -> Result<String> {
+        if self.0.is_empty() {
+            return Ok("No Bluetooth adapters\n".to_string());
+        }
+        let mut ret = String::new();
+        for Adapter { index, info, state } in &self.0 {
+            writeln!(ret, "{index}: {info} {state}")?;
+        }
+        Ok(ret)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProbedService {
+    pub name: String,
+    pub characteristics: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Probe {
+    pub address: String,
+    pub name: Option<String>,
+    pub rssi: Option<i16>,
+    pub characteristics: Vec<String>,
+    pub services: Vec<ProbedService>,
+}
+
+impl Report for Probe {
+    fn text(&self) -> Result<String> {
+        let mut ret = self.address.clone();
+        if let Some(name) = &self.name {
+            write!(ret, ": {name}")?;
+        }
+        ret.push('\n');
+        if let Some(rssi) = self.rssi {
+            writeln!(ret, "rssi: {rssi}")?;
+        }
+        ret.push_str("Characteristics\n");
+        for chara in &self.characteristics {
+            writeln!(ret, "  {chara}")?;
+        }
+        ret.push_str("--------------------------\n");
+        ret.push_str("Services\n");
+        for srv in &self.services {
+            writeln!(ret, "  {}", srv.name)?;
+            for chara in &srv.characteristics {
+                writeln!(ret, "    {chara}")?;
+            }
+        }
+        ret.push_str("--------------------------\n");
+        Ok(ret)
+    }
+}
+
+/// Every service and characteristic a device has, for `gatt-dump`
+#[derive(Debug, Serialize)]
+pub struct GattDump {
+    pub address: String,
+    pub name: Option<String>,
+    pub services: Vec<GattService>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GattService {
+    pub uuid: Uuid,
+    pub name: Option<&'static str>,
+    pub characteristics: Vec<GattCharacteristic>,
+}
+
+/// A characteristic and what reading it returned, `value` is colon separated
+/// hex and `text` is the value if it is printable UTF-8
+#[derive(Debug, Serialize)]
+pub struct GattCharacteristic {
+    pub uuid: Uuid,
+    pub name: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl GattCharacteristic {
+    pub fn new(uuid: Uuid, read: std::result::Result<Vec<u8>, String>) -> Self {
+        let mut ret = Self {
+            uuid,
+            name: cole_mine::gatt_names::characteristic_name(uuid),
+            value: None,
+            text: None,
+            error: None,
+        };
+        match read {
+            Ok(bytes) => {
+                let hex: Vec<String> = bytes.iter().map(|b| format!("{b:02x}")).collect();
+                ret.value = Some(hex.join(":"));
+                ret.text = std::str::from_utf8(&bytes)
+                    .ok()
+                    .filter(|text| !text.is_empty() && !text.chars().any(char::is_control))
+                    .map(ToString::to_string);
+            }
+            Err(e) => ret.error = Some(e),
+        }
+        ret
+    }
+}
+
+impl Report for GattDump {
+    fn text(&self) -> Result<String> {
+        let mut ret = self.address.clone();
+        if let Some(name) = &self.name {
+            write!(ret, ": {name}")?;
+        }
+        ret.push('\n');
+        for srv in &self.services {
+            write!(ret, "  ")?;
+            if let Some(name) = srv.name {
+                write!(ret, "{name} ")?;
+            }
+            writeln!(ret, "({})", srv.uuid)?;
+            for chara in &srv.characteristics {
+                write!(ret, "    ")?;
+                if let Some(name) = chara.name {
+                    write!(ret, "{name} ")?;
+                }
+                write!(ret, "({})", chara.uuid)?;
+                match (&chara.value, &chara.text, &chara.error) {
+                    (_, Some(text), _) => writeln!(ret, ": {text:?}")?,
+                    (Some(value), None, _) if value.is_empty() => writeln!(ret, ": empty")?,
+                    (Some(value), None, _) => writeln!(ret, ": {value}")?,
+                    (None, _, Some(error)) => writeln!(ret, ": read failed, {error}")?,
+                    (None, _, None) => ret.push('\n'),
+                }
+            }
+        }
+        Ok(ret)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct FoundRing {
+    pub address: String,
+    pub name: Option<String>,
+    pub rssi: Option<i16>,
+}
+
+impl Report for FoundRing {
+    fn text(&self) -> Result<String> {
+        let mut ret = self.address.clone();
+        if let Some(name) = &self.name {
+            write!(ret, ": {name}")?;
+        }
+        if let Some(rssi) = self.rssi {
+            write!(ret, " ({rssi} dBm)")?;
+        }
+        ret.push('\n');
+        Ok(ret)
+    }
+}
+
+/// The config file along with where it is
+#[derive(Debug, Serialize)]
+pub struct ShownConfig<'a> {
+    pub path: String,
+    #[serde(flatten)]
+    pub config: &'a Config,
+}
+
+impl Report for ShownConfig<'_> {
+    fn text(&self) -> Result<String> {
+        Ok(format!("# {}\n{}", self.path, self.config.to_toml()?))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Goals {
+    pub steps: u32,
+    pub calories: u32,
+    pub distance: u32,
+}
+
+impl Report for Goals {
+    fn text(&self) -> Result<String> {
+        Ok(format!(
+            "steps: {}\ncalories: {}\ndistance: {}\n",
+            self.steps, self.calories, self.distance
+        ))
+    }
+}
+
+impl Report for GoalReport {
+    fn text(&self) -> Result<String> {
+        let mut ret = String::new();
+        for day in &self.days {
+            write!(
+                ret,
+                "{}: {}/{} ({:.0}%)",
+                day.date, day.steps, self.goal, day.percent
+            )?;
+            if day.met {
+                ret.push_str(" met");
+            }
+            if day.partial {
+                ret.push_str(" so far");
+            }
+            ret.push('\n');
+        }
+        writeln!(
+            ret,
+            "current streak: {} days, best: {} days",
+            self.current_streak, self.best_streak
+        )?;
+        Ok(ret)
+    }
+}
+
+impl Report for DeviceDetails {
+    fn text(&self) -> Result<String> {
+        let or_missing =
+            |s: &Option<String>| s.clone().unwrap_or_else(|| "<not found>".to_string());
+        Ok(format!(
+            "Hardware: {}\nFirmware: {}\n",
+            or_missing(&self.hw),
+            or_missing(&self.fw)
+        ))
+    }
+}
+
+/// What `sync` stored for one category, `counts` is `None` if the ring never
+/// replied
+#[derive(Debug, Serialize)]
+pub struct Synced {
+    pub category: SyncCategory,
+    pub counts: Option<UpsertCounts>,
+}
+
+impl Report for Synced {
+    fn text(&self) -> Result<String> {
+        let category = self.category;
+        Ok(match self.counts {
+            Some(counts) => format!(
+                "{category}: {} inserted, {} updated\n",
+                counts.inserted, counts.updated
+            ),
+            None => format!("{category}: no reply\n"),
+        })
+    }
+}
+
+/// What `sync --all-known` stored for one ring, `error` is why the ring
+/// failed if it did
+#[derive(Debug, Serialize)]
+pub struct RingSynced {
+    pub mac: String,
+    pub synced: Vec<Synced>,
+    pub error: Option<String>,
+}
+
+impl Report for RingSynced {
+    fn text(&self) -> Result<String> {
+        let mut ret = match &self.error {
+            Some(error) => format!("{}: failed: {error}\n", self.mac),
+            None => format!("{}:\n", self.mac),
+        };
+        for synced in &self.synced {
+            write!(ret, "  {}", synced.text()?)?;
+        }
+        Ok(ret)
+    }
+}
+
+/// What a conveyor server accepted from `push` or `sync --push`
+#[derive(Debug, Serialize)]
+pub struct Pushed {
+    pub url: String,
+    pub mac: String,
+    pub sent: usize,
+    pub accepted: usize,
+    pub failed: Vec<PushFailure>,
+}
+
+/// An event the server rejected, `index` is its position in what was sent
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct PushFailure {
+    pub index: usize,
+    pub error: String,
+}
+
+impl Report for Pushed {
+    fn text(&self) -> Result<String> {
+        let mut ret = format!(
+            "pushed {} of {} events for {} to {}\n",
+            self.accepted, self.sent, self.mac, self.url
+        );
+        for failure in &self.failed {
+            writeln!(ret, "event {} rejected: {}", failure.index, failure.error)?;
+        }
+        Ok(ret)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct InfluxWritten {
+    pub url: String,
+    pub bucket: String,
+    pub points: usize,
+    pub batches: usize,
+}
+
+impl Report for InfluxWritten {
+    fn text(&self) -> Result<String> {
+        Ok(format!(
+            "wrote {} points to {} at {} in {} requests\n",
+            self.points, self.bucket, self.url, self.batches
+        ))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Compacted {
+    pub path: String,
+    pub before: u64,
+    pub after: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Pruned {
+    pub removed: usize,
+    #[serde(with = "time::serde::rfc3339")]
+    pub cutoff: OffsetDateTime,
+    pub compacted: Option<Compacted>,
+}
+
+impl Report for Pruned {
+    fn text(&self) -> Result<String> {
+        let mut ret = format!(
+            "removed {} events from before {}\n",
+            self.removed, self.cutoff
+        );
+        if let Some(Compacted {
+            path,
+            before,
+            after,
+        }) = &self.compacted
+        {
+            writeln!(ret, "compacted {path} from {before} to {after} bytes")?;
+        }
+        Ok(ret)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Exported {
+    pub path: String,
+    pub records: usize,
+}
+
+impl Report for Exported {
+    fn text(&self) -> Result<String> {
+        Ok(format!("wrote {} records to {}\n", self.records, self.path))
+    }
+}
+
+/// A line of a capture decoded by `replay`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Replayed {
+    Sent(Command),
+    SendError(String),
+    Received(CommandReply),
+    ReceiveError(String),
+}
+
+impl Report for Replayed {
+    fn text(&self) -> Result<String> {
+        Ok(match self {
+            Self::Sent(command) => format!("> {command:?}\n"),
+            Self::SendError(e) => format!("> error: {e}\n"),
+            Self::Received(reply) => format!("< {reply:?}\n"),
+            Self::ReceiveError(e) => format!("< error: {e}\n"),
+        })
+    }
+}
+
+/// A real time reading along with when it arrived
+#[derive(Debug, Serialize)]
+pub struct RealTimeSample {
+    #[serde(with = "time::serde::rfc3339")]
+    pub time: OffsetDateTime,
+    #[serde(flatten)]
+    pub event: RealTimeEvent,
+}
+
+impl Report for RealTimeSample {
+    fn text(&self) -> Result<String> {
+        let time = self.time.format(format_description!(
+            "[hour repr:12]:[minute]:[second] [period]"
+        ))?;
+        Ok(match self.event {
+            RealTimeEvent::HeartRate(bpm) => format!("{time} {bpm:>3} bpm\n"),
+            RealTimeEvent::Oxygen(spo2) => format!("{time} {spo2:>3}%\n"),
+            RealTimeEvent::Error(code) => format!("{time} error {code}\n"),
+        })
+    }
+}
+
+/// A notification the ring sent on its own along with when it arrived
+#[derive(Debug, Serialize)]
+pub struct Noticed {
+    #[serde(with = "time::serde::rfc3339")]
+    pub time: OffsetDateTime,
+    pub notification: Notification,
+}
+
+impl Report for Noticed {
+    fn text(&self) -> Result<String> {
+        noticed_text(self, UnitSystem::current())
+    }
+}
+
+fn noticed_text(noticed: &Noticed, units: UnitSystem) -> Result<String> {
+    let time = noticed.time.format(format_description!(
+        "[hour repr:12]:[minute]:[second] [period]"
+    ))?;
+    let line = match noticed.notification {
+        Notification::NewData(DataName::HeartRate) => "new heart rate data available".to_string(),
+        Notification::NewData(DataName::Oxygen) => "new blood oxygen data available".to_string(),
+        Notification::NewData(DataName::Steps) => "new step data available".to_string(),
+        Notification::Battery(level) => format!("battery {level}%"),
+        Notification::Activity(activity) => format!(
+            "{} steps, {:.1} kcal, {}",
+            activity.steps,
+            activity.calories,
+            units
+                .distance(Meters(activity.distance as f32))
+                .trim_start()
+        ),
+    };
+    Ok(format!("{time} {line}\n"))
+}
+
+impl Report for CommandReply {
+    fn text(&self) -> Result<String> {
+        Ok(format!("{self:?}\n"))
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeSet {
+    #[serde(with = "time::serde::rfc3339")]
+    pub sent: OffsetDateTime,
+    /// The time the ring reported after setting it, if its firmware does
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub ring_time: Option<OffsetDateTime>,
+}
+
+impl Report for TimeSet {
+    fn text(&self) -> Result<String> {
+        let Some(ring_time) = self.ring_time else {
+            return Ok("Time set, the ring didn't report its time\n".to_string());
+        };
+        Ok(format!(
+            "Ring time: {} ({:.1}s from the time sent)\n",
+            ring_time.format(&Rfc3339)?,
+            (ring_time - self.sent).as_seconds_f64()
+        ))
+    }
+}
+
+impl Report for StressData {
+    fn text(&self) -> Result<String> {
+        let mut ret = String::new();
+        for (time, value) in self.samples() {
+            let time = time.format(format_description!("[year]-[month]-[day] [hour]:[minute]"))?;
+            match value {
+                Some(value) => writeln!(ret, "{time}: {value}")?,
+                None => writeln!(ret, "{time}: no reading")?,
+            }
+        }
+        Ok(ret)
+    }
+}
+
+impl Report for SportDetail {
+    fn text(&self) -> Result<String> {
+        sport_detail_text(self, UnitSystem::current())
+    }
+}
+
+fn sport_detail_text(detail: &SportDetail, units: UnitSystem) -> Result<String> {
+    let mut ret = match detail
+        .date()
+        .and_then(|date| Ok((date, detail.start_time()?)))
+    {
+        Ok((date, start)) => {
+            let end = start + SLOT_DURATION;
+            format!(
+                "{} {}\u{2013}{}\n",
+                date.format(format_description!("[year]-[month]-[day]"))?,
+                start.format(format_description!("[hour]:[minute]"))?,
+                end.format(format_description!("[hour]:[minute]"))?,
+            )
+        }
+        // still shown so the values aren't lost
+        Err(e) => format!(
+            "{}-{:02}-{:02} slot {} ({e})\n",
+            detail.year, detail.month, detail.day, detail.time_index
+        ),
+    };
+    writeln!(ret, "  Cals: {:>5.2}", detail.kilocalories().0)?;
+    writeln!(ret, "  Stps: {:>8}", detail.steps)?;
+    writeln!(ret, "  Dist: {}", units.distance(detail.meters()))?;
+    Ok(ret)
+}
+
+/// A day of heart rates along with the interval they were taken at
+#[derive(Debug, Serialize)]
+pub struct HeartRateDay {
+    #[serde(flatten)]
+    pub heart_rate: HeartRate,
+    /// Minutes between each rate
+    pub interval: u64,
+    pub summary: Option<HeartRateSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<HrDailyStats>,
+}
+
+impl HeartRateDay {
+    pub fn new(heart_rate: HeartRate, interval: std::time::Duration) -> Self {
+        let summary = heart_rate.summary();
+        Self {
+            heart_rate,
+            interval: interval.as_secs() / 60,
+            summary,
+            stats: None,
+        }
+    }
+
+    /// Add the day's stats, `config`'s interval is replaced with the one the
+    /// rates were taken at
+    pub fn with_stats(mut self, config: &HrConfig) -> Self {
+        let config = HrConfig {
+            interval: std::time::Duration::minutes(self.interval),
+            ..config.clone()
+        };
+        let samples: Vec<_> = self
+            .heart_rate
+            .samples(config.interval)
+            .map(|(when, rate)| (when, rate.unwrap_or(0)))
+            .collect();
+        self.stats = Some(hr_daily_stats(&samples, &config));
+        self
+    }
+}
+
+impl Report for HeartRateDay {
+    fn text(&self) -> Result<String> {
+        let hr = &self.heart_rate;
+        let mut ret = format!(
+            "Heart Rates {}-{:02}-{:02} {}\n",
+            hr.date.year(),
+            hr.date.month() as u8,
+            hr.date.day(),
+            hr.range
+        );
+        let interval = std::time::Duration::minutes(self.interval);
+        for (time, rate) in hr.samples(interval) {
+            let time = time.format(format_description!("[hour repr:12]:[minute] [period]"))?;
+            match rate {
+                Some(rate) => writeln!(ret, "  {time} {rate:>3}")?,
+                None => writeln!(ret, "  {time}   -")?,
+            }
+        }
+        if let Some(summary) = self.summary {
+            writeln!(
+                ret,
+                "min: {} max: {} avg: {:.1} resting: {}",
+                summary.min,
+                summary.max,
+                summary.avg,
+                summary
+                    .resting
+                    .map(|r| r.to_string())
+                    .unwrap_or_else(|| "-".to_string())
+            )?;
+        }
+        if let Some(stats) = &self.stats {
+            match stats.resting {
+                Some(resting) => writeln!(ret, "resting (early morning): {resting}")?,
+                None => writeln!(ret, "resting (early morning): -")?,
+            }
+            if let Some(zones) = stats.zone_minutes {
+                let zones: Vec<_> = zones
+                    .iter()
+                    .zip(1..)
+                    .map(|(minutes, zone)| format!("{zone}: {minutes}m"))
+                    .collect();
+                writeln!(ret, "zones: {}", zones.join(" "))?;
+            }
+        }
+        Ok(ret)
+    }
+}
+
+/// A day `read-heart-rate` couldn't read
+#[derive(Debug, Serialize)]
+pub struct FailedDay {
+    pub date: Date,
+    pub error: String,
+}
+
+/// The days `read-heart-rate` read, along with the ones it couldn't
+#[derive(Debug, Default, Serialize)]
+pub struct HeartRates {
+    pub days: Vec<HeartRateDay>,
+    pub failed: Vec<FailedDay>,
+}
+
+impl Report for HeartRates {
+    fn text(&self) -> Result<String> {
+        let mut ret = self.days.text()?;
+        for FailedDay { date, error } in &self.failed {
+            writeln!(ret, "failed to read {date}: {error}")?;
+        }
+        Ok(ret)
+    }
+}
+
+impl Report for BatteryInfo {
+    fn text(&self) -> Result<String> {
+        Ok(format!("{}% {}\n", self.level, self.charging))
+    }
+}
+
+/// A battery level that was stored along with how long the battery should
+/// last
+#[derive(Debug, Serialize)]
+pub struct RecordedBattery {
+    #[serde(flatten)]
+    pub info: BatteryInfo,
+    /// `None` until enough levels are stored to see it draining
+    pub hours_remaining: Option<f32>,
+}
+
+impl Report for RecordedBattery {
+    fn text(&self) -> Result<String> {
+        let mut ret = self.info.text()?;
+        if let Some(hours) = self.hours_remaining {
+            writeln!(ret, "about {hours:.1} hours remaining")?;
+        }
+        Ok(ret)
+    }
+}
+
+impl Report for HeartRateSettings {
+    fn text(&self) -> Result<String> {
+        Ok(format!(
+            "enabled: {}, interval: {}\n",
+            self.enabled, self.interval
+        ))
+    }
+}
+
+/// The heart rate settings the ring reports after changing them
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+pub struct UpdatedHeartRateSettings(pub HeartRateSettings);
+
+impl Report for UpdatedHeartRateSettings {
+    fn text(&self) -> Result<String> {
+        Ok(format!("Updated {}", self.0.text()?))
+    }
+}
+
+/// Whether the ring takes a measurement on its own through the day
+#[derive(Debug, Serialize)]
+pub struct AutoPref {
+    pub enabled: bool,
+}
+
+impl Report for AutoPref {
+    fn text(&self) -> Result<String> {
+        Ok(format!("enabled: {}\n", self.enabled))
+    }
+}
+
+/// The automatic measurement setting the ring reports after changing it
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+pub struct UpdatedAutoPref(pub AutoPref);
+
+impl Report for UpdatedAutoPref {
+    fn text(&self) -> Result<String> {
+        Ok(format!("Updated {}", self.0.text()?))
+    }
+}
+
+/// The name lode uses for a preference key, the key's number for keys
+/// cole-mine doesn't know
+pub fn preference_key(key: PreferenceKey) -> String {
+    key.name()
+        .map_or_else(|| format!("{:#04x}", u8::from(key)), String::from)
+}
+
+/// The text lode prints for a preference's value and accepts for `prefs set`,
+/// colon separated hex for values cole-mine can't decode
+pub fn preference_value(pref: &Preference) -> String {
+    match pref {
+        Preference::TimeFormat(TimeFormat::TwentyFourHour) => "24h".to_string(),
+        Preference::TimeFormat(TimeFormat::TwelveHour) => "12h".to_string(),
+        Preference::Units(Units::Metric) => "metric".to_string(),
+        Preference::Units(Units::Imperial) => "imperial".to_string(),
+        Preference::WearHand(Hand::Left) => "left".to_string(),
+        Preference::WearHand(Hand::Right) => "right".to_string(),
+        Preference::RaiseToWake(true) => "on".to_string(),
+        Preference::RaiseToWake(false) => "off".to_string(),
+        Preference::Unknown { value, .. } if value.is_empty() => "00".to_string(),
+        Preference::Unknown { value, .. } => {
+            let hex: Vec<String> = value.iter().map(|b| format!("{b:02x}")).collect();
+            hex.join(":")
+        }
+    }
+}
+
+impl Report for Preference {
+    fn text(&self) -> Result<String> {
+        Ok(format!(
+            "{}: {}\n",
+            preference_key(self.key()),
+            preference_value(self)
+        ))
+    }
+}
+
+/// The preference the ring reports after changing it
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+pub struct UpdatedPreference(pub Preference);
+
+impl Report for UpdatedPreference {
+    fn text(&self) -> Result<String> {
+        Ok(format!("Updated {}", self.0.text()?))
+    }
+}
+
+/// What the ring replied to setting the phone name, the bytes after the
+/// command byte or `None` if it didn't reply
+#[derive(Debug, Serialize)]
+pub struct PhoneNameReply {
+    pub reply: Option<Vec<u8>>,
+}
+
+impl Report for PhoneNameReply {
+    fn text(&self) -> Result<String> {
+        let Some(reply) = &self.reply else {
+            return Ok("ring didn't reply\n".to_string());
+        };
+        let hex: Vec<String> = reply.iter().map(|b| format!("{b:02x}")).collect();
+        Ok(format!("ring replied {}\n", hex.join(":")))
+    }
+}
+
+/// How many find device commands were sent and how many the ring acknowledged
+#[derive(Debug, Default, Serialize)]
+pub struct FoundDevice {
+    pub sent: u32,
+    pub acknowledged: u32,
+}
+
+impl Report for FoundDevice {
+    fn text(&self) -> Result<String> {
+        Ok(format!(
+            "ring acknowledged {} of {} find device commands\n",
+            self.acknowledged, self.sent
+        ))
+    }
+}
+
+/// If the ring replied to a command that has nothing else to report, only
+/// printed for `--format json`
+#[derive(Debug, Serialize)]
+pub struct Acknowledged {
+    pub acknowledged: bool,
+}
+
+impl Report for Acknowledged {
+    fn text(&self) -> Result<String> {
+        Ok(String::new())
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SleepSessionReport {
+    #[serde(flatten)]
+    pub session: SleepSession,
+    pub totals: SleepTotals,
+    pub score: SleepScore,
+}
+
+/// The sleep sessions `read-sleep` prints, those that started on `days` if
+/// given
+#[derive(Debug, Serialize)]
+pub struct SleepReport {
+    pub sessions: Vec<SleepSessionReport>,
+}
+
+impl SleepReport {
+    pub fn new(data: SleepData, days: Option<RangeInclusive<Date>>) -> Self {
+        let sessions = data
+            .sessions
+            .into_iter()
+            .filter(|session| {
+                days.as_ref()
+                    .is_none_or(|days| days.contains(&session.start.date()))
+            })
+            .map(|session| SleepSessionReport {
+                totals: session.totals(),
+                score: sleep_score(&session, &SleepScoreConfig::default()),
+                session,
+            })
+            .collect();
+        Self { sessions }
+    }
+}
+
+impl Report for SleepReport {
+    fn text(&self) -> Result<String> {
+        self.sessions.iter().map(sleep_session_text).collect()
+    }
+}
+
+/// Only the oxygen samples taken on `days`
+pub fn oxygen_on(data: OxygenData, days: &RangeInclusive<Date>) -> OxygenData {
+    OxygenData {
+        samples: data
+            .samples
+            .into_iter()
+            .filter(|sample| days.contains(&sample.when.date()))
+            .collect(),
+    }
+}
+
+fn sleep_session_text(report: &SleepSessionReport) -> Result<String> {
+    let SleepSessionReport {
+        session,
+        totals,
+        score,
+    } = report;
+    let date_fmt = format_description!("[year]-[month]-[day]");
+    let mut time = session.start;
+    // a session is listed under the day it started
+    let mut ret = format!("--{}--", time.date().format(date_fmt)?);
+    if session.end.date() != session.start.date() {
+        write!(ret, " (ends {})", session.end.date().format(date_fmt)?)?;
+    }
+    ret.push('\n');
+    let fmt = format_description!("[year]-[month]-[day] [hour repr:12]:[minute] [period]");
+    for stage in &session.stages {
+        let (n, m) = match *stage {
+            SleepStage::Light(m) => ("Light", m as i64),
+            SleepStage::Deep(m) => ("Deep", m as i64),
+            SleepStage::Rem(m) => ("REM", m as i64),
+            SleepStage::Awake(m) => ("Awake", m as i64),
+        };
+        let end = time + Duration::minutes(m);
+        writeln!(ret, "{}-{} ({m}): {n}", time.format(fmt)?, end.format(fmt)?)?;
+        time = end;
+    }
+    let asleep = totals.asleep();
+    writeln!(
+        ret,
+        "Total: {}h {:02}m asleep, Light: {}, Deep: {}, REM: {}, Awake: {}, Efficiency: {:.0}%",
+        asleep / 60,
+        asleep % 60,
+        totals.light,
+        totals.deep,
+        totals.rem,
+        totals.awake,
+        totals.efficiency
+    )?;
+    writeln!(
+        ret,
+        "Score: {}{} (duration: {:.1}, deep: {:.1}, REM: {:.1}, awake: -{:.1}, {} awakenings: -{:.1})",
+        score.score,
+        if score.nap { " nap" } else { "" },
+        score.duration_points,
+        score.deep_points,
+        score.rem_points,
+        score.awake_penalty,
+        score.awakenings,
+        score.fragmentation_penalty,
+    )?;
+    Ok(ret)
+}
+
+impl Report for OxygenData {
+    fn text(&self) -> Result<String> {
+        self.samples.iter().map(oxygen_text).collect()
+    }
+}
+
+fn oxygen_text(oxy: &OxygenMeasurement) -> Result<String> {
+    if oxy.is_empty() {
+        return Ok(String::new());
+    }
+    let mut ret = format!(
+        "{}:",
+        oxy.when.format(format_description!(
+            "[year]-[month]-[day] [hour repr:12]:[minute] [period]"
+        ))?
+    );
+    if oxy.max == 0 || oxy.min == 0 {
+        let v = oxy.max.max(oxy.min);
+        write!(ret, "{v:>7} ±  0 ~{:.02}", v as f32)?;
+    } else {
+        write!(
+            ret,
+            "{:>3}-{:<3} ±{:>3} ~{:.02}",
+            oxy.min,
+            oxy.max,
+            oxy.min.max(oxy.max) - oxy.min.min(oxy.max),
+            (oxy.min + oxy.max) as f32 / 2.0,
+        )?;
+    }
+    ret.push('\n');
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cole_mine::stress::StressReading;
+    use time::macros::{date, datetime};
+
+    /// Snapshot `report` rendered as both text and JSON
+    fn snapshot<R: Report + ?Sized>(name: &str, report: &R) {
+        insta::assert_snapshot!(
+            format!("{name}_text"),
+            render(report, Format::Text).unwrap()
+        );
+        insta::assert_snapshot!(
+            format!("{name}_json"),
+            render(report, Format::Json).unwrap()
+        );
+    }
+
+    #[test]
+    fn goal_progress() {
+        let days = [
+            (date!(2024 - 11 - 17), 9_000),
+            (date!(2024 - 11 - 18), 10_250),
+            (date!(2024 - 11 - 19), 10_000),
+            (date!(2024 - 11 - 20), 4_100),
+        ];
+        snapshot(
+            "goal_progress",
+            &cole_mine::analysis::goal_progress(10_000, &days, date!(2024 - 11 - 20)),
+        );
+    }
+
+    #[test]
+    fn recorded_battery() {
+        let info = BatteryInfo {
+            level: 64,
+            charging: false,
+        };
+        snapshot(
+            "recorded_battery",
+            &RecordedBattery {
+                info,
+                hours_remaining: Some(31.25),
+            },
+        );
+        let unknown = RecordedBattery {
+            info,
+            hours_remaining: None,
+        };
+        assert_eq!(unknown.text().unwrap(), "64% false\n");
+    }
+
+    #[test]
+    fn goals() {
+        snapshot(
+            "goals",
+            &Goals {
+                steps: 8000,
+                calories: 500,
+                distance: 5000,
+            },
+        );
+    }
+
+    #[test]
+    fn preferences() {
+        snapshot(
+            "preference",
+            &Preference::TimeFormat(TimeFormat::TwelveHour),
+        );
+        snapshot(
+            "unknown_preference",
+            &UpdatedPreference(Preference::Unknown {
+                key: 0x7f,
+                value: vec![4, 0, 0x1f],
+            }),
+        );
+    }
+
+    #[test]
+    fn device_details() {
+        snapshot(
+            "device_details",
+            &DeviceDetails {
+                hw: Some("R02_V3.0".to_string()),
+                fw: None,
+            },
+        );
+    }
+
+    #[test]
+    fn sport_details() {
+        let details = vec![
+            SportDetail {
+                year: 2024,
+                month: 11,
+                day: 20,
+                time_index: 32,
+                calories: 12500,
+                steps: 412,
+                distance: 290,
+            },
+            SportDetail {
+                year: 2024,
+                month: 11,
+                day: 20,
+                time_index: 33,
+                calories: 45000,
+                steps: 9000,
+                distance: 60000,
+            },
+        ];
+        snapshot("sport_details", &details);
+        let metric: Vec<_> = details
+            .iter()
+            .map(|detail| sport_detail_text(detail, UnitSystem::Metric).unwrap())
+            .collect();
+        insta::assert_snapshot!("sport_details_metric", metric.concat());
+    }
+
+    #[test]
+    fn heart_rate() {
+        let mut rates = vec![0; 288];
+        rates[..6].copy_from_slice(&[62, 60, 0, 58, 57, 59]);
+        let day = HeartRateDay::new(
+            HeartRate {
+                range: 5,
+                rates,
+                date: datetime!(2024-11-20 0:00),
+                gaps: Vec::new(),
+            },
+            std::time::Duration::minutes(5),
+        );
+        // only the first few samples matter, the rest of the day has no readings
+        let text = render(&day, Format::Text).unwrap();
+        let mut lines: Vec<_> = text.lines().take(8).collect();
+        lines.push(text.lines().last().unwrap());
+        insta::assert_snapshot!("heart_rate_text", lines.join("\n"));
+        let json: serde_json::Value =
+            serde_json::from_str(&render(&day, Format::Json).unwrap()).unwrap();
+        assert_eq!(json["interval"], 5);
+        assert_eq!(json["rates"].as_array().unwrap().len(), 288);
+        insta::assert_snapshot!(
+            "heart_rate_summary_json",
+            serde_json::to_string_pretty(&json["summary"]).unwrap()
+        );
+    }
+
+    #[test]
+    fn heart_rate_stats() {
+        let mut rates = vec![0; 288];
+        rates[..6].copy_from_slice(&[62, 60, 0, 58, 57, 59]);
+        let hr = HeartRate {
+            range: 5,
+            rates,
+            date: datetime!(2024-11-20 0:00),
+            gaps: Vec::new(),
+        };
+        let interval = std::time::Duration::minutes(5);
+        let day = HeartRateDay::new(hr.clone(), interval);
+        let json: serde_json::Value =
+            serde_json::from_str(&render(&day, Format::Json).unwrap()).unwrap();
+        assert!(json.get("stats").is_none());
+        let config = HrConfig {
+            max_hr: Some(120),
+            ..Default::default()
+        };
+        let day = HeartRateDay::new(hr, interval).with_stats(&config);
+        let stats = day.stats.as_ref().unwrap();
+        // the gap at 0:10 splits the readings, 57 and 59 hold the lowest
+        assert_eq!(stats.resting, Some(58));
+        assert_eq!(stats.zone_minutes, Some([10, 0, 0, 0, 0]));
+        let text = render(&day, Format::Text).unwrap();
+        let lines: Vec<_> = text.lines().rev().take(3).collect();
+        insta::assert_snapshot!("heart_rate_stats_text", lines.join("\n"));
+    }
+
+    #[test]
+    fn heart_rates_with_failed_days() {
+        let rates = HeartRates {
+            days: vec![HeartRateDay::new(
+                HeartRate {
+                    range: 5,
+                    rates: vec![62, 60],
+                    date: datetime!(2024-11-19 0:00),
+                    gaps: Vec::new(),
+                },
+                std::time::Duration::minutes(5),
+            )],
+            failed: vec![
+                FailedDay {
+                    date: date!(2024 - 11 - 20),
+                    error: "no reply".to_string(),
+                },
+                FailedDay {
+                    date: date!(2024 - 11 - 21),
+                    error: "disconnected".to_string(),
+                },
+            ],
+        };
+        snapshot("heart_rates_with_failed_days", &rates);
+    }
+
+    #[test]
+    fn stress() {
+        snapshot(
+            "stress",
+            &StressData {
+                date: date!(2024 - 11 - 20),
+                minutes_apart: 30,
+                readings: vec![
+                    StressReading {
+                        index: 0,
+                        value: Some(24),
+                    },
+                    StressReading {
+                        index: 1,
+                        value: None,
+                    },
+                    StressReading {
+                        index: 2,
+                        value: Some(41),
+                    },
+                ],
+            },
+        );
+    }
+
+    fn sleep_data() -> SleepData {
+        SleepData {
+            sessions: vec![
+                SleepSession {
+                    start: datetime!(2024-11-18 23:30),
+                    end: datetime!(2024-11-19 6:30),
+                    stages: vec![SleepStage::Light(200), SleepStage::Deep(220)],
+                },
+                SleepSession {
+                    start: datetime!(2024-11-19 22:45),
+                    end: datetime!(2024-11-20 0:15),
+                    stages: vec![
+                        SleepStage::Light(30),
+                        SleepStage::Deep(45),
+                        SleepStage::Rem(10),
+                        SleepStage::Awake(5),
+                    ],
+                },
+                SleepSession {
+                    start: datetime!(2024-11-20 13:00),
+                    end: datetime!(2024-11-20 13:40),
+                    stages: vec![SleepStage::Light(25), SleepStage::Light(15)],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn sleep() {
+        snapshot("sleep", &SleepReport::new(sleep_data(), None));
+    }
+
+    #[test]
+    fn sleep_on_day_started() {
+        let report = SleepReport::new(
+            sleep_data(),
+            Some(date!(2024 - 11 - 19)..=date!(2024 - 11 - 19)),
+        );
+        assert_eq!(report.sessions.len(), 1);
+        assert_eq!(
+            report.sessions[0].session.start,
+            datetime!(2024-11-19 22:45)
+        );
+        let report = SleepReport::new(
+            sleep_data(),
+            Some(date!(2024 - 11 - 19)..=date!(2024 - 11 - 20)),
+        );
+        assert_eq!(report.sessions.len(), 2);
+    }
+
+    #[test]
+    fn oxygen_on_days() {
+        let data = OxygenData {
+            samples: [
+                datetime!(2024-11-18 23:00),
+                datetime!(2024-11-19 0:00),
+                datetime!(2024-11-19 23:00),
+                datetime!(2024-11-20 0:00),
+            ]
+            .into_iter()
+            .map(|when| OxygenMeasurement {
+                min: 95,
+                max: 98,
+                when,
+            })
+            .collect(),
+        };
+        let data = oxygen_on(data, &(date!(2024 - 11 - 19)..=date!(2024 - 11 - 19)));
+        let when: Vec<_> = data.samples.iter().map(|sample| sample.when).collect();
+        assert_eq!(
+            when,
+            [datetime!(2024-11-19 0:00), datetime!(2024-11-19 23:00)]
+        );
+    }
+
+    #[test]
+    fn oxygen() {
+        snapshot(
+            "oxygen",
+            &OxygenData {
+                samples: vec![
+                    OxygenMeasurement {
+                        min: 0,
+                        max: 0,
+                        when: datetime!(2024-11-20 1:00),
+                    },
+                    OxygenMeasurement {
+                        min: 95,
+                        max: 98,
+                        when: datetime!(2024-11-20 2:00),
+                    },
+                    OxygenMeasurement {
+                        min: 0,
+                        max: 97,
+                        when: datetime!(2024-11-20 3:00),
+                    },
+                ],
+            },
+        );
+    }
+
+    #[test]
+    fn synced() {
+        snapshot(
+            "synced",
+            &vec![
+                Synced {
+                    category: SyncCategory::Activity,
+                    counts: Some(UpsertCounts {
+                        inserted: 12,
+                        updated: 3,
+                        skipped: 0,
+                    }),
+                },
+                Synced {
+                    category: SyncCategory::HeartRate,
+                    counts: None,
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn ring_synced() {
+        snapshot(
+            "ring_synced",
+            &vec![
+                RingSynced {
+                    mac: "A1:B2:C3:D4:E5:F6".to_string(),
+                    synced: vec![Synced {
+                        category: SyncCategory::Sleep,
+                        counts: Some(UpsertCounts {
+                            inserted: 2,
+                            updated: 0,
+                            skipped: 1,
+                        }),
+                    }],
+                    error: None,
+                },
+                RingSynced {
+                    mac: "A1:B2:C3:D4:E5:F7".to_string(),
+                    synced: Vec::new(),
+                    error: Some("failed to find the ring: device not found".to_string()),
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn gatt_dump() {
+        snapshot(
+            "gatt_dump",
+            &GattDump {
+                address: "A1:B2:C3:D4:E5:F6".to_string(),
+                name: Some("R02_1234".to_string()),
+                services: vec![
+                    GattService {
+                        uuid: uuid::uuid!("0000180a-0000-1000-8000-00805f9b34fb"),
+                        name: Some("Device Information"),
+                        characteristics: vec![
+                            GattCharacteristic::new(
+                                uuid::uuid!("00002a29-0000-1000-8000-00805f9b34fb"),
+                                Ok(b"Colmi".to_vec()),
+                            ),
+                            GattCharacteristic::new(
+                                uuid::uuid!("00002a50-0000-1000-8000-00805f9b34fb"),
+                                Ok(vec![0x01, 0x0a, 0x00]),
+                            ),
+                        ],
+                    },
+                    GattService {
+                        uuid: uuid::uuid!("6e40fff0-b5a3-f393-e0a9-e50e24dcca9e"),
+                        name: Some("Colmi UART Service"),
+                        characteristics: vec![
+                            GattCharacteristic::new(
+                                uuid::uuid!("6e400002-b5a3-f393-e0a9-e50e24dcca9e"),
+                                Err("Operation not permitted".to_string()),
+                            ),
+                            GattCharacteristic::new(
+                                uuid::uuid!("00001234-0000-1000-8000-00805f9b34fb"),
+                                Ok(Vec::new()),
+                            ),
+                        ],
+                    },
+                ],
+            },
+        );
+    }
+
+    #[test]
+    fn exported() {
+        snapshot(
+            "exported",
+            &Exported {
+                path: "export.xml".to_string(),
+                records: 12,
+            },
+        );
+    }
+
+    #[test]
+    fn influx_written() {
+        snapshot(
+            "influx_written",
+            &InfluxWritten {
+                url: "http://localhost:8086".to_string(),
+                bucket: "rings".to_string(),
+                points: 7500,
+                batches: 2,
+            },
+        );
+    }
+
+    #[test]
+    fn pushed() {
+        snapshot(
+            "pushed",
+            &Pushed {
+                url: "https://rings.example.com".to_string(),
+                mac: "A1:B2:C3:D4:E5:F6".to_string(),
+                sent: 3,
+                accepted: 2,
+                failed: vec![PushFailure {
+                    index: 2,
+                    error: "in the future".to_string(),
+                }],
+            },
+        );
+    }
+
+    #[test]
+    fn pruned() {
+        snapshot(
+            "pruned",
+            &Pruned {
+                removed: 42,
+                cutoff: datetime!(2024-08-22 12:00 UTC),
+                compacted: Some(Compacted {
+                    path: "rings.db".to_string(),
+                    before: 4096,
+                    after: 1024,
+                }),
+            },
+        );
+    }
+
+    #[test]
+    fn replayed() {
+        snapshot(
+            "replayed",
+            &vec![
+                Replayed::Sent(Command::BatteryInfo),
+                Replayed::Received(CommandReply::BatteryInfo {
+                    level: 80,
+                    charging: false,
+                }),
+                Replayed::ReceiveError("invalid checksum".to_string()),
+            ],
+        );
+    }
+
+    #[test]
+    fn time_set() {
+        snapshot(
+            "time_set",
+            &TimeSet {
+                sent: datetime!(2024-11-20 8:30 -6),
+                ring_time: Some(datetime!(2024-11-20 8:30:02 -6)),
+            },
+        );
+    }
+
+    #[test]
+    fn real_time_samples() {
+        let samples = vec![
+            RealTimeSample {
+                time: datetime!(2024-11-20 13:05:01 UTC),
+                event: RealTimeEvent::HeartRate(72),
+            },
+            RealTimeSample {
+                time: datetime!(2024-11-20 13:05:03 UTC),
+                event: RealTimeEvent::Oxygen(98),
+            },
+        ];
+        insta::assert_snapshot!(render(&samples, Format::Text).unwrap());
+        let lines: Vec<_> = samples
+            .iter()
+            .map(|sample| serde_json::to_string(sample).unwrap())
+            .collect();
+        insta::assert_snapshot!(lines.join("\n"));
+    }
+
+    #[test]
+    fn notices() {
+        let time = datetime!(2024-11-20 13:05:01 UTC);
+        let notices = [
+            Notification::NewData(DataName::HeartRate),
+            Notification::NewData(DataName::Oxygen),
+            Notification::NewData(DataName::Steps),
+            Notification::Battery(47),
+            Notification::Activity(cole_mine::incoming_messages::notification::LiveActivity {
+                steps: 1_234,
+                calories: 56.7,
+                distance: 890,
+            }),
+        ]
+        .map(|notification| Noticed { time, notification });
+        let text: String = notices
+            .iter()
+            .map(|noticed| noticed_text(noticed, UnitSystem::Metric).unwrap())
+            .collect();
+        insta::assert_snapshot!(text);
+        let lines: Vec<_> = notices
+            .iter()
+            .map(|noticed| serde_json::to_string(noticed).unwrap())
+            .collect();
+        insta::assert_snapshot!(lines.join("\n"));
+    }
+
+    #[test]
+    fn acknowledged_prints_no_text() {
+        let ack = Acknowledged { acknowledged: true };
+        assert_eq!(render(&ack, Format::Text).unwrap(), "");
+        assert_eq!(
+            render(&ack, Format::Json).unwrap(),
+            "{\n  \"acknowledged\": true\n}\n"
+        );
+    }
+
+    #[test]
+    fn error_object() {
+        let e = exit::Error::Connect(cole_mine::Error::Timeout);
+        assert_eq!(
+            error_json(&e, exit::code(&e)),
+            r#"{"code":4,"error":"failed to connect to the ring: Timed out"}"#
+        );
+    }
+}
@@ -0,0 +1,501 @@
+//! Uploading events to a conveyor server
+//!
+//! Events are sent to the newline delimited `POST /api/events/:mac/import`
+//! endpoint, falling back to `POST /api/events/:mac?register=true` in chunks
+//! for servers that don't have it
+
+use std::fmt;
+use std::time::Duration;
+
+use fissure::RingEvent;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::Deserialize;
+
+use crate::output::{PushFailure, Pushed};
+
+/// How many times a request is retried when the server responds with a 5xx or
+/// can't be reached
+const RETRIES: u32 = 3;
+/// The wait before the first retry, doubled after each one
+const BACKOFF: Duration = Duration::from_millis(500);
+/// Events per request when posting to the non-import endpoint
+const CHUNK_SIZE: usize = 500;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The request couldn't be sent or its response read
+    Http(reqwest::Error),
+    /// The server responded with an error status
+    Status {
+        url: String,
+        status: StatusCode,
+        message: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Http(_) => write!(f, "failed to push events"),
+            Self::Status {
+                url,
+                status,
+                message,
+            } => write!(f, "{url} responded {status}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Http(e) => Some(e),
+            Self::Status { .. } => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(value: reqwest::Error) -> Self {
+        Self::Http(value)
+    }
+}
+
+/// The parts of conveyor's `ImportSummary` needed to count what was accepted
+#[derive(Debug, Deserialize)]
+struct ImportSummary {
+    inserted: usize,
+    updated: usize,
+    failed: Vec<ImportFailure>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportFailure {
+    /// Starting from 1
+    line: usize,
+    error: String,
+}
+
+/// The parts of conveyor's `ApiError` needed to report why a request failed
+#[derive(Debug, Deserialize)]
+struct ApiError {
+    error: String,
+    #[serde(default)]
+    invalid: Vec<InvalidEvent>,
+}
+
+#[derive(Debug, Deserialize)]
+struct InvalidEvent {
+    index: usize,
+    error: String,
+}
+
+pub struct Pusher {
+    client: reqwest::Client,
+    base_url: String,
+    token: Option<String>,
+    backoff: Duration,
+}
+
+impl Pusher {
+    /// Push to the server at `base_url`, sending `token` as a bearer token if
+    /// there is one
+    pub fn new(base_url: &str, token: Option<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token,
+            backoff: BACKOFF,
+        }
+    }
+
+    /// Upload `events` for the ring with `mac`, the ring is registered with
+    /// the server if it doesn't know about it
+    pub async fn push(&self, mac: &str, events: &[RingEvent]) -> Result<Pushed, Error> {
+        let mut ret = Pushed {
+            url: self.base_url.clone(),
+            mac: mac.to_string(),
+            sent: events.len(),
+            accepted: 0,
+            failed: Vec::new(),
+        };
+        if events.is_empty() {
+            return Ok(ret);
+        }
+        if let Some(summary) = self.import(mac, events).await? {
+            ret.accepted = summary.inserted + summary.updated;
+            ret.failed = summary
+                .failed
+                .into_iter()
+                .map(|failure| PushFailure {
+                    index: failure.line.saturating_sub(1),
+                    error: failure.error,
+                })
+                .collect();
+            return Ok(ret);
+        }
+        log::info!("{} can't import, posting events instead", self.base_url);
+        for (i, chunk) in events.chunks(CHUNK_SIZE).enumerate() {
+            self.add_events(mac, chunk, i * CHUNK_SIZE, &mut ret)
+                .await?;
+        }
+        Ok(ret)
+    }
+
+    /// Send `events` as newline delimited JSON, `None` if the server doesn't
+    /// have the endpoint or doesn't know the ring yet
+    async fn import(
+        &self,
+        mac: &str,
+        events: &[RingEvent],
+    ) -> Result<Option<ImportSummary>, Error> {
+        let url = format!("{}/api/events/{mac}/import", self.base_url);
+        let mut body = String::new();
+        for event in events {
+            // RingEvent always serializes
+            body.push_str(&serde_json::to_string(event).unwrap_or_default());
+            body.push('\n');
+        }
+        let response = self
+            .send(|| self.client.post(&url).body(body.clone()))
+            .await?;
+        match response.status() {
+            StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED => Ok(None),
+            status if status.is_success() => Ok(Some(response.json().await?)),
+            _ => Err(status_error(&url, response).await),
+        }
+    }
+
+    /// Send a chunk of events as a JSON array, `offset` is the position of the
+    /// chunk's first event in everything being pushed
+    ///
+    /// The server rejects the whole chunk if any event is invalid, so the
+    /// invalid events are recorded as failed and the rest are sent again
+    async fn add_events(
+        &self,
+        mac: &str,
+        chunk: &[RingEvent],
+        offset: usize,
+        pushed: &mut Pushed,
+    ) -> Result<(), Error> {
+        let url = format!("{}/api/events/{mac}?register=true", self.base_url);
+        let response = self.send(|| self.client.post(&url).json(chunk)).await?;
+        if response.status().is_success() {
+            pushed.accepted += chunk.len();
+            return Ok(());
+        }
+        if response.status() != StatusCode::UNPROCESSABLE_ENTITY {
+            return Err(status_error(&url, response).await);
+        }
+        let error: ApiError = response.json().await?;
+        let mut valid = Vec::with_capacity(chunk.len());
+        for (index, event) in chunk.iter().enumerate() {
+            match error.invalid.iter().find(|invalid| invalid.index == index) {
+                Some(invalid) => pushed.failed.push(PushFailure {
+                    index: offset + index,
+                    error: invalid.error.clone(),
+                }),
+                None => valid.push(event),
+            }
+        }
+        if valid.is_empty() {
+            return Ok(());
+        }
+        let response = self.send(|| self.client.post(&url).json(&valid)).await?;
+        if !response.status().is_success() {
+            return Err(status_error(&url, response).await);
+        }
+        pushed.accepted += valid.len();
+        Ok(())
+    }
+
+    /// Send the request `build` makes, retrying with backoff while the server
+    /// responds with a 5xx or can't be reached
+    async fn send(&self, build: impl Fn() -> RequestBuilder) -> Result<Response, Error> {
+        let mut backoff = self.backoff;
+        let mut attempt = 0;
+        loop {
+            let mut request = build();
+            if let Some(token) = &self.token {
+                request = request.bearer_auth(token);
+            }
+            let ret = request.send().await;
+            let transient = match &ret {
+                Ok(response) => response.status().is_server_error(),
+                Err(e) => e.is_connect() || e.is_timeout(),
+            };
+            if !transient || attempt == RETRIES {
+                return Ok(ret?);
+            }
+            attempt += 1;
+            match &ret {
+                Ok(response) => log::warn!("server responded {}, retrying", response.status()),
+                Err(e) => log::warn!("failed to reach the server, retrying: {e}"),
+            }
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+}
+
+/// Describe an error response, using the `error` from the body if the server
+/// sent an `ApiError`
+async fn status_error(url: &str, response: Response) -> Error {
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    let message = serde_json::from_str::<ApiError>(&body)
+        .map(|e| e.error)
+        .unwrap_or(body);
+    Error::Status {
+        url: url.to_string(),
+        status,
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use axum::{
+        extract::{OriginalUri, State},
+        http::HeaderMap,
+        routing::post,
+        Json, Router,
+    };
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    const MAC: &str = "A1:B2:C3:D4:E5:F6";
+
+    /// A request the test server received
+    #[derive(Debug, Clone)]
+    struct Received {
+        uri: String,
+        authorization: Option<String>,
+        body: String,
+    }
+
+    /// Every request received and the responses to give, the last response is
+    /// repeated once the others are used
+    #[derive(Clone)]
+    struct Server {
+        received: Arc<Mutex<Vec<Received>>>,
+        responses: Arc<Mutex<Vec<(u16, Value)>>>,
+    }
+
+    impl Server {
+        fn new(responses: Vec<(u16, Value)>) -> Self {
+            Self {
+                received: Arc::default(),
+                responses: Arc::new(Mutex::new(responses)),
+            }
+        }
+
+        fn received(&self) -> Vec<Received> {
+            self.received.lock().unwrap().clone()
+        }
+    }
+
+    async fn record(
+        server: State<Server>,
+        uri: OriginalUri,
+        headers: HeaderMap,
+        body: String,
+    ) -> (axum::http::StatusCode, Json<Value>) {
+        server.received.lock().unwrap().push(Received {
+            uri: uri.0.to_string(),
+            authorization: headers
+                .get("authorization")
+                .and_then(|value| value.to_str().ok())
+                .map(ToString::to_string),
+            body,
+        });
+        let mut responses = server.responses.lock().unwrap();
+        let (status, body) = if responses.len() > 1 {
+            responses.remove(0)
+        } else {
+            responses[0].clone()
+        };
+        (
+            axum::http::StatusCode::from_u16(status).unwrap(),
+            Json(body),
+        )
+    }
+
+    /// Serve `router` on a random local port, returning its url
+    async fn serve(router: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        format!("http://{addr}/")
+    }
+
+    async fn import_server(server: &Server) -> String {
+        serve(
+            Router::new()
+                .route("/api/events/:mac/import", post(record))
+                .with_state(server.clone()),
+        )
+        .await
+    }
+
+    async fn add_server(server: &Server) -> String {
+        serve(
+            Router::new()
+                .route("/api/events/:mac", post(record))
+                .with_state(server.clone()),
+        )
+        .await
+    }
+
+    fn pusher(url: &str) -> Pusher {
+        let mut ret = Pusher::new(url, Some("secret".to_string()));
+        ret.backoff = Duration::from_millis(1);
+        ret
+    }
+
+    fn events() -> Vec<RingEvent> {
+        serde_json::from_value(json!([
+            { "mac": MAC, "when": "2024-11-19T08:00:00Z", "value": { "type": "HeartRate", "data": 60 } },
+            { "mac": MAC, "when": "2024-11-19T08:05:00Z", "value": { "type": "HeartRate", "data": 61 } },
+            { "mac": MAC, "when": "2024-11-19T08:10:00Z", "value": { "type": "HeartRate", "data": 62 } },
+        ]))
+        .unwrap()
+    }
+
+    fn parse_lines(body: &str) -> Vec<RingEvent> {
+        body.lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn import() {
+        let server = Server::new(vec![(
+            200,
+            json!({
+                "inserted": 1,
+                "updated": 1,
+                "failed": [{ "line": 3, "error": "too old" }],
+            }),
+        )]);
+        let url = import_server(&server).await;
+        let pushed = pusher(&url).push(MAC, &events()).await.unwrap();
+        assert_eq!(pushed.sent, 3);
+        assert_eq!(pushed.accepted, 2);
+        assert_eq!(
+            pushed.failed,
+            [PushFailure {
+                index: 2,
+                error: "too old".to_string(),
+            }]
+        );
+        let received = server.received();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].uri, format!("/api/events/{MAC}/import"));
+        assert_eq!(received[0].authorization.as_deref(), Some("Bearer secret"));
+        assert_eq!(parse_lines(&received[0].body), events());
+    }
+
+    #[tokio::test]
+    async fn falls_back_without_import() {
+        let server = Server::new(vec![(200, json!({}))]);
+        let url = add_server(&server).await;
+        let pushed = pusher(&url).push(MAC, &events()).await.unwrap();
+        assert_eq!(pushed.accepted, 3);
+        assert!(pushed.failed.is_empty());
+        let received = server.received();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].uri, format!("/api/events/{MAC}?register=true"));
+        assert_eq!(received[0].authorization.as_deref(), Some("Bearer secret"));
+        let sent: Vec<RingEvent> = serde_json::from_str(&received[0].body).unwrap();
+        assert_eq!(sent, events());
+    }
+
+    #[tokio::test]
+    async fn fallback_resends_valid_events() {
+        let server = Server::new(vec![
+            (
+                422,
+                json!({
+                    "error": "1 invalid events",
+                    "context": "add_events",
+                    "code": "unprocessable_entity",
+                    "invalid": [{ "index": 1, "error": "in the future" }],
+                }),
+            ),
+            (200, json!({})),
+        ]);
+        let url = add_server(&server).await;
+        let pushed = pusher(&url).push(MAC, &events()).await.unwrap();
+        assert_eq!(pushed.accepted, 2);
+        assert_eq!(
+            pushed.failed,
+            [PushFailure {
+                index: 1,
+                error: "in the future".to_string(),
+            }]
+        );
+        let received = server.received();
+        assert_eq!(received.len(), 2);
+        let resent: Vec<RingEvent> = serde_json::from_str(&received[1].body).unwrap();
+        let mut expected = events();
+        expected.remove(1);
+        assert_eq!(resent, expected);
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors() {
+        let server = Server::new(vec![
+            (503, json!({})),
+            (502, json!({})),
+            (200, json!({ "inserted": 3, "updated": 0, "failed": [] })),
+        ]);
+        let url = import_server(&server).await;
+        let pushed = pusher(&url).push(MAC, &events()).await.unwrap();
+        assert_eq!(pushed.accepted, 3);
+        assert_eq!(server.received().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_retries() {
+        let server = Server::new(vec![(
+            500,
+            json!({ "error": "database is locked", "context": "import_events", "code": "internal" }),
+        )]);
+        let url = import_server(&server).await;
+        let e = pusher(&url).push(MAC, &events()).await.unwrap_err();
+        assert!(
+            matches!(&e, Error::Status { status, message, .. }
+                if *status == StatusCode::INTERNAL_SERVER_ERROR && message == "database is locked"),
+            "{e:?}"
+        );
+        assert_eq!(server.received().len(), 1 + RETRIES as usize);
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_not_retried() {
+        let server = Server::new(vec![(
+            401,
+            json!({ "error": "missing or invalid bearer token", "context": "authorization", "code": "unauthorized" }),
+        )]);
+        let url = import_server(&server).await;
+        let e = pusher(&url).push(MAC, &events()).await.unwrap_err();
+        assert!(
+            matches!(&e, Error::Status { status, .. } if *status == StatusCode::UNAUTHORIZED),
+            "{e:?}"
+        );
+        assert_eq!(server.received().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn nothing_to_push() {
+        let server = Server::new(vec![(500, json!({}))]);
+        let url = import_server(&server).await;
+        let pushed = pusher(&url).push(MAC, &[]).await.unwrap();
+        assert_eq!(pushed.sent, 0);
+        assert!(server.received().is_empty());
+    }
+}
@@ -0,0 +1,64 @@
+//! Reacting to the notifications a ring sends on its own for `lode watch`
+//!
+//! Every notification is printed, a new data notification can also start a
+//! sync of the data it announces
+
+use cole_mine::incoming_messages::notification::{DataName, Notification};
+use fissure::SyncCategory;
+
+/// What `lode watch` does about a notification after printing it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// The notification carries everything there is to know
+    Nothing,
+    /// Read today's data for the category the notification says has more
+    Sync(SyncCategory),
+}
+
+/// The action for `notification`, only new data is synced and only when
+/// `auto_sync` is set
+pub fn action(notification: &Notification, auto_sync: bool) -> Action {
+    match notification {
+        Notification::NewData(name) if auto_sync => Action::Sync(match name {
+            DataName::HeartRate => SyncCategory::HeartRate,
+            DataName::Oxygen => SyncCategory::Oxygen,
+            DataName::Steps => SyncCategory::Activity,
+        }),
+        Notification::NewData(_) | Notification::Activity(_) | Notification::Battery(_) => {
+            Action::Nothing
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cole_mine::incoming_messages::notification::LiveActivity;
+
+    use super::*;
+
+    #[test]
+    fn new_data_is_synced() {
+        for (name, category) in [
+            (DataName::HeartRate, SyncCategory::HeartRate),
+            (DataName::Oxygen, SyncCategory::Oxygen),
+            (DataName::Steps, SyncCategory::Activity),
+        ] {
+            let notification = Notification::NewData(name);
+            assert_eq!(action(&notification, true), Action::Sync(category));
+            assert_eq!(action(&notification, false), Action::Nothing);
+        }
+    }
+
+    #[test]
+    fn others_are_only_printed() {
+        let activity = Notification::Activity(LiveActivity {
+            steps: 1_200,
+            calories: 45.5,
+            distance: 900,
+        });
+        for notification in [activity, Notification::Battery(47)] {
+            assert_eq!(action(&notification, true), Action::Nothing);
+            assert_eq!(action(&notification, false), Action::Nothing);
+        }
+    }
+}
@@ -1,9 +1,15 @@
-use cole_mine::discover;
+use std::time::Duration;
+
+use cole_mine::{discover_with, ScanOptions};
 use futures::StreamExt;
 
 #[tokio::main]
 async fn main() {
-    let mut stream = discover(true, false).await.unwrap();
+    let options = ScanOptions::builder()
+        .timeout(Duration::from_secs(15))
+        .name_prefixes(Vec::new())
+        .build();
+    let mut stream = discover_with(options).await.unwrap();
     while let Some(dev) = stream.next().await {
         println!(
             "{}: {}",
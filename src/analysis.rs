@@ -0,0 +1,742 @@
+//! Metrics derived from the heart rates, sleep and activity a ring recorded
+
+use std::{ops::Range, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use time::{macros::time, Date, OffsetDateTime, PrimitiveDateTime, Time};
+
+use crate::{
+    big_data::{SleepSession, SleepStage},
+    util::DurationExt as _,
+};
+
+/// How many heart rate zones time is counted in
+pub const ZONE_COUNT: usize = 5;
+
+/// What the heart rate metrics depend on besides the rates themselves
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HrConfig {
+    /// The highest rate the wearer's heart reaches, the zones are fractions of
+    /// it so time in each is only counted when it's known
+    pub max_hr: Option<u8>,
+    /// How far apart the samples are, two further apart have a gap between them
+    pub interval: Duration,
+    /// How long a rate has to be held to count as resting
+    pub resting_window: Duration,
+    /// When resting rates are looked for without a sleep session, in the
+    /// ring's local time
+    pub early_morning: Range<Time>,
+}
+
+impl Default for HrConfig {
+    fn default() -> Self {
+        Self {
+            max_hr: None,
+            interval: Duration::minutes(5),
+            resting_window: Duration::minutes(10),
+            early_morning: time!(0:00)..time!(7:00),
+        }
+    }
+}
+
+/// Heart rate metrics for a day, values are `None` when there were no readings
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HrDailyStats {
+    /// How many samples had a reading
+    pub readings: usize,
+    pub min: Option<u8>,
+    pub max: Option<u8>,
+    pub avg: Option<f32>,
+    /// See `resting_hr`, only early morning rates are considered
+    pub resting: Option<u8>,
+    /// Minutes in each zone, the first from 50% of the max rate up to 60% and
+    /// the last from 90% up. `None` without a max rate
+    pub zone_minutes: Option<[u32; ZONE_COUNT]>,
+}
+
+/// Summarize a day of `samples`, each a time in the ring's offset and the rate
+/// taken then with 0 meaning there was no reading
+pub fn hr_daily_stats(samples: &[(OffsetDateTime, u8)], config: &HrConfig) -> HrDailyStats {
+    let readings: Vec<u8> = samples
+        .iter()
+        .map(|(_, rate)| *rate)
+        .filter(|rate| *rate != 0)
+        .collect();
+    let total: u32 = readings.iter().copied().map(u32::from).sum();
+    let zone_minutes = config.max_hr.filter(|max| *max > 0).map(|max| {
+        let mut seconds = [0u64; ZONE_COUNT];
+        for rate in &readings {
+            if let Some(zone) = zone(*rate, max) {
+                seconds[zone] += config.interval.as_secs();
+            }
+        }
+        seconds.map(|s| (s / 60) as u32)
+    });
+    HrDailyStats {
+        readings: readings.len(),
+        min: readings.iter().min().copied(),
+        max: readings.iter().max().copied(),
+        avg: (!readings.is_empty()).then(|| total as f32 / readings.len() as f32),
+        resting: resting_hr(samples, config, None),
+        zone_minutes,
+    }
+}
+
+/// The lowest average rate held for `config.resting_window` while asleep in
+/// `sleep`, or in the early morning without one
+///
+/// The window is a run of readings without a gap, so with readings 5 minutes
+/// apart a 10 minute window is two readings. `None` when no run is long enough
+pub fn resting_hr(
+    samples: &[(OffsetDateTime, u8)],
+    config: &HrConfig,
+    sleep: Option<&SleepSession>,
+) -> Option<u8> {
+    // sessions are in the ring's local time like the samples' wall time
+    let considered = |when: OffsetDateTime| match sleep {
+        Some(session) => {
+            let wall = PrimitiveDateTime::new(when.date(), when.time());
+            session.start <= wall && wall < session.end
+        }
+        None => config.early_morning.contains(&when.time()),
+    };
+    let mut readings: Vec<_> = samples
+        .iter()
+        .copied()
+        .filter(|(when, rate)| *rate != 0 && considered(*when))
+        .collect();
+    readings.sort_by_key(|(when, _)| *when);
+    let interval = config.interval.as_secs().max(1);
+    let window = config.resting_window.as_secs().div_ceil(interval).max(1) as usize;
+    readings
+        .chunk_by(|a, b| b.0 - a.0 <= config.interval)
+        .flat_map(|run| run.windows(window))
+        .map(|window| {
+            let total: u32 = window.iter().map(|(_, rate)| u32::from(*rate)).sum();
+            total as f32 / window.len() as f32
+        })
+        .min_by(f32::total_cmp)
+        .map(|avg| avg.round() as u8)
+}
+
+/// Which zone `rate` falls in, `None` below half of `max_hr`
+fn zone(rate: u8, max_hr: u8) -> Option<usize> {
+    let percent = u32::from(rate) * 100 / u32::from(max_hr);
+    if percent < 50 {
+        return None;
+    }
+    Some((((percent - 50) / 10) as usize).min(ZONE_COUNT - 1))
+}
+
+/// How each part of a sleep score is weighed, the weights of the parts that
+/// add points make up the whole score
+#[derive(Debug, Clone, PartialEq)]
+pub struct SleepScoreConfig {
+    /// How long to be asleep for the full duration points
+    pub target: Duration,
+    /// Sessions shorter than this are naps, they're scored but flagged as
+    /// not comparable to a night
+    pub min_night: Duration,
+    pub duration_weight: f32,
+    pub deep_weight: f32,
+    /// The percent of time asleep in deep sleep for the full deep points
+    pub deep_target: f32,
+    pub rem_weight: f32,
+    /// The percent of time asleep in REM for the full REM points
+    pub rem_target: f32,
+    /// Points lost for each minute awake
+    pub awake_penalty: f32,
+    /// Points lost each time sleep was broken by being awake
+    pub awakening_penalty: f32,
+}
+
+impl Default for SleepScoreConfig {
+    fn default() -> Self {
+        Self {
+            target: Duration::hours(8),
+            min_night: Duration::hours(3),
+            duration_weight: 50.0,
+            deep_weight: 25.0,
+            deep_target: 20.0,
+            rem_weight: 25.0,
+            rem_target: 25.0,
+            awake_penalty: 0.25,
+            awakening_penalty: 2.0,
+        }
+    }
+}
+
+/// A 0 to 100 score for a sleep session and the parts it was added up from
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SleepScore {
+    pub score: u8,
+    /// The session was shorter than `SleepScoreConfig::min_night`
+    pub nap: bool,
+    pub asleep_minutes: u32,
+    pub duration_points: f32,
+    /// The percent of time asleep in deep sleep
+    pub deep_percent: f32,
+    pub deep_points: f32,
+    /// The percent of time asleep in REM
+    pub rem_percent: f32,
+    pub rem_points: f32,
+    pub awake_minutes: u32,
+    /// Points lost to `awake_minutes`
+    pub awake_penalty: f32,
+    /// How many times sleep was broken by being awake, waking up at the end
+    /// doesn't count
+    pub awakenings: u32,
+    /// Points lost to `awakenings`
+    pub fragmentation_penalty: f32,
+}
+
+/// Score `session` from how long it was and how it was spent, each part gets
+/// its full weight once its target is reached
+pub fn sleep_score(session: &SleepSession, config: &SleepScoreConfig) -> SleepScore {
+    let totals = session.totals();
+    let asleep = totals.asleep();
+    let percent_of_asleep = |minutes: u32| {
+        if asleep == 0 {
+            return 0.0;
+        }
+        minutes as f32 * 100.0 / asleep as f32
+    };
+    let points = |value: f32, target: f32, weight: f32| {
+        if target <= 0.0 {
+            return weight;
+        }
+        (value / target).min(1.0) * weight
+    };
+    let target_minutes = config.target.as_secs() as f32 / 60.0;
+    let deep_percent = percent_of_asleep(totals.deep);
+    let rem_percent = percent_of_asleep(totals.rem);
+    let awakenings = awakenings(&session.stages);
+    let mut ret = SleepScore {
+        score: 0,
+        nap: session.end - session.start < config.min_night,
+        asleep_minutes: asleep,
+        duration_points: points(asleep as f32, target_minutes, config.duration_weight),
+        deep_percent,
+        deep_points: points(deep_percent, config.deep_target, config.deep_weight),
+        rem_percent,
+        rem_points: points(rem_percent, config.rem_target, config.rem_weight),
+        awake_minutes: totals.awake,
+        awake_penalty: totals.awake as f32 * config.awake_penalty,
+        awakenings,
+        fragmentation_penalty: awakenings as f32 * config.awakening_penalty,
+    };
+    let score = ret.duration_points + ret.deep_points + ret.rem_points
+        - ret.awake_penalty
+        - ret.fragmentation_penalty;
+    ret.score = score.round().clamp(0.0, 100.0) as u8;
+    ret
+}
+
+/// How many times a run of awake stages has sleep on both sides
+fn awakenings(stages: &[SleepStage]) -> u32 {
+    let mut ret = 0;
+    let mut slept = false;
+    let mut woke = false;
+    for stage in stages {
+        match stage {
+            SleepStage::Awake(_) => woke = slept,
+            _ => {
+                if woke {
+                    ret += 1;
+                    woke = false;
+                }
+                slept = true;
+            }
+        }
+    }
+    ret
+}
+
+/// A day's steps compared to the step goal
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GoalDay {
+    pub date: Date,
+    pub steps: u32,
+    /// The percent of the goal walked, past 100 when it was beaten
+    pub percent: f32,
+    pub met: bool,
+    /// The day is today so more steps may still come, it doesn't count
+    /// towards a streak
+    pub partial: bool,
+}
+
+/// How a run of days did against a step goal
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GoalReport {
+    pub goal: u32,
+    /// Each day with steps, oldest first
+    pub days: Vec<GoalDay>,
+    /// Days in a row the goal was met up until yesterday, 0 if it wasn't met
+    /// yesterday
+    pub current_streak: u32,
+    /// The most days in a row the goal was met
+    pub best_streak: u32,
+}
+
+/// Compare each day's steps in `days` to `goal`, a goal of 0 is met every day
+///
+/// A day missing from `days` breaks a streak, `today` is reported but left
+/// out of the streaks since it isn't over. Days after `today` are ignored
+pub fn goal_progress(goal: u32, days: &[(Date, u32)], today: Date) -> GoalReport {
+    let mut days: Vec<_> = days.iter().filter(|(date, _)| *date <= today).collect();
+    days.sort_by_key(|(date, _)| *date);
+    let mut ret = GoalReport {
+        goal,
+        ..Default::default()
+    };
+    let mut streak = 0;
+    let mut last_met: Option<Date> = None;
+    for &&(date, steps) in &days {
+        let percent = if goal == 0 {
+            100.0
+        } else {
+            steps as f32 * 100.0 / goal as f32
+        };
+        let met = steps >= goal;
+        let partial = date == today;
+        if met && !partial {
+            let continued = last_met.and_then(Date::next_day) == Some(date);
+            streak = if continued { streak + 1 } else { 1 };
+            last_met = Some(date);
+            ret.best_streak = ret.best_streak.max(streak);
+        }
+        ret.days.push(GoalDay {
+            date,
+            steps,
+            percent,
+            met,
+            partial,
+        });
+    }
+    if last_met.is_some() && last_met == today.previous_day() {
+        ret.current_streak = streak;
+    }
+    ret
+}
+
+/// A battery level read from the ring
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatterySample {
+    pub when: OffsetDateTime,
+    /// Percent charged
+    pub level: u8,
+    pub charging: bool,
+}
+
+/// Estimate the hours until the battery is empty from the last discharge in
+/// `history`
+///
+/// The discharge is the samples since the ring was last charging or its level
+/// went up, a least squares line through them gives how fast it drains.
+/// `None` while charging or when the discharge has fewer than two samples or
+/// isn't draining
+pub fn estimate_hours_remaining(history: &[BatterySample]) -> Option<f32> {
+    let mut history = history.to_vec();
+    history.sort_by_key(|sample| sample.when);
+    if history.last()?.charging {
+        return None;
+    }
+    let mut start = history.len() - 1;
+    while start > 0 {
+        let (prev, next) = (&history[start - 1], &history[start]);
+        if prev.charging || prev.level < next.level {
+            break;
+        }
+        start -= 1;
+    }
+    let discharge = &history[start..];
+    if discharge.len() < 2 {
+        return None;
+    }
+    let first = discharge[0].when;
+    let points: Vec<(f64, f64)> = discharge
+        .iter()
+        .map(|sample| {
+            let hours = (sample.when - first).as_seconds_f64() / 3600.0;
+            (hours, f64::from(sample.level))
+        })
+        .collect();
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let sxx: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    let sxy: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    if sxx == 0.0 {
+        return None;
+    }
+    let slope = sxy / sxx;
+    if slope >= 0.0 {
+        return None;
+    }
+    // the level the line puts the latest sample at, not the noisy reading
+    let (last_x, _) = points[points.len() - 1];
+    let level = mean_y + slope * (last_x - mean_x);
+    Some((level.max(0.0) / -slope) as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    /// `rate(i)` for each 5 minute sample of 2024-11-27
+    fn day(rate: impl Fn(usize) -> u8) -> Vec<(OffsetDateTime, u8)> {
+        let start = datetime!(2024-11-27 0:00 UTC);
+        (0..288)
+            .map(|i| (start + Duration::minutes(i as u64 * 5), rate(i)))
+            .collect()
+    }
+
+    /// 0:00 through 6:55
+    const NIGHT: usize = 84;
+
+    #[test]
+    fn flat_night() {
+        let samples: Vec<_> = day(|_| 60).into_iter().take(NIGHT).collect();
+        let stats = hr_daily_stats(&samples, &HrConfig::default());
+        assert_eq!(
+            stats,
+            HrDailyStats {
+                readings: NIGHT,
+                min: Some(60),
+                max: Some(60),
+                avg: Some(60.0),
+                resting: Some(60),
+                zone_minutes: None,
+            }
+        );
+        let config = HrConfig {
+            max_hr: Some(200),
+            ..Default::default()
+        };
+        // 30% of the max is below every zone
+        assert_eq!(
+            hr_daily_stats(&samples, &config).zone_minutes,
+            Some([0; ZONE_COUNT])
+        );
+    }
+
+    #[test]
+    fn noisy_day() {
+        let samples = day(|i| match i {
+            200 => 185,
+            i if i < NIGHT => [55, 53, 57, 51, 59][i % 5],
+            i => 100 + (i % 7) as u8 * 10,
+        });
+        let config = HrConfig {
+            max_hr: Some(190),
+            ..Default::default()
+        };
+        let stats = hr_daily_stats(&samples, &config);
+        assert_eq!(stats.readings, 288);
+        assert_eq!(stats.min, Some(51));
+        assert_eq!(stats.max, Some(185));
+        assert!((stats.avg.unwrap() - 31151.0 / 288.0).abs() < 0.001);
+        // a single 51 isn't held, 55 and 53 or 57 and 51 average the lowest
+        assert_eq!(stats.resting, Some(54));
+        // 100 and 110, 120 and 130, 140 and 150, 160, then the one 185
+        assert_eq!(stats.zone_minutes, Some([295, 290, 285, 145, 5]));
+    }
+
+    #[test]
+    fn missing_morning() {
+        let samples = day(|i| match i {
+            i if i < 108 => 0,
+            156..=167 => 62,
+            _ => 70,
+        });
+        let stats = hr_daily_stats(&samples, &HrConfig::default());
+        assert_eq!(stats.readings, 180);
+        assert_eq!(stats.min, Some(62));
+        assert_eq!(stats.resting, None);
+        // a nap from 13:00 to 14:00 has the readings the morning was missing
+        let nap = SleepSession {
+            start: datetime!(2024-11-27 13:00),
+            end: datetime!(2024-11-27 14:00),
+            stages: Vec::new(),
+        };
+        assert_eq!(
+            resting_hr(&samples, &HrConfig::default(), Some(&nap)),
+            Some(62)
+        );
+        let night = SleepSession {
+            start: datetime!(2024-11-26 22:00),
+            end: datetime!(2024-11-27 6:30),
+            stages: Vec::new(),
+        };
+        assert_eq!(
+            resting_hr(&samples, &HrConfig::default(), Some(&night)),
+            None
+        );
+    }
+
+    #[test]
+    fn windows_stop_at_gaps() {
+        let samples = [
+            (datetime!(2024-11-27 1:00 UTC), 50),
+            (datetime!(2024-11-27 1:10 UTC), 52),
+            (datetime!(2024-11-27 1:15 UTC), 68),
+            (datetime!(2024-11-27 1:20 UTC), 0),
+            (datetime!(2024-11-27 1:25 UTC), 50),
+        ];
+        let stats = hr_daily_stats(&samples, &HrConfig::default());
+        assert_eq!(stats.readings, 4);
+        assert_eq!(stats.resting, Some(60));
+    }
+
+    #[test]
+    fn no_readings() {
+        let samples = day(|_| 0);
+        assert_eq!(
+            hr_daily_stats(&samples, &HrConfig::default()),
+            HrDailyStats::default()
+        );
+        assert_eq!(hr_daily_stats(&[], &HrConfig::default()).avg, None);
+    }
+
+    /// A session starting at 23:00 with `stages` back to back
+    fn night(stages: Vec<SleepStage>) -> SleepSession {
+        let start = datetime!(2024-11-26 23:00);
+        let minutes: u64 = stages.iter().map(|stage| u64::from(stage.minutes())).sum();
+        SleepSession {
+            start,
+            end: start + Duration::minutes(minutes),
+            stages,
+        }
+    }
+
+    #[test]
+    fn textbook_night() {
+        // 8 hours asleep, 20% deep and 25% REM, after 10 minutes falling asleep
+        let session = night(vec![
+            SleepStage::Awake(10),
+            SleepStage::Light(90),
+            SleepStage::Deep(60),
+            SleepStage::Light(90),
+            SleepStage::Rem(60),
+            SleepStage::Light(84),
+            SleepStage::Deep(36),
+            SleepStage::Rem(60),
+        ]);
+        let score = sleep_score(&session, &SleepScoreConfig::default());
+        assert_eq!(score.score, 98);
+        insta::assert_debug_snapshot!(score);
+    }
+
+    #[test]
+    fn fragmented_night() {
+        let session = night(vec![
+            SleepStage::Light(60),
+            SleepStage::Awake(15),
+            SleepStage::Light(45),
+            SleepStage::Deep(30),
+            SleepStage::Awake(10),
+            SleepStage::Awake(20),
+            SleepStage::Light(60),
+            SleepStage::Rem(30),
+            SleepStage::Awake(5),
+            SleepStage::Light(75),
+            SleepStage::Awake(30),
+        ]);
+        let score = sleep_score(&session, &SleepScoreConfig::default());
+        // the two awake stages in a row are one awakening, the last isn't one
+        assert_eq!(score.awakenings, 3);
+        assert!(!score.nap);
+        insta::assert_debug_snapshot!(score);
+    }
+
+    #[test]
+    fn nap() {
+        let session = night(vec![SleepStage::Light(25), SleepStage::Light(15)]);
+        let score = sleep_score(&session, &SleepScoreConfig::default());
+        assert!(score.nap);
+        insta::assert_debug_snapshot!(score);
+        let config = SleepScoreConfig {
+            min_night: Duration::minutes(30),
+            ..Default::default()
+        };
+        assert!(!sleep_score(&session, &config).nap);
+    }
+
+    #[test]
+    fn empty_session() {
+        let score = sleep_score(&night(Vec::new()), &SleepScoreConfig::default());
+        assert_eq!(score.score, 0);
+        assert!(score.nap);
+        assert_eq!(score.deep_percent, 0.0);
+    }
+
+    fn steps(days: &[(u8, u32)]) -> Vec<(Date, u32)> {
+        days.iter()
+            .map(|(day, steps)| {
+                (
+                    Date::from_calendar_date(2024, time::Month::November, *day).unwrap(),
+                    *steps,
+                )
+            })
+            .collect()
+    }
+
+    const TODAY: Date = time::macros::date!(2024 - 11 - 20);
+
+    #[test]
+    fn streak_boundaries() {
+        // exactly the goal counts, one step short breaks the streak
+        let days = steps(&[
+            (12, 10_000),
+            (13, 12_000),
+            (14, 9_999),
+            (15, 10_000),
+            (16, 11_000),
+            (17, 10_500),
+            (18, 10_001),
+            (19, 10_000),
+            (20, 2_000),
+        ]);
+        let report = goal_progress(10_000, &days, TODAY);
+        assert_eq!(report.best_streak, 5);
+        assert_eq!(report.current_streak, 5);
+        assert_eq!(report.days.len(), 9);
+        assert!(!report.days[2].met);
+        assert_eq!(report.days[2].percent, 99.99);
+        // today is reported but unfinished, it doesn't end the streak
+        let today = report.days.last().unwrap();
+        assert_eq!(today.percent, 20.0);
+        assert!(today.partial && !today.met);
+
+        // missing yesterday ends the current streak
+        let report = goal_progress(10_000, &days[..7], TODAY);
+        assert_eq!(report.best_streak, 4);
+        assert_eq!(report.current_streak, 0);
+    }
+
+    #[test]
+    fn today_met_is_not_counted() {
+        let days = steps(&[(19, 10_000), (20, 15_000)]);
+        let report = goal_progress(10_000, &days, TODAY);
+        assert_eq!(report.current_streak, 1);
+        assert_eq!(report.best_streak, 1);
+        assert!(report.days[1].met);
+        // the days of a range ending in the past are all finished
+        let days = steps(&[(19, 10_000), (20, 15_000), (21, 20_000)]);
+        let report = goal_progress(10_000, &days, TODAY.next_day().unwrap());
+        assert_eq!(report.current_streak, 2);
+        assert_eq!(report.days.len(), 3);
+    }
+
+    #[test]
+    fn gap_day() {
+        let days = steps(&[(15, 10_000), (16, 10_000), (18, 10_000), (19, 10_000)]);
+        let report = goal_progress(10_000, &days, TODAY);
+        assert_eq!(report.best_streak, 2);
+        assert_eq!(report.current_streak, 2);
+        assert_eq!(report.days.len(), 4);
+        // given out of order
+        let mut reversed = days.clone();
+        reversed.reverse();
+        assert_eq!(goal_progress(10_000, &reversed, TODAY), report);
+    }
+
+    #[test]
+    fn no_days() {
+        assert_eq!(
+            goal_progress(10_000, &[], TODAY),
+            GoalReport {
+                goal: 10_000,
+                ..Default::default()
+            }
+        );
+        let future = steps(&[(21, 10_000)]);
+        assert!(goal_progress(10_000, &future, TODAY).days.is_empty());
+    }
+
+    /// A sample each hour from 2024-11-27 0:00 of each level and if it was
+    /// charging
+    fn battery(levels: &[(u8, bool)]) -> Vec<BatterySample> {
+        let start = datetime!(2024-11-27 0:00 UTC);
+        levels
+            .iter()
+            .zip(0..)
+            .map(|(&(level, charging), hour)| BatterySample {
+                when: start + Duration::hours(hour),
+                level,
+                charging,
+            })
+            .collect()
+    }
+
+    fn draining(levels: impl IntoIterator<Item = u8>) -> Vec<(u8, bool)> {
+        levels.into_iter().map(|level| (level, false)).collect()
+    }
+
+    fn assert_hours(history: &[BatterySample], expected: f32) {
+        let hours = estimate_hours_remaining(history).unwrap();
+        assert!((hours - expected).abs() < 0.01, "{hours} != {expected}");
+    }
+
+    #[test]
+    fn steady_discharge() {
+        // 2% an hour from 100 down to 80
+        let history = battery(&draining((0..=10).map(|i| 100 - i * 2)));
+        assert_hours(&history, 40.0);
+        // given out of order
+        let mut reversed = history.clone();
+        reversed.reverse();
+        assert_hours(&reversed, 40.0);
+    }
+
+    #[test]
+    fn noisy_discharge() {
+        // the readings wobble around 3% an hour, the fit puts the last at 70
+        let history = battery(&draining([100, 96, 95, 91, 88, 84, 82, 79, 76, 72, 70]));
+        let hours = estimate_hours_remaining(&history).unwrap();
+        assert!((22.0..25.0).contains(&hours), "{hours}");
+    }
+
+    #[test]
+    fn charge_in_the_middle() {
+        // 5% an hour, on the charger for two hours, then 2% an hour
+        let mut levels = draining([90, 85, 80, 75, 70, 65, 60]);
+        levels.extend([(75, true), (90, true)]);
+        levels.extend(draining([100, 98, 96, 94]));
+        let history = battery(&levels);
+        assert_hours(&history, 47.0);
+        // only the faster drain before charging was fit up to the charger
+        assert_hours(&history[..7], 12.0);
+        // still on the charger
+        assert_eq!(estimate_hours_remaining(&history[..9]), None);
+        // just off the charger there's nothing to fit yet
+        assert_eq!(estimate_hours_remaining(&history[..10]), None);
+    }
+
+    #[test]
+    fn charge_without_a_charging_sample() {
+        // the level going up starts a new discharge even if charging wasn't seen
+        let mut levels = draining([50, 45, 40]);
+        levels.extend(draining([100, 99, 98]));
+        assert_hours(&battery(&levels), 98.0);
+    }
+
+    #[test]
+    fn not_draining() {
+        assert_eq!(estimate_hours_remaining(&[]), None);
+        assert_eq!(estimate_hours_remaining(&battery(&[(80, false)])), None);
+        assert_eq!(
+            estimate_hours_remaining(&battery(&draining([80, 80, 80]))),
+            None
+        );
+        // two readings at the same time can't give a rate
+        let mut history = battery(&draining([80, 79]));
+        history[1].when = history[0].when;
+        assert_eq!(estimate_hours_remaining(&history), None);
+    }
+}
@@ -0,0 +1,225 @@
+//! Recording the raw packets exchanged with a ring and replaying them later
+//!
+//! Captures are newline delimited JSON, one [`CaptureRecord`] per line, for example
+//! `{"dir":"rx","chan":"uart","bytes":[3,50,0,...],"ts":1732665600000}`
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::Path,
+};
+
+use time::OffsetDateTime;
+
+use crate::{
+    client::Command,
+    constants::UART_PACKET_LEN,
+    incoming_messages::{ClientReceiver, RawPacket},
+    Error, Result,
+};
+
+/// Which way a captured packet was travelling
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// Sent by the ring
+    Rx,
+    /// Sent to the ring
+    Tx,
+}
+
+/// The characteristic a captured packet was sent on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    Uart,
+    V2,
+}
+
+/// A single line of a capture file
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct CaptureRecord {
+    pub dir: Direction,
+    pub chan: Channel,
+    pub bytes: Vec<u8>,
+    /// Milliseconds since the unix epoch
+    pub ts: i64,
+}
+
+impl CaptureRecord {
+    pub fn new(dir: Direction, chan: Channel, bytes: Vec<u8>) -> Self {
+        let ts = OffsetDateTime::now_utc().unix_timestamp_nanos() / 1_000_000;
+        Self {
+            dir,
+            chan,
+            bytes,
+            ts: ts as i64,
+        }
+    }
+
+    /// Record a packet the ring sent
+    pub fn received(packet: &RawPacket) -> Self {
+        let (chan, bytes) = match packet {
+            RawPacket::Uart(bytes) => (Channel::Uart, bytes),
+            RawPacket::V2(bytes) => (Channel::V2, bytes),
+        };
+        Self::new(Direction::Rx, chan, bytes.clone())
+    }
+
+    /// The packet as `ClientReceiver` would have seen it
+    pub fn packet(&self) -> RawPacket {
+        match self.chan {
+            Channel::Uart => RawPacket::Uart(self.bytes.clone()),
+            Channel::V2 => RawPacket::V2(self.bytes.clone()),
+        }
+    }
+
+    /// Decode the command a sent record carries, `None` for received records
+    pub fn command(&self) -> Option<Result<Command>> {
+        if self.dir != Direction::Tx {
+            return None;
+        }
+        let Ok(bytes) = <&[u8; UART_PACKET_LEN]>::try_from(self.bytes.as_slice()) else {
+            return Some(Err(Error::PacketLength {
+                command: self.bytes.first().copied(),
+                length: self.bytes.len(),
+                expected: UART_PACKET_LEN,
+            }));
+        };
+        Some(Command::try_from(bytes))
+    }
+}
+
+/// Appends records to a capture file
+#[derive(Debug)]
+pub struct CaptureWriter {
+    file: File,
+}
+
+impl CaptureWriter {
+    /// Open `path` for appending, creating it if it doesn't exist so reconnecting
+    /// keeps adding to the same capture
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file })
+    }
+
+    pub fn write(&mut self, record: &CaptureRecord) -> Result {
+        let mut line = serde_json::to_vec(record).map_err(std::io::Error::from)?;
+        line.push(b'\n');
+        // written in one call so a crash never leaves half a line behind
+        self.file.write_all(&line)?;
+        Ok(())
+    }
+}
+
+/// Read every record from the capture at `path`, blank lines are skipped
+pub fn read_records(path: impl AsRef<Path>) -> Result<Vec<CaptureRecord>> {
+    let file = BufReader::new(File::open(path)?);
+    let mut ret = Vec::new();
+    for (idx, line) in file.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line).map_err(|e| Error::InvalidCapture {
+            line: idx + 1,
+            reason: e.to_string(),
+        })?;
+        ret.push(record);
+    }
+    Ok(ret)
+}
+
+/// Read the packets the ring sent from the capture at `path`
+pub fn read(path: impl AsRef<Path>) -> Result<Vec<RawPacket>> {
+    Ok(read_records(path)?
+        .into_iter()
+        .filter(|record| record.dir == Direction::Rx)
+        .map(|record| record.packet())
+        .collect())
+}
+
+/// A receiver that parses the packets from the capture at `path` as if they
+/// were arriving from a ring
+pub fn replay(path: impl AsRef<Path>) -> Result<ClientReceiver> {
+    let packets = read(path)?;
+    Ok(ClientReceiver::from_stream(Box::pin(
+        futures::stream::iter(packets),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{constants, incoming_messages::CommandReply, util::checksum};
+
+    use super::*;
+
+    fn battery(level: u8) -> Vec<u8> {
+        let mut ret = vec![constants::CMD_BATTERY, level, 0];
+        ret.resize(constants::UART_PACKET_LEN, 0);
+        ret[15] = checksum(&ret);
+        ret
+    }
+
+    #[test]
+    fn record_format() {
+        let record = CaptureRecord {
+            dir: Direction::Rx,
+            chan: Channel::V2,
+            bytes: vec![188, 39],
+            ts: 1732665600000,
+        };
+        assert_eq!(
+            serde_json::to_string(&record).unwrap(),
+            r#"{"dir":"rx","chan":"v2","bytes":[188,39],"ts":1732665600000}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("capture.jsonl");
+        let mut writer = CaptureWriter::open(&path).unwrap();
+        writer
+            .write(&CaptureRecord::new(
+                Direction::Tx,
+                Channel::Uart,
+                battery(0),
+            ))
+            .unwrap();
+        writer
+            .write(&CaptureRecord::received(&RawPacket::Uart(battery(42))))
+            .unwrap();
+        let records = read_records(&path).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].command().unwrap().unwrap(), Command::BatteryInfo);
+        assert!(records[1].command().is_none());
+        assert_eq!(read(&path).unwrap(), vec![RawPacket::Uart(battery(42))]);
+        let mut rx = replay(&path).unwrap();
+        assert_eq!(
+            rx.next().await,
+            Some(CommandReply::BatteryInfo {
+                level: 42,
+                charging: false
+            })
+        );
+        assert_eq!(rx.next().await, None);
+    }
+
+    #[test]
+    fn invalid_line_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("capture.jsonl");
+        std::fs::write(
+            &path,
+            "{\"dir\":\"rx\",\"chan\":\"uart\",\"bytes\":[1],\"ts\":0}\n\nnot json\n",
+        )
+        .unwrap();
+        let err = read(&path).unwrap_err();
+        assert!(
+            matches!(err, Error::InvalidCapture { line: 3, .. }),
+            "{err:?}"
+        );
+    }
+}
@@ -1,17 +1,514 @@
-use bleasy::{Characteristic, Device, ScanConfig};
-use futures::{FutureExt, StreamExt};
+#[cfg(feature = "ble")]
+use std::{
+    future::Future,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
+#[cfg(feature = "ble")]
+use bleasy::{Device, ScanConfig};
+#[cfg(feature = "ble")]
+use futures::{Stream, StreamExt};
+use time::{OffsetDateTime, PrimitiveDateTime, UtcOffset};
+#[cfg(feature = "ble")]
+use tokio::time::Instant;
+
+#[cfg(feature = "ble")]
+use crate::{
+    capture::Channel,
+    incoming_messages::{
+        ClientReceiver, CommandReply, RawPacket, RealTimeEvent, SyncProgress, UnhandledHook,
+    },
+    preferences::{Preference, PreferenceKey},
+    request_queue::{self, RequestQueue, DEFAULT_REQUEST_TIMEOUT},
+    transport::{BleTransport, Transport},
+};
 use crate::{
     constants,
-    incoming_messages::{ClientReceiver, CommandReply},
-    Result,
+    util::{checksum, trim_padding, verify_checksum},
+    Error, Result,
 };
 
+/// How often the ring needs to be told to keep a real time reading going
+#[cfg(feature = "ble")]
+const REAL_TIME_CONTINUE_INTERVAL: Duration = Duration::from_secs(10);
+/// How many times each connection step is retried unless configured otherwise
+#[cfg(feature = "ble")]
+const DEFAULT_RETRIES: u8 = 3;
+/// The delay before the first retry, doubled for each one after
+#[cfg(feature = "ble")]
+const DEFAULT_BACKOFF: Duration = Duration::from_millis(250);
+/// How long a single scan attempt waits for the device to show up
+#[cfg(feature = "ble")]
+const SCAN_ATTEMPT_TIMEOUT: Duration = Duration::from_secs(15);
+/// The longest connecting may take, scanning and discovery included, unless
+/// configured otherwise
+#[cfg(feature = "ble")]
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+/// How many times a dropped connection is re-established unless configured otherwise
+#[cfg(feature = "ble")]
+const DEFAULT_RECONNECTS: u8 = 3;
+/// How many connection events are held for slow subscribers
+#[cfg(feature = "ble")]
+const CONNECTION_EVENT_CAPACITY: usize = 16;
+/// How many received packets are held for slow subscribers, enough for the
+/// largest sleep reply
+#[cfg(feature = "ble")]
+const RAW_PACKET_CAPACITY: usize = 1024;
+
+#[cfg(feature = "ble")]
 pub struct Client {
-    pub device: Device,
-    rx: Option<ClientReceiver>,
-    tx: Characteristic,
-    tx2: Characteristic,
+    transport: Arc<dyn Transport>,
+    queue: RequestQueue,
+    verify_checksums: bool,
+    skip_empty_oxygen: bool,
+    retry: RetryPolicy,
+    capture: Option<PathBuf>,
+    unhandled: Option<UnhandledHook>,
+    progress: tokio::sync::watch::Sender<Option<SyncProgress>>,
+    reconnects: u8,
+    connection: tokio::sync::broadcast::Sender<ConnectionEvent>,
+    raw: tokio::sync::broadcast::Sender<RawPacket>,
+    lifecycle: Option<Lifecycle>,
+    activity: Arc<Activity>,
+    /// Started on the first connect when there is a `lifecycle`
+    lifecycle_task: std::sync::Mutex<Option<AbortOnDrop>>,
+}
+
+/// A change in the connection to the ring, see [`Client::connection_events`]
+#[cfg(feature = "ble")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    /// The client subscribed to the ring's replies
+    Connected,
+    /// The ring dropped the connection
+    Disconnected,
+    /// A dropped connection was re-established on this attempt
+    Reconnected { attempt: u8 },
+    /// The client disconnected after being idle, see
+    /// [`ClientBuilder::idle_disconnect`]. The next operation connects again
+    Idle,
+}
+
+/// What a client does with a connection it isn't using
+#[cfg(feature = "ble")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Lifecycle {
+    /// Disconnect once nothing has used the connection for this long
+    IdleDisconnect(Duration),
+    /// Request the battery level whenever nothing has used the connection for
+    /// this long
+    KeepAlive(Duration),
+}
+
+/// How a client is using its connection, shared with the task that manages it
+/// while it isn't
+#[cfg(feature = "ble")]
+struct Activity {
+    /// Held for as long as an operation uses the connection so the task never
+    /// disconnects in the middle of one
+    gate: Arc<tokio::sync::Mutex<()>>,
+    /// When an operation last started or finished
+    last: std::sync::Mutex<Instant>,
+    /// The client is connected and the task should be counting
+    connected: AtomicBool,
+    /// The task disconnected the client for being idle
+    idle: AtomicBool,
+    /// Wakes the task when the client connects
+    wake: tokio::sync::Notify,
+}
+
+#[cfg(feature = "ble")]
+impl Default for Activity {
+    fn default() -> Self {
+        Self {
+            gate: Default::default(),
+            last: std::sync::Mutex::new(Instant::now()),
+            connected: AtomicBool::new(false),
+            idle: AtomicBool::new(false),
+            wake: tokio::sync::Notify::new(),
+        }
+    }
+}
+
+#[cfg(feature = "ble")]
+impl Activity {
+    fn last(&self) -> Instant {
+        *self.last.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn touch(&self) {
+        *self.last.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now();
+    }
+
+    fn set_connected(&self, connected: bool) {
+        self.connected.store(connected, Ordering::SeqCst);
+        if connected {
+            self.touch();
+            self.wake.notify_one();
+        }
+    }
+
+    /// Wait for the operations ahead of this one, the connection counts as
+    /// used until the returned guard is dropped
+    async fn busy(self: &Arc<Self>) -> Busy {
+        let gate = self.gate.clone().lock_owned().await;
+        self.touch();
+        Busy {
+            activity: self.clone(),
+            _gate: gate,
+        }
+    }
+}
+
+/// An operation using the connection, see `Activity::busy`
+#[cfg(feature = "ble")]
+struct Busy {
+    activity: Arc<Activity>,
+    _gate: tokio::sync::OwnedMutexGuard<()>,
+}
+
+#[cfg(feature = "ble")]
+impl Drop for Busy {
+    fn drop(&mut self) {
+        self.activity.touch();
+    }
+}
+
+/// Aborts a task when the client that started it is dropped
+#[cfg(feature = "ble")]
+struct AbortOnDrop(tokio::task::JoinHandle<()>);
+
+#[cfg(feature = "ble")]
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Disconnect whenever nothing has used the connection for `idle`
+#[cfg(feature = "ble")]
+async fn idle_disconnect(
+    activity: Arc<Activity>,
+    transport: Arc<dyn Transport>,
+    connection: tokio::sync::broadcast::Sender<ConnectionEvent>,
+    idle: Duration,
+) {
+    loop {
+        if !activity.connected.load(Ordering::SeqCst) {
+            activity.wake.notified().await;
+            continue;
+        }
+        let deadline = activity.last() + idle;
+        if Instant::now() < deadline {
+            tokio::time::sleep_until(deadline).await;
+            continue;
+        }
+        let _gate = activity.gate.lock().await;
+        // an operation may have used the connection while waiting for the gate
+        if activity.last() + idle > Instant::now() || !activity.connected.load(Ordering::SeqCst) {
+            continue;
+        }
+        log::debug!("disconnecting after {idle:?} idle");
+        if let Err(e) = transport.disconnect().await {
+            log::warn!("disconnecting an idle connection failed: {e}");
+        }
+        activity.connected.store(false, Ordering::SeqCst);
+        activity.idle.store(true, Ordering::SeqCst);
+        // an error only means nobody is subscribed
+        let _ = connection.send(ConnectionEvent::Idle);
+    }
+}
+
+/// Request the battery level whenever nothing has used the connection for
+/// `interval`, the reply arrives like any other
+#[cfg(feature = "ble")]
+async fn keep_alive(activity: Arc<Activity>, transport: Arc<dyn Transport>, interval: Duration) {
+    let Ok(battery) = <[u8; 16]>::try_from(Command::BatteryInfo) else {
+        return;
+    };
+    loop {
+        if !activity.connected.load(Ordering::SeqCst) {
+            activity.wake.notified().await;
+            continue;
+        }
+        let deadline = activity.last() + interval;
+        if Instant::now() < deadline {
+            tokio::time::sleep_until(deadline).await;
+            continue;
+        }
+        log::trace!("keeping the connection alive");
+        if let Err(e) = transport.write_uart(&battery).await {
+            log::warn!("keep alive request failed: {e}");
+        }
+        activity.touch();
+    }
+}
+
+/// Configures how a `Client` connects to a ring
+///
+/// Scanning, characteristic discovery and subscribing to notifications are each
+/// retried with exponential backoff since these rings drop connections often
+#[cfg(feature = "ble")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClientBuilder {
+    retry: RetryPolicy,
+    request_timeout: Option<Duration>,
+    reconnects: Option<u8>,
+    lifecycle: Option<Lifecycle>,
+}
+
+#[cfg(feature = "ble")]
+impl ClientBuilder {
+    /// How many times to retry each step after the first failure
+    pub fn retries(mut self, retries: u8) -> Self {
+        self.retry.retries = retries;
+        self
+    }
+
+    /// The delay before the first retry, each retry after waits twice as long
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.retry.backoff = backoff;
+        self
+    }
+
+    /// The longest connecting may take, including all retries, connecting
+    /// fails with `Error::Timeout` after it. Defaults to
+    /// `DEFAULT_CONNECT_TIMEOUT`
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.retry.deadline = Some(deadline);
+        self
+    }
+
+    /// How long `Client::send_request` waits for a reply before giving up
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// How many times to try re-establishing a connection the ring dropped, 0
+    /// leaves the client disconnected like the stream ending used to
+    pub fn reconnects(mut self, reconnects: u8) -> Self {
+        self.reconnects = Some(reconnects);
+        self
+    }
+
+    /// Disconnect once no operation has used the connection for `idle`, the
+    /// next one connects again. Replaces `keep_alive`
+    ///
+    /// Waiting in `read_next` or `wait_for` counts as using the connection
+    pub fn idle_disconnect(mut self, idle: Duration) -> Self {
+        self.lifecycle = Some(Lifecycle::IdleDisconnect(idle));
+        self
+    }
+
+    /// Request the battery level whenever no operation has used the connection
+    /// for `interval` so the ring doesn't drop it. Replaces `idle_disconnect`
+    ///
+    /// The replies reach `read_next` and `notifications` like the battery
+    /// notifications the ring sends on its own
+    pub fn keep_alive(mut self, interval: Duration) -> Self {
+        self.lifecycle = Some(Lifecycle::KeepAlive(interval));
+        self
+    }
+
+    /// Scan for the device with `addr` and look up its characteristics
+    pub async fn build(self, addr: impl Into<bleasy::BDAddr>) -> Result<Client> {
+        let addr = addr.into();
+        self.retry
+            .with_deadline(async {
+                let device = self
+                    .retry
+                    .run("scan", || async {
+                        let mut s = bleasy::Scanner::new();
+                        s.start(ScanConfig::default().filter_by_address(move |w| w == addr))
+                            .await?;
+                        first_found(s.device_stream(), SCAN_ATTEMPT_TIMEOUT).await
+                    })
+                    .await?;
+                self.build_with_device_(device).await
+            })
+            .await
+    }
+
+    /// Look up the characteristics of an already discovered `device`
+    pub async fn build_with_device(self, device: Device) -> Result<Client> {
+        self.retry
+            .with_deadline(self.build_with_device_(device))
+            .await
+    }
+
+    async fn build_with_device_(self, device: Device) -> Result<Client> {
+        let transport = self
+            .retry
+            .run("characteristic discovery", || {
+                BleTransport::new(device.clone())
+            })
+            .await?;
+        Ok(self.build_with_transport(transport))
+    }
+
+    /// Use `transport` to talk to the ring instead of bluetooth
+    pub fn build_with_transport(self, transport: impl Transport + 'static) -> Client {
+        Client {
+            transport: Arc::new(transport),
+            queue: RequestQueue::new(self.request_timeout.unwrap_or(DEFAULT_REQUEST_TIMEOUT)),
+            verify_checksums: true,
+            skip_empty_oxygen: false,
+            retry: self.retry,
+            capture: None,
+            unhandled: None,
+            progress: tokio::sync::watch::channel(None).0,
+            reconnects: self.reconnects.unwrap_or(DEFAULT_RECONNECTS),
+            connection: tokio::sync::broadcast::channel(CONNECTION_EVENT_CAPACITY).0,
+            raw: tokio::sync::broadcast::channel(RAW_PACKET_CAPACITY).0,
+            lifecycle: self.lifecycle,
+            activity: Default::default(),
+            lifecycle_task: Default::default(),
+        }
+    }
+}
+
+#[cfg(feature = "ble")]
+#[derive(Debug, Clone, Copy)]
+struct RetryPolicy {
+    retries: u8,
+    backoff: Duration,
+    deadline: Option<Duration>,
+}
+
+#[cfg(feature = "ble")]
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: DEFAULT_RETRIES,
+            backoff: DEFAULT_BACKOFF,
+            deadline: Some(DEFAULT_CONNECT_TIMEOUT),
+        }
+    }
+}
+
+/// The first device a scan finds, `Error::DeviceNotFound` if it finds none
+/// within `timeout`
+#[cfg(feature = "ble")]
+async fn first_found<T>(
+    mut devices: impl Stream<Item = T> + Unpin,
+    timeout: Duration,
+) -> Result<T> {
+    tokio::time::timeout(timeout, devices.next())
+        .await
+        .ok()
+        .flatten()
+        .ok_or(Error::DeviceNotFound)
+}
+
+#[cfg(feature = "ble")]
+impl RetryPolicy {
+    /// The delay after failed attempt number `attempt`, starting at 0
+    fn delay(&self, attempt: u8) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(attempt.into()))
+    }
+
+    async fn run<T, F, Fut>(&self, step: &str, mut f: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            log::debug!("{step} attempt {}", attempt + 1);
+            match f().await {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt < self.retries => {
+                    let delay = self.delay(attempt);
+                    log::warn!(
+                        "{step} attempt {} failed: {e}, retrying in {delay:?}",
+                        attempt + 1
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn with_deadline<T>(&self, fut: impl Future<Output = Result<T>>) -> Result<T> {
+        match self.deadline {
+            Some(deadline) => tokio::time::timeout(deadline, fut).await?,
+            None => fut.await,
+        }
+    }
+}
+
+/// The ring's battery level, see [`Client::battery`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct BatteryInfo {
+    /// Percent charged
+    pub level: u8,
+    pub charging: bool,
+}
+
+/// How often the ring measures heart rate on its own, see
+/// [`Client::heart_rate_settings`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct HeartRateSettings {
+    pub enabled: bool,
+    pub interval: HrInterval,
+}
+
+/// Minutes between automatic heart rate measurements, a multiple of 5 up to
+/// 60. The ring ignores any other interval and reports 0 when it has none
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Deserialize, serde::Serialize,
+)]
+#[serde(try_from = "u8", into = "u8")]
+pub struct HrInterval(u8);
+
+impl HrInterval {
+    pub const MAX: u8 = 60;
+    pub const STEP: u8 = 5;
+
+    pub fn try_new(minutes: u8) -> Result<Self> {
+        if !minutes.is_multiple_of(Self::STEP) || minutes > Self::MAX {
+            return Err(Error::InvalidHrInterval(minutes));
+        }
+        Ok(Self(minutes))
+    }
+
+    pub fn minutes(self) -> u8 {
+        self.0
+    }
+
+    /// Every interval the ring accepts
+    pub fn allowed() -> impl Iterator<Item = Self> {
+        (0..=Self::MAX).step_by(Self::STEP.into()).map(Self)
+    }
+}
+
+impl TryFrom<u8> for HrInterval {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self> {
+        Self::try_new(value)
+    }
+}
+
+impl From<HrInterval> for u8 {
+    fn from(value: HrInterval) -> Self {
+        value.0
+    }
+}
+
+impl std::fmt::Display for HrInterval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
 }
 
 #[derive(Default, serde::Deserialize, serde::Serialize)]
@@ -20,116 +517,646 @@ pub struct DeviceDetails {
     pub fw: Option<String>,
 }
 
+/// Sends the stop command for a real time reading when the stream
+/// that owns it is dropped
+#[cfg(feature = "ble")]
+struct StopOnDrop {
+    transport: Arc<dyn Transport>,
+    stop: [u8; 16],
+}
+
+#[cfg(feature = "ble")]
+impl Drop for StopOnDrop {
+    fn drop(&mut self) {
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            log::warn!("no runtime available to stop real time reading");
+            return;
+        };
+        let transport = self.transport.clone();
+        let stop = self.stop;
+        handle.spawn(async move {
+            if let Err(e) = transport.write_uart(&stop).await {
+                log::warn!("failed to stop real time reading: {e}");
+            }
+        });
+    }
+}
+
+#[cfg(feature = "ble")]
 impl Client {
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::default()
+    }
+
+    /// Scan for the ring with `addr` and connect to it, giving up after
+    /// `DEFAULT_CONNECT_TIMEOUT`
     pub async fn new(addr: impl Into<bleasy::BDAddr>) -> Result<Self> {
-        let addr = addr.into();
-        let mut s = bleasy::Scanner::new();
-        s.start(ScanConfig::default().filter_by_address(move |w| w == addr))
-            .await?;
-        let device = s
-            .device_stream()
-            .next()
-            .await
-            .ok_or_else(|| "No device found".to_string())?;
-        Self::with_device(device).await
+        Self::builder().build(addr).await
+    }
+
+    /// Like `new` but giving up after `timeout`, `Error::DeviceNotFound` means
+    /// the scans didn't find the ring and `Error::Timeout` that `timeout`
+    /// passed first
+    ///
+    /// ```no_run
+    /// # async fn connect(addr: cole_mine::BDAddr) -> cole_mine::Result<cole_mine::Client> {
+    /// use std::time::Duration;
+    ///
+    /// use cole_mine::{Client, Error};
+    ///
+    /// loop {
+    ///     match Client::new_with_timeout(addr, Duration::from_secs(10)).await {
+    ///         Ok(client) => return Ok(client),
+    ///         // out of range, scan again once it might be back
+    ///         Err(Error::DeviceNotFound | Error::Timeout) => {
+    ///             tokio::time::sleep(Duration::from_secs(60)).await;
+    ///         }
+    ///         Err(e) => return Err(e),
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub async fn new_with_timeout(
+        addr: impl Into<bleasy::BDAddr>,
+        timeout: Duration,
+    ) -> Result<Self> {
+        Self::builder().deadline(timeout).build(addr).await
     }
 
     pub async fn with_device(device: Device) -> Result<Self> {
-        let (tx, tx2) = Self::find_tx_characteristics(&device)
-            .await
-            .map_err(|e| format!("Error looking up uart_rx characteristic: {e}"))?;
-        Ok(Self {
-            device,
-            tx,
-            tx2,
-            rx: None,
-        })
+        Self::builder().build_with_device(device).await
+    }
+
+    pub fn with_transport(transport: impl Transport + 'static) -> Self {
+        Self::builder().build_with_transport(transport)
+    }
+
+    /// The bluetooth device this client is connected to, `None` when it is using
+    /// some other transport
+    pub fn device(&self) -> Option<&Device> {
+        self.transport.device()
     }
 
     pub async fn connect(&mut self) -> Result {
-        self.rx = Some(ClientReceiver::connect_device(&self.device).await?);
+        let rx = self.open_receiver().await?;
+        *self.queue.receiver_mut() = Some(rx);
+        self.publish(ConnectionEvent::Connected);
+        self.start_lifecycle();
+        self.activity.set_connected(true);
+        Ok(())
+    }
+
+    /// Start managing the idle connection if configured and not already started
+    fn start_lifecycle(&self) {
+        let mut started = self
+            .lifecycle_task
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if started.is_some() {
+            return;
+        }
+        let activity = self.activity.clone();
+        let transport = self.transport.clone();
+        let task = match self.lifecycle {
+            None => return,
+            Some(Lifecycle::IdleDisconnect(idle)) => tokio::spawn(idle_disconnect(
+                activity,
+                transport,
+                self.connection.clone(),
+                idle,
+            )),
+            Some(Lifecycle::KeepAlive(interval)) => {
+                tokio::spawn(keep_alive(activity, transport, interval))
+            }
+        };
+        *started = Some(AbortOnDrop(task));
+    }
+
+    /// Connect again after disconnecting for being idle, `None` if the client
+    /// wasn't
+    async fn resume(&self) -> Option<Result<ClientReceiver>> {
+        if !self.activity.idle.swap(false, Ordering::SeqCst) {
+            return None;
+        }
+        log::debug!("reconnecting after being idle");
+        let rx = async {
+            self.transport.reconnect().await?;
+            self.open_receiver().await
+        }
+        .await;
+        match &rx {
+            Ok(_) => {
+                self.publish(ConnectionEvent::Connected);
+                self.activity.set_connected(true);
+            }
+            // the next operation tries again
+            Err(_) => self.activity.idle.store(true, Ordering::SeqCst),
+        }
+        Some(rx)
+    }
+
+    /// Connect if this client hasn't yet, or reconnect if the ring dropped the
+    /// connection, `send` and `read_next` call this before using the connection
+    pub async fn ensure_connected(&mut self) -> Result {
+        if let Some(rx) = self.resume().await {
+            *self.queue.receiver_mut() = Some(rx?);
+            return Ok(());
+        }
+        match self.queue.receiver_mut() {
+            None => self.connect().await,
+            Some(rx) if rx.is_closed() => self.reconnect().await,
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Re-establish a dropped connection, trying up to the configured number of
+    /// reconnects
+    ///
+    /// A multi-packet reply that was arriving is abandoned since the ring won't
+    /// resume it, the sync has to be requested again
+    async fn reconnect(&mut self) -> Result {
+        self.queue.receiver_mut().take();
+        *self.queue.receiver_mut() = Some(self.reopen().await?);
+        Ok(())
+    }
+
+    /// Publish that the ring dropped the connection and try up to the configured
+    /// number of reconnects, returning the new receiver
+    async fn reopen(&self) -> Result<ClientReceiver> {
+        if let Some(progress) = self.progress.send_replace(None) {
+            log::warn!(
+                "connection dropped during a {} sync after {}/{}, it has to be requested again",
+                progress.kind,
+                progress.received,
+                progress.expected
+            );
+        }
+        self.publish(ConnectionEvent::Disconnected);
+        for attempt in 1..=self.reconnects {
+            log::debug!("reconnect attempt {attempt}");
+            let rx = async {
+                self.transport.reconnect().await?;
+                self.open_receiver().await
+            }
+            .await;
+            match rx {
+                Ok(rx) => {
+                    self.publish(ConnectionEvent::Reconnected { attempt });
+                    return Ok(rx);
+                }
+                Err(e) if attempt < self.reconnects => {
+                    let delay = self.retry.delay(attempt - 1);
+                    log::warn!("reconnect attempt {attempt} failed: {e}, retrying in {delay:?}");
+                    tokio::time::sleep(delay).await;
+                }
+                Err(e) => log::warn!("reconnect attempt {attempt} failed: {e}"),
+            }
+        }
+        Err(Error::ConnectionLost {
+            attempts: self.reconnects,
+        })
+    }
+
+    /// Changes to the connection, like the ring dropping it and the client
+    /// reconnecting
+    pub fn connection_events(&self) -> tokio::sync::broadcast::Receiver<ConnectionEvent> {
+        self.connection.subscribe()
+    }
+
+    fn publish(&self, event: ConnectionEvent) {
+        log::debug!("connection event: {event:?}");
+        // an error only means nobody is subscribed
+        let _ = self.connection.send(event);
+    }
+
+    async fn open_receiver(&self) -> Result<ClientReceiver> {
+        let stream = self
+            .retry
+            .with_deadline(self.retry.run("subscribe", || self.transport.subscribe()))
+            .await?;
+        let mut rx =
+            ClientReceiver::from_stream(stream).with_checksum_validation(self.verify_checksums);
+        if let Some(path) = &self.capture {
+            rx.set_capture(path)?;
+        }
+        rx.set_skip_empty_oxygen(self.skip_empty_oxygen);
+        if let Some(hook) = &self.unhandled {
+            rx.on_unhandled(hook.clone());
+        }
+        rx.share_progress(self.progress.clone());
+        rx.share_raw(self.raw.clone());
+        Ok(rx)
+    }
+
+    /// Enable or disable checking the trailing checksum byte of incoming packets,
+    /// some firmwares don't populate it. Validation is enabled by default
+    pub fn set_checksum_validation(&mut self, enabled: bool) {
+        self.verify_checksums = enabled;
+        if let Some(rx) = self.queue.receiver_mut().as_mut() {
+            rx.set_checksum_validation(enabled);
+        }
+    }
+
+    /// Leave the hours without a reading out of oxygen replies, they are kept
+    /// by default
+    pub fn set_skip_empty_oxygen(&mut self, skip: bool) {
+        self.skip_empty_oxygen = skip;
+        if let Some(rx) = self.queue.receiver_mut().as_mut() {
+            rx.set_skip_empty_oxygen(skip);
+        }
+    }
+
+    /// Append every packet sent to or received from the ring to the capture file
+    /// at `path`, see [`crate::capture`] for the format
+    pub fn set_capture(&mut self, path: impl Into<PathBuf>) -> Result {
+        let path = path.into();
+        if let Some(rx) = self.queue.receiver_mut().as_mut() {
+            rx.set_capture(&path)?;
+        }
+        self.capture = Some(path);
         Ok(())
     }
 
+    /// Call `hook` with every packet that isn't converted into a typed reply, see
+    /// [`ClientReceiver::on_unhandled`]
+    pub fn on_unhandled(&mut self, hook: UnhandledHook) {
+        if let Some(rx) = self.queue.receiver_mut().as_mut() {
+            rx.on_unhandled(hook.clone());
+        }
+        self.unhandled = Some(hook);
+    }
+
     pub async fn disconnect(&mut self) -> Result {
-        self.device.disconnect().await?;
-        if let Some(rx) = self.rx.take() {
+        self.activity.set_connected(false);
+        self.activity.idle.store(false, Ordering::SeqCst);
+        self.transport.disconnect().await?;
+        if let Some(rx) = self.queue.receiver_mut().take() {
             rx.disconnect().await?
         }
         Ok(())
     }
 
+    /// Send `command` without waiting for a reply, reconnecting and sending it
+    /// again if the write fails because the ring dropped the connection
     pub async fn send(&mut self, command: Command) -> Result {
         log::trace!("sending {command:?}");
-        let cmd_bytes: [u8; 16] = command.into();
-        log::trace!("serialized: {cmd_bytes:?}");
-        if cmd_bytes[0] == crate::constants::CMD_BIG_DATA_V2
-            || cmd_bytes[0] == crate::constants::CMD_NOTIFICATION
-        {
-            self.tx2.write_command(&cmd_bytes).await?;
-        } else {
-            self.tx.write_command(&cmd_bytes).await?;
+        let _busy = self.activity.busy().await;
+        self.ensure_connected().await?;
+        let (chan, cmd_bytes) =
+            request_queue::prepare(command.clone(), self.queue.receiver_mut().as_mut())?;
+        match self.write(chan, cmd_bytes).await {
+            Err(Error::Ble(e)) if self.reconnects > 0 => {
+                log::warn!("writing {command:?} failed: {e}, reconnecting");
+                self.reconnect().await?;
+                let (chan, cmd_bytes) =
+                    request_queue::prepare(command, self.queue.receiver_mut().as_mut())?;
+                self.write(chan, cmd_bytes).await
+            }
+            ret => ret,
         }
-        Ok(())
     }
 
+    /// Send `command` and wait for its reply, safe to call from several tasks at once
+    ///
+    /// Requests are sent one at a time in the order they were made, each waiting
+    /// for the reply with the same command byte, or the request timeout, before the
+    /// next is sent. Other replies that arrive in the meantime are published to
+    /// [`Client::notifications`]. If the ring dropped the connection the client
+    /// reconnects like [`Client::ensure_connected`] does.
+    pub async fn send_request(&self, command: Command) -> Result<CommandReply> {
+        log::trace!("requesting {command:?}");
+        let _busy = self.activity.busy().await;
+        if let Some(rx) = self.resume().await {
+            *self.queue.lock().await = Some(rx?);
+        }
+        self.queue
+            .request(
+                command,
+                || async {
+                    let rx = self.open_receiver().await?;
+                    self.start_lifecycle();
+                    self.activity.set_connected(true);
+                    Ok(rx)
+                },
+                || self.reopen(),
+                |chan, bytes| self.write(chan, bytes),
+            )
+            .await
+    }
+
+    /// Replies that arrive during `send_request` without answering the request,
+    /// like the notifications the ring sends on its own
+    pub fn notifications(&self) -> tokio::sync::broadcast::Receiver<CommandReply> {
+        self.queue.notifications()
+    }
+
+    /// Follow how far along a multi-packet reply is, see
+    /// [`ClientReceiver::progress`]. Keeps working across reconnects
+    pub fn progress(&self) -> tokio::sync::watch::Receiver<Option<SyncProgress>> {
+        self.progress.subscribe()
+    }
+
+    /// Every packet received from the ring as it arrived, before it is parsed.
+    /// Keeps working across reconnects
+    ///
+    /// Uart notifications haven't been reassembled into 16 byte packets yet,
+    /// see [`crate::incoming_messages::framer::PacketFramer`]
+    pub fn raw_packets(&self) -> tokio::sync::broadcast::Receiver<RawPacket> {
+        self.raw.subscribe()
+    }
+
+    async fn write(&self, chan: Channel, bytes: [u8; 16]) -> Result {
+        match chan {
+            Channel::V2 => self.transport.write_v2(&bytes).await,
+            Channel::Uart => self.transport.write_uart(&bytes).await,
+        }
+    }
+
+    /// The next reply from the ring, packets that fail to parse are skipped
+    ///
+    /// If the ring drops the connection the client reconnects and keeps reading,
+    /// `None` is only returned when reconnecting is disabled
     pub async fn read_next(&mut self) -> Result<Option<CommandReply>> {
-        if self.rx.is_none() {
-            self.connect().await?;
+        loop {
+            match self.read_next_raw().await? {
+                Some(Ok(reply)) => {
+                    log::trace!("reply: {reply:?}");
+                    return Ok(Some(reply));
+                }
+                Some(Err(e)) => log::debug!("skipping unparsable packet: {e}"),
+                None => return Ok(None),
+            }
         }
-        let Some(rx) = &mut self.rx else {
-            return Err("fatal error, rx was none after `connect`"
-                .to_string()
-                .into());
-        };
-        Ok(rx
-            .next()
-            .map(|rply| {
-                log::trace!("reply: {rply:?}");
-                rply
-            })
-            .await)
     }
 
-    async fn find_tx_characteristics(device: &Device) -> Result<(Characteristic, Characteristic)> {
-        let mut one = None;
-        let mut two = None;
-        let services = device.services().await?;
-        'services: for service in services {
-            if one.is_some() && two.is_some() {
-                break;
+    /// Like `read_next` but packets that fail to parse are returned as errors
+    /// instead of being skipped
+    ///
+    /// The outer result is an error if the client couldn't connect, the inner
+    /// one carries the packet that was rejected and which parser rejected it
+    pub async fn read_next_raw(&mut self) -> Result<Option<Result<CommandReply>>> {
+        // each reconnect that ends without a packet counts against the limit so
+        // a ring that keeps dropping the connection can't stall the caller
+        let mut drops = 0;
+        let _busy = self.activity.busy().await;
+        loop {
+            self.ensure_connected().await?;
+            let Some(rx) = self.queue.receiver_mut() else {
+                return Err(Error::NotConnected);
+            };
+            if let Some(reply) = rx.try_next().await {
+                return Ok(Some(reply));
+            }
+            if self.reconnects == 0 {
+                return Ok(None);
+            }
+            drops += 1;
+            if drops > self.reconnects {
+                return Err(Error::ConnectionLost {
+                    attempts: self.reconnects,
+                });
             }
-            if service.uuid() == crate::constants::UART_SERVICE_UUID {
-                for ch in service.characteristics() {
-                    if ch.uuid() == crate::constants::UART_RX_CHAR_UUID {
-                        one = Some(ch);
-                        continue 'services;
+            log::warn!("the ring dropped the connection");
+        }
+    }
+
+    /// Wait up to `timeout` for a reply that satisfies `matcher`, replies that don't
+    /// match are held and returned by later calls to `read_next`
+    pub async fn wait_for(
+        &mut self,
+        matcher: impl Fn(&CommandReply) -> bool,
+        timeout: Duration,
+    ) -> Result<Option<CommandReply>> {
+        let _busy = self.activity.busy().await;
+        self.ensure_connected().await?;
+        let Some(rx) = self.queue.receiver_mut() else {
+            return Err(Error::NotConnected);
+        };
+        Ok(rx.wait_for(matcher, timeout).await)
+    }
+
+    /// Send `command` and then wait up to `timeout` for a reply that satisfies `matcher`
+    ///
+    /// Returns `Ok(None)` if no matching reply arrived in time
+    pub async fn send_and_wait(
+        &mut self,
+        command: Command,
+        matcher: impl Fn(&CommandReply) -> bool,
+        timeout: Duration,
+    ) -> Result<Option<CommandReply>> {
+        self.send(command).await?;
+        self.wait_for(matcher, timeout).await
+    }
+
+    /// Start a real time heart rate reading, the returned stream will keep the reading
+    /// alive while it is being polled and stop it when dropped
+    ///
+    /// If the ring reports an error the stream yields `Error::RealTime` and then ends
+    pub async fn realtime_heart_rate(
+        &mut self,
+    ) -> Result<impl Stream<Item = Result<RealTimeEvent>> + '_> {
+        self.realtime(
+            Command::StartRealTimeHeartRate,
+            Command::ContinueRealTimeHeartRate,
+            Command::StopRealTimeHeartRate,
+            |ev| matches!(ev, RealTimeEvent::HeartRate(_) | RealTimeEvent::Error(_)),
+        )
+        .await
+    }
+
+    /// Start a real time SpO2 reading, see `realtime_heart_rate`
+    pub async fn realtime_spo2(
+        &mut self,
+    ) -> Result<impl Stream<Item = Result<RealTimeEvent>> + '_> {
+        self.realtime(
+            Command::StartSpo2,
+            Command::ContinueSpo2,
+            Command::StopSpo2,
+            |ev| matches!(ev, RealTimeEvent::Oxygen(_) | RealTimeEvent::Error(_)),
+        )
+        .await
+    }
+
+    async fn realtime(
+        &mut self,
+        start: Command,
+        keep_going: Command,
+        stop: Command,
+        matcher: fn(&RealTimeEvent) -> bool,
+    ) -> Result<impl Stream<Item = Result<RealTimeEvent>> + '_> {
+        if self.queue.receiver_mut().is_none() {
+            self.connect().await?;
+        }
+        self.send(start).await?;
+        let guard = StopOnDrop {
+            transport: self.transport.clone(),
+            stop: stop.try_into()?,
+        };
+        Ok(async_stream::stream! {
+            let _guard = guard;
+            let mut deadline = Instant::now() + REAL_TIME_CONTINUE_INTERVAL;
+            loop {
+                let reply = self
+                    .wait_for(
+                        |r| matches!(r, CommandReply::RealTimeData(ev) if matcher(ev)),
+                        deadline.saturating_duration_since(Instant::now()),
+                    )
+                    .await;
+                match reply {
+                    Ok(Some(CommandReply::RealTimeData(RealTimeEvent::Error(code)))) => {
+                        yield Err(Error::RealTime(code));
+                        break;
+                    }
+                    Ok(Some(CommandReply::RealTimeData(ev))) => yield Ok(ev),
+                    Ok(_) if Instant::now() < deadline => {
+                        log::debug!("receiver closed during real time reading");
+                        break;
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        yield Err(e);
+                        break;
                     }
                 }
-            }
-            if service.uuid() == crate::constants::CHARACTERISTIC_SERVICE_V2 {
-                for ch in service.characteristics() {
-                    if ch.uuid() == crate::constants::CHARACTERISTIC_COMMAND {
-                        two = Some(ch);
-                        continue 'services;
+                if Instant::now() >= deadline {
+                    if let Err(e) = self.send(keep_going.clone()).await {
+                        yield Err(e);
+                        break;
                     }
+                    deadline = Instant::now() + REAL_TIME_CONTINUE_INTERVAL;
                 }
             }
+        })
+    }
+
+    /// Set the ring's clock to `when` as seen in `tz`, or in the offset `when`
+    /// already has if `tz` is `None`
+    ///
+    /// Returns the time the ring reports once it is set, when the firmware echoes
+    /// it, so callers can check for drift
+    pub async fn set_time(
+        &self,
+        when: OffsetDateTime,
+        tz: Option<UtcOffset>,
+        language: Language,
+    ) -> Result<Option<PrimitiveDateTime>> {
+        match self
+            .send_request(Command::set_time(when, tz, language))
+            .await?
+        {
+            CommandReply::SetTime { device_time } => Ok(device_time),
+            reply => Err(Error::UnexpectedReply(Box::new(reply))),
+        }
+    }
+
+    /// Request the battery level, waiting up to the request timeout for the reply
+    pub async fn battery(&self) -> Result<BatteryInfo> {
+        match self.send_request(Command::BatteryInfo).await? {
+            CommandReply::BatteryInfo { level, charging } => Ok(BatteryInfo { level, charging }),
+            reply => Err(Error::UnexpectedReply(Box::new(reply))),
+        }
+    }
+
+    /// Request the automatic heart rate settings, waiting up to the request timeout
+    /// for the reply
+    pub async fn heart_rate_settings(&self) -> Result<HeartRateSettings> {
+        self.request_heart_rate_settings(Command::GetHeartRateSettings)
+            .await
+    }
+
+    /// Update the automatic heart rate settings, returning the settings the ring
+    /// reports once they are applied
+    pub async fn set_heart_rate_settings(
+        &self,
+        settings: HeartRateSettings,
+    ) -> Result<HeartRateSettings> {
+        self.request_heart_rate_settings(Command::SetHeartRateSettings(settings))
+            .await
+    }
+
+    /// Request whether the ring measures blood oxygen on its own, waiting up to
+    /// the request timeout for the reply
+    pub async fn spo2_settings(&self) -> Result<bool> {
+        self.request_spo2_pref(Command::GetSpo2Pref).await
+    }
+
+    /// Turn automatic blood oxygen measurements on or off, returning whether the
+    /// ring reports them enabled once applied
+    pub async fn set_spo2_settings(&self, enabled: bool) -> Result<bool> {
+        self.request_spo2_pref(Command::SetSpo2Pref { enabled })
+            .await
+    }
+
+    /// Request whether the ring measures stress on its own, waiting up to the
+    /// request timeout for the reply
+    pub async fn stress_settings(&self) -> Result<bool> {
+        self.request_stress_pref(Command::GetStressPref).await
+    }
+
+    /// Turn automatic stress measurements on or off, returning whether the ring
+    /// reports them enabled once applied
+    pub async fn set_stress_settings(&self, enabled: bool) -> Result<bool> {
+        self.request_stress_pref(Command::SetStressPref { enabled })
+            .await
+    }
+
+    /// Read the preference stored under `key`, waiting up to the request timeout
+    /// for the reply
+    pub async fn preference(&self, key: PreferenceKey) -> Result<Preference> {
+        self.request_preference(key.read()).await
+    }
+
+    /// Store `preference`, returning the value the ring reports once it is
+    /// applied
+    pub async fn set_preference(&self, preference: &Preference) -> Result<Preference> {
+        self.request_preference(preference.write()).await
+    }
+
+    /// Remove the preference stored under `key` so the ring uses its default
+    pub async fn delete_preference(&self, key: PreferenceKey) -> Result {
+        self.request_preference(key.delete()).await?;
+        Ok(())
+    }
+
+    async fn request_preference(&self, command: Command) -> Result<Preference> {
+        match self.send_request(command).await? {
+            CommandReply::Preference { key, value } => Ok(Preference::decode(key, &value)),
+            reply => Err(Error::UnexpectedReply(Box::new(reply))),
         }
-        match (one, two) {
-            (Some(one), Some(two)) => Ok((one, two)),
-            (Some(_), None) => Err("failed to find v2 characteristic".into()),
-            (None, Some(_)) => Err("failed to find uart characteristic".into()),
-            (None, None) => Err("no characteristics found".into()),
+    }
+
+    async fn request_spo2_pref(&self, command: Command) -> Result<bool> {
+        match self.send_request(command).await? {
+            CommandReply::Spo2Pref { enabled } => Ok(enabled),
+            reply => Err(Error::UnexpectedReply(Box::new(reply))),
+        }
+    }
+
+    async fn request_stress_pref(&self, command: Command) -> Result<bool> {
+        match self.send_request(command).await? {
+            CommandReply::StressPref { enabled } => Ok(enabled),
+            reply => Err(Error::UnexpectedReply(Box::new(reply))),
+        }
+    }
+
+    async fn request_heart_rate_settings(&self, command: Command) -> Result<HeartRateSettings> {
+        match self.send_request(command).await? {
+            CommandReply::HeartRateSettings(settings) => Ok(settings),
+            reply => Err(Error::UnexpectedReply(Box::new(reply))),
         }
     }
 
     pub async fn device_details(&self) -> Result<DeviceDetails> {
-        let services = self.device.services().await?;
+        let Some(device) = self.device() else {
+            return Ok(DeviceDetails::default());
+        };
+        let services = device.services().await?;
         let service = services
             .into_iter()
             .find(|s| s.uuid() == crate::constants::DEVICE_INFO_UUID)
-            .ok_or_else(|| "Unable to find service with device info uuid".to_string())?;
+            .ok_or(Error::ServiceMissing {
+                uuid: crate::constants::DEVICE_INFO_UUID,
+            })?;
         let mut ret = DeviceDetails::default();
         for ch in service.characteristics() {
             if ch.uuid() == crate::constants::DEVICE_HW_UUID {
@@ -151,7 +1178,7 @@ impl Client {
     }
 }
 
-#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 #[serde(tag = "command", content = "data", rename_all = "camelCase")]
 pub enum Command {
     ReadSportDetail {
@@ -163,30 +1190,119 @@ pub enum Command {
     ReadStress {
         day_offset: u8,
     },
-    GetHeartRateSettings,
-    SetHeartRateSettings {
+    ReadHrv {
+        day_offset: u8,
+    },
+    SetAutoHrvPref {
         enabled: bool,
         interval: u8,
     },
+    GetHeartRateSettings,
+    SetHeartRateSettings(HeartRateSettings),
+    /// Ask whether the ring measures blood oxygen on its own through the day
+    GetSpo2Pref,
+    SetSpo2Pref {
+        enabled: bool,
+    },
+    /// Ask whether the ring measures stress on its own through the day
+    GetStressPref,
+    SetStressPref {
+        enabled: bool,
+    },
+    /// Read the user preference stored under `key`, see
+    /// [`PreferenceKey`](crate::preferences::PreferenceKey) for the known keys
+    ReadPreference {
+        key: u8,
+    },
+    /// Store `value` under `key`, at most 12 bytes fit in a packet
+    WritePreference {
+        key: u8,
+        value: Vec<u8>,
+    },
+    DeletePreference {
+        key: u8,
+    },
     StartRealTimeHeartRate,
     ContinueRealTimeHeartRate,
     StopRealTimeHeartRate,
     StartSpo2,
+    /// Keep a real time SpO2 reading going, the reading type followed by the
+    /// continue action
+    ContinueSpo2,
     StopSpo2,
     Reboot,
+    PowerOff,
+    /// Erase all data and settings on the ring
+    FactoryReset,
+    /// Set the ring's clock to the wall clock time of `when` in its own offset,
+    /// see [`Command::set_time`] to pick the offset
     SetTime {
         when: time::OffsetDateTime,
-        language: u8,
+        language: Language,
     },
     BlinkTwice,
+    /// Tell the ring the name of the phone it is connected to, names longer
+    /// than 12 bytes are truncated and anything but printable ASCII is an error
+    SetPhoneName(String),
+    /// Make the ring vibrate and flash so it can be found, a stronger signal
+    /// than [`Command::BlinkTwice`]
+    FindDevice,
     BatteryInfo,
     SyncOxygen,
     SyncSleep,
+    GetGoals,
+    /// Set the daily goals, each value is sent as a 24 bit integer and a larger
+    /// one is `Error::GoalTooLarge`
+    SetGoals {
+        steps: u32,
+        calories: u32,
+        distance: u32,
+    },
+    /// Bytes sent as the body of a packet, at most 15 fit before the checksum
     Raw(Vec<u8>),
+    /// Like [`Command::Raw`] but anything past 15 bytes is dropped instead of
+    /// being an error
+    RawUnchecked(Vec<u8>),
+    /// A whole packet sent exactly as given, without a checksum being applied,
+    /// for replaying captured packets
+    RawExact([u8; 16]),
+}
+
+impl Command {
+    /// Set the time to `when` as seen in `tz`, or in the offset `when` already has
+    /// if `tz` is `None`
+    pub fn set_time(when: OffsetDateTime, tz: Option<UtcOffset>, language: Language) -> Self {
+        let when = tz.map_or(when, |tz| when.to_offset(tz));
+        Self::SetTime { when, language }
+    }
+
+    /// If the ring is expected to drop the connection once it has this command,
+    /// which makes sending it again unsafe
+    #[cfg(feature = "ble")]
+    pub(crate) fn drops_connection(&self) -> bool {
+        matches!(self, Self::Reboot | Self::PowerOff | Self::FactoryReset)
+    }
+}
+
+/// The language the ring displays, sent along with the time
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Language {
+    Chinese = 0,
+    #[default]
+    English = 1,
+}
+
+impl From<Language> for u8 {
+    fn from(language: Language) -> u8 {
+        language as u8
+    }
 }
 
-impl From<Command> for [u8; 16] {
-    fn from(cmd: Command) -> [u8; 16] {
+impl TryFrom<Command> for [u8; 16] {
+    type Error = Error;
+
+    fn try_from(cmd: Command) -> Result<[u8; 16]> {
         let mut ret = [0u8; 16];
         match cmd {
             Command::ReadSportDetail { day_offset } => {
@@ -200,14 +1316,69 @@ impl From<Command> for [u8; 16] {
                 ret[0] = 55;
                 ret[1] = day_offset;
             }
+            Command::ReadHrv { day_offset } => {
+                ret[0..2].copy_from_slice(&[constants::CMD_SYNC_HRV, day_offset]);
+            }
+            Command::SetAutoHrvPref { enabled, interval } => {
+                ret[0..4].copy_from_slice(&[
+                    constants::CMD_AUTO_HRV_PREF,
+                    constants::PREF_WRITE,
+                    u8::from(enabled),
+                    interval,
+                ]);
+            }
             Command::GetHeartRateSettings => {
                 ret[0..2].copy_from_slice(&[22, 1]);
             }
-            Command::SetHeartRateSettings { enabled, interval } => {
+            Command::SetHeartRateSettings(HeartRateSettings { enabled, interval }) => {
                 ret[0] = 22;
                 ret[1] = 2;
                 ret[2] = if enabled { 1 } else { 2 };
-                ret[3] = interval;
+                ret[3] = interval.minutes();
+            }
+            Command::GetSpo2Pref => {
+                ret[0..2].copy_from_slice(&[constants::CMD_AUTO_SPO2_PREF, constants::PREF_READ]);
+            }
+            Command::SetSpo2Pref { enabled } => {
+                ret[0..3].copy_from_slice(&[
+                    constants::CMD_AUTO_SPO2_PREF,
+                    constants::PREF_WRITE,
+                    u8::from(enabled),
+                ]);
+            }
+            Command::GetStressPref => {
+                ret[0..2].copy_from_slice(&[constants::CMD_AUTO_STRESS_PREF, constants::PREF_READ]);
+            }
+            Command::SetStressPref { enabled } => {
+                ret[0..3].copy_from_slice(&[
+                    constants::CMD_AUTO_STRESS_PREF,
+                    constants::PREF_WRITE,
+                    u8::from(enabled),
+                ]);
+            }
+            Command::ReadPreference { key } => {
+                ret[0..3].copy_from_slice(&[constants::CMD_PREFERENCES, constants::PREF_READ, key]);
+            }
+            Command::WritePreference { key, value } => {
+                if value.len() > constants::PREF_VALUE_LEN {
+                    return Err(Error::CommandTooLong {
+                        length: value.len() + 3,
+                        max: 15,
+                    });
+                }
+                ret[0..3].copy_from_slice(&[
+                    constants::CMD_PREFERENCES,
+                    constants::PREF_WRITE,
+                    key,
+                ]);
+                ret[3..3 + value.len()].copy_from_slice(&value);
+            }
+            Command::DeletePreference { key } => {
+                ret[0..3].copy_from_slice(&[
+                    constants::CMD_PREFERENCES,
+                    constants::PREF_DELETE,
+                    key,
+                ]);
             }
             Command::StartRealTimeHeartRate => {
                 ret[0..2].copy_from_slice(&[105, 1]);
@@ -221,11 +1392,25 @@ impl From<Command> for [u8; 16] {
             Command::StartSpo2 => {
                 ret[0..3].copy_from_slice(&[105, 0x03, 0x25]);
             }
+            Command::ContinueSpo2 => {
+                ret[0..3].copy_from_slice(&[105, 0x03, 0x03]);
+            }
             Command::StopSpo2 => {
                 ret[0..2].copy_from_slice(&[106, 0x03]);
             }
             Command::Reboot => {
-                ret[0..2].copy_from_slice(&[8, 1]);
+                ret[0..2].copy_from_slice(&[constants::CMD_POWER_OFF, constants::POWER_OFF_REBOOT]);
+            }
+            Command::PowerOff => {
+                ret[0..2]
+                    .copy_from_slice(&[constants::CMD_POWER_OFF, constants::POWER_OFF_SHUTDOWN]);
+            }
+            Command::FactoryReset => {
+                ret[0..3].copy_from_slice(&[
+                    constants::CMD_FACTORY_RESET,
+                    constants::FACTORY_RESET_CONFIRM,
+                    constants::FACTORY_RESET_CONFIRM,
+                ]);
             }
             Command::SetTime { when, language } => {
                 ret[0..8].copy_from_slice(&[
@@ -237,12 +1422,25 @@ impl From<Command> for [u8; 16] {
                     when.hour(),
                     when.minute(),
                     when.second(),
-                    language,
+                    language.into(),
                 ]);
             }
             Command::BlinkTwice => {
                 ret[0] = 16;
             }
+            Command::SetPhoneName(name) => {
+                if !name.chars().all(|c| c.is_ascii_graphic() || c == ' ') {
+                    return Err(Error::InvalidPhoneName(name));
+                }
+                let name = &name.as_bytes()[..name.len().min(constants::PHONE_NAME_LEN)];
+                ret[0] = constants::CMD_PHONE_NAME;
+                ret[1..3].copy_from_slice(&constants::PHONE_NAME_HEADER);
+                ret[3..3 + name.len()].copy_from_slice(name);
+            }
+            Command::FindDevice => {
+                ret[0] = constants::CMD_FIND_DEVICE;
+                ret[1..3].copy_from_slice(&constants::FIND_DEVICE_PAYLOAD);
+            }
             Command::BatteryInfo => {
                 ret[0] = 3;
             }
@@ -264,66 +1462,716 @@ impl From<Command> for [u8; 16] {
                 ret[5] = 0;
                 ret[6] = 0xff;
             }
-            Command::Raw(mut bytes) => {
+            Command::GetGoals => {
+                ret[0..2].copy_from_slice(&[constants::CMD_GOALS, constants::PREF_READ]);
+            }
+            Command::SetGoals {
+                steps,
+                calories,
+                distance,
+            } => {
+                ret[0..2].copy_from_slice(&[constants::CMD_GOALS, constants::PREF_WRITE]);
+                for (i, value) in [steps, calories, distance].into_iter().enumerate() {
+                    if value > 0xff_ffff {
+                        return Err(Error::GoalTooLarge(value));
+                    }
+                    let start = 2 + i * 3;
+                    ret[start..start + 3].copy_from_slice(&value.to_le_bytes()[..3]);
+                }
+            }
+            Command::Raw(bytes) => {
+                if bytes.len() > 15 {
+                    return Err(Error::CommandTooLong {
+                        length: bytes.len(),
+                        max: 15,
+                    });
+                }
+                ret[..bytes.len()].copy_from_slice(&bytes);
+            }
+            Command::RawUnchecked(mut bytes) => {
                 if bytes.len() > 15 {
                     log::warn!("truncating message longer than 15 bytes");
                 }
                 bytes.resize(16, 0);
                 ret[0..15].copy_from_slice(&bytes[0..15]);
             }
+            Command::RawExact(bytes) => return Ok(bytes),
         }
         ret[15] = checksum(&ret);
-        ret
+        Ok(ret)
+    }
+}
+
+impl TryFrom<&[u8; 16]> for Command {
+    type Error = Error;
+
+    /// Decode a packet sent to a ring, packets that aren't exactly what this
+    /// crate would send for a command decode as [`Command::Raw`]
+    ///
+    /// The ring's clock has no offset so [`Command::SetTime`] decodes in UTC
+    fn try_from(bytes: &[u8; 16]) -> Result<Self> {
+        verify_checksum(bytes)?;
+        if let Some(cmd) = decode_command(bytes) {
+            // anything the encoder wouldn't reproduce, like extra trailing bytes,
+            // is kept as raw so nothing is lost
+            if <[u8; 16]>::try_from(cmd.clone()).is_ok_and(|encoded| &encoded == bytes) {
+                return Ok(cmd);
+            }
+        }
+        Ok(Command::Raw(bytes[..15].to_vec()))
     }
 }
 
-fn checksum(packet: &[u8]) -> u8 {
-    let sum: u32 = packet.iter().copied().map(|v| v as u32).sum();
-    let trunc = sum & 255;
-    trunc as u8
+fn decode_command(bytes: &[u8; 16]) -> Option<Command> {
+    Some(match bytes[..4] {
+        [constants::CMD_SYNC_ACTIVITY, day_offset, ..] => Command::ReadSportDetail { day_offset },
+        [constants::CMD_SYNC_HEART_RATE, ..] => Command::ReadHeartRate {
+            timestamp: u32::from_le_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]),
+        },
+        [constants::CMD_SYNC_STRESS, day_offset, ..] => Command::ReadStress { day_offset },
+        [constants::CMD_SYNC_HRV, day_offset, ..] => Command::ReadHrv { day_offset },
+        [constants::CMD_AUTO_HRV_PREF, constants::PREF_WRITE, enabled, interval] => {
+            Command::SetAutoHrvPref {
+                enabled: enabled != 0,
+                interval,
+            }
+        }
+        [constants::CMD_AUTO_HR_PREF, constants::PREF_READ, ..] => Command::GetHeartRateSettings,
+        [constants::CMD_AUTO_HR_PREF, constants::PREF_WRITE, enabled, interval] => {
+            Command::SetHeartRateSettings(HeartRateSettings {
+                enabled: enabled == 1,
+                interval: HrInterval::try_new(interval).ok()?,
+            })
+        }
+        [constants::CMD_AUTO_SPO2_PREF, constants::PREF_READ, ..] => Command::GetSpo2Pref,
+        [constants::CMD_AUTO_SPO2_PREF, constants::PREF_WRITE, enabled, _] => {
+            Command::SetSpo2Pref {
+                enabled: enabled == 1,
+            }
+        }
+        [constants::CMD_AUTO_STRESS_PREF, constants::PREF_READ, ..] => Command::GetStressPref,
+        [constants::CMD_AUTO_STRESS_PREF, constants::PREF_WRITE, enabled, _] => {
+            Command::SetStressPref {
+                enabled: enabled == 1,
+            }
+        }
+        [constants::CMD_PREFERENCES, constants::PREF_READ, key, _] => {
+            Command::ReadPreference { key }
+        }
+        [constants::CMD_PREFERENCES, constants::PREF_WRITE, key, _] => Command::WritePreference {
+            key,
+            value: trim_padding(&bytes[3..15]).to_vec(),
+        },
+        [constants::CMD_PREFERENCES, constants::PREF_DELETE, key, _] => {
+            Command::DeletePreference { key }
+        }
+        [constants::CMD_MANUAL_HEART_RATE, 0x01, ..] => Command::StartRealTimeHeartRate,
+        [constants::CMD_MANUAL_HEART_RATE, 0x03, 0x03, ..] => Command::ContinueSpo2,
+        [constants::CMD_MANUAL_HEART_RATE, 0x03, ..] => Command::StartSpo2,
+        [30, 3, ..] => Command::ContinueRealTimeHeartRate,
+        [constants::CMD_STOP_REAL_TIME, 0x01, ..] => Command::StopRealTimeHeartRate,
+        [constants::CMD_STOP_REAL_TIME, 0x03, ..] => Command::StopSpo2,
+        [constants::CMD_POWER_OFF, constants::POWER_OFF_REBOOT, ..] => Command::Reboot,
+        [constants::CMD_POWER_OFF, constants::POWER_OFF_SHUTDOWN, ..] => Command::PowerOff,
+        [constants::CMD_FACTORY_RESET, ..] => Command::FactoryReset,
+        [constants::CMD_SET_DATE_TIME, year, month, day] => {
+            let date = time::Date::from_calendar_date(
+                2000 + i32::from(year),
+                time::Month::try_from(month).ok()?,
+                day,
+            )
+            .ok()?;
+            let time = time::Time::from_hms(bytes[4], bytes[5], bytes[6]).ok()?;
+            let language = match bytes[7] {
+                0 => Language::Chinese,
+                1 => Language::English,
+                _ => return None,
+            };
+            Command::SetTime {
+                when: PrimitiveDateTime::new(date, time).assume_utc(),
+                language,
+            }
+        }
+        [constants::CMD_BLINK, ..] => Command::BlinkTwice,
+        [constants::CMD_FIND_DEVICE, 0x55, 0xaa, _] => Command::FindDevice,
+        [constants::CMD_PHONE_NAME, 0x02, 0x0a, _] => {
+            Command::SetPhoneName(String::from_utf8(trim_padding(&bytes[3..15]).to_vec()).ok()?)
+        }
+        [constants::CMD_BATTERY, ..] => Command::BatteryInfo,
+        [constants::CMD_BIG_DATA_V2, constants::BIG_DATA_TYPE_SLEEP, ..] => Command::SyncSleep,
+        [constants::CMD_BIG_DATA_V2, constants::BIG_DATA_TYPE_SPO2, ..] => Command::SyncOxygen,
+        [constants::CMD_GOALS, constants::PREF_READ, ..] => Command::GetGoals,
+        [constants::CMD_GOALS, constants::PREF_WRITE, ..] => {
+            let goal = |start: usize| {
+                u32::from_le_bytes([bytes[start], bytes[start + 1], bytes[start + 2], 0])
+            };
+            Command::SetGoals {
+                steps: goal(2),
+                calories: goal(5),
+                distance: goal(8),
+            }
+        }
+        _ => return None,
+    })
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "ble"))]
 mod tests {
 
     use std::collections::VecDeque;
 
     use time::macros::date;
 
-    use crate::incoming_messages::{
-        big_data::{BigDataPacket, BigDataState, SleepData},
-        RawPacket,
-    };
+    use crate::{
+        incoming_messages::{
+            big_data::{BigDataPacket, BigDataState, OxygenData, SleepData},
+            notification::{DataName, Notification},
+            RawPacket,
+        },
+        mock::MockTransport,
+        PacketKind, SleepStage,
+    };
+
+    use super::*;
+
+    /// `send_request` is meant to be called from several tasks sharing a client
+    #[allow(dead_code)]
+    fn client_can_be_shared(client: std::sync::Arc<Client>) {
+        fn assert_send<T: Send>(_: T) {}
+        assert_send(async move { client.send_request(Command::BatteryInfo).await });
+    }
+
+    fn fail_times(
+        failures: usize,
+        calls: &std::cell::Cell<usize>,
+    ) -> impl FnMut() -> std::future::Ready<Result<usize>> + '_ {
+        move || {
+            let call = calls.get();
+            calls.set(call + 1);
+            std::future::ready(if call < failures {
+                Err(Error::DeviceNotFound)
+            } else {
+                Ok(call)
+            })
+        }
+    }
+
+    #[test]
+    fn retry_backoff_doubles() {
+        let policy = RetryPolicy {
+            retries: 4,
+            backoff: Duration::from_millis(100),
+            deadline: None,
+        };
+        let delays: Vec<_> = (0..4).map(|attempt| policy.delay(attempt)).collect();
+        assert_eq!(
+            delays,
+            [100, 200, 400, 800].map(Duration::from_millis).to_vec()
+        );
+        assert!(policy.delay(u8::MAX) > policy.delay(4));
+    }
+
+    #[tokio::test]
+    async fn retry_until_success() {
+        let policy = RetryPolicy {
+            retries: 3,
+            backoff: Duration::from_millis(1),
+            deadline: None,
+        };
+        let calls = std::cell::Cell::new(0);
+        let value = policy.run("test", fail_times(2, &calls)).await.unwrap();
+        assert_eq!(value, 2);
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[tokio::test]
+    async fn retry_gives_up() {
+        let policy = RetryPolicy {
+            retries: 3,
+            backoff: Duration::from_millis(1),
+            deadline: None,
+        };
+        let calls = std::cell::Cell::new(0);
+        let err = policy
+            .run("test", fail_times(10, &calls))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::DeviceNotFound));
+        assert_eq!(calls.get(), 4);
+    }
+
+    #[tokio::test]
+    async fn scan_finds_first_device() {
+        let found = first_found(futures::stream::iter([1, 2]), Duration::from_millis(10))
+            .await
+            .unwrap();
+        assert_eq!(found, 1);
+    }
+
+    #[tokio::test]
+    async fn scan_attempt_times_out() {
+        let err = first_found(futures::stream::pending::<()>(), Duration::from_millis(10))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::DeviceNotFound), "{err:?}");
+        // a scan that ends without finding the ring
+        let err = first_found(futures::stream::empty::<()>(), Duration::from_secs(10))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::DeviceNotFound), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn connect_deadline_bounds_scans() {
+        let policy = RetryPolicy {
+            retries: 10,
+            backoff: Duration::from_millis(1),
+            deadline: Some(Duration::from_millis(50)),
+        };
+        let scans = std::cell::Cell::new(0);
+        let err = policy
+            .with_deadline(policy.run("scan", || {
+                scans.set(scans.get() + 1);
+                first_found(futures::stream::pending::<()>(), Duration::from_millis(20))
+            }))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Timeout), "{err:?}");
+        assert!((1..10).contains(&scans.get()), "{}", scans.get());
+        assert_eq!(
+            RetryPolicy::default().deadline,
+            Some(DEFAULT_CONNECT_TIMEOUT)
+        );
+    }
+
+    #[tokio::test]
+    async fn retry_deadline() {
+        let policy = RetryPolicy {
+            retries: 10,
+            backoff: Duration::from_millis(20),
+            deadline: Some(Duration::from_millis(50)),
+        };
+        let calls = std::cell::Cell::new(0);
+        let err = policy
+            .with_deadline(policy.run("test", fail_times(10, &calls)))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Timeout));
+        assert!(calls.get() < 10);
+    }
+
+    #[test]
+    fn commands_serialize() {
+        use Command::*;
+        let commands: Vec<[u8; 16]> = [
+            ReadSportDetail { day_offset: 0 },
+            ReadHeartRate { timestamp: 0 },
+            GetHeartRateSettings,
+            SetHeartRateSettings(HeartRateSettings {
+                enabled: false,
+                interval: HrInterval::try_new(0).unwrap(),
+            }),
+            StartRealTimeHeartRate,
+            ContinueRealTimeHeartRate,
+            StopRealTimeHeartRate,
+            StartSpo2,
+            StopSpo2,
+            Reboot,
+            SetTime {
+                when: time::OffsetDateTime::from_unix_timestamp(0).unwrap(),
+                language: Language::Chinese,
+            },
+            BlinkTwice,
+            BatteryInfo,
+        ]
+        .into_iter()
+        .map(|cmd| {
+            let bytes: [u8; 16] = cmd.try_into().unwrap();
+            bytes
+        })
+        .collect();
+        insta::assert_debug_snapshot!(commands);
+    }
+
+    #[test]
+    fn set_time_serializes_in_offset() {
+        let when = time::macros::datetime!(2024-12-31 22:45:10 UTC);
+        let commands: Vec<[u8; 16]> = [
+            None,
+            Some(time::macros::offset!(+05:30)),
+            Some(time::macros::offset!(+14:00)),
+            Some(time::macros::offset!(-08:00)),
+            Some(time::macros::offset!(-03:30)),
+        ]
+        .into_iter()
+        .map(|tz| {
+            Command::set_time(when, tz, Language::English)
+                .try_into()
+                .unwrap()
+        })
+        .collect();
+        insta::assert_debug_snapshot!(commands);
+    }
+
+    #[test]
+    fn goals_commands_serialize() {
+        let commands: Vec<[u8; 16]> = [
+            Command::GetGoals,
+            Command::SetGoals {
+                steps: 8000,
+                calories: 300_000,
+                distance: 6000,
+            },
+        ]
+        .into_iter()
+        .map(|cmd| {
+            let bytes: [u8; 16] = cmd.try_into().unwrap();
+            bytes
+        })
+        .collect();
+        insta::assert_debug_snapshot!(commands);
+    }
+
+    #[test]
+    fn goals_over_24_bits() {
+        let at_max: [u8; 16] = Command::SetGoals {
+            steps: 0xff_ffff,
+            calories: 0,
+            distance: 0,
+        }
+        .try_into()
+        .unwrap();
+        assert_eq!(at_max[2..5], [0xff; 3]);
+        let err = <[u8; 16]>::try_from(Command::SetGoals {
+            steps: 0,
+            calories: 0x100_0000,
+            distance: 0,
+        })
+        .unwrap_err();
+        assert!(matches!(err, Error::GoalTooLarge(0x100_0000)), "{err:?}");
+    }
+
+    #[test]
+    fn pref_commands_serialize() {
+        let commands: Vec<[u8; 16]> = [
+            Command::GetSpo2Pref,
+            Command::SetSpo2Pref { enabled: true },
+            Command::SetSpo2Pref { enabled: false },
+            Command::GetStressPref,
+            Command::SetStressPref { enabled: true },
+            Command::SetStressPref { enabled: false },
+        ]
+        .into_iter()
+        .map(|cmd| {
+            let bytes: [u8; 16] = cmd.try_into().unwrap();
+            bytes
+        })
+        .collect();
+        insta::assert_debug_snapshot!(commands);
+    }
+
+    #[test]
+    fn preference_commands_serialize() {
+        let commands: Vec<[u8; 16]> = [
+            PreferenceKey::TimeFormat.read(),
+            Preference::TimeFormat(crate::preferences::TimeFormat::TwelveHour).write(),
+            Preference::RaiseToWake(false).write(),
+            Command::WritePreference {
+                key: 0x7f,
+                value: (1..=12).collect(),
+            },
+            PreferenceKey::WearHand.delete(),
+        ]
+        .into_iter()
+        .map(|cmd| {
+            let bytes: [u8; 16] = cmd.try_into().unwrap();
+            bytes
+        })
+        .collect();
+        insta::assert_debug_snapshot!(commands);
+        let err = <[u8; 16]>::try_from(Command::WritePreference {
+            key: 1,
+            value: vec![1; 13],
+        })
+        .unwrap_err();
+        assert!(
+            matches!(
+                err,
+                Error::CommandTooLong {
+                    length: 16,
+                    max: 15
+                }
+            ),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn raw_commands_serialize() {
+        let bytes: [u8; 16] = Command::Raw(vec![3]).try_into().unwrap();
+        assert_eq!(bytes, <[u8; 16]>::try_from(Command::BatteryInfo).unwrap());
+        let err = <[u8; 16]>::try_from(Command::Raw(vec![1; 16])).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                Error::CommandTooLong {
+                    length: 16,
+                    max: 15
+                }
+            ),
+            "{err:?}"
+        );
+        let bytes: [u8; 16] = Command::RawUnchecked(vec![1; 20]).try_into().unwrap();
+        assert_eq!(bytes[..15], [1; 15]);
+        assert_eq!(bytes[15], checksum(&bytes[..15]));
+        let exact = [2; 16];
+        let bytes: [u8; 16] = Command::RawExact(exact).try_into().unwrap();
+        assert_eq!(bytes, exact);
+    }
+
+    /// Every variant the encoder produces, with a spread of field values
+    fn constructible_commands() -> impl Iterator<Item = Command> {
+        let days = [0u8, 1, 6, 29, 255];
+        let flags = [
+            (false, 0u8),
+            (true, 5),
+            (true, 30),
+            (true, 60),
+            (false, 255),
+        ];
+        let times = [
+            time::macros::datetime!(2000-01-01 00:00:00 UTC),
+            time::macros::datetime!(2024-12-31 23:59:59 UTC),
+            time::macros::datetime!(2099-06-15 12:30:45 UTC),
+        ];
+        let fixed = [
+            Command::GetHeartRateSettings,
+            Command::GetSpo2Pref,
+            Command::GetStressPref,
+            Command::StartRealTimeHeartRate,
+            Command::ContinueRealTimeHeartRate,
+            Command::StopRealTimeHeartRate,
+            Command::StartSpo2,
+            Command::ContinueSpo2,
+            Command::StopSpo2,
+            Command::Reboot,
+            Command::PowerOff,
+            Command::FactoryReset,
+            Command::BlinkTwice,
+            Command::FindDevice,
+            Command::SetPhoneName(String::new()),
+            Command::SetPhoneName("GB".to_string()),
+            Command::SetPhoneName("Pixel 8 Pro!".to_string()),
+            Command::BatteryInfo,
+            Command::SyncOxygen,
+            Command::SyncSleep,
+            Command::GetGoals,
+            Command::Raw((0x40..0x4f).collect()),
+        ];
+        days.into_iter()
+            .flat_map(|day_offset| {
+                [
+                    Command::ReadSportDetail { day_offset },
+                    Command::ReadStress { day_offset },
+                    Command::ReadHrv { day_offset },
+                ]
+            })
+            .chain(
+                [0, 1, 1_732_665_600, u32::MAX]
+                    .map(|timestamp| Command::ReadHeartRate { timestamp }),
+            )
+            .chain(flags.map(|(enabled, interval)| Command::SetAutoHrvPref { enabled, interval }))
+            .chain([false, true].into_iter().flat_map(|enabled| {
+                [
+                    Command::SetSpo2Pref { enabled },
+                    Command::SetStressPref { enabled },
+                ]
+            }))
+            .chain([0u8, 1, 0x7f, 255].into_iter().flat_map(|key| {
+                [
+                    Command::ReadPreference { key },
+                    Command::WritePreference { key, value: vec![] },
+                    Command::WritePreference {
+                        key,
+                        value: vec![1],
+                    },
+                    Command::WritePreference {
+                        key,
+                        value: vec![0, 2, 0, 3],
+                    },
+                    Command::WritePreference {
+                        key,
+                        value: vec![0xff; 12],
+                    },
+                    Command::DeletePreference { key },
+                ]
+            }))
+            .chain(flags.into_iter().filter_map(|(enabled, interval)| {
+                let interval = HrInterval::try_new(interval).ok()?;
+                Some(Command::SetHeartRateSettings(HeartRateSettings {
+                    enabled,
+                    interval,
+                }))
+            }))
+            .chain(times.into_iter().flat_map(|when| {
+                [Language::Chinese, Language::English]
+                    .map(|language| Command::SetTime { when, language })
+            }))
+            .chain(
+                [(0, 0, 0), (8000, 300_000, 6000), (0xff_ffff, 1, 0xff_ffff)].map(
+                    |(steps, calories, distance)| Command::SetGoals {
+                        steps,
+                        calories,
+                        distance,
+                    },
+                ),
+            )
+            .chain(fixed)
+    }
+
+    #[test]
+    fn commands_round_trip() {
+        for cmd in constructible_commands() {
+            let bytes: [u8; 16] = cmd.clone().try_into().unwrap();
+            assert_eq!(Command::try_from(&bytes).unwrap(), cmd, "{bytes:?}");
+        }
+    }
+
+    #[test]
+    fn captured_commands_decode() {
+        let captured: [[u8; 16]; 5] = [
+            // vendor app setting the time, 2024-11-27 08:15:00 English
+            [1, 24, 11, 27, 8, 15, 0, 1, 0, 0, 0, 0, 0, 0, 0, 87],
+            // vendor app syncing heart rate since 2024-11-27 00:00 UTC
+            [21, 0x00, 0x61, 0x46, 0x67, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 35],
+            // vendor app turning on auto heart rate every 5 minutes
+            [22, 2, 1, 5, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 30],
+            // vendor app asking for the packet size
+            [0x2f, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 47],
+            // battery request with a stray trailing byte
+            [3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 4],
+        ];
+        let decoded: Vec<_> = captured
+            .iter()
+            .map(|bytes| Command::try_from(bytes).unwrap())
+            .collect();
+        assert_eq!(
+            decoded,
+            vec![
+                Command::SetTime {
+                    when: time::macros::datetime!(2024-11-27 08:15:00 UTC),
+                    language: Language::English,
+                },
+                Command::ReadHeartRate {
+                    timestamp: 1_732_665_600
+                },
+                Command::SetHeartRateSettings(HeartRateSettings {
+                    enabled: true,
+                    interval: HrInterval::try_new(5).unwrap(),
+                }),
+                Command::Raw(captured[3][..15].to_vec()),
+                Command::Raw(captured[4][..15].to_vec()),
+            ]
+        );
+        let mut bad = captured[0];
+        bad[15] ^= 1;
+        let err = Command::try_from(&bad).unwrap_err();
+        assert!(matches!(err, Error::Checksum { .. }), "{err:?}");
+    }
+
+    #[test]
+    fn power_commands_serialize() {
+        let commands: Vec<[u8; 16]> = [Command::Reboot, Command::PowerOff, Command::FactoryReset]
+            .into_iter()
+            .map(|cmd| {
+                let bytes: [u8; 16] = cmd.try_into().unwrap();
+                bytes
+            })
+            .collect();
+        insta::assert_debug_snapshot!(commands);
+    }
+
+    #[tokio::test]
+    async fn parse_reply_power_acks() {
+        let packets = [
+            [constants::CMD_POWER_OFF, constants::POWER_OFF_REBOOT],
+            [constants::CMD_POWER_OFF, constants::POWER_OFF_SHUTDOWN],
+            [
+                constants::CMD_FACTORY_RESET,
+                constants::FACTORY_RESET_CONFIRM,
+            ],
+        ]
+        .map(|prefix| {
+            let mut packet = [0u8; 16];
+            packet[0..2].copy_from_slice(&prefix);
+            packet[15] = checksum(&packet);
+            RawPacket::Uart(packet.to_vec())
+        });
+        let mut rx = ClientReceiver::from_stream(Box::pin(futures::stream::iter(packets)));
+        assert_eq!(rx.next().await.unwrap(), CommandReply::Reboot);
+        assert_eq!(rx.next().await.unwrap(), CommandReply::PowerOff);
+        assert_eq!(rx.next().await.unwrap(), CommandReply::FactoryReset);
+        assert!(rx.next().await.is_none());
+    }
+
+    #[test]
+    fn find_device_serializes() {
+        let bytes: [u8; 16] = Command::FindDevice.try_into().unwrap();
+        insta::assert_debug_snapshot!(bytes);
+    }
+
+    #[test]
+    fn phone_name_serializes() {
+        let commands: Vec<[u8; 16]> = ["GB", "Pixel 8 Pro!", "Pixel 8 Pro XL"]
+            .into_iter()
+            .map(|name| Command::SetPhoneName(name.to_string()).try_into().unwrap())
+            .collect();
+        insta::assert_debug_snapshot!(commands);
+        // too long names are cut off after 12 bytes
+        assert_eq!(commands[2][3..15], *b"Pixel 8 Pro ");
+        assert_eq!(
+            Command::try_from(&commands[2]).unwrap(),
+            Command::SetPhoneName("Pixel 8 Pro ".to_string())
+        );
+        for name in ["Zoë's phone", "tab\there", "nul\0"] {
+            let err = <[u8; 16]>::try_from(Command::SetPhoneName(name.to_string())).unwrap_err();
+            assert!(
+                matches!(&err, Error::InvalidPhoneName(n) if n == name),
+                "{err:?}"
+            );
+        }
+    }
 
-    use super::*;
+    #[tokio::test]
+    async fn find_device_ack() {
+        // the ring echoes the command back once it starts buzzing
+        let mock = MockTransport::new()
+            .with_responder(|_, bytes| vec![RawPacket::Uart(make_packet(&bytes[..3]))]);
+        let mut client = Client::with_transport(mock.clone());
+        let reply = client
+            .send_and_wait(
+                Command::FindDevice,
+                |reply| matches!(reply, CommandReply::FindDevice),
+                Duration::from_secs(1),
+            )
+            .await
+            .unwrap();
+        assert_eq!(reply, Some(CommandReply::FindDevice));
+        assert_eq!(mock.written()[0].1[..3], [0x50, 0x55, 0xaa]);
+    }
 
     #[test]
-    fn commands_serialize() {
-        use Command::*;
+    fn hrv_commands_serialize() {
         let commands: Vec<[u8; 16]> = [
-            ReadSportDetail { day_offset: 0 },
-            ReadHeartRate { timestamp: 0 },
-            GetHeartRateSettings,
-            SetHeartRateSettings {
-                enabled: false,
-                interval: 0,
-            },
-            StartRealTimeHeartRate,
-            ContinueRealTimeHeartRate,
-            StopRealTimeHeartRate,
-            StartSpo2,
-            StopSpo2,
-            Reboot,
-            SetTime {
-                when: time::OffsetDateTime::from_unix_timestamp(0).unwrap(),
-                language: 0,
+            Command::ReadHrv { day_offset: 1 },
+            Command::SetAutoHrvPref {
+                enabled: true,
+                interval: 30,
             },
-            BlinkTwice,
-            BatteryInfo,
         ]
         .into_iter()
         .map(|cmd| {
-            let bytes: [u8; 16] = cmd.into();
+            let bytes: [u8; 16] = cmd.try_into().unwrap();
             bytes
         })
         .collect();
@@ -331,62 +2179,440 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn parse_reply_battery_not_charging() {
-        let expected = CommandReply::BatteryInfo {
-            charging: false,
-            level: 1,
+    async fn parse_reply_hrv() {
+        let packets = [
+            *b"\x39\x00\x02\x1e\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x59",
+            *b"\x39\x01\x1e\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x2a\x30\xb2",
+        ]
+        .map(|p| RawPacket::Uart(p.to_vec()));
+        let mut rx = ClientReceiver::from_stream(Box::pin(futures::stream::iter(packets)));
+        let CommandReply::Hrv {
+            time_interval_sec,
+            readings,
+        } = rx.next().await.unwrap()
+        else {
+            panic!("expected hrv reply");
         };
+        assert_eq!(time_interval_sec, 30 * 60);
+        assert_eq!(readings.len(), 12);
+        assert_eq!(&readings[10..], &[42, 48]);
+    }
 
-        let mut packet = [0u8; 16];
-        packet[0] = 3;
-        packet[1] = 1;
+    #[tokio::test]
+    async fn parse_reply_goals() {
+        // captured from a ring with the default goals of 8000 steps,
+        // 300 kcal and 6 km
+        let packet = [
+            0x21, 0x01, 0x40, 0x1f, 0x00, 0xe0, 0x93, 0x04, 0x70, 0x17, 0x00, 0x3c, 0x00, 0xe0,
+            0x01, 0x9c,
+        ];
         let mut rx = ClientReceiver::from_stream(Box::pin(futures::stream::once(async move {
             RawPacket::Uart(packet.to_vec())
         })));
         let parsed = rx.next().await.unwrap();
-        assert_eq!(parsed, expected);
+        assert_eq!(
+            parsed,
+            CommandReply::Goals {
+                steps: 8000,
+                calories: 300_000,
+                distance: 6000,
+            }
+        );
+    }
+
+    /// A client whose ring answers every command with `reply`
+    fn mock_client(reply: Vec<u8>) -> (MockTransport, Client) {
+        let mock =
+            MockTransport::new().with_responder(move |_, _| vec![RawPacket::Uart(reply.clone())]);
+        (mock.clone(), Client::with_transport(mock))
     }
 
     #[tokio::test]
-    async fn parse_reply_battery_charging() {
-        let expected = CommandReply::BatteryInfo {
-            charging: true,
-            level: 2,
-        };
+    async fn battery_not_charging_round_trip() {
+        let (mock, client) = mock_client(make_packet(&[3, 1]));
+        let reply = client.send_request(Command::BatteryInfo).await.unwrap();
+        assert_eq!(
+            reply,
+            CommandReply::BatteryInfo {
+                charging: false,
+                level: 1,
+            }
+        );
+        assert_eq!(
+            mock.written(),
+            vec![(
+                Channel::Uart,
+                <[u8; 16]>::try_from(Command::BatteryInfo).unwrap().to_vec()
+            )]
+        );
+    }
 
-        let mut packet = [0u8; 16];
-        packet[0] = 3;
-        packet[1] = 2;
-        packet[2] = 1;
-        let mut rx = ClientReceiver::from_stream(Box::pin(futures::stream::once(async move {
-            RawPacket::Uart(packet.to_vec())
-        })));
-        let parsed = rx.next().await.unwrap();
-        assert_eq!(parsed, expected);
+    #[tokio::test]
+    async fn battery_charging_round_trip() {
+        let (_, mut client) = mock_client(make_packet(&[3, 2, 1]));
+        let reply = client
+            .send_and_wait(
+                Command::BatteryInfo,
+                |r| matches!(r, CommandReply::BatteryInfo { .. }),
+                Duration::from_secs(1),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            reply,
+            Some(CommandReply::BatteryInfo {
+                charging: true,
+                level: 2,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn set_time_returns_echoed_time() {
+        let (mock, client) = mock_client(make_packet(&[1, 24, 12, 31, 17, 15, 12]));
+        let when = time::macros::datetime!(2024-12-31 22:45:10 UTC);
+        let device_time = client
+            .set_time(when, Some(time::macros::offset!(-05:30)), Language::Chinese)
+            .await
+            .unwrap();
+        assert_eq!(
+            device_time,
+            Some(time::macros::datetime!(2024-12-31 17:15:12))
+        );
+        let written = &mock.written()[0].1;
+        assert_eq!(&written[..8], &[1, 24, 12, 31, 17, 15, 10, 0]);
+    }
+
+    #[tokio::test]
+    async fn set_time_without_echo() {
+        let (_, client) = mock_client(make_packet(&[1]));
+        let when = time::macros::datetime!(2024-12-31 22:45:10 UTC);
+        let device_time = client
+            .set_time(when, None, Language::English)
+            .await
+            .unwrap();
+        assert_eq!(device_time, None);
+    }
+
+    #[tokio::test]
+    async fn heart_rate_settings_disabled_round_trip() {
+        let (mock, client) = mock_client(make_packet(&[22, 0, 2]));
+        let reply = client
+            .send_request(Command::GetHeartRateSettings)
+            .await
+            .unwrap();
+        assert_eq!(
+            reply,
+            CommandReply::HeartRateSettings(HeartRateSettings {
+                enabled: false,
+                interval: HrInterval::try_new(0).unwrap(),
+            })
+        );
+        assert_eq!(mock.written()[0].1[..2], [22, 1]);
     }
 
     #[tokio::test]
-    async fn parse_reply_hear_rate_settings_disabled() {
-        let expected = CommandReply::HeartRateSettings {
-            enabled: false,
-            interval: 0,
+    async fn heart_rate_settings_enabled_round_trip() {
+        let (_, client) = mock_client(make_packet(&[22, 0, 1, 60]));
+        let reply = client
+            .send_request(Command::GetHeartRateSettings)
+            .await
+            .unwrap();
+        assert_eq!(
+            reply,
+            CommandReply::HeartRateSettings(HeartRateSettings {
+                enabled: true,
+                interval: HrInterval::try_new(60).unwrap(),
+            })
+        );
+    }
+
+    #[test]
+    fn hr_interval_bounds() {
+        for minutes in [0, 5, 10, 55, 60] {
+            assert_eq!(HrInterval::try_new(minutes).unwrap().minutes(), minutes);
+        }
+        for minutes in [1, 4, 7, 59, 61, 65, 127, 255] {
+            let err = HrInterval::try_new(minutes).unwrap_err();
+            assert!(
+                matches!(err, Error::InvalidHrInterval(m) if m == minutes),
+                "{err:?}"
+            );
+        }
+        let allowed: Vec<u8> = HrInterval::allowed().map(HrInterval::minutes).collect();
+        assert_eq!(allowed, (0..=60).step_by(5).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn hr_interval_serde() {
+        let settings = HeartRateSettings {
+            enabled: true,
+            interval: HrInterval::try_new(15).unwrap(),
         };
-        let stream = futures::stream::iter([RawPacket::Uart(make_packet(&[22, 0, 2]))]);
-        let mut rx = ClientReceiver::from_stream(Box::pin(stream));
-        let parsed = rx.next().await.unwrap();
-        assert_eq!(parsed, expected);
+        let json = serde_json::to_string(&settings).unwrap();
+        assert_eq!(json, r#"{"enabled":true,"interval":15}"#);
+        assert_eq!(
+            serde_json::from_str::<HeartRateSettings>(&json).unwrap(),
+            settings
+        );
+        assert!(
+            serde_json::from_str::<HeartRateSettings>(r#"{"enabled":true,"interval":7}"#).is_err()
+        );
+    }
+
+    #[test]
+    fn invalid_heart_rate_interval_is_not_decoded() {
+        let bytes: [u8; 16] = Command::RawUnchecked(vec![22, 2, 1, 7]).try_into().unwrap();
+        assert_eq!(
+            Command::try_from(&bytes).unwrap(),
+            Command::Raw(bytes[..15].to_vec())
+        );
+    }
+
+    #[tokio::test]
+    async fn battery_getter() {
+        let (_, client) = mock_client(make_packet(&[3, 64, 1]));
+        assert_eq!(
+            client.battery().await.unwrap(),
+            BatteryInfo {
+                level: 64,
+                charging: true
+            }
+        );
     }
 
     #[tokio::test]
-    async fn parse_reply_hear_rate_settings_enabled() {
-        let expected = CommandReply::HeartRateSettings {
+    async fn heart_rate_settings_getters() {
+        let mock = MockTransport::new().with_responder(|_, bytes| {
+            // the ring echoes the settings back after a write
+            let reply = match bytes[1] {
+                constants::PREF_WRITE => make_packet(&bytes[..4]),
+                _ => make_packet(&[22, 1, 2, 10]),
+            };
+            vec![RawPacket::Uart(reply)]
+        });
+        let client = Client::with_transport(mock.clone());
+        assert_eq!(
+            client.heart_rate_settings().await.unwrap(),
+            HeartRateSettings {
+                enabled: false,
+                interval: HrInterval::try_new(10).unwrap(),
+            }
+        );
+        let settings = HeartRateSettings {
             enabled: true,
-            interval: 127,
+            interval: HrInterval::try_new(30).unwrap(),
         };
-        let stream = futures::stream::iter([RawPacket::Uart(make_packet(&[22, 0, 1, 127]))]);
-        let mut rx = ClientReceiver::from_stream(Box::pin(stream));
-        let parsed = rx.next().await.unwrap();
-        assert_eq!(parsed, expected);
+        assert_eq!(
+            client.set_heart_rate_settings(settings).await.unwrap(),
+            settings
+        );
+        assert_eq!(mock.written()[1].1[..4], [22, 2, 1, 30]);
+    }
+
+    #[tokio::test]
+    async fn auto_pref_getters() {
+        let mock = MockTransport::new().with_responder(|_, bytes| {
+            // the ring echoes the setting back after a write
+            let reply = match bytes[1] {
+                constants::PREF_WRITE => make_packet(&bytes[..3]),
+                _ => make_packet(&[bytes[0], constants::PREF_READ, 1]),
+            };
+            vec![RawPacket::Uart(reply)]
+        });
+        let client = Client::with_transport(mock.clone());
+        assert!(client.spo2_settings().await.unwrap());
+        assert!(!client.set_spo2_settings(false).await.unwrap());
+        assert!(client.stress_settings().await.unwrap());
+        assert!(client.set_stress_settings(true).await.unwrap());
+        let written: Vec<_> = mock
+            .written()
+            .iter()
+            .map(|(_, b)| b[..3].to_vec())
+            .collect();
+        assert_eq!(
+            written,
+            [
+                [constants::CMD_AUTO_SPO2_PREF, constants::PREF_READ, 0],
+                [constants::CMD_AUTO_SPO2_PREF, constants::PREF_WRITE, 0],
+                [constants::CMD_AUTO_STRESS_PREF, constants::PREF_READ, 0],
+                [constants::CMD_AUTO_STRESS_PREF, constants::PREF_WRITE, 1],
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn preference_getters() {
+        let mock = MockTransport::new().with_responder(|_, bytes| {
+            // the ring echoes the key and value back, deleting resets to 0
+            let reply = match bytes[1] {
+                constants::PREF_READ => make_packet(&[bytes[0], bytes[1], bytes[2], 1]),
+                constants::PREF_WRITE => make_packet(&bytes[..15]),
+                _ => make_packet(&bytes[..3]),
+            };
+            vec![RawPacket::Uart(reply)]
+        });
+        let client = Client::with_transport(mock.clone());
+        assert_eq!(
+            client.preference(PreferenceKey::WearHand).await.unwrap(),
+            Preference::WearHand(crate::preferences::Hand::Right)
+        );
+        let units = Preference::Units(crate::preferences::Units::Imperial);
+        assert_eq!(client.set_preference(&units).await.unwrap(), units);
+        let unknown = Preference::Unknown {
+            key: 0x7f,
+            value: vec![4, 0, 5],
+        };
+        assert_eq!(client.set_preference(&unknown).await.unwrap(), unknown);
+        client
+            .delete_preference(PreferenceKey::RaiseToWake)
+            .await
+            .unwrap();
+        let written: Vec<_> = mock
+            .written()
+            .iter()
+            .map(|(_, b)| b[..6].to_vec())
+            .collect();
+        assert_eq!(
+            written,
+            [
+                [constants::CMD_PREFERENCES, constants::PREF_READ, 3, 0, 0, 0],
+                [
+                    constants::CMD_PREFERENCES,
+                    constants::PREF_WRITE,
+                    2,
+                    1,
+                    0,
+                    0
+                ],
+                [
+                    constants::CMD_PREFERENCES,
+                    constants::PREF_WRITE,
+                    0x7f,
+                    4,
+                    0,
+                    5
+                ],
+                [
+                    constants::CMD_PREFERENCES,
+                    constants::PREF_DELETE,
+                    4,
+                    0,
+                    0,
+                    0
+                ],
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn getter_times_out() {
+        let client = Client::builder()
+            .request_timeout(Duration::from_millis(50))
+            .build_with_transport(MockTransport::new());
+        let err = client.battery().await.unwrap_err();
+        assert!(matches!(err, Error::Timeout), "{err:?}");
+        let err = client.heart_rate_settings().await.unwrap_err();
+        assert!(matches!(err, Error::Timeout), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn getter_skips_interleaved_replies() {
+        let mock = MockTransport::new().with_responder(|_, _| {
+            vec![
+                RawPacket::Uart(make_packet(&[
+                    constants::CMD_NOTIFICATION,
+                    constants::NOTIFICATION_BATTERY_LEVEL,
+                    42,
+                ])),
+                RawPacket::Uart(make_packet(&[16])),
+                RawPacket::Uart(make_packet(&[3, 42])),
+            ]
+        });
+        let client = Client::with_transport(mock);
+        let mut notifications = client.notifications();
+        assert_eq!(
+            client.battery().await.unwrap(),
+            BatteryInfo {
+                level: 42,
+                charging: false
+            }
+        );
+        assert!(matches!(
+            notifications.recv().await.unwrap(),
+            CommandReply::Notification(_)
+        ));
+        assert_eq!(
+            notifications.recv().await.unwrap(),
+            CommandReply::BlinkTwice
+        );
+    }
+
+    #[tokio::test]
+    async fn getter_rejects_unparsed_reply() {
+        // an interval read with neither enabled nor disabled isn't understood
+        let (_, client) = mock_client(make_packet(&[22, 1, 0, 5]));
+        let err = client.heart_rate_settings().await.unwrap_err();
+        assert!(matches!(err, Error::UnexpectedReply(_)), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn oversized_raw_command_is_not_sent() {
+        let mock = MockTransport::new();
+        let mut client = Client::with_transport(mock.clone());
+        let err = client.send(Command::Raw(vec![1; 16])).await.unwrap_err();
+        assert!(matches!(err, Error::CommandTooLong { .. }), "{err:?}");
+        let err = client
+            .send_request(Command::Raw(vec![1; 16]))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::CommandTooLong { .. }), "{err:?}");
+        assert!(mock.written().is_empty());
+    }
+
+    #[tokio::test]
+    async fn commands_are_routed_by_characteristic() {
+        let mock = MockTransport::new();
+        let mut client = Client::with_transport(mock.clone());
+        client.send(Command::BatteryInfo).await.unwrap();
+        client.send(Command::SyncSleep).await.unwrap();
+        client.send(Command::SyncOxygen).await.unwrap();
+        client
+            .send(Command::Raw(vec![constants::CMD_NOTIFICATION, 1]))
+            .await
+            .unwrap();
+        client.send(Command::BlinkTwice).await.unwrap();
+        let channels: Vec<_> = mock
+            .written()
+            .into_iter()
+            .map(|(chan, bytes)| (chan, bytes[0]))
+            .collect();
+        assert_eq!(
+            channels,
+            vec![
+                (Channel::Uart, constants::CMD_BATTERY),
+                (Channel::V2, constants::CMD_BIG_DATA_V2),
+                (Channel::V2, constants::CMD_BIG_DATA_V2),
+                (Channel::V2, constants::CMD_NOTIFICATION),
+                (Channel::Uart, constants::CMD_BLINK),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn read_next_connects_and_disconnect_reaches_transport() {
+        let mock = MockTransport::new();
+        mock.push(RawPacket::Uart(make_packet(&[3, 80])));
+        let mut client = Client::with_transport(mock.clone());
+        assert_eq!(
+            client.read_next().await.unwrap(),
+            Some(CommandReply::BatteryInfo {
+                level: 80,
+                charging: false
+            })
+        );
+        client.disconnect().await.unwrap();
+        assert_eq!(mock.disconnects(), 1);
     }
 
     #[tokio::test]
@@ -424,13 +2650,7 @@ mod tests {
                 );
             }
         };
-        let mut sleep_data: SleepData = packet.try_into().unwrap();
-        sleep_data.sessions[0].start = sleep_data.sessions[0]
-            .start
-            .replace_date(date!(2024 - 11 - 26));
-        sleep_data.sessions[0].end = sleep_data.sessions[0]
-            .end
-            .replace_date(date!(2024 - 11 - 27));
+        let sleep_data = SleepData::parse(&packet, date!(2024 - 11 - 28)).unwrap();
         insta::assert_debug_snapshot!(sleep_data);
     }
 
@@ -439,13 +2659,14 @@ mod tests {
         env_logger::builder().is_test(true).try_init().ok();
         let expected_dates = [
             date!(2024 - 11 - 22),
-            date!(2024 - 11 - 23),
+            date!(2024 - 11 - 22),
             date!(2024 - 11 - 24),
+            date!(2024 - 11 - 24),
+            date!(2024 - 11 - 25),
             date!(2024 - 11 - 25),
+            // the last session starts before midnight
             date!(2024 - 11 - 25),
             date!(2024 - 11 - 26),
-            date!(2024 - 11 - 27),
-            date!(2024 - 11 - 27),
         ];
         let packet = vec![
             5u8, 6, 26, 177, 0, 11, 2, 2, 67, 3, 35, 2, 15, 4, 34, 2, 95, 3, 16, 2, 1, 5, 13, 2,
@@ -456,17 +2677,669 @@ mod tests {
             33, 2, 101, 3, 32, 2, 17, 4, 15, 2, 32, 3, 18, 2, 29, 5, 13, 2, 23, 1, 12, 66, 0, 214,
             0, 2, 72, 3, 30, 2, 17, 4, 29,
         ];
-        let mut dates = expected_dates.iter().copied();
-        let mut sleep_data: SleepData = BigDataPacket::Sleep(packet).try_into().unwrap();
-        for session in sleep_data.sessions.iter_mut() {
-            let date = dates.next().unwrap();
-            session.start = session.start.replace_date(date);
-            let date = dates.next().unwrap();
-            session.end = session.end.replace_date(date);
-        }
+        let sleep_data =
+            SleepData::parse(&BigDataPacket::Sleep(packet), date!(2024 - 11 - 28)).unwrap();
+        let dates: Vec<_> = sleep_data
+            .sessions
+            .iter()
+            .flat_map(|s| [s.start.date(), s.end.date()])
+            .collect();
+        assert_eq!(dates, expected_dates);
         insta::assert_debug_snapshot!(&sleep_data)
     }
 
+    #[tokio::test]
+    async fn wait_for_holds_unmatched_replies() {
+        let stream = futures::stream::iter([
+            RawPacket::Uart(make_packet(&[3, 80, 0])),
+            RawPacket::Uart(make_packet(&[16])),
+            RawPacket::Uart(make_packet(&[22, 0, 1, 30])),
+            RawPacket::Uart(make_packet(&[1])),
+        ]);
+        let mut rx = ClientReceiver::from_stream(Box::pin(stream));
+        let reply = rx
+            .wait_for(
+                |r| matches!(r, CommandReply::HeartRateSettings(_)),
+                Duration::from_secs(1),
+            )
+            .await;
+        assert_eq!(
+            reply,
+            Some(CommandReply::HeartRateSettings(HeartRateSettings {
+                enabled: true,
+                interval: HrInterval::try_new(30).unwrap(),
+            }))
+        );
+        assert_eq!(
+            rx.next().await,
+            Some(CommandReply::BatteryInfo {
+                level: 80,
+                charging: false
+            })
+        );
+        assert_eq!(rx.next().await, Some(CommandReply::BlinkTwice));
+        assert_eq!(
+            rx.next().await,
+            Some(CommandReply::SetTime { device_time: None })
+        );
+        assert_eq!(rx.next().await, None);
+    }
+
+    #[tokio::test]
+    async fn wait_for_checks_held_replies_first() {
+        let stream = futures::stream::iter([
+            RawPacket::Uart(make_packet(&[16])),
+            RawPacket::Uart(make_packet(&[3, 80, 0])),
+        ]);
+        let mut rx = ClientReceiver::from_stream(Box::pin(stream));
+        let battery = rx
+            .wait_for(
+                |r| matches!(r, CommandReply::BatteryInfo { .. }),
+                Duration::from_secs(1),
+            )
+            .await;
+        assert!(battery.is_some());
+        let blink = rx
+            .wait_for(
+                |r| matches!(r, CommandReply::BlinkTwice),
+                Duration::from_secs(1),
+            )
+            .await;
+        assert_eq!(blink, Some(CommandReply::BlinkTwice));
+    }
+
+    #[tokio::test]
+    async fn wait_for_timeout_is_none() {
+        let stream = futures::stream::iter([RawPacket::Uart(make_packet(&[16]))])
+            .chain(futures::stream::pending());
+        let mut rx = ClientReceiver::from_stream(Box::pin(stream));
+        let reply = rx
+            .wait_for(
+                |r| matches!(r, CommandReply::BatteryInfo { .. }),
+                Duration::from_millis(50),
+            )
+            .await;
+        assert_eq!(reply, None);
+        assert_eq!(rx.next().await, Some(CommandReply::BlinkTwice));
+    }
+
+    #[tokio::test]
+    async fn parse_reply_notification() {
+        let stream = futures::stream::iter([
+            RawPacket::Uart(make_packet(&[
+                constants::CMD_NOTIFICATION,
+                constants::NOTIFICATION_NEW_HR_DATA,
+            ])),
+            RawPacket::Uart(make_packet(&[
+                constants::CMD_NOTIFICATION,
+                constants::NOTIFICATION_BATTERY_LEVEL,
+                42,
+            ])),
+        ]);
+        let mut rx = ClientReceiver::from_stream(Box::pin(stream));
+        let reply = rx.next().await.unwrap();
+        assert_eq!(
+            reply,
+            CommandReply::Notification(Notification::NewData(DataName::HeartRate))
+        );
+        let json = serde_json::to_string(&reply).unwrap();
+        assert_eq!(serde_json::from_str::<CommandReply>(&json).unwrap(), reply);
+        assert_eq!(
+            rx.next().await.unwrap(),
+            CommandReply::Notification(Notification::Battery(42))
+        );
+    }
+
+    #[tokio::test]
+    async fn flipped_bit_is_rejected() {
+        let mut corrupted = make_packet(&[3, 80, 0]);
+        corrupted[1] ^= 0b0000_0100;
+        let stream = futures::stream::iter([
+            RawPacket::Uart(corrupted),
+            RawPacket::Uart(make_packet(&[3, 80, 0])),
+        ]);
+        let mut rx = ClientReceiver::from_stream(Box::pin(stream));
+        let err = rx.try_next().await.unwrap().unwrap_err();
+        assert!(
+            matches!(err, Error::Checksum { expected: 87, .. }),
+            "{err:?}"
+        );
+        assert_eq!(
+            rx.try_next().await.unwrap().unwrap(),
+            CommandReply::BatteryInfo {
+                level: 80,
+                charging: false
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn flipped_bit_does_not_corrupt_multi_packet() {
+        let mut packets = [
+            [67, 240, 6, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 58],
+            [67, 36, 17, 34, 60, 0, 6, 159, 0, 33, 0, 22, 0, 0, 0, 178],
+            [67, 36, 17, 34, 64, 1, 6, 88, 0, 22, 0, 13, 0, 0, 0, 92],
+            [67, 36, 17, 34, 68, 2, 6, 43, 2, 119, 0, 79, 0, 0, 0, 217],
+            [67, 36, 17, 34, 72, 3, 6, 58, 3, 162, 0, 118, 0, 0, 0, 64],
+            [67, 36, 17, 34, 76, 4, 6, 88, 9, 51, 2, 86, 1, 0, 0, 221],
+            [67, 36, 17, 34, 80, 5, 6, 187, 0, 38, 0, 27, 0, 0, 0, 241],
+        ];
+        packets[3][9] ^= 0b1000_0000;
+        let stream = futures::stream::iter(packets.map(|p| RawPacket::Uart(p.to_vec())));
+        let mut rx = ClientReceiver::from_stream(Box::pin(stream));
+        let err = rx.try_next().await.unwrap().unwrap_err();
+        assert!(matches!(err, Error::Checksum { .. }), "{err:?}");
+        let Some(Ok(CommandReply::SportDetail(details))) = rx.try_next().await else {
+            panic!("expected sport detail");
+        };
+        assert_eq!(
+            details.iter().map(|d| d.time_index).collect::<Vec<_>>(),
+            [60, 64, 72, 76, 80]
+        );
+        assert!(details.iter().all(|d| d.steps != 119 + 128));
+    }
+
+    /// A sleep sync split across two chunks followed by a battery reply,
+    /// `stage` is the type byte of the only sleep stage
+    fn sleep_sync_then_battery(stage: u8) -> ClientReceiver {
+        let stream = futures::stream::iter([
+            RawPacket::V2(vec![188, 39, 9, 0, 0, 0, 2, 1, 6, 0x64]),
+            RawPacket::V2(vec![0x05, 0xa4, 0x01, stage, 10]),
+            RawPacket::Uart(make_packet(&[3, 80, 0])),
+        ]);
+        ClientReceiver::from_stream(Box::pin(stream))
+    }
+
+    #[tokio::test]
+    async fn sleep_sync_then_battery_parses() {
+        let mut rx = sleep_sync_then_battery(constants::SLEEP_TYPE_LIGHT);
+        let Some(Ok(CommandReply::Sleep(sleep))) = rx.try_next().await else {
+            panic!("expected sleep");
+        };
+        assert_eq!(sleep.sessions[0].stages, [SleepStage::Light(10)]);
+        assert!(matches!(
+            rx.try_next().await,
+            Some(Ok(CommandReply::BatteryInfo { level: 80, .. }))
+        ));
+    }
+
+    #[tokio::test]
+    async fn corrupted_sleep_chunk_is_an_error_item() {
+        let mut rx = sleep_sync_then_battery(9);
+        let err = rx.try_next().await.unwrap().unwrap_err();
+        assert_eq!(err.packet_kind(), Some(PacketKind::Sleep), "{err:?}");
+        assert!(matches!(
+            rx.try_next().await,
+            Some(Ok(CommandReply::BatteryInfo { level: 80, .. }))
+        ));
+        assert!(rx.try_next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn corrupted_sleep_chunk_is_skipped_by_next() {
+        let mut rx = sleep_sync_then_battery(9);
+        assert!(matches!(
+            rx.next().await,
+            Some(CommandReply::BatteryInfo { level: 80, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn heart_rate_out_of_order_is_an_error_item() {
+        let stream = futures::stream::iter([
+            RawPacket::Uart(make_packet(&[21, 0, 24, 5])),
+            RawPacket::Uart(make_packet(&[21, 0, 24, 5])),
+            RawPacket::Uart(make_packet(&[3, 80, 0])),
+        ]);
+        let mut rx = ClientReceiver::from_stream(Box::pin(stream));
+        let err = rx.try_next().await.unwrap().unwrap_err();
+        assert_eq!(err.packet_kind(), Some(PacketKind::HeartRate), "{err:?}");
+        assert!(matches!(
+            rx.try_next().await,
+            Some(Ok(CommandReply::BatteryInfo { level: 80, .. }))
+        ));
+    }
+
+    #[tokio::test]
+    async fn checksum_validation_can_be_disabled() {
+        let mut packet = make_packet(&[3, 80, 0]);
+        packet[15] = 0;
+        let stream = futures::stream::iter([RawPacket::Uart(packet)]);
+        let mut rx = ClientReceiver::from_stream(Box::pin(stream)).with_checksum_validation(false);
+        assert_eq!(
+            rx.try_next().await.unwrap().unwrap(),
+            CommandReply::BatteryInfo {
+                level: 80,
+                charging: false
+            }
+        );
+    }
+
+    fn oxygen_chunks() -> Vec<Vec<u8>> {
+        let mut data = vec![2u8];
+        for days_ago in [1u8, 0] {
+            data.push(days_ago);
+            for hour in 0..24u8 {
+                data.push(90 + hour % 5);
+                data.push(95 + hour % 5);
+            }
+        }
+        let len = (data.len() as u16).to_le_bytes();
+        let mut first = vec![
+            constants::CMD_BIG_DATA_V2,
+            constants::BIG_DATA_TYPE_SPO2,
+            len[0],
+            len[1],
+            0,
+            0,
+        ];
+        let mut chunks = data.chunks(20);
+        first.extend_from_slice(chunks.next().unwrap());
+        let mut ret = vec![first];
+        ret.extend(chunks.map(|c| c.to_vec()));
+        ret
+    }
+
+    fn assert_oxygen(oxy: &OxygenData) {
+        assert_eq!(oxy.samples.len(), 48);
+        for (i, sample) in oxy.samples.iter().enumerate() {
+            let hour = (i % 24) as u8;
+            assert_eq!(sample.min, 90 + hour % 5);
+            assert_eq!(sample.max, 95 + hour % 5);
+            assert_eq!(sample.when.hour(), hour);
+        }
+        assert_eq!(
+            oxy.samples[24].when.date().previous_day().unwrap(),
+            oxy.samples[0].when.date()
+        );
+    }
+
+    #[test]
+    fn big_data_oxygen() {
+        let mut packets = VecDeque::from(oxygen_chunks());
+        let initial = packets.pop_front().unwrap();
+        let mut state = BigDataState::new(&initial).unwrap();
+        for packet in packets {
+            state.step(&packet).unwrap();
+        }
+        let BigDataState::Complete(packet) = state else {
+            panic!("Expected complete found {state:?}");
+        };
+        let oxy = OxygenData::parse(&packet, date!(2024 - 11 - 28)).unwrap();
+        assert_oxygen(&oxy);
+        assert_eq!(oxy.samples[0].when.date(), date!(2024 - 11 - 27));
+        assert_eq!(oxy.samples[24].when.date(), date!(2024 - 11 - 28));
+    }
+
+    #[tokio::test]
+    async fn big_data_oxygen_reply() {
+        let stream = futures::stream::iter(oxygen_chunks().into_iter().map(RawPacket::V2));
+        let mut rx = ClientReceiver::from_stream(Box::pin(stream));
+        let Some(CommandReply::Oxygen(oxy)) = rx.next().await else {
+            panic!("Expected oxygen reply");
+        };
+        assert_oxygen(&oxy);
+        assert_eq!(rx.next().await, None);
+    }
+
+    /// A heart rate sync of 3 data packets
+    fn heart_rate_sync() -> Vec<RawPacket> {
+        let timestamp = 1_732_060_800u32.to_le_bytes();
+        [
+            vec![constants::CMD_SYNC_HEART_RATE, 0, 4, 5],
+            vec![
+                constants::CMD_SYNC_HEART_RATE,
+                1,
+                timestamp[0],
+                timestamp[1],
+                timestamp[2],
+                timestamp[3],
+                60,
+            ],
+            vec![constants::CMD_SYNC_HEART_RATE, 2, 61],
+            vec![constants::CMD_SYNC_HEART_RATE, 3, 62],
+        ]
+        .iter()
+        .map(|bytes| RawPacket::Uart(make_packet(bytes)))
+        .collect()
+    }
+
+    #[tokio::test]
+    async fn raw_packets_before_parsing() {
+        let mock = MockTransport::new();
+        let mut client = Client::with_transport(mock.clone());
+        let mut raw = client.raw_packets();
+        client.connect().await.unwrap();
+        let sync = heart_rate_sync();
+        for packet in &sync {
+            mock.push(packet.clone());
+        }
+        assert!(matches!(
+            client.read_next().await.unwrap(),
+            Some(CommandReply::HeartRate(_))
+        ));
+        let mut seen = Vec::new();
+        while let Ok(packet) = raw.try_recv() {
+            seen.push(packet);
+        }
+        assert_eq!(seen, sync);
+    }
+
+    #[tokio::test]
+    async fn reconnects_when_dropped_mid_sync() {
+        let mock = MockTransport::new();
+        let mut client = Client::builder()
+            .backoff(Duration::ZERO)
+            .build_with_transport(mock.clone());
+        let mut events = client.connection_events();
+        let progress = client.progress();
+        client.connect().await.unwrap();
+        let sync = heart_rate_sync();
+        mock.push(sync[0].clone());
+        mock.push(sync[1].clone());
+        mock.push(RawPacket::Uart(make_packet(&[3, 80])));
+        assert!(matches!(
+            client.read_next().await.unwrap(),
+            Some(CommandReply::BatteryInfo { level: 80, .. })
+        ));
+        assert!(progress.borrow().is_some());
+        mock.drop_connection();
+        // the ring starts the sync over once it is requested again
+        for packet in sync {
+            mock.push(packet);
+        }
+        let Some(CommandReply::HeartRate(hr)) = client.read_next().await.unwrap() else {
+            panic!("expected a heart rate reply");
+        };
+        assert_eq!(hr.range, 5);
+        assert_eq!(hr.rates[0], 60);
+        assert!(hr.gaps.is_empty(), "{:?}", hr.gaps);
+        assert_eq!(mock.reconnects(), 1);
+        assert_eq!(*progress.borrow(), None);
+        let mut seen = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            seen.push(event);
+        }
+        assert_eq!(
+            seen,
+            [
+                ConnectionEvent::Connected,
+                ConnectionEvent::Disconnected,
+                ConnectionEvent::Reconnected { attempt: 1 },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn reconnect_retries_then_gives_up() {
+        let mock = MockTransport::new();
+        let mut client = Client::builder()
+            .backoff(Duration::ZERO)
+            .reconnects(2)
+            .build_with_transport(mock.clone());
+        client.connect().await.unwrap();
+        mock.drop_connection();
+        mock.fail_reconnects(1);
+        mock.push(RawPacket::Uart(make_packet(&[3, 80])));
+        assert!(matches!(
+            client.read_next().await.unwrap(),
+            Some(CommandReply::BatteryInfo { level: 80, .. })
+        ));
+        assert_eq!(mock.reconnects(), 2);
+        mock.drop_connection();
+        mock.fail_reconnects(2);
+        let err = client.read_next().await.unwrap_err();
+        assert!(
+            matches!(err, Error::ConnectionLost { attempts: 2 }),
+            "{err:?}"
+        );
+        assert_eq!(mock.reconnects(), 4);
+    }
+
+    #[tokio::test]
+    async fn stream_end_without_reconnects() {
+        let mock = MockTransport::new();
+        let mut client = Client::builder()
+            .reconnects(0)
+            .build_with_transport(mock.clone());
+        client.connect().await.unwrap();
+        mock.drop_connection();
+        assert_eq!(client.read_next().await.unwrap(), None);
+        assert_eq!(mock.reconnects(), 0);
+    }
+
+    #[tokio::test]
+    async fn request_reconnects_when_dropped() {
+        let mock = battery_ring();
+        let client = Client::builder()
+            .backoff(Duration::ZERO)
+            .reconnects(2)
+            .build_with_transport(mock.clone());
+        let mut rx = client.connection_events();
+        assert_eq!(client.battery().await.unwrap().level, 80);
+        mock.drop_connection();
+        assert_eq!(client.battery().await.unwrap().level, 80);
+        assert_eq!(mock.reconnects(), 1);
+        // the command sent before noticing is sent again
+        assert_eq!(mock.written().len(), 3);
+        assert_eq!(
+            events(&mut rx),
+            [
+                ConnectionEvent::Disconnected,
+                ConnectionEvent::Reconnected { attempt: 1 },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn request_reconnect_gives_up() {
+        let mock = battery_ring();
+        let client = Client::builder()
+            .backoff(Duration::ZERO)
+            .reconnects(2)
+            .build_with_transport(mock.clone());
+        client.battery().await.unwrap();
+        mock.drop_connection();
+        mock.fail_reconnects(2);
+        let err = client.battery().await.unwrap_err();
+        assert!(
+            matches!(err, Error::ConnectionLost { attempts: 2 }),
+            "{err:?}"
+        );
+        assert_eq!(mock.reconnects(), 2);
+        // the next request tries again
+        assert_eq!(client.battery().await.unwrap().level, 80);
+        assert_eq!(mock.reconnects(), 3);
+    }
+
+    #[tokio::test]
+    async fn request_not_resent_when_reboot_drops() {
+        let mock = MockTransport::new().with_responder(|_, bytes| {
+            if bytes[0] == constants::CMD_BATTERY {
+                vec![RawPacket::Uart(make_packet(&[3, 80]))]
+            } else {
+                Vec::new()
+            }
+        });
+        let client = Client::builder()
+            .backoff(Duration::ZERO)
+            .reconnects(2)
+            .build_with_transport(mock.clone());
+        client.battery().await.unwrap();
+        // the ring reboots instead of replying
+        let (reboot, ()) = tokio::join!(client.send_request(Command::Reboot), async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            mock.drop_connection();
+        });
+        assert!(matches!(reboot, Err(Error::NotConnected)), "{reboot:?}");
+        assert_eq!(mock.reconnects(), 0);
+        let reboot_bytes: [u8; 16] = Command::Reboot.try_into().unwrap();
+        let written = mock.written();
+        assert_eq!(written.len(), 2);
+        assert_eq!(written[1].1, reboot_bytes);
+        // the next request reconnects
+        assert_eq!(client.battery().await.unwrap().level, 80);
+        assert_eq!(mock.reconnects(), 1);
+    }
+
+    /// A ring that answers every command with its battery level
+    fn battery_ring() -> MockTransport {
+        MockTransport::new().with_responder(|_, _| vec![RawPacket::Uart(make_packet(&[3, 80]))])
+    }
+
+    fn events(rx: &mut tokio::sync::broadcast::Receiver<ConnectionEvent>) -> Vec<ConnectionEvent> {
+        std::iter::from_fn(|| rx.try_recv().ok()).collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn realtime_continues_its_own_reading() {
+        let mock = MockTransport::new();
+        let mut client = Client::builder().build_with_transport(mock.clone());
+        let heart_rate: [u8; 16] = Command::ContinueRealTimeHeartRate.try_into().unwrap();
+        let spo2: [u8; 16] = Command::ContinueSpo2.try_into().unwrap();
+        for (kind, continued, other) in
+            [("heart rate", heart_rate, spo2), ("spo2", spo2, heart_rate)]
+        {
+            let sent = mock.written().len();
+            let stream = match kind {
+                "spo2" => client.realtime_spo2().await.unwrap().boxed_local(),
+                _ => client.realtime_heart_rate().await.unwrap().boxed_local(),
+            };
+            let mut stream = std::pin::pin!(stream);
+            // nothing arrives, so the reading is continued once the interval passes
+            let waited = tokio::time::timeout(
+                REAL_TIME_CONTINUE_INTERVAL + Duration::from_secs(1),
+                stream.next(),
+            )
+            .await;
+            assert!(waited.is_err(), "{kind}: {waited:?}");
+            let written: Vec<_> = mock.written().split_off(sent);
+            assert!(
+                written.iter().any(|(_, bytes)| *bytes == continued),
+                "{kind}"
+            );
+            assert!(written.iter().all(|(_, bytes)| *bytes != other), "{kind}");
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_disconnect_after_idle_window() {
+        let mock = battery_ring();
+        let mut client = Client::builder()
+            .keep_alive(Duration::from_secs(5))
+            .idle_disconnect(Duration::from_secs(30))
+            .build_with_transport(mock.clone());
+        let mut rx = client.connection_events();
+        client.connect().await.unwrap();
+        tokio::time::sleep(Duration::from_secs(29)).await;
+        assert_eq!(mock.disconnects(), 0);
+        // using the connection starts the window over
+        client.battery().await.unwrap();
+        tokio::time::sleep(Duration::from_secs(29)).await;
+        assert_eq!(mock.disconnects(), 0);
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert_eq!(mock.disconnects(), 1);
+        // idle_disconnect replaced keep_alive, only the one request was sent
+        assert_eq!(mock.written().len(), 1);
+        assert_eq!(
+            events(&mut rx),
+            [ConnectionEvent::Connected, ConnectionEvent::Idle]
+        );
+        // staying disconnected doesn't disconnect again
+        tokio::time::sleep(Duration::from_secs(120)).await;
+        assert_eq!(mock.disconnects(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn send_after_idle_reconnects() {
+        let mock = battery_ring();
+        let mut client = Client::builder()
+            .idle_disconnect(Duration::from_secs(30))
+            .build_with_transport(mock.clone());
+        let mut rx = client.connection_events();
+        client.connect().await.unwrap();
+        tokio::time::sleep(Duration::from_secs(31)).await;
+        assert_eq!(mock.disconnects(), 1);
+        assert_eq!(mock.reconnects(), 0);
+        client.send(Command::BatteryInfo).await.unwrap();
+        assert_eq!(mock.reconnects(), 1);
+        assert!(matches!(
+            client.read_next().await.unwrap(),
+            Some(CommandReply::BatteryInfo { level: 80, .. })
+        ));
+        // requests reconnect too
+        tokio::time::sleep(Duration::from_secs(31)).await;
+        assert_eq!(mock.disconnects(), 2);
+        assert_eq!(client.battery().await.unwrap().level, 80);
+        assert_eq!(mock.reconnects(), 2);
+        assert_eq!(
+            events(&mut rx),
+            [
+                ConnectionEvent::Connected,
+                ConnectionEvent::Idle,
+                ConnectionEvent::Connected,
+                ConnectionEvent::Idle,
+                ConnectionEvent::Connected,
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_waits_for_reads() {
+        let mock = battery_ring();
+        let mut client = Client::builder()
+            .idle_disconnect(Duration::from_secs(30))
+            .build_with_transport(mock.clone());
+        client.connect().await.unwrap();
+        // waiting for a reply uses the connection however long it takes
+        let reply = client
+            .wait_for(|_| false, Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(reply, None);
+        assert_eq!(mock.disconnects(), 0);
+        tokio::time::sleep(Duration::from_secs(31)).await;
+        assert_eq!(mock.disconnects(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn keep_alive_until_dropped() {
+        let mock = battery_ring();
+        let mut client = Client::builder()
+            .keep_alive(Duration::from_secs(10))
+            .build_with_transport(mock.clone());
+        client.connect().await.unwrap();
+        tokio::time::sleep(Duration::from_secs(35)).await;
+        assert_eq!(mock.written().len(), 3);
+        assert!(mock
+            .written()
+            .iter()
+            .all(|(chan, bytes)| *chan == Channel::Uart && bytes[0] == constants::CMD_BATTERY));
+        assert_eq!(mock.disconnects(), 0);
+        // the replies arrive like the ring's own notifications
+        assert!(matches!(
+            client.read_next().await.unwrap(),
+            Some(CommandReply::BatteryInfo { level: 80, .. })
+        ));
+        drop(client);
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        assert_eq!(mock.written().len(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn no_lifecycle_before_connecting() {
+        let mock = battery_ring();
+        let mut client = Client::builder()
+            .keep_alive(Duration::from_secs(10))
+            .build_with_transport(mock.clone());
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        assert!(mock.written().is_empty());
+        client.connect().await.unwrap();
+        client.disconnect().await.unwrap();
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        assert!(mock.written().is_empty());
+    }
+
     fn make_packet(bytes: &[u8]) -> Vec<u8> {
         let mut ret = bytes.to_vec();
         ret.resize(16, 0);
@@ -8,6 +8,9 @@ pub const CHARACTERISTIC_COMMAND: Uuid = uuid::uuid!("de5bf72a-d711-4e47-af26-65
 pub const UART_TX_CHAR_UUID: Uuid = uuid::uuid!("6e400003-b5a3-f393-e0a9-e50e24dcca9e");
 pub const CHARACTERISTIC_NOTIFY_V2: Uuid = uuid::uuid!("de5bf729-d711-4e47-af26-65e3012a5dc7");
 
+/// Every packet on the uart characteristics is this long, including the checksum
+pub const UART_PACKET_LEN: usize = 16;
+
 pub const CMD_SET_DATE_TIME: u8 = 0x01;
 pub const CMD_BATTERY: u8 = 0x03;
 pub const CMD_PHONE_NAME: u8 = 0x04;
@@ -26,12 +29,29 @@ pub const CMD_SYNC_HRV: u8 = 0x39;
 pub const CMD_SYNC_ACTIVITY: u8 = 0x43;
 pub const CMD_FIND_DEVICE: u8 = 0x50;
 pub const CMD_MANUAL_HEART_RATE: u8 = 0x69;
+pub const CMD_STOP_REAL_TIME: u8 = 0x6a;
 pub const CMD_NOTIFICATION: u8 = 0x73;
 pub const CMD_BIG_DATA_V2: u8 = 0xbc;
 pub const CMD_FACTORY_RESET: u8 = 0xff;
+/// The sub command `Command::Reboot` has always sent
+pub const POWER_OFF_REBOOT: u8 = 0x01;
+/// Not confirmed against a capture
+pub const POWER_OFF_SHUTDOWN: u8 = 0x05;
+pub const FACTORY_RESET_CONFIRM: u8 = 0x66;
+/// Sent after [`CMD_PHONE_NAME`], the name fills the rest of the packet
+pub const PHONE_NAME_HEADER: [u8; 2] = [0x02, 0x0a];
+pub const PHONE_NAME_LEN: usize = 12;
+/// Sent after [`CMD_FIND_DEVICE`] to make the ring vibrate and flash
+pub const FIND_DEVICE_PAYLOAD: [u8; 2] = [0x55, 0xaa];
 pub const PREF_READ: u8 = 0x01;
 pub const PREF_WRITE: u8 = 0x02;
 pub const PREF_DELETE: u8 = 0x03;
+pub const PREF_KEY_TIME_FORMAT: u8 = 0x01;
+pub const PREF_KEY_UNITS: u8 = 0x02;
+pub const PREF_KEY_WEAR_HAND: u8 = 0x03;
+pub const PREF_KEY_RAISE_TO_WAKE: u8 = 0x04;
+/// Bytes left for a preference value after the command, sub command and key
+pub const PREF_VALUE_LEN: usize = 12;
 pub const NOTIFICATION_NEW_HR_DATA: u8 = 0x01;
 pub const NOTIFICATION_NEW_SPO2_DATA: u8 = 0x03;
 pub const NOTIFICATION_NEW_STEPS_DATA: u8 = 0x04;
@@ -0,0 +1,238 @@
+//! Scanning for rings over bluetooth, only available with the `ble` feature
+
+use bleasy::{BDAddr, Device, ScanConfig};
+use futures::{Stream, StreamExt};
+use std::{collections::BTreeMap, pin::Pin, time::Duration};
+
+use crate::Result;
+
+/// Options controlling how [`discover_with`] scans for devices
+#[derive(Debug, Clone, bon::Builder)]
+pub struct ScanOptions {
+    /// Stop scanning after this long, `None` scans until the stream is dropped
+    pub timeout: Option<Duration>,
+    /// Skip devices with a signal weaker than this
+    pub min_rssi: Option<i16>,
+    /// Only report devices with a name starting with one of these, an empty
+    /// list reports every device
+    #[builder(default = default_name_prefixes())]
+    pub name_prefixes: Vec<String>,
+    /// Try and force devices to disconnect from their current connection
+    #[builder(default)]
+    pub force_disconnect: bool,
+    /// Stop scanning after this many devices have been reported
+    pub max_devices: Option<usize>,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+fn default_name_prefixes() -> Vec<String> {
+    crate::constants::DEVICE_NAME_PREFIXES
+        .iter()
+        .map(|p| p.to_string())
+        .collect()
+}
+
+/// Scan for rings, or every device when `all` is true
+pub async fn discover(
+    all: bool,
+    force_disconnect: bool,
+) -> Result<Pin<Box<dyn Stream<Item = Device>>>> {
+    log::trace!("discover({all}, {force_disconnect})");
+    let mut options = ScanOptions::builder()
+        .force_disconnect(force_disconnect)
+        .build();
+    if all {
+        options.name_prefixes.clear();
+    }
+    discover_with(options).await
+}
+
+/// Scan for devices matching `options`
+///
+/// The stream ends once `options.timeout` elapses or `options.max_devices`
+/// devices have been reported
+pub async fn discover_with(options: ScanOptions) -> Result<Pin<Box<dyn Stream<Item = Device>>>> {
+    log::trace!("discover_with({options:?})");
+    let ScanOptions {
+        timeout,
+        min_rssi,
+        name_prefixes,
+        force_disconnect,
+        max_devices,
+    } = options;
+    let mut config = ScanConfig::default().force_disconnect(force_disconnect);
+    if !name_prefixes.is_empty() {
+        config = config.filter_by_name(move |n| name_prefixes.iter().any(|p| n.starts_with(p)));
+    }
+    discover_(config, timeout, min_rssi, max_devices).await
+}
+
+/// How long to wait for a single device to report its name or signal strength
+const DEVICE_INFO_TIMEOUT: Duration = Duration::from_secs(2);
+/// How many devices to look up at once while scanning continues
+const DEVICE_INFO_CONCURRENCY: usize = 8;
+
+/// A device found while scanning along with the details most callers want
+pub struct DiscoveredRing {
+    pub address: BDAddr,
+    pub name: Option<String>,
+    pub rssi: Option<i16>,
+    pub device: Device,
+}
+
+impl DiscoveredRing {
+    async fn resolve(device: Device) -> Self {
+        let address = device.address();
+        let (name, rssi) = futures::join!(
+            tokio::time::timeout(DEVICE_INFO_TIMEOUT, device.local_name()),
+            tokio::time::timeout(DEVICE_INFO_TIMEOUT, device.rssi()),
+        );
+        if name.is_err() || rssi.is_err() {
+            log::debug!("timed out looking up details for {address}");
+        }
+        Self {
+            address,
+            name: name.ok().flatten(),
+            rssi: rssi.ok().flatten(),
+            device,
+        }
+    }
+}
+
+/// Scan for devices matching `options`, looking up each device's name and
+/// signal strength as it is found
+pub async fn discover_rings(
+    options: ScanOptions,
+) -> Result<Pin<Box<dyn Stream<Item = DiscoveredRing>>>> {
+    let stream = discover_with(options).await?;
+    Ok(stream
+        .map(DiscoveredRing::resolve)
+        .buffer_unordered(DEVICE_INFO_CONCURRENCY)
+        .boxed_local())
+}
+
+/// Drain `stream` into a list with one entry per address, strongest signal first
+pub async fn sorted_by_rssi(stream: impl Stream<Item = DiscoveredRing>) -> Vec<DiscoveredRing> {
+    dedup_by_rssi(stream, |ring| (ring.address, ring.rssi)).await
+}
+
+async fn dedup_by_rssi<T>(
+    stream: impl Stream<Item = T>,
+    key: impl Fn(&T) -> (BDAddr, Option<i16>),
+) -> Vec<T> {
+    let mut seen: BTreeMap<BDAddr, T> = BTreeMap::new();
+    futures::pin_mut!(stream);
+    while let Some(item) = stream.next().await {
+        let (address, rssi) = key(&item);
+        match seen.get(&address) {
+            Some(existing) if key(existing).1 >= rssi => {}
+            _ => {
+                seen.insert(address, item);
+            }
+        }
+    }
+    let mut ret: Vec<T> = seen.into_values().collect();
+    ret.sort_by_key(|item| std::cmp::Reverse(key(item).1));
+    ret
+}
+
+pub async fn discover_by_name(name: String) -> Result<Pin<Box<dyn Stream<Item = Device>>>> {
+    log::trace!("discover_by_name: `{name}`");
+    let config = ScanConfig::default().filter_by_name(move |n| n == name);
+    discover_(config, None, None, None).await
+}
+
+async fn discover_(
+    config: ScanConfig,
+    timeout: Option<Duration>,
+    min_rssi: Option<i16>,
+    max_devices: Option<usize>,
+) -> Result<Pin<Box<dyn Stream<Item = Device>>>> {
+    let mut scanner = bleasy::Scanner::new();
+    log::trace!("starting scan");
+    scanner.start(config).await?;
+    let deadline = timeout.map(|timeout| {
+        log::debug!("Scanning for {timeout:?}");
+        tokio::time::Instant::now() + timeout
+    });
+    Ok(async_stream::stream! {
+        let mut stream = scanner.device_stream();
+        let mut found = 0;
+        while max_devices.is_none_or(|max| found < max) {
+            let next = if let Some(deadline) = deadline {
+                match tokio::time::timeout_at(deadline, stream.next()).await {
+                    Ok(next) => next,
+                    Err(_) => {
+                        log::debug!("Scan timed out");
+                        break;
+                    }
+                }
+            } else {
+                stream.next().await
+            };
+            let Some(dev) = next else {
+                break;
+            };
+            if let Some(min_rssi) = min_rssi {
+                if dev.rssi().await.is_none_or(|rssi| rssi < min_rssi) {
+                    log::trace!("Skipping device {} with weak signal", dev.address());
+                    continue;
+                }
+            }
+            log::debug!("Stream returned device");
+            found += 1;
+            yield dev;
+        }
+        if let Err(e) = scanner.stop().await {
+            log::warn!("Error stopping scan: {e}");
+        }
+    }
+    .boxed_local())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(last: u8) -> BDAddr {
+        BDAddr::from([0, 0, 0, 0, 0, last])
+    }
+
+    #[tokio::test]
+    async fn dedup_keeps_strongest_and_sorts() {
+        let devices = futures::stream::iter([
+            (addr(1), Some(-80)),
+            (addr(2), None),
+            (addr(3), Some(-40)),
+            (addr(1), Some(-60)),
+            (addr(3), Some(-70)),
+            (addr(4), Some(-90)),
+            (addr(2), None),
+        ]);
+        let sorted = dedup_by_rssi(devices, |d| *d).await;
+        assert_eq!(
+            sorted,
+            vec![
+                (addr(3), Some(-40)),
+                (addr(1), Some(-60)),
+                (addr(4), Some(-90)),
+                (addr(2), None),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn dedup_empty_stream() {
+        let sorted = dedup_by_rssi(
+            futures::stream::iter(Vec::<(BDAddr, Option<i16>)>::new()),
+            |d| *d,
+        )
+        .await;
+        assert!(sorted.is_empty());
+    }
+}
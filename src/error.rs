@@ -0,0 +1,263 @@
+use std::fmt::{self, Display};
+
+use uuid::Uuid;
+
+use crate::incoming_messages::CommandReply;
+
+/// Everything that can go wrong talking to a ring or parsing its replies
+#[derive(Debug)]
+pub enum Error {
+    /// No device matched the address or name provided
+    DeviceNotFound,
+    /// A GATT service the client relies on was not found on the device
+    ServiceMissing { uuid: Uuid },
+    /// A GATT characteristic the client relies on was not found on the device
+    CharacteristicMissing { uuid: Uuid },
+    /// The bytes sent by the device could not be parsed
+    PacketParse {
+        kind: PacketKind,
+        bytes: Vec<u8>,
+        reason: String,
+    },
+    /// A packet was too short to hold what its command byte describes,
+    /// `command` is `None` for an empty packet
+    PacketLength {
+        command: Option<u8>,
+        length: usize,
+        expected: usize,
+    },
+    /// A multi-packet sync was missing too many packets to be filled in
+    MissingPackets { kind: PacketKind, missing: usize },
+    /// A big data reply's payload didn't match the CRC sent with it
+    Crc {
+        kind: PacketKind,
+        expected: u16,
+        found: u16,
+    },
+    /// A multi-day sync was a length that doesn't fit the number of days it
+    /// declared
+    DayCount {
+        kind: PacketKind,
+        days: u8,
+        length: usize,
+    },
+    /// A raw command didn't fit in a packet, `max` bytes fit before the checksum
+    CommandTooLong { length: usize, max: usize },
+    /// A packet's trailing checksum byte didn't match the rest of its contents
+    Checksum { bytes: Vec<u8>, expected: u8 },
+    /// The ring reported an error code during a real time reading
+    RealTime(u8),
+    /// An error from the underlying bluetooth stack
+    #[cfg(feature = "ble")]
+    Ble(bleasy::Error),
+    /// An operation did not complete before its deadline
+    Timeout,
+    /// A read was attempted before the client was connected
+    NotConnected,
+    /// The ring dropped the connection and it couldn't be re-established
+    ConnectionLost { attempts: u8 },
+    /// The ring answered a request with a reply that doesn't fit it
+    UnexpectedReply(Box<CommandReply>),
+    /// Reading or writing a capture file failed
+    Io(std::io::Error),
+    /// A line of a capture file could not be parsed
+    InvalidCapture { line: usize, reason: String },
+    /// A heart rate interval the ring doesn't accept, see `HrInterval`
+    InvalidHrInterval(u8),
+    /// A daily goal too large for the 24 bits the ring stores it in
+    GoalTooLarge(u32),
+    /// A phone name with characters the ring can't display, only printable
+    /// ASCII is sent
+    InvalidPhoneName(String),
+    /// The ring reported a day that doesn't exist
+    InvalidDate { year: u16, month: u8, day: u8 },
+    /// A `SportDetail::time_index` past the last quarter hour of the day
+    InvalidTimeIndex(u8),
+    /// A number of days too long for a `Duration`
+    DurationOverflow { days: u64 },
+}
+
+/// The kind of packet that failed to parse
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketKind {
+    Uart,
+    Notification,
+    HeartRate,
+    Stress,
+    Hrv,
+    SportDetail,
+    BigData,
+    Sleep,
+    Oxygen,
+}
+
+impl Error {
+    pub(crate) fn parse(kind: PacketKind, bytes: &[u8], reason: impl Display) -> Self {
+        Self::PacketParse {
+            kind,
+            bytes: bytes.to_vec(),
+            reason: reason.to_string(),
+        }
+    }
+
+    /// Which parser rejected the packet, if this is a parse error
+    pub fn packet_kind(&self) -> Option<PacketKind> {
+        match self {
+            Self::PacketParse { kind, .. }
+            | Self::MissingPackets { kind, .. }
+            | Self::DayCount { kind, .. }
+            | Self::Crc { kind, .. } => Some(*kind),
+            _ => None,
+        }
+    }
+
+    /// If this error came from the bluetooth stack or a timeout, as opposed to
+    /// the device sending something unexpected
+    pub fn is_connection_error(&self) -> bool {
+        #[cfg(feature = "ble")]
+        if let Self::Ble(_) = self {
+            return true;
+        }
+        matches!(
+            self,
+            Self::DeviceNotFound | Self::Timeout | Self::NotConnected | Self::ConnectionLost { .. }
+        )
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DeviceNotFound => write!(f, "No device found"),
+            Self::ServiceMissing { uuid } => write!(f, "failed to find service {uuid}"),
+            Self::CharacteristicMissing { uuid } => {
+                write!(f, "failed to find characteristic {uuid}")
+            }
+            Self::PacketParse {
+                kind,
+                bytes,
+                reason,
+            } => write!(f, "Error parsing {kind} packet {bytes:?}: {reason}"),
+            Self::PacketLength {
+                command: Some(command),
+                length,
+                expected,
+            } => write!(
+                f,
+                "Packet for command {command:#04x} was {length} bytes, expected at least {expected}"
+            ),
+            Self::PacketLength {
+                command: None,
+                expected,
+                ..
+            } => write!(f, "Empty packet, expected at least {expected} bytes"),
+            Self::MissingPackets { kind, missing } => {
+                write!(f, "{kind} sync was missing {missing} packets")
+            }
+            Self::Crc {
+                kind,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{kind} sync failed its crc, expected {expected:#06x} found {found:#06x}"
+            ),
+            Self::DayCount { kind, days, length } => {
+                write!(f, "{kind} sync of {days} days can't be {length} bytes")
+            }
+            Self::CommandTooLong { length, max } => write!(
+                f,
+                "Raw command was {length} bytes, at most {max} fit before the checksum"
+            ),
+            Self::Checksum { bytes, expected } => {
+                write!(
+                    f,
+                    "Invalid checksum for packet {bytes:?}, expected {expected}"
+                )
+            }
+            Self::RealTime(code) => write!(f, "Ring reported real time error code {code}"),
+            #[cfg(feature = "ble")]
+            Self::Ble(e) => write!(f, "Bluetooth error: {e}"),
+            Self::Timeout => write!(f, "Timed out"),
+            Self::NotConnected => write!(f, "client not connected"),
+            Self::ConnectionLost { attempts } => write!(
+                f,
+                "connection lost, reconnecting failed after {attempts} attempts"
+            ),
+            Self::UnexpectedReply(reply) => write!(f, "Unexpected reply {reply:?}"),
+            Self::Io(e) => write!(f, "IO error: {e}"),
+            Self::InvalidCapture { line, reason } => {
+                write!(f, "Invalid capture on line {line}: {reason}")
+            }
+            Self::InvalidHrInterval(minutes) => write!(
+                f,
+                "Invalid heart rate interval {minutes}, it must be a multiple of 5 up to 60"
+            ),
+            Self::GoalTooLarge(value) => write!(
+                f,
+                "Goal {value} is too large, at most 16777215 fits in 24 bits"
+            ),
+            Self::InvalidPhoneName(name) => write!(
+                f,
+                "Invalid phone name {name:?}, only printable ASCII is supported"
+            ),
+            Self::InvalidDate { year, month, day } => {
+                write!(f, "Invalid date {year}-{month:02}-{day:02}")
+            }
+            Self::InvalidTimeIndex(index) => write!(
+                f,
+                "Invalid time index {index}, a day only has 96 quarter hours"
+            ),
+            Self::DurationOverflow { days } => {
+                write!(f, "{days} days is too long for a duration")
+            }
+        }
+    }
+}
+
+impl Display for PacketKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Uart => "uart",
+            Self::Notification => "notification",
+            Self::HeartRate => "heart rate",
+            Self::Stress => "stress",
+            Self::Hrv => "hrv",
+            Self::SportDetail => "sport detail",
+            Self::BigData => "big data",
+            Self::Sleep => "sleep",
+            Self::Oxygen => "oxygen",
+        };
+        f.write_str(s)
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "ble")]
+            Self::Ble(e) => Some(e),
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "ble")]
+impl From<bleasy::Error> for Error {
+    fn from(value: bleasy::Error) -> Self {
+        Self::Ble(value)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<tokio::time::error::Elapsed> for Error {
+    fn from(_: tokio::time::error::Elapsed) -> Self {
+        Self::Timeout
+    }
+}
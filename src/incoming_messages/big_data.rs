@@ -1,11 +1,13 @@
 use std::{fmt::Display, time::Duration};
 
-use time::{OffsetDateTime, PrimitiveDateTime};
+use time::{Date, PrimitiveDateTime};
 
 use crate::{
     constants,
-    util::{try_u16_from_iter, try_u16_from_le_slice, DurationExt as _},
-    Result,
+    util::{
+        check_len, crc16, local_today, try_u16_from_iter, try_u16_from_le_slice, DurationExt as _,
+    },
+    Error, PacketKind, Result,
 };
 
 #[derive(Debug)]
@@ -43,53 +45,203 @@ pub enum SleepStage {
     Awake(u8),
 }
 
+impl SleepStage {
+    pub fn minutes(&self) -> u8 {
+        match *self {
+            Self::Light(m) | Self::Deep(m) | Self::Rem(m) | Self::Awake(m) => m,
+        }
+    }
+}
+
+/// Minutes spent in each stage of a sleep session
+#[derive(Debug, Default, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct SleepTotals {
+    pub light: u32,
+    pub deep: u32,
+    pub rem: u32,
+    pub awake: u32,
+    /// The percent of the session spent asleep, 0 for a session without
+    /// stages
+    pub efficiency: f64,
+}
+
+impl SleepTotals {
+    /// Every stage but awake
+    pub fn asleep(&self) -> u32 {
+        self.light + self.deep + self.rem
+    }
+}
+
+impl SleepSession {
+    /// Split the session wherever an awake stage lasts at least `min_gap`,
+    /// like a nap and the night's sleep the ring recorded as one session
+    ///
+    /// The awake stages that split the session are dropped and each session's
+    /// start and end are recomputed from the lengths of its stages. A session
+    /// without a long enough gap is returned as is
+    pub fn split_on_awake_gap(&self, min_gap: Duration) -> Vec<SleepSession> {
+        let is_gap = |stage: &SleepStage| matches!(stage, SleepStage::Awake(m) if Duration::minutes((*m).into()) >= min_gap);
+        if !self.stages.iter().any(is_gap) {
+            return vec![self.clone()];
+        }
+        let mut ret = Vec::new();
+        let mut start = self.start;
+        let mut time = self.start;
+        let mut stages = Vec::new();
+        for stage in &self.stages {
+            let end = time + Duration::minutes(stage.minutes().into());
+            if is_gap(stage) {
+                if !stages.is_empty() {
+                    ret.push(SleepSession {
+                        start,
+                        end: time,
+                        stages: std::mem::take(&mut stages),
+                    });
+                }
+                start = end;
+            } else {
+                stages.push(stage.clone());
+            }
+            time = end;
+        }
+        if !stages.is_empty() {
+            ret.push(SleepSession {
+                start,
+                end: time,
+                stages,
+            });
+        }
+        ret
+    }
+
+    /// The minutes spent in each stage and how much of the session was spent
+    /// asleep
+    pub fn totals(&self) -> SleepTotals {
+        let mut ret = SleepTotals::default();
+        for stage in &self.stages {
+            match *stage {
+                SleepStage::Light(m) => ret.light += u32::from(m),
+                SleepStage::Deep(m) => ret.deep += u32::from(m),
+                SleepStage::Rem(m) => ret.rem += u32::from(m),
+                SleepStage::Awake(m) => ret.awake += u32::from(m),
+            }
+        }
+        let total = ret.asleep() + ret.awake;
+        if total > 0 {
+            ret.efficiency = f64::from(ret.asleep()) * 100.0 / f64::from(total);
+        }
+        ret
+    }
+}
+
+/// The date `days_ago` days before `reference`, the day a sync was requested
+///
+/// Both sleep and oxygen syncs count back from the day of the request, 0 is
+/// that day and 1 the day before. A sleep session is counted on the day it
+/// ended
+///
+/// This hasn't been checked against a sleep and an oxygen sync captured from
+/// the same ring. Sleep used to count from the day after the request, which
+/// moved the dates in the captured sleep snapshots back a day
+pub(crate) fn day_from_days_ago(reference: Date, days_ago: u8) -> Result<Date> {
+    reference
+        .checked_sub(time::Duration::days(days_ago.into()))
+        .ok_or_else(|| {
+            Error::parse(
+                PacketKind::BigData,
+                &[days_ago],
+                format!("{days_ago} days before {reference} is out of range"),
+            )
+        })
+}
+
+/// Parse against today's date, see `SleepData::parse` to pick the date the
+/// sync was requested
 impl TryFrom<BigDataPacket> for SleepData {
-    type Error = Box<dyn std::error::Error>;
+    type Error = Error;
     fn try_from(value: BigDataPacket) -> std::result::Result<Self, Self::Error> {
+        Self::parse(&value, local_today())
+    }
+}
+
+impl SleepData {
+    /// Parse a complete sleep packet, `reference` is the date the sync was
+    /// requested which the ring's "days ago" values are relative to
+    pub fn parse(value: &BigDataPacket, reference: Date) -> Result<Self> {
         let BigDataPacket::Sleep(data) = value else {
-            return Err(format!("Invlaid big data packet for sleep: {value:?}").into());
+            return Err(Error::parse(
+                PacketKind::Sleep,
+                value.get_data_ref(),
+                "Invalid big data packet for sleep",
+            ));
         };
-        let days = data.first().copied().unwrap_or_default();
+        let (&days, body) = data
+            .split_first()
+            .ok_or_else(|| Error::parse(PacketKind::Sleep, data, "Packet sized 0"))?;
         log::debug!("trying to parse sleep data with {days} days");
         log::trace!("{:?}", data);
         let mut sessions = Vec::with_capacity(days as _);
-        fn too_short_error(idx: u8, msg: impl Display) -> impl Fn() -> Box<dyn std::error::Error> {
-            move || -> Box<dyn std::error::Error + 'static> {
-                format!("Packet too short at {idx}: {msg}").into()
+        fn too_short_error<'a>(
+            data: &'a [u8],
+            idx: u8,
+            msg: impl Display + 'a,
+        ) -> impl Fn() -> Error + 'a {
+            move || {
+                Error::parse(
+                    PacketKind::Sleep,
+                    data,
+                    format!("Packet too short at {idx}: {msg}"),
+                )
             }
         }
 
-        let mut iter = data[1..].iter().copied();
-        let now = OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc());
-        let today = now.date();
+        let mut iter = body.iter().copied();
         for i in 1..days {
-            let days_ago = iter.next().ok_or_else(too_short_error(i, "days ago"))?;
+            let days_ago = iter
+                .next()
+                .ok_or_else(too_short_error(data, i, "days ago"))?;
             log::trace!("handling day {days_ago} days in the past");
-            let day = today - Duration::days(days_ago as u64 - 1);
+            let day = day_from_days_ago(reference, days_ago)?;
             log::trace!("{day:?}");
-            let day_bytes = iter.next().ok_or_else(too_short_error(i, "day bytes"))?;
+            let day_bytes = iter
+                .next()
+                .ok_or_else(too_short_error(data, i, "day bytes"))?;
             log::trace!("day bytes: {day_bytes}");
-            let start = try_u16_from_iter(&mut iter).ok_or_else(too_short_error(i, "start"))?;
-            let end = try_u16_from_iter(&mut iter).ok_or_else(too_short_error(i, "end"))?;
+            let start =
+                try_u16_from_iter(&mut iter).ok_or_else(too_short_error(data, i, "start"))?;
+            let end = try_u16_from_iter(&mut iter).ok_or_else(too_short_error(data, i, "end"))?;
+            // both are minutes after midnight of the day the session ended, a
+            // start after the end was the evening before
             let start = if start > end {
-                println!("{} {}", start, (start as i32) - 1440);
-                day.midnight() - Duration::minutes(1440 - start as u64)
+                let before_midnight = 1440u64.saturating_sub(start.into());
+                log::trace!("session started {before_midnight} minutes before midnight");
+                day.midnight() - Duration::minutes(before_midnight)
             } else {
-                day.previous_day().ok_or("Invalid day")?.midnight() + Duration::minutes(start as _)
+                day.midnight() + Duration::minutes(start as _)
             };
             let end = day.midnight() + Duration::minutes(end as _);
             log::debug!("sleep session {start:?}-{end:?}",);
             let mut stages = Vec::new();
-            let mut remaining_bytes = day_bytes - 4;
+            let mut remaining_bytes = day_bytes.checked_sub(4).ok_or_else(|| {
+                Error::parse(
+                    PacketKind::Sleep,
+                    data,
+                    format!("{i} session length {day_bytes} shorter than its header"),
+                )
+            })?;
             while remaining_bytes > 0 {
-                let stage = iter
-                    .next()
-                    .ok_or_else(too_short_error(i, &format!("{remaining_bytes} stage")))?;
-                let minutes = iter
-                    .next()
-                    .ok_or_else(too_short_error(i, &format!("{remaining_bytes} minutes")))?;
+                let stage = iter.next().ok_or_else(too_short_error(
+                    data,
+                    i,
+                    &format!("{remaining_bytes} stage"),
+                ))?;
+                let minutes = iter.next().ok_or_else(too_short_error(
+                    data,
+                    i,
+                    &format!("{remaining_bytes} minutes"),
+                ))?;
                 log::debug!("{stage}-{minutes}");
-                remaining_bytes -= 2;
+                remaining_bytes = remaining_bytes.saturating_sub(2);
                 stages.push(match stage {
                     0 => {
                         log::warn!("empty sleep stage");
@@ -100,10 +252,11 @@ impl TryFrom<BigDataPacket> for SleepData {
                     constants::SLEEP_TYPE_REM => SleepStage::Rem(minutes),
                     constants::SLEEP_TYPE_AWAKE => SleepStage::Awake(minutes),
                     _ => {
-                        return Err(format!(
-                            "{i}/{remaining_bytes} sleep sample type invalid {stage}"
-                        )
-                        .into())
+                        return Err(Error::parse(
+                            PacketKind::Sleep,
+                            data,
+                            format!("{i}/{remaining_bytes} sleep sample type invalid {stage}"),
+                        ))
                     }
                 });
             }
@@ -114,12 +267,28 @@ impl TryFrom<BigDataPacket> for SleepData {
 }
 
 impl BigDataState {
+    /// Start a reply from its first packet, a 6 byte header of the command,
+    /// the type, the payload length and the payload's CRC followed by the start
+    /// of the payload
     pub fn new(bytes: &[u8]) -> Result<Self> {
+        check_len(bytes, 6)?;
         if bytes[0] != crate::constants::CMD_BIG_DATA_V2 {
-            return Err(format!("Invalid bytes for bigdata state: {bytes:?}").into());
+            return Err(Error::parse(
+                PacketKind::BigData,
+                bytes,
+                "Invalid bytes for bigdata state",
+            ));
         }
         log::debug!("with bytes {}", bytes.len());
-        let target_length = try_u16_from_le_slice(&bytes[2..4]).unwrap() as usize;
+        // a u16, so the length can't be more than 64 KiB
+        let target_length = try_u16_from_le_slice(&bytes[2..4])
+            .map(usize::from)
+            .ok_or_else(|| Error::parse(PacketKind::BigData, bytes, "missing length"))?;
+        // every payload starts with its day count, an empty one has nothing to
+        // parse
+        if target_length == 0 {
+            return Err(Error::parse(PacketKind::BigData, bytes, "empty payload"));
+        }
         let data = Vec::with_capacity(target_length);
         let tag = bytes[1];
         let mut ret = Self::Partial {
@@ -129,30 +298,96 @@ impl BigDataState {
             } else if bytes[1] == constants::BIG_DATA_TYPE_SPO2 {
                 BigDataPacket::Oxygen(data)
             } else {
-                return Err(format!("Unknown big data type: {bytes:?}").into());
+                return Err(Error::parse(
+                    PacketKind::BigData,
+                    bytes,
+                    "Unknown big data type",
+                ));
             },
         };
         ret.step(&bytes[6..])?;
         Ok(ret)
     }
 
+    /// If `bytes` is the first packet of a sleep or oxygen reply
+    pub fn is_header(bytes: &[u8]) -> bool {
+        bytes.len() >= 6
+            && bytes[0] == constants::CMD_BIG_DATA_V2
+            && matches!(
+                bytes[1],
+                constants::BIG_DATA_TYPE_SLEEP | constants::BIG_DATA_TYPE_SPO2
+            )
+    }
+
+    /// What kind of reply this is with the bytes received and expected, `None`
+    /// once complete
+    pub fn progress(&self) -> Option<(PacketKind, usize, usize)> {
+        let Self::Partial {
+            target_length,
+            packet,
+        } = self
+        else {
+            return None;
+        };
+        Some((packet.kind(), packet.len(), *target_length))
+    }
+
+    /// Add the next packet of the payload
+    ///
+    /// Some firmwares follow the payload with its CRC in the last packet, the
+    /// bytes past the payload are trimmed and the CRC is checked if they are
+    /// one
     pub fn step(&mut self, bytes: &[u8]) -> Result {
         let Self::Partial {
             target_length,
             packet,
         } = self
         else {
-            return Err("step after complete".into());
+            return Err(Error::parse(
+                PacketKind::BigData,
+                bytes,
+                "step after complete",
+            ));
         };
         packet.extend_from_slice(bytes);
-        if packet.len() == *target_length {
-            *self = Self::Complete(packet.clone());
+        if packet.len() < *target_length {
+            return Ok(());
         }
+        let trailer = packet.get_data_mut().split_off(*target_length);
+        match trailer.as_slice() {
+            [] => {}
+            &[lo, hi] => {
+                let found = u16::from_le_bytes([lo, hi]);
+                let expected = crc16(packet.get_data_ref());
+                if found != expected {
+                    return Err(Error::Crc {
+                        kind: packet.kind(),
+                        expected,
+                        found,
+                    });
+                }
+            }
+            extra => log::warn!(
+                "discarding {} bytes past the end of the {} reply",
+                extra.len(),
+                packet.kind()
+            ),
+        }
+        // the partial buffer is moved into the complete state, not copied
+        let packet = std::mem::replace(packet, BigDataPacket::Sleep(Vec::new()));
+        *self = Self::Complete(packet);
         Ok(())
     }
 }
 
 impl BigDataPacket {
+    pub fn kind(&self) -> PacketKind {
+        match self {
+            Self::Sleep(_) => PacketKind::Sleep,
+            Self::Oxygen(_) => PacketKind::Oxygen,
+        }
+    }
+
     pub fn extend_from_slice(&mut self, slice: &[u8]) {
         self.get_data_mut().extend_from_slice(slice);
     }
@@ -184,53 +419,79 @@ impl BigDataPacket {
     }
 }
 
-#[derive(Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 pub struct OxygenData {
     pub samples: Vec<OxygenMeasurement>,
 }
 
-#[derive(Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 pub struct OxygenMeasurement {
     pub min: u8,
     pub max: u8,
     pub when: PrimitiveDateTime,
 }
 
+impl OxygenMeasurement {
+    /// The ring reports hours without a reading as a minimum and maximum of 0
+    pub fn is_empty(&self) -> bool {
+        self.min == 0 && self.max == 0
+    }
+}
+
+/// The bytes for one day of oxygen readings, its days ago followed by a
+/// minimum and maximum for each hour
+const OXYGEN_DAY_LEN: usize = 1 + 24 * 2;
+
+/// Parse against today's date, see `OxygenData::parse` to pick the date the
+/// sync was requested
 impl TryFrom<BigDataPacket> for OxygenData {
-    type Error = String;
+    type Error = Error;
     fn try_from(value: BigDataPacket) -> std::result::Result<Self, Self::Error> {
+        Self::parse(&value, local_today())
+    }
+}
+
+impl OxygenData {
+    /// Parse a complete oxygen packet, see `SleepData::parse`
+    ///
+    /// The days have no length of their own, every day is 24 hours except the
+    /// last which is the day of the request and only has the hours that have
+    /// started. A packet that doesn't fit the days it declares is a
+    /// `Error::DayCount`
+    pub fn parse(value: &BigDataPacket, reference: Date) -> Result<Self> {
         let BigDataPacket::Oxygen(data) = value else {
-            return Err(format!(
-                "Error, attempt to parse oxygen data with wron packet: {value:?}"
+            return Err(Error::parse(
+                PacketKind::Oxygen,
+                value.get_data_ref(),
+                "attempt to parse oxygen data with wrong packet",
             ));
         };
-        let mut iter = data.iter().copied().peekable();
-
-        let day_in_packet = iter.next().ok_or_else(|| format!("Packet sized 7"))?;
-        let mut samples = Vec::new();
-        let now = OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc());
-        let today = now.date().midnight();
-        for i in 0..day_in_packet {
-            let days_ago = iter
-                .next()
-                .ok_or_else(|| format!("Error, days ago for day {i} was none"))?;
-            let day = today - Duration::days(days_ago as u64);
-            for j in 0..24 {
-                let hour = day + Duration::hours(j);
-                let min = iter.next().ok_or_else(|| {
-                    format!("Error processing hour {j} in day {i} expected minimum found none")
-                })?;
-                let max = iter.next().ok_or_else(|| {
-                    format!("Error processing hour {j} in day {i} expected maximum found none")
-                })?;
+        let (&days, body) = data
+            .split_first()
+            .ok_or_else(|| Error::parse(PacketKind::Oxygen, data, "Packet sized 7"))?;
+        let full_days = usize::from(days.saturating_sub(1)) * OXYGEN_DAY_LEN;
+        let last_day = body.len().checked_sub(full_days);
+        let fits = match last_day {
+            _ if days == 0 => body.is_empty(),
+            Some(len) => (1..=OXYGEN_DAY_LEN).contains(&len) && (len - 1).is_multiple_of(2),
+            None => false,
+        };
+        if !fits {
+            return Err(Error::DayCount {
+                kind: PacketKind::Oxygen,
+                days,
+                length: data.len(),
+            });
+        }
+        let mut samples = Vec::with_capacity(usize::from(days) * 24);
+        for day in body.chunks(OXYGEN_DAY_LEN) {
+            let midnight = day_from_days_ago(reference, day[0])?.midnight();
+            for (hour, pair) in day[1..].chunks_exact(2).enumerate() {
                 samples.push(OxygenMeasurement {
-                    max,
-                    min,
-                    when: hour,
+                    min: pair[0],
+                    max: pair[1],
+                    when: midnight + Duration::hours(hour as u64),
                 });
-                if iter.peek().is_none() {
-                    break;
-                }
             }
         }
         Ok(Self { samples })
@@ -239,7 +500,491 @@ impl TryFrom<BigDataPacket> for OxygenData {
 
 #[cfg(test)]
 mod tests {
-    use time::OffsetDateTime;
+    use time::{
+        macros::{date, datetime},
+        OffsetDateTime,
+    };
+
+    use super::*;
+
+    fn session(stages: Vec<SleepStage>) -> SleepSession {
+        let start = datetime!(2024-11-19 21:00);
+        let minutes = stages.iter().map(|s| u64::from(s.minutes())).sum();
+        SleepSession {
+            start,
+            end: start + Duration::minutes(minutes),
+            stages,
+        }
+    }
+
+    /// Each session's start, end and stages
+    fn spans(
+        sessions: &[SleepSession],
+    ) -> Vec<(PrimitiveDateTime, PrimitiveDateTime, Vec<SleepStage>)> {
+        sessions
+            .iter()
+            .map(|s| (s.start, s.end, s.stages.clone()))
+            .collect()
+    }
+
+    /// Parse a sleep packet with a single session on the day of the request,
+    /// `start` and `end` are the raw minute values and `stages` are pairs of
+    /// stage type and minutes
+    fn parse_session(start: u16, end: u16, stages: &[(u8, u8)]) -> SleepSession {
+        let mut data = vec![2, 0, 4 + 2 * stages.len() as u8];
+        data.extend(start.to_le_bytes());
+        data.extend(end.to_le_bytes());
+        data.extend(
+            stages
+                .iter()
+                .flat_map(|(stage, minutes)| [*stage, *minutes]),
+        );
+        let mut sleep =
+            SleepData::parse(&BigDataPacket::Sleep(data), date!(2024 - 11 - 20)).unwrap();
+        assert_eq!(sleep.sessions.len(), 1);
+        sleep.sessions.remove(0)
+    }
+
+    /// The session is as long as its stages
+    fn assert_plausible(session: &SleepSession) {
+        assert!(session.start < session.end, "{session:?}");
+        let minutes: i64 = session.stages.iter().map(|s| i64::from(s.minutes())).sum();
+        assert_eq!((session.end - session.start).whole_minutes(), minutes);
+    }
+
+    #[test]
+    fn sleep_before_midnight() {
+        let session = parse_session(
+            22 * 60 + 30,
+            6 * 60 + 10,
+            &[
+                (constants::SLEEP_TYPE_LIGHT, 200),
+                (constants::SLEEP_TYPE_DEEP, 200),
+                (constants::SLEEP_TYPE_REM, 60),
+            ],
+        );
+        assert_eq!(session.start, datetime!(2024-11-19 22:30));
+        assert_eq!(session.end, datetime!(2024-11-20 6:10));
+        assert_plausible(&session);
+    }
+
+    #[test]
+    fn sleep_after_midnight() {
+        let session = parse_session(
+            40,
+            7 * 60,
+            &[
+                (constants::SLEEP_TYPE_LIGHT, 200),
+                (constants::SLEEP_TYPE_DEEP, 180),
+            ],
+        );
+        assert_eq!(session.start, datetime!(2024-11-20 0:40));
+        assert_eq!(session.end, datetime!(2024-11-20 7:00));
+        assert_plausible(&session);
+    }
+
+    /// The first packet of a big data reply with `payload` and its CRC, split
+    /// into packets of at most 20 bytes
+    fn big_data_packets(kind: u8, payload: &[u8]) -> Vec<Vec<u8>> {
+        let mut bytes = vec![constants::CMD_BIG_DATA_V2, kind];
+        bytes.extend((payload.len() as u16).to_le_bytes());
+        bytes.extend(crc16(payload).to_le_bytes());
+        bytes.extend(payload);
+        bytes.chunks(20).map(<[u8]>::to_vec).collect()
+    }
+
+    fn assemble(packets: &[Vec<u8>]) -> Result<BigDataState> {
+        let mut state = BigDataState::new(&packets[0])?;
+        for packet in &packets[1..] {
+            state.step(packet)?;
+        }
+        Ok(state)
+    }
+
+    #[test]
+    fn big_data_exact_length() {
+        let payload = partial_oxygen();
+        let packets = big_data_packets(constants::BIG_DATA_TYPE_SPO2, &payload);
+        let mut state = BigDataState::new(&packets[0]).unwrap();
+        for packet in &packets[1..] {
+            assert!(state.progress().is_some());
+            state.step(packet).unwrap();
+        }
+        let BigDataState::Complete(BigDataPacket::Oxygen(data)) = state else {
+            panic!("expected complete oxygen, found {state:?}");
+        };
+        assert_eq!(data, payload);
+    }
+
+    #[test]
+    fn big_data_overshoot_with_crc() {
+        let payload = partial_oxygen();
+        let mut packets = big_data_packets(constants::BIG_DATA_TYPE_SPO2, &payload);
+        packets
+            .last_mut()
+            .unwrap()
+            .extend(crc16(&payload).to_le_bytes());
+        let BigDataState::Complete(BigDataPacket::Oxygen(data)) = assemble(&packets).unwrap()
+        else {
+            panic!("expected complete oxygen");
+        };
+        assert_eq!(data, payload);
+        // padding past the payload isn't a CRC and is dropped
+        packets.last_mut().unwrap().push(0);
+        assert!(matches!(
+            assemble(&packets).unwrap(),
+            BigDataState::Complete(BigDataPacket::Oxygen(data)) if data == payload
+        ));
+    }
+
+    #[test]
+    fn big_data_bad_crc() {
+        let payload = partial_oxygen();
+        let mut packets = big_data_packets(constants::BIG_DATA_TYPE_SPO2, &payload);
+        let expected = crc16(&payload);
+        packets
+            .last_mut()
+            .unwrap()
+            .extend((expected ^ 1).to_le_bytes());
+        let e = assemble(&packets).unwrap_err();
+        assert!(
+            matches!(e, Error::Crc { kind: PacketKind::Oxygen, expected: x, found }
+                if x == expected && found == expected ^ 1),
+            "{e:?}"
+        );
+    }
+
+    #[test]
+    fn big_data_empty_payload() {
+        for kind in [
+            constants::BIG_DATA_TYPE_SLEEP,
+            constants::BIG_DATA_TYPE_SPO2,
+        ] {
+            let packets = big_data_packets(kind, &[]);
+            let e = BigDataState::new(&packets[0]).unwrap_err();
+            assert!(
+                matches!(
+                    e,
+                    Error::PacketParse {
+                        kind: PacketKind::BigData,
+                        ..
+                    }
+                ),
+                "{e:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn days_ago_counts_back_from_request() {
+        let reference = date!(2024 - 11 - 20);
+        assert_eq!(day_from_days_ago(reference, 0).unwrap(), reference);
+        assert_eq!(
+            day_from_days_ago(reference, 1).unwrap(),
+            date!(2024 - 11 - 19)
+        );
+        assert_eq!(
+            day_from_days_ago(reference, u8::MAX).unwrap(),
+            date!(2024 - 03 - 10)
+        );
+        assert_eq!(day_from_days_ago(Date::MIN, 0).unwrap(), Date::MIN);
+        assert!(matches!(
+            day_from_days_ago(Date::MIN, 1),
+            Err(Error::PacketParse {
+                kind: PacketKind::BigData,
+                ..
+            })
+        ));
+    }
+
+    /// A sleep and oxygen sync requested on 2024-11-20, the sleep covers the
+    /// night before and an afternoon nap the day of the request
+    ///
+    /// Built by hand rather than captured from a ring, so the tests using it
+    /// only check that the two parsers agree with each other, see
+    /// `day_from_days_ago`
+    const SYNC_SLEEP: &[u8] = &[
+        3, // sessions + 1
+        1, 10, 0x82, 0x05, 0x00, 0x01, 2, 150, 3, 90, 4, 46, // 23:30-04:16 days ago 1
+        0, 6, 0x0c, 0x03, 0x48, 0x03, 2, 60, // 13:00-14:00 days ago 0
+    ];
+
+    /// The oxygen readings from the same sync as `SYNC_SLEEP`, the lowest
+    /// readings of each day were during its sleep
+    fn sync_oxygen() -> Vec<u8> {
+        let mut data = vec![2];
+        for days_ago in [1, 0] {
+            data.push(days_ago);
+            for hour in 0..24 {
+                let asleep = match days_ago {
+                    1 => hour < 5,
+                    _ => hour == 13,
+                };
+                data.extend(if asleep { [91, 94] } else { [96, 99] });
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn sleep_and_oxygen_days_align() {
+        let reference = date!(2024 - 11 - 20);
+        let sleep =
+            SleepData::parse(&BigDataPacket::Sleep(SYNC_SLEEP.to_vec()), reference).unwrap();
+        let oxygen = OxygenData::parse(&BigDataPacket::Oxygen(sync_oxygen()), reference).unwrap();
+        assert_eq!(
+            sleep
+                .sessions
+                .iter()
+                .map(|s| (s.start, s.end))
+                .collect::<Vec<_>>(),
+            [
+                (datetime!(2024-11-18 23:30), datetime!(2024-11-19 4:16)),
+                (datetime!(2024-11-20 13:00), datetime!(2024-11-20 14:00)),
+            ]
+        );
+        assert_eq!(oxygen.samples.len(), 48);
+        assert_eq!(oxygen.samples[0].when, datetime!(2024-11-19 0:00));
+        assert_eq!(oxygen.samples[24].when, datetime!(2024-11-20 0:00));
+        // every low reading falls within a sleep session from the same days ago
+        let low: Vec<_> = oxygen.samples.iter().filter(|s| s.min < 95).collect();
+        assert_eq!(low.len(), 6);
+        for sample in low {
+            assert!(
+                sleep
+                    .sessions
+                    .iter()
+                    .any(|s| s.start <= sample.when && sample.when < s.end),
+                "{sample:?} outside of {:?}",
+                sleep.sessions
+            );
+        }
+    }
+
+    /// An oxygen sync requested at 9am, yesterday is complete and today only has
+    /// the hours that have started, built by hand like `SYNC_SLEEP`
+    fn partial_oxygen() -> Vec<u8> {
+        let mut data = vec![2, 1];
+        data.extend([96, 99].repeat(23));
+        data.extend([0, 0]);
+        data.push(0);
+        data.extend([94, 98].repeat(8));
+        data.extend([95, 97]);
+        data
+    }
+
+    #[test]
+    fn oxygen_partial_today() {
+        let oxygen = OxygenData::parse(
+            &BigDataPacket::Oxygen(partial_oxygen()),
+            date!(2024 - 11 - 20),
+        )
+        .unwrap();
+        assert_eq!(oxygen.samples.len(), 24 + 9);
+        assert_eq!(oxygen.samples[0].when, datetime!(2024-11-19 0:00));
+        assert_eq!(
+            oxygen.samples[23],
+            OxygenMeasurement {
+                min: 0,
+                max: 0,
+                when: datetime!(2024-11-19 23:00),
+            }
+        );
+        assert!(oxygen.samples[23].is_empty());
+        assert_eq!(oxygen.samples[24].when, datetime!(2024-11-20 0:00));
+        assert_eq!(
+            oxygen.samples[32],
+            OxygenMeasurement {
+                min: 95,
+                max: 97,
+                when: datetime!(2024-11-20 8:00),
+            }
+        );
+    }
+
+    #[test]
+    fn oxygen_length_must_fit_days() {
+        let reference = date!(2024 - 11 - 20);
+        let parse = |data: Vec<u8>| OxygenData::parse(&BigDataPacket::Oxygen(data), reference);
+        assert!(parse(vec![0]).unwrap().samples.is_empty());
+        // today's days ago but none of its hours
+        assert_eq!(parse(vec![1, 0]).unwrap().samples.len(), 0);
+        let mut short_day = partial_oxygen();
+        short_day[0] = 3;
+        let mut odd = partial_oxygen();
+        odd.pop();
+        let mut extra = partial_oxygen();
+        extra[0] = 1;
+        for (data, days) in [(short_day, 3), (odd, 2), (extra, 1), (vec![0, 0], 0)] {
+            let length = data.len();
+            let e = parse(data).unwrap_err();
+            assert!(
+                matches!(e, Error::DayCount { kind: PacketKind::Oxygen, days: d, length: l }
+                    if d == days && l == length),
+                "{e:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn sleep_empty_payload() {
+        // a reply of `[0xbc, 0x27, 0, 0, ..]`
+        let e =
+            SleepData::parse(&BigDataPacket::Sleep(Vec::new()), date!(2024 - 11 - 20)).unwrap_err();
+        assert!(
+            matches!(
+                e,
+                Error::PacketParse {
+                    kind: PacketKind::Sleep,
+                    ..
+                }
+            ),
+            "{e:?}"
+        );
+    }
+
+    #[test]
+    fn sleep_nap() {
+        let session = parse_session(13 * 60, 14 * 60, &[(constants::SLEEP_TYPE_LIGHT, 60)]);
+        assert_eq!(session.start, datetime!(2024-11-20 13:00));
+        assert_eq!(session.end, datetime!(2024-11-20 14:00));
+        assert_plausible(&session);
+    }
+
+    #[test]
+    fn split_without_gap() {
+        let session = session(vec![
+            SleepStage::Light(30),
+            SleepStage::Awake(20),
+            SleepStage::Deep(40),
+        ]);
+        assert_eq!(
+            session.split_on_awake_gap(Duration::minutes(60)),
+            std::slice::from_ref(&session)
+        );
+    }
+
+    #[test]
+    fn split_gap_in_middle() {
+        let session = session(vec![
+            SleepStage::Light(30),
+            SleepStage::Deep(20),
+            SleepStage::Awake(90),
+            SleepStage::Light(60),
+            SleepStage::Awake(10),
+            SleepStage::Rem(30),
+        ]);
+        assert_eq!(
+            spans(&session.split_on_awake_gap(Duration::minutes(60))),
+            [
+                (
+                    datetime!(2024-11-19 21:00),
+                    datetime!(2024-11-19 21:50),
+                    vec![SleepStage::Light(30), SleepStage::Deep(20)]
+                ),
+                (
+                    datetime!(2024-11-19 23:20),
+                    datetime!(2024-11-20 1:00),
+                    vec![
+                        SleepStage::Light(60),
+                        SleepStage::Awake(10),
+                        SleepStage::Rem(30)
+                    ]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn split_gap_at_start() {
+        let session = session(vec![
+            SleepStage::Awake(60),
+            SleepStage::Light(30),
+            SleepStage::Deep(45),
+        ]);
+        assert_eq!(
+            spans(&session.split_on_awake_gap(Duration::minutes(60))),
+            [(
+                datetime!(2024-11-19 22:00),
+                datetime!(2024-11-19 23:15),
+                vec![SleepStage::Light(30), SleepStage::Deep(45)]
+            )]
+        );
+    }
+
+    #[test]
+    fn split_gap_at_end() {
+        let session = session(vec![
+            SleepStage::Light(30),
+            SleepStage::Deep(45),
+            SleepStage::Awake(120),
+        ]);
+        assert_eq!(
+            spans(&session.split_on_awake_gap(Duration::minutes(60))),
+            [(
+                datetime!(2024-11-19 21:00),
+                datetime!(2024-11-19 22:15),
+                vec![SleepStage::Light(30), SleepStage::Deep(45)]
+            )]
+        );
+    }
+
+    #[test]
+    fn split_multiple_gaps() {
+        let session = session(vec![
+            SleepStage::Light(20),
+            SleepStage::Awake(60),
+            SleepStage::Deep(30),
+            SleepStage::Awake(90),
+            SleepStage::Awake(70),
+            SleepStage::Rem(15),
+        ]);
+        let sessions = session.split_on_awake_gap(Duration::minutes(60));
+        assert_eq!(
+            spans(&sessions),
+            [
+                (
+                    datetime!(2024-11-19 21:00),
+                    datetime!(2024-11-19 21:20),
+                    vec![SleepStage::Light(20)]
+                ),
+                (
+                    datetime!(2024-11-19 22:20),
+                    datetime!(2024-11-19 22:50),
+                    vec![SleepStage::Deep(30)]
+                ),
+                (
+                    datetime!(2024-11-20 1:30),
+                    datetime!(2024-11-20 1:45),
+                    vec![SleepStage::Rem(15)]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn totals() {
+        let totals = session(vec![
+            SleepStage::Light(30),
+            SleepStage::Deep(45),
+            SleepStage::Light(20),
+            SleepStage::Rem(5),
+            SleepStage::Awake(25),
+        ])
+        .totals();
+        assert_eq!(
+            totals,
+            SleepTotals {
+                light: 50,
+                deep: 45,
+                rem: 5,
+                awake: 25,
+                efficiency: 80.0,
+            }
+        );
+        assert_eq!(totals.asleep(), 100);
+        assert_eq!(session(Vec::new()).totals(), SleepTotals::default());
+    }
 
     #[test]
     fn platform_can_get_local_time() {
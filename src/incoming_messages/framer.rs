@@ -0,0 +1,136 @@
+use crate::{constants::UART_PACKET_LEN, util::checksum};
+
+/// Reassembles 16 byte uart packets from notifications that don't line up with them
+///
+/// Some firmware splits a packet across two notifications or merges the end of one
+/// packet with the start of the next. Bytes are buffered until a full packet is
+/// available, when the buffered bytes don't start with a packet that passes its
+/// checksum the framer skips ahead to the first one that does.
+#[derive(Debug, Default)]
+pub struct PacketFramer {
+    buffer: Vec<u8>,
+    skip_checksum: bool,
+}
+
+impl PacketFramer {
+    /// When checksum validation is disabled packets are split every 16 bytes
+    /// without looking for boundaries
+    pub fn set_checksum_validation(&mut self, enabled: bool) {
+        self.skip_checksum = !enabled;
+    }
+
+    /// Add the bytes of a notification, returning any packets that are now complete
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<Vec<u8>> {
+        if self.buffer.is_empty() && chunk.len() == UART_PACKET_LEN {
+            // the common case, leave the checksum for the parser to report on
+            return vec![chunk.to_vec()];
+        }
+        self.buffer.extend_from_slice(chunk);
+        let mut ret = Vec::new();
+        while self.buffer.len() >= UART_PACKET_LEN {
+            let Some(start) = self.frame_start() else {
+                let keep = UART_PACKET_LEN - 1;
+                let dropped = self.buffer.len() - keep;
+                log::warn!("no uart packet found, dropping {dropped} bytes");
+                self.buffer.drain(..dropped);
+                break;
+            };
+            if start > 0 {
+                log::warn!(
+                    "dropping {start} bytes before uart packet: {:?}",
+                    &self.buffer[..start]
+                );
+            }
+            ret.push(self.buffer[start..start + UART_PACKET_LEN].to_vec());
+            self.buffer.drain(..start + UART_PACKET_LEN);
+        }
+        ret
+    }
+
+    /// Bytes that have been received but aren't part of a complete packet yet
+    pub fn pending(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    fn frame_start(&self) -> Option<usize> {
+        if self.skip_checksum {
+            return Some(0);
+        }
+        self.buffer.windows(UART_PACKET_LEN).position(|window| {
+            let (&found, body) = window.split_last().expect("windows are never empty");
+            checksum(body) == found
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(command: u8, value: u8) -> Vec<u8> {
+        let mut ret = vec![0u8; UART_PACKET_LEN];
+        ret[0] = command;
+        ret[1] = value;
+        ret[15] = checksum(&ret);
+        ret
+    }
+
+    #[test]
+    fn whole_packets_pass_through() {
+        let mut framer = PacketFramer::default();
+        let mut bad = packet(3, 50);
+        bad[15] ^= 1;
+        assert_eq!(framer.push(&bad), vec![bad]);
+        assert!(framer.pending().is_empty());
+    }
+
+    #[test]
+    fn split_packet() {
+        let mut framer = PacketFramer::default();
+        let battery = packet(3, 50);
+        assert!(framer.push(&battery[..12]).is_empty());
+        assert_eq!(framer.pending(), &battery[..12]);
+        assert_eq!(framer.push(&battery[12..]), vec![battery]);
+        assert!(framer.pending().is_empty());
+    }
+
+    #[test]
+    fn merged_packets() {
+        let mut framer = PacketFramer::default();
+        let first = packet(3, 50);
+        let second = packet(3, 60);
+        let bytes = [first.clone(), second.clone()].concat();
+        assert_eq!(framer.push(&bytes[..20]), vec![first]);
+        assert_eq!(framer.push(&bytes[20..]), vec![second]);
+        assert!(framer.pending().is_empty());
+    }
+
+    #[test]
+    fn skips_to_valid_packet() {
+        let mut framer = PacketFramer::default();
+        let battery = packet(3, 50);
+        assert!(framer.push(&[21, 0, 24]).is_empty());
+        assert_eq!(framer.push(&battery), vec![battery]);
+        assert!(framer.pending().is_empty());
+    }
+
+    #[test]
+    fn garbage_is_dropped() {
+        let mut framer = PacketFramer::default();
+        assert!(framer.push(&[1; 40]).is_empty());
+        assert_eq!(framer.pending().len(), UART_PACKET_LEN - 1);
+    }
+
+    #[test]
+    fn without_checksum_validation() {
+        let mut framer = PacketFramer::default();
+        framer.set_checksum_validation(false);
+        let bytes: Vec<u8> = (0..40).collect();
+        assert!(framer.push(&bytes[..10]).is_empty());
+        assert_eq!(
+            framer.push(&bytes[10..]),
+            vec![bytes[..16].to_vec(), bytes[16..32].to_vec()]
+        );
+        assert_eq!(framer.pending(), &bytes[32..]);
+    }
+}
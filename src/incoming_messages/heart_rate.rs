@@ -1,11 +1,86 @@
-use crate::Result;
+use std::{ops::Range, time::Duration};
+
+use crate::{
+    util::{check_len, local_offset},
+    Error, PacketKind, Result,
+};
 use time::{OffsetDateTime, PrimitiveDateTime};
 
-#[derive(Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+/// How many consecutive readings a rate has to hold for to count as resting
+const RESTING_WINDOW: usize = 3;
+/// How many rates each packet after the date packet carries
+const RATES_PER_PACKET: usize = 13;
+/// The most packets a sync can be missing before it is rejected
+const MAX_MISSING_PACKETS: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 pub struct HeartRate {
     pub range: u8,
     pub rates: Vec<u8>,
     pub date: PrimitiveDateTime,
+    /// Ranges of `rates` that were filled with 0 because their packet never arrived
+    #[serde(default)]
+    pub gaps: Vec<Range<usize>>,
+}
+
+/// Aggregate values for the non-zero readings in a `HeartRate`
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct HeartRateSummary {
+    pub min: u8,
+    pub max: u8,
+    pub avg: f32,
+    /// The lowest rate held across several consecutive readings, `None` if
+    /// no run of readings was long enough
+    pub resting: Option<u8>,
+}
+
+impl HeartRate {
+    /// Pair each rate with the time it was taken, `interval` apart starting at
+    /// `self.date` in the local timezone
+    ///
+    /// Rates of 0 mean no reading was taken and are `None`, samples that would
+    /// fall on the following day are skipped
+    pub fn samples(
+        &self,
+        interval: Duration,
+    ) -> impl Iterator<Item = (OffsetDateTime, Option<u8>)> + '_ {
+        let start = self.date.assume_offset(local_offset());
+        (0u32..)
+            .zip(self.rates.iter().copied())
+            .map(move |(i, rate)| (start + interval * i, (rate != 0).then_some(rate)))
+            .take_while(move |(when, _)| when.date() == start.date())
+    }
+
+    /// Summarize the non-zero rates, `None` if there are none
+    pub fn summary(&self) -> Option<HeartRateSummary> {
+        let readings = self.rates.iter().copied().filter(|r| *r != 0);
+        let (min, max, total, count) = readings.fold(
+            (u8::MAX, u8::MIN, 0u32, 0u32),
+            |(min, max, total, count), rate| {
+                (
+                    min.min(rate),
+                    max.max(rate),
+                    total + u32::from(rate),
+                    count + 1,
+                )
+            },
+        );
+        if count == 0 {
+            return None;
+        }
+        let resting = self
+            .rates
+            .split(|r| *r == 0)
+            .flat_map(|run| run.windows(RESTING_WINDOW))
+            .filter_map(|window| window.iter().max().copied())
+            .min();
+        Some(HeartRateSummary {
+            min,
+            max,
+            avg: total as f32 / count as f32,
+            resting,
+        })
+    }
 }
 
 #[derive(Debug)]
@@ -19,37 +94,47 @@ pub enum HeartRateState {
         size: u8,
         range: u8,
         rates: Vec<u8>,
+        next_index: u8,
+        gaps: Vec<Range<usize>>,
     },
     Complete {
         range: u8,
         rates: Vec<u8>,
         date: PrimitiveDateTime,
+        gaps: Vec<Range<usize>>,
     },
 }
 
 impl TryFrom<&[u8]> for HeartRateState {
-    type Error = Box<dyn std::error::Error>;
+    type Error = Error;
 
     fn try_from(value: &[u8]) -> std::result::Result<Self, Self::Error> {
+        check_len(value, 2)?;
         if value[1] == 255 {
             return Ok(Self::Complete {
                 rates: Vec::new(),
                 date: PrimitiveDateTime::MIN,
                 range: 0,
+                gaps: Vec::new(),
             });
         }
         if value.len() < 15 {
             log::debug!("Invalid heart rate packet: {value:?}");
-            return Err(
-                format!("Packet too short for heart rate data 15 < {}", value.len()).into(),
-            );
+            return Err(Error::parse(
+                PacketKind::HeartRate,
+                value,
+                format!("Packet too short for heart rate data 15 < {}", value.len()),
+            ));
         }
         if value[1] != 0 {
-            return Err(format!(
-                "unexpected initial heart rate message expected 0 found {}",
-                value[1]
-            )
-            .into());
+            return Err(Error::parse(
+                PacketKind::HeartRate,
+                value,
+                format!(
+                    "unexpected initial heart rate message expected 0 found {}",
+                    value[1]
+                ),
+            ));
         }
         Ok(Self::Length {
             size: value[2].saturating_sub(1),
@@ -59,46 +144,109 @@ impl TryFrom<&[u8]> for HeartRateState {
 }
 
 impl HeartRateState {
+    /// Advance with the next packet, which should have its checksum byte trimmed
+    ///
+    /// Packets that were already seen are ignored, a small number of missing
+    /// packets are filled with 0 rates and recorded as gaps
     pub fn step(&mut self, packet: &[u8]) -> Result {
-        *self = match self {
-            HeartRateState::Length { size, range } => Self::step_length(*size, *range, packet)?,
+        check_len(packet, 15)?;
+        match self {
+            HeartRateState::Length { size, range } => {
+                *self = Self::step_length(*size, *range, packet)?;
+            }
             HeartRateState::Recieving {
                 date,
                 size,
                 range,
                 rates,
+                next_index,
+                gaps,
             } => {
-                let rates = core::mem::take(rates);
-                Self::step_receiving(*size, *range, *date, rates, packet)?
+                let index = packet[1];
+                if index == 0 {
+                    return Err(Error::parse(
+                        PacketKind::HeartRate,
+                        packet,
+                        "Unexpected size packet after date packet",
+                    ));
+                }
+                if index > *size {
+                    return Err(Error::parse(
+                        PacketKind::HeartRate,
+                        packet,
+                        format!("packet index {index} is past the last packet {size}"),
+                    ));
+                }
+                if index < *next_index {
+                    log::debug!("ignoring repeated heart rate packet {index}");
+                    return Ok(());
+                }
+                let missing = usize::from(index - *next_index);
+                if missing > MAX_MISSING_PACKETS {
+                    return Err(Error::MissingPackets {
+                        kind: PacketKind::HeartRate,
+                        missing,
+                    });
+                }
+                if missing > 0 {
+                    log::warn!("heart rate packets {next_index}..{index} never arrived");
+                    let start = rates.len();
+                    rates.resize(start + missing * RATES_PER_PACKET, 0);
+                    gaps.push(start..rates.len());
+                }
+                rates.extend_from_slice(&packet[2..15]);
+                *next_index = index + 1;
+                if index == *size {
+                    *self = Self::Complete {
+                        range: *range,
+                        rates: std::mem::take(rates),
+                        date: *date,
+                        gaps: std::mem::take(gaps),
+                    };
+                }
             }
             HeartRateState::Complete { .. } => {
-                return Err("Unexpected packet after complete!".to_string().into())
+                return Err(Error::parse(
+                    PacketKind::HeartRate,
+                    packet,
+                    "Unexpected packet after complete!",
+                ))
             }
         };
         Ok(())
     }
 
+    /// The packets after the length packet received and expected, `None` once
+    /// complete
+    pub fn progress(&self) -> Option<(usize, usize)> {
+        match self {
+            Self::Length { size, .. } => Some((0, (*size).into())),
+            Self::Recieving {
+                size, next_index, ..
+            } => Some((usize::from(*next_index) - 1, (*size).into())),
+            Self::Complete { .. } => None,
+        }
+    }
+
     fn step_length(size: u8, range: u8, packet: &[u8]) -> Result<Self> {
         if packet[1] != 1 {
-            return Err(format!(
-                "heart rate packet stream missing datetime packet found sub_type {}",
-                packet[1]
-            )
-            .into());
+            return Err(Error::parse(
+                PacketKind::HeartRate,
+                packet,
+                format!(
+                    "heart rate packet stream missing datetime packet found sub_type {}",
+                    packet[1]
+                ),
+            ));
         }
         let mut timestamp_bytes = [0u8; 4];
         timestamp_bytes.copy_from_slice(&packet[2..6]);
         let timestamp_int = u32::from_le_bytes(timestamp_bytes);
-        println!(
-            "wire: {timestamp_int}\n utc: {}\n lcl: {}",
-            OffsetDateTime::now_utc().unix_timestamp(),
-            OffsetDateTime::now_local()
-                .map(|d| d.unix_timestamp())
-                .unwrap_or_default()
-        );
-        let base_date = OffsetDateTime::from_unix_timestamp(timestamp_int as _)?;
+        log::trace!("heart rate timestamp: {timestamp_int}");
+        let base_date = OffsetDateTime::from_unix_timestamp(timestamp_int as _)
+            .map_err(|e| Error::parse(PacketKind::HeartRate, packet, e))?;
         let date = PrimitiveDateTime::new(base_date.date(), base_date.time());
-        let mut rates = Vec::with_capacity(size as usize * 13);
+        let mut rates = Vec::with_capacity(size as usize * RATES_PER_PACKET);
         for &byte in &packet[6..15] {
             rates.push(byte);
         }
@@ -107,88 +255,149 @@ impl HeartRateState {
             date,
             rates,
             size,
-        })
-    }
-
-    fn step_receiving(
-        size: u8,
-        range: u8,
-        date: PrimitiveDateTime,
-        mut rates: Vec<u8>,
-        packet: &[u8],
-    ) -> Result<Self> {
-        if packet[1] == 0 {
-            return Err("Unexpected size packet after date packet"
-                .to_string()
-                .into());
-        }
-        if packet[1] == 1 {
-            return Err("Unexpected date packet after date packet"
-                .to_string()
-                .into());
-        }
-        for &byte in &packet[2..15] {
-            rates.push(byte);
-        }
-        Ok(if packet[1] == size {
-            Self::Complete { range, rates, date }
-        } else {
-            Self::Recieving {
-                date,
-                size,
-                range,
-                rates,
-            }
+            next_index: 2,
+            gaps: Vec::new(),
         })
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::collections::VecDeque;
+    use std::{
+        future::Future,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        task::{Context, Poll},
+    };
 
-    use time::{Date, Time};
+    use futures::task::ArcWake;
+    use time::{macros::datetime, Date, Time};
+
+    use crate::incoming_messages::{ClientReceiver, CommandReply, RawPacket};
+    use crate::util::DurationExt as _;
 
     use super::*;
 
+    /// A full day of heart rate data as captured from a ring
+    const SYNC_PACKETS: [[u8; 16]; 24] = [
+        *b"\x15\x00\x18\x05\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x002",
+        *b"\x15\x01\x80\xad\xb6f\x00\x00\x00\x00\x00\x00\x00\x00\x00_",
+        *b"\x15\x02\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x17",
+        *b"\x15\x03\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x18",
+        *b"\x15\x04\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x19",
+        *b"\x15\x05\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x1a",
+        *b"\x15\x06\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x1b",
+        *b"\x15\x07\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x1c",
+        *b"\x15\x08\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x1d",
+        *b"\x15\t\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x1e",
+        *b"\x15\n\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x1f",
+        *b"\x15\x0b\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00 ",
+        *b"\x15\x0c\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00!",
+        *b"\x15\r\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\"",
+        *b"\x15\x0e\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00#",
+        *b"\x15\x0f\x00\x00Y\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00}",
+        *b"\x15\x10\x00k\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x90",
+        *b"\x15\x11`\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00k\xf1",
+        *b"\x15\x12\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00'",
+        *b"\x15\x13\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00P\x00\x00x",
+        *b"\x15\x14\x00\x00\x00\x00\x00\x00\x00\x00\x00F\x00\x00\x00o",
+        *b"\x15\x15\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00*",
+        *b"\x15\x16\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00+",
+        *b"\x15\x17\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00,",
+    ];
+
+    fn day_with_gaps() -> HeartRate {
+        let mut rates = vec![0u8; 300];
+        rates[12..17].copy_from_slice(&[64, 60, 58, 61, 70]);
+        rates[100..102].copy_from_slice(&[50, 90]);
+        rates[287] = 80;
+        rates[288] = 99;
+        HeartRate {
+            range: 5,
+            rates,
+            date: datetime!(2024-11-27 0:00),
+            gaps: Vec::new(),
+        }
+    }
+
     #[test]
-    fn parse_multi_packet() {
-        let mut packets = VecDeque::from_iter(
-            [
-                *b"\x15\x00\x18\x05\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x002",
-                *b"\x15\x01\x80\xad\xb6f\x00\x00\x00\x00\x00\x00\x00\x00\x00_",
-                *b"\x15\x02\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x17",
-                *b"\x15\x03\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x18",
-                *b"\x15\x04\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x19",
-                *b"\x15\x05\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x1a",
-                *b"\x15\x06\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x1b",
-                *b"\x15\x07\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x1c",
-                *b"\x15\x08\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x1d",
-                *b"\x15\t\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x1e",
-                *b"\x15\n\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x1f",
-                *b"\x15\x0b\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00 ",
-                *b"\x15\x0c\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00!",
-                *b"\x15\r\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\"",
-                *b"\x15\x0e\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00#",
-                *b"\x15\x0f\x00\x00Y\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00}",
-                *b"\x15\x10\x00k\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x90",
-                *b"\x15\x11`\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00k\xf1",
-                *b"\x15\x12\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00'",
-                *b"\x15\x13\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00P\x00\x00x",
-                *b"\x15\x14\x00\x00\x00\x00\x00\x00\x00\x00\x00F\x00\x00\x00o",
-                *b"\x15\x15\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00*",
-                *b"\x15\x16\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00+",
-                *b"\x15\x17\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00,",
-            ]
-            .into_iter(),
-        );
-        let mut state = HeartRateState::try_from(packets.pop_front().unwrap().as_slice()).unwrap();
+    fn samples_skip_leading_and_trailing_zeros() {
+        let hr = day_with_gaps();
+        let samples: Vec<_> = hr.samples(Duration::minutes(5)).collect();
+        assert_eq!(samples.len(), 288);
+        assert_eq!(samples[0].1, None);
+        assert_eq!(samples[11].1, None);
+        let (when, rate) = samples[12];
+        assert_eq!((when.hour(), when.minute()), (1, 0));
+        assert_eq!(rate, Some(64));
+        let (when, rate) = samples[287];
+        assert_eq!((when.hour(), when.minute()), (23, 55));
+        assert_eq!(rate, Some(80));
+    }
+
+    #[test]
+    fn samples_follow_interval() {
+        let hr = day_with_gaps();
+        let samples: Vec<_> = hr.samples(Duration::minutes(30)).collect();
+        assert_eq!(samples.len(), 48);
+        let (when, rate) = samples[13];
+        assert_eq!((when.hour(), when.minute()), (6, 30));
+        assert_eq!(rate, Some(60));
+    }
+
+    #[test]
+    fn summary() {
+        let summary = day_with_gaps().summary().unwrap();
+        assert_eq!(summary.min, 50);
+        assert_eq!(summary.max, 99);
+        assert_eq!(summary.avg, 632.0 / 9.0);
+        assert_eq!(summary.resting, Some(61));
+    }
+
+    #[test]
+    fn summary_without_readings() {
+        let hr = HeartRate {
+            range: 5,
+            rates: vec![0; 10],
+            date: datetime!(2024-11-27 0:00),
+            gaps: Vec::new(),
+        };
+        assert_eq!(hr.summary(), None);
+    }
+
+    /// Feed `packets` through the state machine, trimming checksums like the receiver does
+    fn parse<'a>(packets: impl IntoIterator<Item = &'a [u8; 16]>) -> Result<HeartRateState> {
+        let mut packets = packets.into_iter();
+        let mut state = HeartRateState::try_from(packets.next().unwrap().as_slice())?;
         for packet in packets {
-            state.step(&packet[..packet.len() - 1]).unwrap();
+            state.step(&packet[..packet.len() - 1])?;
         }
-        let HeartRateState::Complete { range, rates, date } = state else {
+        Ok(state)
+    }
+
+    #[track_caller]
+    fn complete(state: HeartRateState) -> (Vec<u8>, Vec<Range<usize>>) {
+        let HeartRateState::Complete { rates, gaps, .. } = state else {
+            panic!("invalid state: {state:?}");
+        };
+        (rates, gaps)
+    }
+
+    #[test]
+    fn parse_multi_packet() {
+        let state = parse(&SYNC_PACKETS).unwrap();
+        let HeartRateState::Complete {
+            range,
+            rates,
+            date,
+            gaps,
+        } = state
+        else {
             panic!("invalid state: {state:?}");
         };
+        assert!(gaps.is_empty());
         assert_eq!(range, 5);
         assert_eq!(
             date,
@@ -199,4 +408,134 @@ mod tests {
         );
         insta::assert_debug_snapshot!(rates);
     }
+
+    #[test]
+    fn dropped_packet_is_filled() {
+        let (expected, _) = complete(parse(&SYNC_PACKETS).unwrap());
+        let (rates, gaps) = complete(
+            parse(
+                SYNC_PACKETS
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, p)| (i != 7).then_some(p)),
+            )
+            .unwrap(),
+        );
+        assert_eq!(gaps, vec![74..87]);
+        assert_eq!(rates.len(), expected.len());
+        assert_eq!(rates, expected);
+        assert_eq!(rates[180], 89);
+    }
+
+    #[test]
+    fn repeated_packets_are_ignored() {
+        let (expected, _) = complete(parse(&SYNC_PACKETS).unwrap());
+        let mut packets = SYNC_PACKETS.to_vec();
+        packets.insert(16, SYNC_PACKETS[15]);
+        packets.insert(10, SYNC_PACKETS[1]);
+        let (rates, gaps) = complete(parse(&packets).unwrap());
+        assert!(gaps.is_empty());
+        assert_eq!(rates, expected);
+    }
+
+    #[test]
+    fn out_of_order_packet_is_filled_then_ignored() {
+        let (expected, _) = complete(parse(&SYNC_PACKETS).unwrap());
+        let mut packets = SYNC_PACKETS.to_vec();
+        packets.swap(15, 16);
+        let (rates, gaps) = complete(parse(&packets).unwrap());
+        assert_eq!(gaps, vec![178..191]);
+        assert_eq!(rates[180], 0);
+        assert_eq!(rates[..178], expected[..178]);
+        assert_eq!(rates[191..], expected[191..]);
+    }
+
+    #[test]
+    fn too_many_missing_packets_is_an_error() {
+        let err = parse(SYNC_PACKETS[..3].iter().chain(&SYNC_PACKETS[9..])).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                Error::MissingPackets {
+                    kind: PacketKind::HeartRate,
+                    missing: 6
+                }
+            ),
+            "{err}"
+        );
+    }
+
+    struct CountingWaker(AtomicUsize);
+
+    impl ArcWake for CountingWaker {
+        fn wake_by_ref(arc_self: &Arc<Self>) {
+            arc_self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn is_heart_rate(poll: &Poll<Option<CommandReply>>) -> bool {
+        matches!(poll, Poll::Ready(Some(CommandReply::HeartRate(hr))) if hr.rates.len() == 9 + 22 * 13)
+    }
+
+    #[test]
+    fn receiver_drains_sync_in_one_poll() {
+        let stream = futures::stream::iter(SYNC_PACKETS.map(|p| RawPacket::Uart(p.to_vec())));
+        let mut rx = ClientReceiver::from_stream(Box::pin(stream));
+        let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let waker = futures::task::waker(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+        let mut next = Box::pin(rx.next());
+        assert!(is_heart_rate(&next.as_mut().poll(&mut cx)));
+        assert_eq!(counter.0.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn receiver_wakes_for_last_packet() {
+        let (tx, stream) = futures::channel::mpsc::unbounded();
+        let mut rx = ClientReceiver::from_stream(Box::pin(stream));
+        let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let waker = futures::task::waker(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+        let (last, rest) = SYNC_PACKETS.split_last().unwrap();
+        for packet in rest {
+            tx.unbounded_send(RawPacket::Uart(packet.to_vec())).unwrap();
+        }
+        let mut next = Box::pin(rx.next());
+        assert!(next.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(counter.0.load(Ordering::SeqCst), 0);
+        tx.unbounded_send(RawPacket::Uart(last.to_vec())).unwrap();
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+        assert!(is_heart_rate(&next.as_mut().poll(&mut cx)));
+    }
+
+    async fn receive(chunks: Vec<Vec<u8>>) -> Vec<CommandReply> {
+        let stream = futures::stream::iter(chunks.into_iter().map(RawPacket::Uart));
+        let mut rx = ClientReceiver::from_stream(Box::pin(stream));
+        let mut ret = Vec::new();
+        while let Some(reply) = rx.next().await {
+            ret.push(reply);
+        }
+        ret
+    }
+
+    #[tokio::test]
+    async fn receiver_reassembles_rechunked_sync() {
+        let expected = receive(SYNC_PACKETS.iter().map(|p| p.to_vec()).collect()).await;
+        assert_eq!(expected.len(), 1);
+        let bytes = SYNC_PACKETS.concat();
+        for size in [1, 7, 12, 20, 31, bytes.len()] {
+            let chunks = bytes.chunks(size).map(<[u8]>::to_vec).collect();
+            assert_eq!(receive(chunks).await, expected, "chunks of {size}");
+        }
+    }
+
+    #[tokio::test]
+    async fn receiver_reassembles_split_packets() {
+        let expected = receive(SYNC_PACKETS.iter().map(|p| p.to_vec()).collect()).await;
+        let chunks = SYNC_PACKETS
+            .iter()
+            .flat_map(|p| [p[..12].to_vec(), p[12..].to_vec()])
+            .collect();
+        assert_eq!(receive(chunks).await, expected);
+    }
 }
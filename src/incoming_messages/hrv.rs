@@ -0,0 +1,187 @@
+use crate::{constants::CMD_SYNC_HRV, util::check_len, Error, PacketKind, Result};
+
+/// Heart rate variability readings spread across multiple packets
+///
+/// The first packet reports how many packets will follow and how many
+/// minutes apart each reading is, packet index 1 has 12 readings and
+/// every following packet has 13
+#[derive(Debug)]
+pub enum HrvState {
+    Length {
+        last_index: u8,
+        minutes_apart: u8,
+    },
+    Receiving {
+        last_index: u8,
+        readings: Vec<u8>,
+        minutes_apart: u8,
+    },
+    Complete {
+        readings: Vec<u8>,
+        minutes_apart: u8,
+    },
+}
+
+impl HrvState {
+    pub fn new(packet: &[u8]) -> Result<Self> {
+        Self::check_packet(packet)?;
+        if packet[1] == 255 {
+            return Ok(Self::Complete {
+                readings: Vec::new(),
+                minutes_apart: 0,
+            });
+        }
+        if packet[1] != 0 {
+            return Err(Error::parse(
+                PacketKind::Hrv,
+                packet,
+                "unexpected initial hrv state expected index 1 to be 0",
+            ));
+        }
+        if packet[2] == 0 {
+            return Err(Error::parse(
+                PacketKind::Hrv,
+                packet,
+                "hrv packet count must be at least 1",
+            ));
+        }
+        let last_index = packet[2] - 1;
+        let minutes_apart = packet[3];
+        if last_index == 0 {
+            return Ok(Self::Complete {
+                readings: Vec::new(),
+                minutes_apart,
+            });
+        }
+        Ok(Self::Length {
+            last_index,
+            minutes_apart,
+        })
+    }
+
+    pub fn step(&mut self, packet: &[u8]) -> Result {
+        Self::check_packet(packet)?;
+        let index = packet[1];
+        match self {
+            Self::Length {
+                last_index,
+                minutes_apart,
+            } => {
+                if index != 1 {
+                    return Err(Error::parse(
+                        PacketKind::Hrv,
+                        packet,
+                        format!("expected first data packet, found index {index}"),
+                    ));
+                }
+                let mut readings = Vec::with_capacity(*last_index as usize * 13);
+                readings.extend_from_slice(&packet[3..packet.len() - 1]);
+                *self = Self::Receiving {
+                    last_index: *last_index,
+                    readings,
+                    minutes_apart: *minutes_apart,
+                };
+            }
+            Self::Receiving { readings, .. } => {
+                readings.extend_from_slice(&packet[2..packet.len() - 1]);
+            }
+            Self::Complete { .. } => {
+                return Err(Error::parse(
+                    PacketKind::Hrv,
+                    packet,
+                    format!("Step after complete: {self:?}"),
+                ))
+            }
+        }
+        if let Self::Receiving {
+            last_index,
+            readings,
+            minutes_apart,
+        } = self
+        {
+            if index == *last_index {
+                *self = Self::Complete {
+                    readings: std::mem::take(readings),
+                    minutes_apart: *minutes_apart,
+                };
+            }
+        }
+        Ok(())
+    }
+
+    fn check_packet(packet: &[u8]) -> Result {
+        check_len(packet, 4)?;
+        if packet[0] != CMD_SYNC_HRV {
+            return Err(Error::parse(
+                PacketKind::Hrv,
+                packet,
+                "Invalid hrv state packet",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed a captured sequence of packets through the state machine
+    #[track_caller]
+    fn parse(packets: &[[u8; 16]]) -> HrvState {
+        let (first, rest) = packets.split_first().unwrap();
+        let mut state = HrvState::new(first).unwrap();
+        for packet in rest {
+            state.step(packet).unwrap();
+        }
+        state
+    }
+
+    #[test]
+    fn no_data() {
+        let state = parse(&[*b"\x39\xff\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x008"]);
+        let HrvState::Complete {
+            readings,
+            minutes_apart,
+        } = state
+        else {
+            panic!("invalid state: {state:?}");
+        };
+        assert!(readings.is_empty());
+        assert_eq!(minutes_apart, 0);
+    }
+
+    #[test]
+    fn parse_multi_packet() {
+        let state = parse(&[
+            *b"\x39\x00\x03\x1e\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x5a",
+            *b"\x39\x01\x1e\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x2a\x30\xb2",
+            *b"\x39\x02\x31\x2f\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x9b",
+        ]);
+        let HrvState::Complete {
+            readings,
+            minutes_apart,
+        } = state
+        else {
+            panic!("invalid state: {state:?}");
+        };
+        assert_eq!(minutes_apart, 30);
+        assert_eq!(readings.len(), 25);
+        assert_eq!(&readings[10..14], &[42, 48, 49, 47]);
+    }
+
+    #[test]
+    fn out_of_order_first_packet_is_error() {
+        let mut state =
+            HrvState::new(b"\x39\x00\x03\x1e\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x5a")
+                .unwrap();
+        assert!(state
+            .step(b"\x39\x02\x31\x2f\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x9b")
+            .is_err());
+    }
+
+    #[test]
+    fn wrong_command_is_error() {
+        assert!(HrvState::new(&[0x37, 0, 3, 30]).is_err());
+    }
+}
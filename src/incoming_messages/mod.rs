@@ -1,58 +1,275 @@
 use std::{
+    collections::VecDeque,
     ops::Range,
     ops::{Index, RangeTo},
+    path::Path,
     pin::Pin,
+    sync::Arc,
+    time::Duration,
 };
 
 use big_data::{BigDataPacket, BigDataState, OxygenData, SleepData};
+#[cfg(feature = "ble")]
 use bleasy::{Characteristic, Device};
+use framer::PacketFramer;
 use futures::{Stream, StreamExt};
 use heart_rate::{HeartRate, HeartRateState};
+use hrv::HrvState;
 use notification::Notification;
 use sport_detail::{SportDetail, SportDetailState};
-use stress::StressState;
+use stress::{StressData, StressState};
+use time::{Date, PrimitiveDateTime, Time};
+use tokio::sync::{broadcast, watch};
 
 pub mod big_data;
+pub mod framer;
 pub mod heart_rate;
+pub mod hrv;
 pub mod notification;
 pub mod sport_detail;
 pub mod stress;
 
-use crate::{constants, Result};
+use crate::{
+    capture::{CaptureRecord, CaptureWriter, Channel, Direction},
+    client::{HeartRateSettings, HrInterval},
+    constants,
+    util::{check_len, local_today, trim_padding, verify_checksum},
+    Error, PacketKind, Result,
+};
+
+/// The packets received from a ring, on either characteristic
+#[cfg(not(target_arch = "wasm32"))]
+pub type PacketStream = Pin<Box<dyn Stream<Item = RawPacket> + Send>>;
+/// The packets received from a ring, on either characteristic. Javascript
+/// streams aren't `Send` so neither is this on wasm
+#[cfg(target_arch = "wasm32")]
+pub type PacketStream = Pin<Box<dyn Stream<Item = RawPacket>>>;
+
+/// Subscribe to the uart and v2 notify characteristics of `device`, returning the
+/// combined packets and the characteristics that need to be unsubscribed from
+#[cfg(feature = "ble")]
+pub(crate) async fn subscribe_device(
+    device: &Device,
+) -> Result<(PacketStream, Vec<Characteristic>)> {
+    use crate::constants::{
+        CHARACTERISTIC_NOTIFY_V2, CHARACTERISTIC_SERVICE_V2, UART_SERVICE_UUID, UART_TX_CHAR_UUID,
+    };
+    use crate::gatt_names;
+    let mut streams = Vec::with_capacity(2);
+    let mut charas = Vec::with_capacity(2);
+    for s in device.services().await? {
+        log::debug!("service {}", gatt_names::describe_service(s.uuid()));
+        for ch in s.characteristics() {
+            log::debug!(
+                "  characteristic {}",
+                gatt_names::describe_characteristic(ch.uuid())
+            );
+            let wrap: fn(Vec<u8>) -> RawPacket = match (s.uuid(), ch.uuid()) {
+                (UART_SERVICE_UUID, UART_TX_CHAR_UUID) => RawPacket::Uart,
+                (CHARACTERISTIC_SERVICE_V2, CHARACTERISTIC_NOTIFY_V2) => RawPacket::V2,
+                _ => continue,
+            };
+            let stream: Pin<Box<dyn Stream<Item = Vec<u8>> + Send>> = ch.subscribe().await?;
+            let stream: PacketStream = Box::pin(stream.map(wrap));
+            streams.push(stream);
+            charas.push(ch);
+        }
+    }
+    if streams.is_empty() {
+        log::warn!(
+            "{} has neither {} nor {}",
+            device.address(),
+            gatt_names::describe_characteristic(UART_TX_CHAR_UUID),
+            gatt_names::describe_characteristic(CHARACTERISTIC_NOTIFY_V2)
+        );
+    }
+    Ok((Box::pin(futures::stream::select_all(streams)), charas))
+}
 
 pub struct ClientReceiver {
-    stream: Pin<Box<dyn Stream<Item = RawPacket>>>,
+    stream: PacketStream,
     parser: PacketParser,
+    framer: PacketFramer,
+    /// Uart packets the framer has completed that haven't been parsed yet
+    frames: VecDeque<RawPacket>,
+    capture: Option<CaptureWriter>,
+    /// The characteristics subscribed to by `connect_device`
+    #[cfg(feature = "ble")]
     charas: Vec<Characteristic>,
+    pending: VecDeque<CommandReply>,
+    progress: watch::Sender<Option<SyncProgress>>,
+    /// Where every packet received is sent as is, see `ClientReceiver::share_raw`
+    raw: Option<broadcast::Sender<RawPacket>>,
+    /// If the packet stream has ended, the ring dropped the connection
+    closed: bool,
 }
 
+/// Turns the packets from a ring into replies, assembling the replies that span
+/// several packets
+///
+/// [`ClientReceiver`] wraps this for a live connection, it can also be fed
+/// packets captured elsewhere
 #[derive(Debug, Default)]
-struct PacketParser {
+pub struct PacketParser {
     multi_packet_states: MultiPacketStates,
+    skip_checksum: bool,
+    skip_empty_oxygen: bool,
+    unhandled: Option<UnhandledHook>,
+}
+
+/// Why a packet didn't become a typed reply
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unhandled {
+    /// The command byte isn't one the parser understands, the packet was
+    /// returned as `CommandReply::Unknown`
+    Unknown,
+    /// The packet was consumed by an in progress multi-packet reply of this kind
+    Partial(PacketKind),
+    /// The packet failed to parse
+    Failed,
+    /// The packet became a typed reply but its layout is only partly known,
+    /// reported so it can be compared across firmware
+    Unverified,
+}
+
+/// How much of a multi-packet reply has arrived, sleep and sport detail syncs
+/// in particular can take several seconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyncProgress {
+    pub kind: PacketKind,
+    /// Packets received so far, or bytes for sleep and oxygen
+    pub received: usize,
+    pub expected: usize,
+}
+
+type UnhandledFn = dyn Fn(&RawPacket, Unhandled) + Send + Sync;
+
+/// A callback for packets the parser didn't convert into a typed reply
+#[derive(Clone)]
+pub struct UnhandledHook(Arc<UnhandledFn>);
+
+impl UnhandledHook {
+    pub fn new(hook: impl Fn(&RawPacket, Unhandled) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(hook))
+    }
+}
+
+impl std::fmt::Debug for UnhandledHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("UnhandledHook")
+    }
 }
 
 impl PacketParser {
-    fn handle_packet(&mut self, packet: &RawPacket) -> Option<CommandReply> {
+    /// The stress reply doesn't include its date, so the parser is told which
+    /// day was requested before the reply arrives
+    pub fn expect_stress_day(&mut self, date: Date) {
+        self.multi_packet_states.stress_day = Some(date);
+    }
+
+    /// The sleep and oxygen replies count days back from the day they were
+    /// requested, so the parser is told that day before the reply arrives
+    pub fn expect_big_data(&mut self, reference: Date) {
+        self.multi_packet_states.big_data_reference = Some(reference);
+    }
+
+    /// Drop a sleep or oxygen reply that stopped arriving part way through, the
+    /// next big data packet is treated as the start of a new reply. A new
+    /// reply's first packet does this on its own
+    pub fn discard_big_data(&mut self) {
+        self.multi_packet_states.partial_big_data = None;
+    }
+
+    /// How far along the multi-packet reply that is arriving is, `None` when
+    /// there isn't one
+    pub fn progress(&self) -> Option<SyncProgress> {
+        let states = &self.multi_packet_states;
+        let (kind, (received, expected)) = if let Some(s) = &states.sport_detail {
+            (PacketKind::SportDetail, s.progress()?)
+        } else if let Some(s) = &states.heart_rate_state {
+            (PacketKind::HeartRate, s.progress()?)
+        } else {
+            let (kind, received, expected) = states.partial_big_data.as_ref()?.progress()?;
+            (kind, (received, expected))
+        };
+        Some(SyncProgress {
+            kind,
+            received,
+            expected,
+        })
+    }
+
+    /// Report every packet the parser doesn't turn into a typed reply to `hook`
+    pub fn on_unhandled(&mut self, hook: UnhandledHook) {
+        self.unhandled = Some(hook);
+    }
+
+    /// Enable or disable checking the trailing checksum byte of uart packets.
+    /// Validation is enabled by default
+    pub fn set_checksum_validation(&mut self, enabled: bool) {
+        self.skip_checksum = !enabled;
+    }
+
+    /// Leave the hours without a reading out of oxygen replies, see
+    /// `OxygenMeasurement::is_empty`. They are kept by default
+    pub fn set_skip_empty_oxygen(&mut self, skip: bool) {
+        self.skip_empty_oxygen = skip;
+    }
+
+    /// Parse one complete packet, `None` when it was part of a multi-packet reply
+    /// that hasn't finished yet
+    ///
+    /// Uart packets must be whole 16 byte packets, see [`framer::PacketFramer`]
+    /// for reassembling split notifications
+    pub fn handle_packet(&mut self, packet: &RawPacket) -> Result<Option<CommandReply>> {
         log::trace!("handle_packet: {packet:?}");
-        match packet {
+        let ret = match packet {
             RawPacket::Uart(inner) => self.handle_uart(inner),
             RawPacket::V2(inner) => self.handle_v2(inner),
         }
         .inspect_err(|e| {
             log::warn!("Error parsing packet: {e}");
-        })
-        .ok()?
+        });
+        if let Some(UnhandledHook(hook)) = &self.unhandled {
+            match &ret {
+                Ok(Some(CommandReply::Unknown(_))) => hook(packet, Unhandled::Unknown),
+                Ok(Some(CommandReply::SetPhoneName(_))) => hook(packet, Unhandled::Unverified),
+                Ok(Some(_)) => {}
+                Ok(None) => hook(packet, Unhandled::Partial(Self::consumer(packet))),
+                Err(_) => hook(packet, Unhandled::Failed),
+            }
+        }
+        ret
+    }
+
+    /// The kind of multi-packet reply a packet that didn't complete a reply belongs to
+    fn consumer(packet: &RawPacket) -> PacketKind {
+        match packet {
+            RawPacket::V2(_) => PacketKind::BigData,
+            RawPacket::Uart(inner) => match inner.first().copied() {
+                Some(constants::CMD_SYNC_HEART_RATE) => PacketKind::HeartRate,
+                Some(constants::CMD_SYNC_STRESS) => PacketKind::Stress,
+                Some(constants::CMD_SYNC_HRV) => PacketKind::Hrv,
+                Some(constants::CMD_SYNC_ACTIVITY) => PacketKind::SportDetail,
+                _ => PacketKind::Uart,
+            },
+        }
     }
 
     fn handle_uart(&mut self, packet: &[u8]) -> Result<Option<CommandReply>> {
         log::trace!("uart packet: {packet:?}");
+        check_len(packet, constants::UART_PACKET_LEN)?;
+        if !self.skip_checksum {
+            verify_checksum(packet)?;
+        }
         Ok(Some(match packet[0] {
             constants::CMD_NOTIFICATION => {
                 CommandReply::Notification(Notification::try_from(packet)?)
             }
             constants::CMD_SET_DATE_TIME => {
-                log::debug!("SetTime Reply");
-                CommandReply::SetTime
+                let device_time = echoed_time(packet);
+                log::debug!("SetTime Reply {device_time:?}");
+                CommandReply::SetTime { device_time }
             }
             constants::CMD_BATTERY => {
                 log::debug!("Battery Info Reply {}, {}", packet[1], packet[2]);
@@ -61,28 +278,87 @@ impl PacketParser {
                     charging: packet[2] > 0,
                 }
             }
+            // reboot and power off share a command byte, the ring echos
+            // back the sub command in the ack
+            constants::CMD_POWER_OFF if packet[1] == constants::POWER_OFF_SHUTDOWN => {
+                log::debug!("PowerOff Reply");
+                CommandReply::PowerOff
+            }
             constants::CMD_POWER_OFF => {
                 log::debug!("Reboot Reply");
                 CommandReply::Reboot
             }
+            constants::CMD_FACTORY_RESET => {
+                log::debug!("FactoryReset Reply");
+                CommandReply::FactoryReset
+            }
             constants::CMD_BLINK => {
                 log::debug!("BlinkTwice Reply");
                 CommandReply::BlinkTwice
             }
+            constants::CMD_PHONE_NAME => {
+                log::debug!("SetPhoneName Reply {packet:?}");
+                CommandReply::SetPhoneName(packet[1..15].to_vec())
+            }
+            constants::CMD_FIND_DEVICE => {
+                log::debug!("FindDevice Reply");
+                CommandReply::FindDevice
+            }
             constants::CMD_SYNC_HEART_RATE => {
                 return self.handle_heart_rate(packet);
             }
             constants::CMD_AUTO_HR_PREF if packet[2] == 1 || packet[2] == 2 => {
                 log::debug!("HeartRateSettings reply");
-                CommandReply::HeartRateSettings {
+                let interval = HrInterval::try_new(packet[3])
+                    .map_err(|e| Error::parse(PacketKind::Uart, packet, e))?;
+                CommandReply::HeartRateSettings(HeartRateSettings {
+                    enabled: packet[2] == 1,
+                    interval,
+                })
+            }
+            constants::CMD_PREFERENCES => {
+                log::debug!("Preference reply");
+                CommandReply::Preference {
+                    key: packet[2],
+                    value: trim_padding(&packet[3..15]).to_vec(),
+                }
+            }
+            constants::CMD_AUTO_SPO2_PREF => {
+                log::debug!("Spo2Pref reply");
+                CommandReply::Spo2Pref {
                     enabled: packet[2] == 1,
-                    interval: packet[3],
                 }
             }
+            constants::CMD_AUTO_STRESS_PREF => {
+                log::debug!("StressPref reply");
+                CommandReply::StressPref {
+                    enabled: packet[2] == 1,
+                }
+            }
+            constants::CMD_GOALS if packet[1] == constants::PREF_READ => {
+                log::debug!("Goals reply");
+                let read_u24 = |start: usize| {
+                    u32::from_le_bytes([packet[start], packet[start + 1], packet[start + 2], 0])
+                };
+                CommandReply::Goals {
+                    steps: read_u24(2),
+                    calories: read_u24(5),
+                    distance: read_u24(8),
+                }
+            }
+            constants::CMD_GOALS => {
+                log::debug!("SetGoals reply");
+                CommandReply::SetGoals
+            }
             constants::CMD_SYNC_STRESS => return self.handle_stress(packet),
+            constants::CMD_SYNC_HRV => return self.handle_hrv(packet),
+            constants::CMD_AUTO_HRV_PREF => {
+                log::debug!("SetAutoHrvPref reply");
+                CommandReply::SetAutoHrvPref
+            }
             constants::CMD_SYNC_ACTIVITY => return self.handle_sport_detail(packet),
             constants::CMD_MANUAL_HEART_RATE => self.handle_real_time(packet),
-            106 => {
+            constants::CMD_STOP_REAL_TIME => {
                 log::debug!("StopRealTime reply");
                 CommandReply::StopRealTime
             }
@@ -94,10 +370,20 @@ impl PacketParser {
     }
 
     fn handle_v2(&mut self, packet: &[u8]) -> Result<Option<CommandReply>> {
+        if self.multi_packet_states.partial_big_data.is_some() && BigDataState::is_header(packet) {
+            log::warn!("a new big data reply started before the last one finished");
+            self.discard_big_data();
+        }
         if let Some(s) = &mut self.multi_packet_states.partial_big_data {
-            s.step(packet)?;
+            if let Err(e) = s.step(packet) {
+                self.discard_big_data();
+                return Err(e);
+            }
         } else {
             self.multi_packet_states.partial_big_data = Some(BigDataState::new(packet)?);
+            self.multi_packet_states
+                .big_data_reference
+                .get_or_insert_with(local_today);
         }
         self.check_for_complete_big_data()
     }
@@ -124,8 +410,8 @@ impl PacketParser {
             };
             Ok(Some(CommandReply::SportDetail(packets)))
         } else {
-            self.multi_packet_states.sport_detail = SportDetailState::new(packet).ok();
-            return Ok(None);
+            self.multi_packet_states.sport_detail = Some(SportDetailState::new(packet)?);
+            Ok(None)
         }
     }
 
@@ -139,6 +425,16 @@ impl PacketParser {
         Ok(self.check_for_complete_stress())
     }
 
+    fn handle_hrv(&mut self, packet: &[u8]) -> Result<Option<CommandReply>> {
+        log::debug!("Hrv reply {:?}", self.multi_packet_states.hrv_state);
+        if let Some(s) = self.multi_packet_states.hrv_state.as_mut() {
+            s.step(packet)?;
+        } else {
+            self.multi_packet_states.hrv_state = Some(HrvState::new(packet)?);
+        }
+        Ok(self.check_for_complete_hrv())
+    }
+
     fn handle_heart_rate(&mut self, packet: &[u8]) -> Result<Option<CommandReply>> {
         log::debug!("Heart Rate Reply");
         Ok(Some(
@@ -146,23 +442,42 @@ impl PacketParser {
                 log::debug!("Stepping heart rate state");
                 // We need to trim the checksum byte here because the packet will be offset
                 // if we don't
-                if let Err(e) = s.step(&packet[..packet.len() - 1]) {
-                    log::warn!("failed to step heart rate: {e}");
-                    return Ok(None);
-                }
-                let HeartRateState::Complete { date, range, rates } = s else {
+                // a failed step leaves the sync unrecoverable so the state is dropped
+                s.step(&packet[..packet.len() - 1])?;
+                let HeartRateState::Complete {
+                    date,
+                    range,
+                    rates,
+                    gaps,
+                } = s
+                else {
                     log::debug!("heart rate incomplete, waiting for remaining data: {s:?}");
                     self.multi_packet_states.heart_rate_state = Some(s);
                     return Ok(None);
                 };
                 log::debug!("hear rate state complete");
-                CommandReply::HeartRate(HeartRate { range, rates, date })
+                CommandReply::HeartRate(HeartRate {
+                    range,
+                    rates,
+                    date,
+                    gaps,
+                })
             } else {
                 log::debug!("Initial heart rate packet");
                 match HeartRateState::try_from(packet) {
-                    Ok(HeartRateState::Complete { date, range, rates }) => {
+                    Ok(HeartRateState::Complete {
+                        date,
+                        range,
+                        rates,
+                        gaps,
+                    }) => {
                         log::trace!("First packet was only packet for heart rate data");
-                        CommandReply::HeartRate(HeartRate { range, rates, date })
+                        CommandReply::HeartRate(HeartRate {
+                            range,
+                            rates,
+                            date,
+                            gaps,
+                        })
                     }
                     Ok(other) => {
                         log::trace!(
@@ -182,16 +497,25 @@ impl PacketParser {
 
     fn check_for_complete_big_data(&mut self) -> Result<Option<CommandReply>> {
         match self.multi_packet_states.partial_big_data.take() {
-            Some(BigDataState::Complete(packet)) => match &packet {
-                BigDataPacket::Sleep(_) => {
-                    let sleep_data: SleepData = packet.try_into()?;
-                    Ok(Some(CommandReply::Sleep(sleep_data)))
-                }
-                BigDataPacket::Oxygen(_) => {
-                    let oxy_data: OxygenData = packet.try_into()?;
-                    Ok(Some(CommandReply::Oxygen(oxy_data)))
+            Some(BigDataState::Complete(packet)) => {
+                let reference = self
+                    .multi_packet_states
+                    .big_data_reference
+                    .take()
+                    .unwrap_or_else(local_today);
+                match &packet {
+                    BigDataPacket::Sleep(_) => Ok(Some(CommandReply::Sleep(SleepData::parse(
+                        &packet, reference,
+                    )?))),
+                    BigDataPacket::Oxygen(_) => {
+                        let mut oxygen = OxygenData::parse(&packet, reference)?;
+                        if self.skip_empty_oxygen {
+                            oxygen.samples.retain(|sample| !sample.is_empty());
+                        }
+                        Ok(Some(CommandReply::Oxygen(oxygen)))
+                    }
                 }
-            },
+            }
             state => {
                 self.multi_packet_states.partial_big_data = state;
                 Ok(None)
@@ -203,12 +527,18 @@ impl PacketParser {
         match self.multi_packet_states.stress_state.take() {
             Some(StressState::Complete {
                 measurements,
-                minutes_appart,
+                minutes_apart,
             }) => {
-                return Some(CommandReply::Stress {
-                    time_interval_sec: minutes_appart,
+                let date = self
+                    .multi_packet_states
+                    .stress_day
+                    .take()
+                    .unwrap_or_else(local_today);
+                Some(CommandReply::Stress(StressData::new(
+                    date,
+                    minutes_apart,
                     measurements,
-                })
+                )))
             }
             state => {
                 self.multi_packet_states.stress_state = state;
@@ -216,38 +546,135 @@ impl PacketParser {
             }
         }
     }
+
+    fn check_for_complete_hrv(&mut self) -> Option<CommandReply> {
+        match self.multi_packet_states.hrv_state.take() {
+            Some(HrvState::Complete {
+                readings,
+                minutes_apart,
+            }) => Some(CommandReply::Hrv {
+                time_interval_sec: u16::from(minutes_apart) * 60,
+                readings,
+            }),
+            state => {
+                self.multi_packet_states.hrv_state = state;
+                None
+            }
+        }
+    }
 }
 
-#[derive(Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 #[serde(tag = "command", content = "data", rename_all = "camelCase")]
 pub enum CommandReply {
     BatteryInfo {
         level: u8,
         charging: bool,
     },
-    HeartRateSettings {
-        enabled: bool,
-        interval: u8,
-    },
+    HeartRateSettings(HeartRateSettings),
     SportDetail(Vec<SportDetail>),
     HeartRate(HeartRate),
     RealTimeData(RealTimeEvent),
     BlinkTwice,
-    SetTime,
+    FindDevice,
+    /// The ack to setting the phone name, with the bytes after the command byte
+    /// since what they mean varies by firmware
+    SetPhoneName(Vec<u8>),
+    SetTime {
+        /// The time the ring reports after setting it, only some firmware
+        /// echoes it back
+        device_time: Option<PrimitiveDateTime>,
+    },
     Reboot,
+    PowerOff,
+    FactoryReset,
     StopRealTime,
     SetHrSettings,
-    Stress {
-        time_interval_sec: u8,
-        measurements: Vec<u8>,
+    /// The value stored under a preference key, sent after reading, writing or
+    /// deleting it. The zero padding after the value is removed
+    Preference {
+        key: u8,
+        value: Vec<u8>,
+    },
+    /// Whether the ring measures blood oxygen on its own, sent after reading or
+    /// changing it
+    Spo2Pref {
+        enabled: bool,
     },
+    /// Whether the ring measures stress on its own, sent after reading or
+    /// changing it
+    StressPref {
+        enabled: bool,
+    },
+    Goals {
+        steps: u32,
+        calories: u32,
+        distance: u32,
+    },
+    SetGoals,
+    Stress(StressData),
+    Hrv {
+        time_interval_sec: u16,
+        readings: Vec<u8>,
+    },
+    SetAutoHrvPref,
     Sleep(SleepData),
     Oxygen(OxygenData),
     Notification(Notification),
     Unknown(Vec<u8>),
 }
 
-#[derive(Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+/// The device time in a set time reply, laid out the same way as the command
+/// that set it. `None` when the firmware doesn't echo it
+fn echoed_time(packet: &[u8]) -> Option<PrimitiveDateTime> {
+    let &[year, month, day, hour, minute, second] = packet.get(1..7)? else {
+        return None;
+    };
+    if [year, month, day].contains(&0) {
+        return None;
+    }
+    let date = Date::from_calendar_date(
+        2000 + i32::from(year),
+        time::Month::try_from(month).ok()?,
+        day,
+    )
+    .ok()?;
+    let time = Time::from_hms(hour, minute, second).ok()?;
+    Some(PrimitiveDateTime::new(date, time))
+}
+
+impl CommandReply {
+    /// The command byte of the packets this reply was parsed from, `None` for
+    /// notifications the ring sends on its own
+    pub fn command(&self) -> Option<u8> {
+        Some(match self {
+            Self::BatteryInfo { .. } => constants::CMD_BATTERY,
+            Self::HeartRateSettings { .. } | Self::SetHrSettings => constants::CMD_AUTO_HR_PREF,
+            Self::SportDetail(_) => constants::CMD_SYNC_ACTIVITY,
+            Self::HeartRate(_) => constants::CMD_SYNC_HEART_RATE,
+            Self::RealTimeData(_) => constants::CMD_MANUAL_HEART_RATE,
+            Self::BlinkTwice => constants::CMD_BLINK,
+            Self::FindDevice => constants::CMD_FIND_DEVICE,
+            Self::SetPhoneName(_) => constants::CMD_PHONE_NAME,
+            Self::SetTime { .. } => constants::CMD_SET_DATE_TIME,
+            Self::Reboot | Self::PowerOff => constants::CMD_POWER_OFF,
+            Self::FactoryReset => constants::CMD_FACTORY_RESET,
+            Self::StopRealTime => constants::CMD_STOP_REAL_TIME,
+            Self::Preference { .. } => constants::CMD_PREFERENCES,
+            Self::Spo2Pref { .. } => constants::CMD_AUTO_SPO2_PREF,
+            Self::StressPref { .. } => constants::CMD_AUTO_STRESS_PREF,
+            Self::Goals { .. } | Self::SetGoals => constants::CMD_GOALS,
+            Self::Stress(_) => constants::CMD_SYNC_STRESS,
+            Self::Hrv { .. } => constants::CMD_SYNC_HRV,
+            Self::SetAutoHrvPref => constants::CMD_AUTO_HRV_PREF,
+            Self::Sleep(_) | Self::Oxygen(_) => constants::CMD_BIG_DATA_V2,
+            Self::Notification(_) => return None,
+            Self::Unknown(bytes) => return bytes.first().copied(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 #[serde(tag = "event", content = "value", rename_all = "camelCase")]
 pub enum RealTimeEvent {
     HeartRate(u8),
@@ -257,54 +684,214 @@ pub enum RealTimeEvent {
 
 impl ClientReceiver {
     pub async fn next(&mut self) -> Option<CommandReply> {
-        while let Some(event) = self.stream.next().await {
-            if let Some(parsed) = self.parser.handle_packet(&event) {
+        if let Some(reply) = self.pending.pop_front() {
+            return Some(reply);
+        }
+        self.next_from_stream().await
+    }
+
+    /// Wait up to `timeout` for a reply that satisfies `matcher`
+    ///
+    /// Any replies that don't match are held onto and will be returned, in order,
+    /// from subsequent calls to `next`. Returns `None` if the timeout elapses or the
+    /// stream ends before a matching reply arrives.
+    pub async fn wait_for(
+        &mut self,
+        matcher: impl Fn(&CommandReply) -> bool,
+        timeout: Duration,
+    ) -> Option<CommandReply> {
+        if let Some(idx) = self.pending.iter().position(&matcher) {
+            return self.pending.remove(idx);
+        }
+        tokio::time::timeout(timeout, async {
+            while let Some(reply) = self.next_from_stream().await {
+                if matcher(&reply) {
+                    return Some(reply);
+                }
+                log::debug!("holding unmatched reply: {reply:?}");
+                self.pending.push_back(reply);
+            }
+            None
+        })
+        .await
+        .ok()
+        .flatten()
+    }
+
+    /// Like `next` but packets that fail to parse, or fail checksum validation,
+    /// are yielded as errors instead of being skipped
+    pub async fn try_next(&mut self) -> Option<Result<CommandReply>> {
+        if let Some(reply) = self.pending.pop_front() {
+            return Some(Ok(reply));
+        }
+        self.try_next_from_stream().await
+    }
+
+    /// Enable or disable checking the trailing checksum byte of incoming uart
+    /// packets. Validation is enabled by default
+    pub fn with_checksum_validation(mut self, enabled: bool) -> Self {
+        self.set_checksum_validation(enabled);
+        self
+    }
+
+    pub fn set_checksum_validation(&mut self, enabled: bool) {
+        self.parser.set_checksum_validation(enabled);
+        self.framer.set_checksum_validation(enabled);
+    }
+
+    /// Leave the hours without a reading out of oxygen replies, they are kept
+    /// by default
+    pub fn set_skip_empty_oxygen(&mut self, skip: bool) {
+        self.parser.set_skip_empty_oxygen(skip);
+    }
+
+    /// Append every packet sent or received to the capture file at `path`,
+    /// see [`crate::capture`] for the format
+    pub fn with_capture(mut self, path: impl AsRef<Path>) -> Result<Self> {
+        self.set_capture(path)?;
+        Ok(self)
+    }
+
+    pub fn set_capture(&mut self, path: impl AsRef<Path>) -> Result {
+        self.capture = Some(CaptureWriter::open(path)?);
+        Ok(())
+    }
+
+    /// Add a packet written to the ring to the capture, if one is being recorded
+    pub fn record_sent(&mut self, chan: Channel, bytes: &[u8]) {
+        self.record(CaptureRecord::new(Direction::Tx, chan, bytes.to_vec()));
+    }
+
+    fn record(&mut self, record: CaptureRecord) {
+        let Some(capture) = self.capture.as_mut() else {
+            return;
+        };
+        if let Err(e) = capture.write(&record) {
+            log::warn!("failed to write capture, no longer recording: {e}");
+            self.capture = None;
+        }
+    }
+
+    /// Call `hook` with every packet that isn't converted into a typed reply,
+    /// including the ones consumed by multi-packet replies that are still arriving
+    pub fn on_unhandled(&mut self, hook: UnhandledHook) {
+        self.parser.on_unhandled(hook);
+    }
+
+    /// Follow how far along the multi-packet reply that is arriving is, the
+    /// value goes back to `None` once the reply completes or fails
+    pub fn progress(&self) -> watch::Receiver<Option<SyncProgress>> {
+        self.progress.subscribe()
+    }
+
+    /// Publish progress on `progress` instead of this receiver's own channel, so
+    /// a client's subscribers keep working across reconnects
+    #[cfg(feature = "ble")]
+    pub(crate) fn share_progress(&mut self, progress: watch::Sender<Option<SyncProgress>>) {
+        self.progress = progress;
+    }
+
+    /// Send every packet received on `raw` before it is parsed, so a client's
+    /// subscribers keep working across reconnects
+    #[cfg(feature = "ble")]
+    pub(crate) fn share_raw(&mut self, raw: broadcast::Sender<RawPacket>) {
+        self.raw = Some(raw);
+    }
+
+    /// Tell the parser which day the next stress reply describes
+    pub fn expect_stress_day(&mut self, date: Date) {
+        self.parser.expect_stress_day(date);
+    }
+
+    /// Tell the parser which day the next sleep or oxygen reply was requested on
+    pub fn expect_big_data(&mut self, reference: Date) {
+        self.parser.expect_big_data(reference);
+    }
+
+    async fn next_from_stream(&mut self) -> Option<CommandReply> {
+        while let Some(result) = self.try_next_from_stream().await {
+            if let Ok(parsed) = result {
                 return Some(parsed);
             }
         }
         None
     }
 
-    pub async fn connect_device(device: &Device) -> Result<Self> {
-        let mut streams = Vec::with_capacity(2);
-        let mut charas = Vec::with_capacity(2);
-        for s in device.services().await? {
-            if s.uuid() == crate::constants::UART_SERVICE_UUID {
-                for ch in s.characteristics() {
-                    if ch.uuid() == crate::constants::UART_TX_CHAR_UUID {
-                        let stream: Pin<Box<dyn Stream<Item = Vec<u8>>>> = ch.subscribe().await?;
-                        let stream: Pin<Box<dyn Stream<Item = RawPacket>>> =
-                            Box::pin(stream.map(RawPacket::Uart));
-                        streams.push(stream);
-                        charas.push(ch);
-                    }
-                }
+    async fn try_next_from_stream(&mut self) -> Option<Result<CommandReply>> {
+        while let Some(event) = self.next_packet().await {
+            let result = self.parser.handle_packet(&event);
+            let progress = self.parser.progress();
+            self.progress.send_if_modified(|current| {
+                let changed = *current != progress;
+                *current = progress;
+                changed
+            });
+            match result {
+                Ok(Some(parsed)) => return Some(Ok(parsed)),
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
             }
-            if s.uuid() == crate::constants::CHARACTERISTIC_SERVICE_V2 {
-                for ch in s.characteristics() {
-                    if ch.uuid() == crate::constants::CHARACTERISTIC_NOTIFY_V2 {
-                        let stream: Pin<Box<dyn Stream<Item = Vec<u8>>>> = ch.subscribe().await?;
-                        let stream: Pin<Box<dyn Stream<Item = RawPacket>>> =
-                            Box::pin(stream.map(RawPacket::V2));
-                        streams.push(stream);
-                        charas.push(ch);
-                    }
-                }
+        }
+        None
+    }
+
+    /// The next complete packet, uart notifications are reassembled into 16 byte
+    /// packets while v2 notifications are passed along as is
+    async fn next_packet(&mut self) -> Option<RawPacket> {
+        loop {
+            if let Some(frame) = self.frames.pop_front() {
+                return Some(frame);
+            }
+            let Some(packet) = self.stream.next().await else {
+                self.closed = true;
+                return None;
+            };
+            if self.capture.is_some() {
+                self.record(CaptureRecord::received(&packet));
+            }
+            if let Some(raw) = self.raw.as_ref().filter(|raw| raw.receiver_count() > 0) {
+                let _ = raw.send(packet.clone());
+            }
+            match packet {
+                RawPacket::Uart(chunk) => self
+                    .frames
+                    .extend(self.framer.push(&chunk).into_iter().map(RawPacket::Uart)),
+                v2 => return Some(v2),
             }
         }
-        let mut ret = Self::from_stream(Box::pin(futures::stream::select_all(streams)));
+    }
+
+    #[cfg(feature = "ble")]
+    pub async fn connect_device(device: &Device) -> Result<Self> {
+        let (stream, charas) = subscribe_device(device).await?;
+        let mut ret = Self::from_stream(stream);
         ret.charas = charas;
         Ok(ret)
     }
 
-    pub fn from_stream(stream: Pin<Box<dyn Stream<Item = RawPacket>>>) -> Self {
+    pub fn from_stream(stream: PacketStream) -> Self {
         ClientReceiver {
             stream,
             parser: PacketParser::default(),
+            framer: PacketFramer::default(),
+            frames: Default::default(),
+            capture: None,
+            #[cfg(feature = "ble")]
             charas: Default::default(),
+            pending: Default::default(),
+            progress: watch::channel(None).0,
+            raw: None,
+            closed: false,
         }
     }
 
+    /// If the packet stream ended and every held reply has been returned, nothing
+    /// more will arrive until a new receiver is subscribed
+    pub fn is_closed(&self) -> bool {
+        self.closed && self.pending.is_empty()
+    }
+
+    #[cfg(feature = "ble")]
     pub async fn disconnect(&self) -> Result {
         for ch in &self.charas {
             ch.unsubscribe().await?;
@@ -318,7 +905,13 @@ pub struct MultiPacketStates {
     sport_detail: Option<SportDetailState>,
     heart_rate_state: Option<HeartRateState>,
     stress_state: Option<StressState>,
+    /// The day requested by the last `ReadStress` command
+    stress_day: Option<Date>,
+    hrv_state: Option<HrvState>,
     partial_big_data: Option<BigDataState>,
+    /// The date the in progress big data reply was requested, or started
+    /// arriving if the request wasn't seen
+    big_data_reference: Option<Date>,
 }
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize, PartialEq)]
@@ -365,3 +958,506 @@ impl AsRef<[u8]> for RawPacket {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{util::checksum, Error};
+
+    /// Every command byte `PacketParser` knows how to handle
+    const COMMANDS: &[u8] = &[
+        constants::CMD_SET_DATE_TIME,
+        constants::CMD_BATTERY,
+        constants::CMD_PHONE_NAME,
+        constants::CMD_POWER_OFF,
+        constants::CMD_BLINK,
+        constants::CMD_PREFERENCES,
+        constants::CMD_FIND_DEVICE,
+        constants::CMD_SYNC_HEART_RATE,
+        constants::CMD_AUTO_HR_PREF,
+        constants::CMD_AUTO_SPO2_PREF,
+        constants::CMD_GOALS,
+        constants::CMD_AUTO_STRESS_PREF,
+        constants::CMD_SYNC_STRESS,
+        constants::CMD_AUTO_HRV_PREF,
+        constants::CMD_SYNC_HRV,
+        constants::CMD_SYNC_ACTIVITY,
+        constants::CMD_MANUAL_HEART_RATE,
+        constants::CMD_STOP_REAL_TIME,
+        constants::CMD_NOTIFICATION,
+        constants::CMD_BIG_DATA_V2,
+        constants::CMD_FACTORY_RESET,
+    ];
+
+    fn assert_idle(parser: &PacketParser) {
+        let states = &parser.multi_packet_states;
+        assert!(states.sport_detail.is_none(), "{states:?}");
+        assert!(states.heart_rate_state.is_none(), "{states:?}");
+        assert!(states.stress_state.is_none(), "{states:?}");
+        assert!(states.hrv_state.is_none(), "{states:?}");
+        assert!(states.partial_big_data.is_none(), "{states:?}");
+    }
+
+    #[test]
+    fn short_packets_are_errors() {
+        for skip_checksum in [false, true] {
+            for &command in COMMANDS {
+                let mut parser = PacketParser {
+                    skip_checksum,
+                    ..Default::default()
+                };
+                for len in [0, 1, 5] {
+                    let mut packet = vec![0u8; len];
+                    if let Some(first) = packet.first_mut() {
+                        *first = command;
+                    }
+                    for raw in [RawPacket::Uart(packet.clone()), RawPacket::V2(packet)] {
+                        let result = parser.handle_packet(&raw);
+                        assert!(result.is_err(), "{raw:?} parsed as {result:?}");
+                        assert_idle(&parser);
+                    }
+                }
+                let mut battery = vec![constants::CMD_BATTERY, 50, 1];
+                battery.resize(constants::UART_PACKET_LEN, 0);
+                battery[15] = checksum(&battery);
+                assert_eq!(
+                    parser.handle_packet(&RawPacket::Uart(battery)).unwrap(),
+                    Some(CommandReply::BatteryInfo {
+                        level: 50,
+                        charging: true
+                    })
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn short_packet_error_names_command() {
+        let mut parser = PacketParser::default();
+        let err = parser
+            .handle_packet(&RawPacket::Uart(vec![constants::CMD_SYNC_STRESS, 0, 3]))
+            .unwrap_err();
+        let Error::PacketLength {
+            command,
+            length,
+            expected,
+        } = err
+        else {
+            panic!("unexpected error {err:?}");
+        };
+        assert_eq!(command, Some(constants::CMD_SYNC_STRESS));
+        assert_eq!(length, 3);
+        assert_eq!(expected, constants::UART_PACKET_LEN);
+    }
+
+    #[test]
+    fn invalid_heart_rate_interval_is_an_error() {
+        let mut parser = PacketParser::default();
+        let mut packet = vec![constants::CMD_AUTO_HR_PREF, 0, 1, 127];
+        packet.resize(constants::UART_PACKET_LEN, 0);
+        packet[15] = checksum(&packet);
+        let err = parser
+            .handle_packet(&RawPacket::Uart(packet.clone()))
+            .unwrap_err();
+        assert!(matches!(err, Error::PacketParse { .. }), "{err:?}");
+        packet[3] = 60;
+        packet[15] = checksum(&packet[..15]);
+        assert_eq!(
+            parser.handle_packet(&RawPacket::Uart(packet)).unwrap(),
+            Some(CommandReply::HeartRateSettings(HeartRateSettings {
+                enabled: true,
+                interval: HrInterval::try_new(60).unwrap(),
+            }))
+        );
+    }
+
+    #[test]
+    fn find_device_ack() {
+        let mut parser = PacketParser::default();
+        for ack in [
+            vec![0x50, 0x55, 0xaa, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x4f],
+            vec![0x50, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x50],
+        ] {
+            assert_eq!(
+                parser.handle_packet(&RawPacket::Uart(ack)).unwrap(),
+                Some(CommandReply::FindDevice)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn phone_name_reply_reaches_hook() {
+        let mut ack = vec![constants::CMD_PHONE_NAME, 0x02, 0x0a, 1];
+        ack.resize(constants::UART_PACKET_LEN, 0);
+        ack[15] = checksum(&ack);
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let stream = futures::stream::iter([RawPacket::Uart(ack.clone())]);
+        let mut rx = ClientReceiver::from_stream(Box::pin(stream));
+        rx.on_unhandled(UnhandledHook::new({
+            let seen = seen.clone();
+            move |_, why| seen.lock().unwrap().push(why)
+        }));
+        assert_eq!(
+            rx.next().await,
+            Some(CommandReply::SetPhoneName(ack[1..15].to_vec()))
+        );
+        assert_eq!(*seen.lock().unwrap(), vec![Unhandled::Unverified]);
+    }
+
+    #[test]
+    fn captured_preference_reply() {
+        use crate::preferences::{Preference, TimeFormat};
+
+        // ring reporting the 12 hour clock after the vendor app read it
+        let packet = vec![10, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 13];
+        let mut parser = PacketParser::default();
+        let reply = parser.handle_packet(&RawPacket::Uart(packet)).unwrap();
+        assert_eq!(
+            reply,
+            Some(CommandReply::Preference {
+                key: constants::PREF_KEY_TIME_FORMAT,
+                value: vec![1],
+            })
+        );
+        let Some(CommandReply::Preference { key, value }) = reply else {
+            unreachable!()
+        };
+        assert_eq!(
+            Preference::decode(key, &value),
+            Preference::TimeFormat(TimeFormat::TwelveHour)
+        );
+    }
+
+    #[test]
+    fn auto_pref_replies() {
+        let mut parser = PacketParser::default();
+        let mut parse = |bytes: &[u8]| {
+            let mut packet = bytes.to_vec();
+            packet.resize(constants::UART_PACKET_LEN, 0);
+            packet[15] = checksum(&packet);
+            parser.handle_packet(&RawPacket::Uart(packet)).unwrap()
+        };
+        assert_eq!(
+            parse(&[constants::CMD_AUTO_SPO2_PREF, constants::PREF_READ, 1]),
+            Some(CommandReply::Spo2Pref { enabled: true })
+        );
+        assert_eq!(
+            parse(&[constants::CMD_AUTO_SPO2_PREF, constants::PREF_WRITE, 0]),
+            Some(CommandReply::Spo2Pref { enabled: false })
+        );
+        assert_eq!(
+            parse(&[constants::CMD_AUTO_STRESS_PREF, constants::PREF_READ, 0]),
+            Some(CommandReply::StressPref { enabled: false })
+        );
+        assert_eq!(
+            parse(&[constants::CMD_AUTO_STRESS_PREF, constants::PREF_WRITE, 1]),
+            Some(CommandReply::StressPref { enabled: true })
+        );
+    }
+
+    #[test]
+    fn short_packet_mid_sync_keeps_state() {
+        let mut parser = PacketParser::default();
+        let mut first = vec![constants::CMD_SYNC_STRESS, 0, 3, 30];
+        first.resize(constants::UART_PACKET_LEN, 0);
+        first[15] = checksum(&first);
+        assert_eq!(parser.handle_packet(&RawPacket::Uart(first)).unwrap(), None);
+        assert!(parser
+            .handle_packet(&RawPacket::Uart(vec![constants::CMD_SYNC_STRESS, 1]))
+            .is_err());
+        assert!(matches!(
+            parser.multi_packet_states.stress_state,
+            Some(StressState::Length { length: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn big_data_counts_days_from_request() {
+        let mut parser = PacketParser::default();
+        parser.expect_big_data(time::macros::date!(2024 - 11 - 20));
+        // one day of oxygen readings from a day before the request
+        let mut body = vec![1, 1];
+        body.extend([95, 98].repeat(24));
+        let mut packet = vec![constants::CMD_BIG_DATA_V2, constants::BIG_DATA_TYPE_SPO2];
+        packet.extend((body.len() as u16).to_le_bytes());
+        packet.extend([0, 0]);
+        packet.extend(body);
+        let Some(CommandReply::Oxygen(oxygen)) =
+            parser.handle_packet(&RawPacket::V2(packet)).unwrap()
+        else {
+            panic!("expected an oxygen reply");
+        };
+        assert_eq!(oxygen.samples.len(), 24);
+        assert_eq!(
+            oxygen.samples[0].when,
+            time::macros::datetime!(2024-11-19 0:00)
+        );
+        assert!(parser.multi_packet_states.big_data_reference.is_none());
+    }
+
+    #[test]
+    fn new_big_data_header_replaces_partial() {
+        let mut parser = PacketParser::default();
+        parser.expect_big_data(time::macros::date!(2024 - 11 - 20));
+        // a sleep reply that stops after its first packet
+        let mut stale = vec![constants::CMD_BIG_DATA_V2, constants::BIG_DATA_TYPE_SLEEP];
+        stale.extend([40, 0, 0, 0, 2, 1, 6]);
+        assert!(parser
+            .handle_packet(&RawPacket::V2(stale))
+            .unwrap()
+            .is_none());
+        // an empty sleep reply, a day count of 1 has no sessions
+        let mut sleep = vec![constants::CMD_BIG_DATA_V2, constants::BIG_DATA_TYPE_SLEEP];
+        sleep.extend([1, 0, 0, 0, 1]);
+        let reply = parser.handle_packet(&RawPacket::V2(sleep)).unwrap();
+        assert!(
+            matches!(&reply, Some(CommandReply::Sleep(sleep)) if sleep.sessions.is_empty()),
+            "{reply:?}"
+        );
+        assert!(parser.multi_packet_states.partial_big_data.is_none());
+    }
+
+    #[test]
+    fn bad_big_data_crc_resets() {
+        let mut parser = PacketParser::default();
+        let mut packet = vec![constants::CMD_BIG_DATA_V2, constants::BIG_DATA_TYPE_SLEEP];
+        packet.extend([1, 0, 0, 0, 1, 0, 0]);
+        let e = parser.handle_packet(&RawPacket::V2(packet)).unwrap_err();
+        assert!(
+            matches!(
+                e,
+                Error::Crc {
+                    kind: PacketKind::Sleep,
+                    found: 0,
+                    ..
+                }
+            ),
+            "{e:?}"
+        );
+        assert!(parser.multi_packet_states.partial_big_data.is_none());
+    }
+
+    #[test]
+    fn skips_empty_oxygen() {
+        // yesterday with readings for its first two hours, today without any
+        let mut body = vec![2, 1, 95, 98, 94, 99];
+        body.extend([0, 0].repeat(22));
+        body.extend([0, 0, 0]);
+        let mut packet = vec![constants::CMD_BIG_DATA_V2, constants::BIG_DATA_TYPE_SPO2];
+        packet.extend((body.len() as u16).to_le_bytes());
+        packet.extend([0, 0]);
+        packet.extend(body);
+        for (skip, expected) in [(false, 25), (true, 2)] {
+            let mut parser = PacketParser::default();
+            parser.set_skip_empty_oxygen(skip);
+            parser.expect_big_data(time::macros::date!(2024 - 11 - 20));
+            let Some(CommandReply::Oxygen(oxygen)) = parser
+                .handle_packet(&RawPacket::V2(packet.clone()))
+                .unwrap()
+            else {
+                panic!("expected an oxygen reply");
+            };
+            assert_eq!(oxygen.samples.len(), expected);
+            assert_eq!(
+                oxygen.samples[1].when,
+                time::macros::datetime!(2024-11-19 1:00)
+            );
+        }
+    }
+
+    /// A sport detail sync of 6 data packets captured from an R02
+    const SPORT_DETAIL: [[u8; 16]; 7] = [
+        [67, 240, 6, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 58],
+        [67, 36, 17, 34, 60, 0, 6, 159, 0, 33, 0, 22, 0, 0, 0, 178],
+        [67, 36, 17, 34, 64, 1, 6, 88, 0, 22, 0, 13, 0, 0, 0, 92],
+        [67, 36, 17, 34, 68, 2, 6, 43, 2, 119, 0, 79, 0, 0, 0, 217],
+        [67, 36, 17, 34, 72, 3, 6, 58, 3, 162, 0, 118, 0, 0, 0, 64],
+        [67, 36, 17, 34, 76, 4, 6, 88, 9, 51, 2, 86, 1, 0, 0, 221],
+        [67, 36, 17, 34, 80, 5, 6, 187, 0, 38, 0, 27, 0, 0, 0, 241],
+    ];
+
+    fn progress(kind: PacketKind, received: usize, expected: usize) -> Option<SyncProgress> {
+        Some(SyncProgress {
+            kind,
+            received,
+            expected,
+        })
+    }
+
+    #[test]
+    fn sport_detail_progress() {
+        let mut parser = PacketParser::default();
+        let seen: Vec<_> = SPORT_DETAIL
+            .iter()
+            .map(|packet| {
+                parser
+                    .handle_packet(&RawPacket::Uart(packet.to_vec()))
+                    .unwrap();
+                parser.progress()
+            })
+            .collect();
+        let mut expected: Vec<_> = (0..6)
+            .map(|received| progress(PacketKind::SportDetail, received, 6))
+            .collect();
+        expected.push(None);
+        assert_eq!(seen, expected);
+    }
+
+    #[test]
+    fn heart_rate_progress() {
+        let packet = |bytes: &[u8]| {
+            let mut ret = bytes.to_vec();
+            ret.resize(constants::UART_PACKET_LEN, 0);
+            ret[15] = checksum(&ret);
+            RawPacket::Uart(ret)
+        };
+        let timestamp = 1_732_060_800u32.to_le_bytes();
+        let packets = [
+            packet(&[constants::CMD_SYNC_HEART_RATE, 0, 4, 5]),
+            packet(&[
+                constants::CMD_SYNC_HEART_RATE,
+                1,
+                timestamp[0],
+                timestamp[1],
+                timestamp[2],
+                timestamp[3],
+                60,
+            ]),
+            packet(&[constants::CMD_SYNC_HEART_RATE, 2, 61]),
+            packet(&[constants::CMD_SYNC_HEART_RATE, 3, 62]),
+        ];
+        let mut parser = PacketParser::default();
+        let seen: Vec<_> = packets
+            .iter()
+            .map(|packet| {
+                parser.handle_packet(packet).unwrap();
+                parser.progress()
+            })
+            .collect();
+        assert_eq!(
+            seen,
+            [
+                progress(PacketKind::HeartRate, 0, 3),
+                progress(PacketKind::HeartRate, 1, 3),
+                progress(PacketKind::HeartRate, 2, 3),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn big_data_progress() {
+        let mut body = vec![1, 1];
+        body.extend([95, 98].repeat(24));
+        let mut first = vec![constants::CMD_BIG_DATA_V2, constants::BIG_DATA_TYPE_SLEEP];
+        first.extend((body.len() as u16).to_le_bytes());
+        first.extend([0, 0]);
+        first.extend(&body[..20]);
+        let mut parser = PacketParser::default();
+        parser.handle_packet(&RawPacket::V2(first)).unwrap();
+        assert_eq!(parser.progress(), progress(PacketKind::Sleep, 20, 50));
+        parser
+            .handle_packet(&RawPacket::V2(body[20..40].to_vec()))
+            .unwrap();
+        assert_eq!(parser.progress(), progress(PacketKind::Sleep, 40, 50));
+        // the sleep body is nonsense so finishing fails, which still ends the sync
+        let _ = parser.handle_packet(&RawPacket::V2(body[40..].to_vec()));
+        assert_eq!(parser.progress(), None);
+    }
+
+    #[tokio::test]
+    async fn receiver_publishes_progress() {
+        let mut battery = vec![constants::CMD_BATTERY, 80, 0];
+        battery.resize(constants::UART_PACKET_LEN, 0);
+        battery[15] = checksum(&battery);
+        let mut packets: Vec<_> = SPORT_DETAIL
+            .iter()
+            .map(|p| RawPacket::Uart(p.to_vec()))
+            .collect();
+        packets.insert(3, RawPacket::Uart(battery));
+        let mut rx = ClientReceiver::from_stream(Box::pin(futures::stream::iter(packets)));
+        let mut progress_rx = rx.progress();
+        assert_eq!(*progress_rx.borrow_and_update(), None);
+        assert!(matches!(
+            rx.next().await,
+            Some(CommandReply::BatteryInfo { .. })
+        ));
+        assert!(progress_rx.has_changed().unwrap());
+        assert_eq!(
+            *progress_rx.borrow_and_update(),
+            progress(PacketKind::SportDetail, 2, 6)
+        );
+        assert!(matches!(
+            rx.next().await,
+            Some(CommandReply::SportDetail(_))
+        ));
+        assert_eq!(*progress_rx.borrow_and_update(), None);
+    }
+
+    #[tokio::test]
+    async fn receiver_skips_short_packets() {
+        let mut battery = vec![constants::CMD_BATTERY, 80, 0];
+        battery.resize(constants::UART_PACKET_LEN, 0);
+        battery[15] = checksum(&battery);
+        let stream = futures::stream::iter([
+            RawPacket::Uart(Vec::new()),
+            RawPacket::V2(vec![constants::CMD_BIG_DATA_V2]),
+            RawPacket::Uart(vec![constants::CMD_SYNC_HEART_RATE, 0, 24, 5, 0]),
+            RawPacket::Uart(battery),
+        ]);
+        let mut rx = ClientReceiver::from_stream(Box::pin(stream));
+        assert_eq!(
+            rx.next().await,
+            Some(CommandReply::BatteryInfo {
+                level: 80,
+                charging: false
+            })
+        );
+        assert_eq!(rx.next().await, None);
+    }
+
+    #[tokio::test]
+    async fn unhandled_packets_reach_hook() {
+        let packet = |bytes: &[u8]| {
+            let mut ret = bytes.to_vec();
+            ret.resize(constants::UART_PACKET_LEN, 0);
+            ret[15] = checksum(&ret);
+            ret
+        };
+        let stress_start = packet(&[constants::CMD_SYNC_STRESS, 0, 3, 30]);
+        let unknown = packet(&[0x7f, 1, 2, 3]);
+        let mut corrupted = packet(&[constants::CMD_BATTERY, 80, 0]);
+        corrupted[15] ^= 1;
+        let battery = packet(&[constants::CMD_BATTERY, 80, 0]);
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut rx = ClientReceiver::from_stream(Box::pin(futures::stream::iter(
+            [&stress_start, &unknown, &corrupted, &battery].map(|p| RawPacket::Uart(p.clone())),
+        )));
+        rx.on_unhandled(UnhandledHook::new({
+            let seen = seen.clone();
+            move |packet, why| seen.lock().unwrap().push((packet.clone(), why))
+        }));
+        assert_eq!(
+            rx.next().await,
+            Some(CommandReply::Unknown(unknown.clone()))
+        );
+        assert_eq!(
+            rx.next().await,
+            Some(CommandReply::BatteryInfo {
+                level: 80,
+                charging: false
+            })
+        );
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                (
+                    RawPacket::Uart(stress_start),
+                    Unhandled::Partial(PacketKind::Stress)
+                ),
+                (RawPacket::Uart(unknown), Unhandled::Unknown),
+                (RawPacket::Uart(corrupted), Unhandled::Failed),
+            ]
+        );
+    }
+}
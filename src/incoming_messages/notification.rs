@@ -1,4 +1,4 @@
-use crate::constants;
+use crate::{constants, util::check_len, Error, PacketKind};
 
 #[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
 pub enum Notification {
@@ -8,17 +8,14 @@ pub enum Notification {
 }
 
 impl TryFrom<&[u8]> for Notification {
-    type Error = String;
+    type Error = Error;
     fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
-        let tag = value
-            .first()
-            .copied()
-            .ok_or_else(|| format!("0 size buffer for notification packet"))?;
-
-        if tag != constants::CMD_NOTIFICATION {
-            return Err(format!(
-                "Invalid notification packet, tag byte not {}: {value:?}",
-                constants::CMD_NOTIFICATION
+        check_len(value, 3)?;
+        if value[0] != constants::CMD_NOTIFICATION {
+            return Err(Error::parse(
+                PacketKind::Notification,
+                value,
+                format!("tag byte not {}", constants::CMD_NOTIFICATION),
             ));
         }
         Ok(match value[1] {
@@ -30,9 +27,10 @@ impl TryFrom<&[u8]> for Notification {
                 Notification::Activity(LiveActivity::try_from(value)?)
             }
             _ => {
-                return Err(format!(
-                    "Unknown notification type {}: {:?}",
-                    value[1], value
+                return Err(Error::parse(
+                    PacketKind::Notification,
+                    value,
+                    format!("Unknown notification type {}", value[1]),
                 ))
             }
         })
@@ -54,13 +52,14 @@ pub struct LiveActivity {
 }
 
 impl TryFrom<&[u8]> for LiveActivity {
-    type Error = String;
+    type Error = Error;
 
     fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
         if value.len() < 11 {
-            return Err(format!(
-                "LiveActivity packet too short ({}): {value:?}",
-                value.len()
+            return Err(Error::parse(
+                PacketKind::Notification,
+                value,
+                format!("LiveActivity packet too short ({})", value.len()),
             ));
         }
         let steps = [value[4], value[3], value[2], 0];
@@ -76,3 +75,82 @@ impl TryFrom<&[u8]> for LiveActivity {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::checksum;
+
+    fn make_packet(bytes: &[u8]) -> Vec<u8> {
+        let mut ret = bytes.to_vec();
+        ret.resize(16, 0);
+        ret[15] = checksum(&ret);
+        ret
+    }
+
+    #[test]
+    fn new_data() {
+        for (sub_type, name) in [
+            (constants::NOTIFICATION_NEW_HR_DATA, DataName::HeartRate),
+            (constants::NOTIFICATION_NEW_SPO2_DATA, DataName::Oxygen),
+            (constants::NOTIFICATION_NEW_STEPS_DATA, DataName::Steps),
+        ] {
+            let packet = make_packet(&[constants::CMD_NOTIFICATION, sub_type]);
+            assert_eq!(
+                Notification::try_from(packet.as_slice()).unwrap(),
+                Notification::NewData(name)
+            );
+        }
+    }
+
+    #[test]
+    fn battery() {
+        let packet = make_packet(&[
+            constants::CMD_NOTIFICATION,
+            constants::NOTIFICATION_BATTERY_LEVEL,
+            64,
+        ]);
+        assert_eq!(
+            Notification::try_from(packet.as_slice()).unwrap(),
+            Notification::Battery(64)
+        );
+    }
+
+    #[test]
+    fn live_activity() {
+        let packet = make_packet(&[
+            constants::CMD_NOTIFICATION,
+            constants::NOTIFICATION_LIVE_ACTIVITY,
+            0x00,
+            0x01,
+            0x02,
+            0x00,
+            0x01,
+            0xf4,
+            0x00,
+            0x00,
+            0x64,
+        ]);
+        assert_eq!(
+            Notification::try_from(packet.as_slice()).unwrap(),
+            Notification::Activity(LiveActivity {
+                steps: 258,
+                calories: 50.0,
+                distance: 100,
+            })
+        );
+    }
+
+    #[test]
+    fn unknown_sub_type() {
+        let packet = make_packet(&[constants::CMD_NOTIFICATION, 0x7f]);
+        let err = Notification::try_from(packet.as_slice()).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::PacketParse {
+                kind: PacketKind::Notification,
+                ..
+            }
+        ));
+    }
+}
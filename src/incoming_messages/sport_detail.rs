@@ -1,24 +1,48 @@
-use crate::Result;
+use std::time::Duration;
+
+use crate::{
+    units::{Kilocalories, Meters},
+    util::check_len,
+    Error, PacketKind, Result,
+};
 use bon::Builder;
+use time::{Date, Month, OffsetDateTime, Time, UtcOffset};
+
+/// How long each `SportDetail::time_index` slot covers
+pub const SLOT_DURATION: Duration = Duration::from_secs(15 * 60);
+/// How many slots a day is split into
+const SLOTS_PER_DAY: u8 = 96;
 
-#[derive(Default, Builder, PartialEq, Debug, serde::Deserialize, serde::Serialize)]
+/// The activity over one quarter hour
+///
+/// Comparing captures against the steps taken puts `distance` in meters,
+/// 1194 steps cover 873 of them, and `calories` in thousandths of a
+/// kilocalorie, about 50 for each step
+#[derive(Default, Builder, Clone, PartialEq, Debug, serde::Deserialize, serde::Serialize)]
 pub struct SportDetail {
     pub year: u16,
     pub month: u8,
     pub day: u8,
     pub time_index: u8,
-    pub calories: u16,
+    /// Thousandths of a kilocalorie, see `kilocalories`. Rings using the new
+    /// calorie protocol send a tenth of this, the parser scales it
+    pub calories: u32,
     pub steps: u16,
+    /// Meters, see `meters`
     pub distance: u16,
 }
 
 impl TryFrom<&[u8]> for SportDetail {
-    type Error = String;
+    type Error = Error;
     fn try_from(value: &[u8]) -> std::result::Result<Self, Self::Error> {
         if value.len() < 12 {
-            return Err(format!(
-                "SportDetail must be at least 12 bytes found {}",
-                value.len()
+            return Err(Error::parse(
+                PacketKind::SportDetail,
+                value,
+                format!(
+                    "SportDetail must be at least 12 bytes found {}",
+                    value.len()
+                ),
             ));
         }
         let bcd_to_decimal = |b: u8| (((b >> 4) & 15) * 10) + (b & 15);
@@ -28,7 +52,7 @@ impl TryFrom<&[u8]> for SportDetail {
         let time_index = value[3];
         let mut cal_bytes = [0u8; 2];
         cal_bytes.copy_from_slice(&value[6..8]);
-        let calories = u16::from_le_bytes(cal_bytes);
+        let calories = u16::from_le_bytes(cal_bytes).into();
         let mut step_bytes = [0u8; 2];
         step_bytes.copy_from_slice(&value[8..10]);
         let steps = u16::from_le_bytes(step_bytes);
@@ -49,18 +73,68 @@ impl TryFrom<&[u8]> for SportDetail {
 }
 
 impl SportDetail {
-    pub fn apply_new_calories(&mut self) {
+    /// Rings using the new calorie protocol send tenths of the value
+    fn apply_new_calories(&mut self) {
         self.calories *= 10;
     }
+
+    pub fn meters(&self) -> Meters {
+        Meters(self.distance.into())
+    }
+
+    pub fn kilocalories(&self) -> Kilocalories {
+        Kilocalories(self.calories as f32 / 1000.0)
+    }
+
+    /// The day these details were recorded, in the ring's local time
+    pub fn date(&self) -> Result<Date> {
+        Month::try_from(self.month)
+            .ok()
+            .and_then(|month| Date::from_calendar_date(self.year.into(), month, self.day).ok())
+            .ok_or(Error::InvalidDate {
+                year: self.year,
+                month: self.month,
+                day: self.day,
+            })
+    }
+
+    /// When the quarter hour these details cover starts, slot 92 starts at
+    /// 23:00
+    pub fn start_time(&self) -> Result<Time> {
+        if self.time_index >= SLOTS_PER_DAY {
+            return Err(Error::InvalidTimeIndex(self.time_index));
+        }
+        Ok(Time::MIDNIGHT + SLOT_DURATION * u32::from(self.time_index))
+    }
+
+    /// When the quarter hour these details cover starts, the ring keeps its
+    /// local time so `offset` should be the ring's
+    pub fn timestamp(&self, offset: UtcOffset) -> Result<OffsetDateTime> {
+        Ok(self
+            .date()?
+            .with_time(self.start_time()?)
+            .assume_offset(offset))
+    }
 }
 
+/// Used when a sync is joined after its header packet, every firmware seen so
+/// far reports the new calorie protocol
+const MID_STREAM_NEW_CAL_PROTO: bool = true;
+
+/// Activity details spread across multiple packets
+///
+/// A sync starts with a header packet (`0xf0` in byte 1) which reports how many
+/// data packets follow and if calories need to be scaled, each data packet
+/// carries its index in byte 5 and the total count in byte 6
 #[derive(PartialEq, Debug)]
 pub enum SportDetailState {
     Initial {
         new_cal_proto: bool,
+        total: u8,
     },
     Recieving {
         new_cal_proto: bool,
+        total: u8,
         packets: Vec<SportDetail>,
     },
     Complete {
@@ -70,92 +144,194 @@ pub enum SportDetailState {
 
 impl SportDetailState {
     pub fn new(packet: &[u8]) -> Result<Self> {
+        check_len(packet, 2)?;
         if packet[0] != 67 {
-            return Err(format!("Invalid prefix for sport detail state {}", packet[0]).into());
+            return Err(Error::parse(
+                PacketKind::SportDetail,
+                packet,
+                format!("Invalid prefix for sport detail state {}", packet[0]),
+            ));
         }
-        if packet[1] == 255 {
-            return Ok(Self::Complete {
+        match packet[1] {
+            255 => Ok(Self::Complete {
                 packets: Vec::new(),
-            });
-        }
-        if packet[1] == 240 {
-            return Ok(Self::Initial {
-                new_cal_proto: true,
-            });
+            }),
+            240 => {
+                check_len(packet, 4)?;
+                Ok(Self::Initial {
+                    new_cal_proto: packet[3] == 1,
+                    total: packet[2],
+                })
+            }
+            _ => {
+                check_len(packet, 7)?;
+                log::debug!(
+                    "joined sport detail sync at packet {} of {}",
+                    packet[5],
+                    packet[6]
+                );
+                let mut ret = Self::Initial {
+                    new_cal_proto: MID_STREAM_NEW_CAL_PROTO,
+                    total: packet[6],
+                };
+                ret.step(packet)?;
+                Ok(ret)
+            }
         }
-        Ok(Self::Recieving {
-            new_cal_proto: false,
-            packets: vec![SportDetail::try_from(&packet[1..packet.len() - 1])?],
-        })
     }
 
     pub fn step(&mut self, packet: &[u8]) -> Result {
-        match self {
-            Self::Initial { new_cal_proto } => {
-                let done = packet[5] == packet[6] - 1;
-                let mut packet = SportDetail::try_from(&packet[1..])?;
-                if *new_cal_proto {
-                    packet.apply_new_calories();
-                }
-                *self = if done {
-                    Self::Complete {
-                        packets: vec![packet],
-                    }
-                } else {
-                    Self::Recieving {
-                        new_cal_proto: *new_cal_proto,
-                        packets: vec![packet],
-                    }
-                };
-            }
+        check_len(packet, 7)?;
+        let index = packet[5];
+        let total = packet[6];
+        let (new_cal_proto, received) = match self {
+            Self::Initial { new_cal_proto, .. } => (*new_cal_proto, 0),
             Self::Recieving {
-                packets,
                 new_cal_proto,
-            } => {
-                if packet[5] == packet[6] - 1 {
-                    let mut packet = SportDetail::try_from(&packet[1..])?;
-                    if *new_cal_proto {
-                        packet.apply_new_calories();
-                    }
-                    let mut packets = core::mem::take(packets);
-                    packets.push(packet);
-                    *self = Self::Complete { packets };
-                    return Ok(());
-                }
-                let mut packet = SportDetail::try_from(&packet[1..])?;
-                if *new_cal_proto {
-                    packet.apply_new_calories();
-                }
-                packets.push(packet);
-            }
+                packets,
+                ..
+            } => (*new_cal_proto, packets.len()),
             Self::Complete { packets } => {
-                return Err(format!("step after complete: {}", packets.len()).into());
+                return Err(Error::parse(
+                    PacketKind::SportDetail,
+                    packet,
+                    format!("step after complete: {}", packets.len()),
+                ));
             }
+        };
+        if usize::from(index) != received {
+            log::warn!("sport detail packet {index} of {total} arrived after {received} packets");
         }
-
+        // exclude the command byte and the trailing checksum
+        let mut detail = SportDetail::try_from(&packet[1..packet.len() - 1])?;
+        if new_cal_proto {
+            detail.apply_new_calories();
+        }
+        let mut packets = match self {
+            Self::Recieving { packets, .. } => core::mem::take(packets),
+            _ => Vec::with_capacity(total.into()),
+        };
+        packets.push(detail);
+        *self = if u16::from(index) + 1 == u16::from(total) {
+            Self::Complete { packets }
+        } else {
+            Self::Recieving {
+                new_cal_proto,
+                total,
+                packets,
+            }
+        };
         Ok(())
     }
+
+    /// How many data packets the sync is expected to have, `None` once complete
+    pub fn expected_packets(&self) -> Option<u8> {
+        match self {
+            Self::Initial { total, .. } | Self::Recieving { total, .. } => Some(*total),
+            Self::Complete { .. } => None,
+        }
+    }
+
+    /// How many data packets have been received so far
+    pub fn received_packets(&self) -> usize {
+        match self {
+            Self::Initial { .. } => 0,
+            Self::Recieving { packets, .. } | Self::Complete { packets } => packets.len(),
+        }
+    }
+
+    /// The packets received and expected, `None` once complete
+    pub fn progress(&self) -> Option<(usize, usize)> {
+        let expected = self.expected_packets()?;
+        Some((self.received_packets(), expected.into()))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::collections::VecDeque;
 
+    use time::macros::{date, datetime, offset, time};
+
     use super::*;
 
+    const MULTI2: [[u8; 16]; 7] = [
+        [67, 240, 6, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 58],
+        [67, 36, 17, 34, 60, 0, 6, 159, 0, 33, 0, 22, 0, 0, 0, 178],
+        [67, 36, 17, 34, 64, 1, 6, 88, 0, 22, 0, 13, 0, 0, 0, 92],
+        [67, 36, 17, 34, 68, 2, 6, 43, 2, 119, 0, 79, 0, 0, 0, 217],
+        [67, 36, 17, 34, 72, 3, 6, 58, 3, 162, 0, 118, 0, 0, 0, 64],
+        [67, 36, 17, 34, 76, 4, 6, 88, 9, 51, 2, 86, 1, 0, 0, 221],
+        [67, 36, 17, 34, 80, 5, 6, 187, 0, 38, 0, 27, 0, 0, 0, 241],
+    ];
+
+    #[track_caller]
+    fn replay(packets: &[[u8; 16]]) -> SportDetailState {
+        let (first, rest) = packets.split_first().unwrap();
+        let mut state = SportDetailState::new(first).unwrap();
+        for packet in rest {
+            state.step(packet).unwrap();
+        }
+        state
+    }
+
+    #[track_caller]
+    fn complete(state: SportDetailState) -> Vec<SportDetail> {
+        let SportDetailState::Complete { packets } = state else {
+            panic!("Expected complete found {state:?}");
+        };
+        packets
+    }
+
+    #[test]
+    fn join_after_header() {
+        let full = complete(replay(&MULTI2));
+        let joined = complete(replay(&MULTI2[1..]));
+        assert_eq!(joined, full);
+    }
+
+    #[test]
+    fn join_after_first_data_packet() {
+        let full = complete(replay(&MULTI2));
+        let state = replay(&MULTI2[2..4]);
+        assert_eq!(state.expected_packets(), Some(6));
+        assert_eq!(state.received_packets(), 2);
+        let joined = complete(replay(&MULTI2[2..]));
+        assert_eq!(joined, full[1..]);
+    }
+
+    #[test]
+    fn header_reports_total() {
+        let state = SportDetailState::new(&MULTI2[0]).unwrap();
+        assert_eq!(state.expected_packets(), Some(6));
+        assert_eq!(state.received_packets(), 0);
+    }
+
+    #[test]
+    fn legacy_calorie_protocol() {
+        let mut header = MULTI2[0];
+        header[3] = 0;
+        let mut packets = MULTI2;
+        packets[0] = header;
+        let legacy = complete(replay(&packets));
+        let full = complete(replay(&MULTI2));
+        assert_eq!(legacy[0].calories * 10, full[0].calories);
+    }
+
     #[test]
     fn test_parse_simple() {
         let mut state =
-            SportDetailState::new(&*b"C\xf0\x01\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x005")
+            SportDetailState::new(b"C\xf0\x01\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x005")
                 .unwrap();
         assert_eq!(
             state,
             SportDetailState::Initial {
-                new_cal_proto: true
+                new_cal_proto: true,
+                total: 1,
             }
         );
         state
-            .step(&*b"C$\x10\x15\\\x00\x01y\x00\x15\x00\x10\x00\x00\x00\x87")
+            .step(b"C$\x10\x15\\\x00\x01y\x00\x15\x00\x10\x00\x00\x00\x87")
             .unwrap();
         assert_eq!(
             state,
@@ -171,23 +347,17 @@ mod tests {
                     .build()]
             }
         );
+        let SportDetailState::Complete { packets } = state else {
+            unreachable!()
+        };
+        assert_eq!(packets[0].date().unwrap(), date!(2024 - 10 - 15));
+        assert_eq!(packets[0].start_time().unwrap(), time!(23:00));
     }
 
     #[test]
     fn test_parse_multi2() {
         env_logger::try_init().ok();
-        let mut packets = VecDeque::from_iter(
-            [
-                [67, 240, 6, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 58],
-                [67, 36, 17, 34, 60, 0, 6, 159, 0, 33, 0, 22, 0, 0, 0, 178],
-                [67, 36, 17, 34, 64, 1, 6, 88, 0, 22, 0, 13, 0, 0, 0, 92],
-                [67, 36, 17, 34, 68, 2, 6, 43, 2, 119, 0, 79, 0, 0, 0, 217],
-                [67, 36, 17, 34, 72, 3, 6, 58, 3, 162, 0, 118, 0, 0, 0, 64],
-                [67, 36, 17, 34, 76, 4, 6, 88, 9, 51, 2, 86, 1, 0, 0, 221],
-                [67, 36, 17, 34, 80, 5, 6, 187, 0, 38, 0, 27, 0, 0, 0, 241],
-            ]
-            .into_iter(),
-        );
+        let mut packets = VecDeque::from_iter(MULTI2);
         let mut state = SportDetailState::new(&packets.pop_front().unwrap()).unwrap();
         for packet in packets {
             state.step(&packet).unwrap();
@@ -197,21 +367,33 @@ mod tests {
             "Expected complete found {state:?}"
         );
         insta::assert_debug_snapshot!(state);
+        let starts: Vec<_> = complete(state)
+            .iter()
+            .map(|detail| detail.timestamp(offset!(UTC)).unwrap())
+            .collect();
+        assert_eq!(
+            starts,
+            [
+                datetime!(2024-11-22 15:00 UTC),
+                datetime!(2024-11-22 16:00 UTC),
+                datetime!(2024-11-22 17:00 UTC),
+                datetime!(2024-11-22 18:00 UTC),
+                datetime!(2024-11-22 19:00 UTC),
+                datetime!(2024-11-22 20:00 UTC),
+            ]
+        );
     }
 
     #[test]
     fn test_parse_multi() {
-        let mut packets = VecDeque::from_iter(
-            [
-                *b"C\xf0\x05\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x009",
-                *b"C#\x08\x13\x10\x00\x05\xc8\x000\x00\x1b\x00\x00\x00\xa9",
-                *b"C#\x08\x13\x14\x01\x05\xb6\x18\xaa\x04i\x03\x00\x00\x83",
-                *b"C#\x08\x13\x18\x02\x058\x04\xe1\x00\x95\x00\x00\x00R",
-                *b"C#\x08\x13\x1c\x03\x05\x05\x02l\x00H\x00\x00\x00`",
-                *b"C#\x08\x13L\x04\x05\xef\x01c\x00D\x00\x00\x00m",
-            ]
-            .into_iter(),
-        );
+        let mut packets = VecDeque::from_iter([
+            *b"C\xf0\x05\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x009",
+            *b"C#\x08\x13\x10\x00\x05\xc8\x000\x00\x1b\x00\x00\x00\xa9",
+            *b"C#\x08\x13\x14\x01\x05\xb6\x18\xaa\x04i\x03\x00\x00\x83",
+            *b"C#\x08\x13\x18\x02\x058\x04\xe1\x00\x95\x00\x00\x00R",
+            *b"C#\x08\x13\x1c\x03\x05\x05\x02l\x00H\x00\x00\x00`",
+            *b"C#\x08\x13L\x04\x05\xef\x01c\x00D\x00\x00\x00m",
+        ]);
         let expected = [
             SportDetail {
                 year: 2023,
@@ -268,6 +450,127 @@ mod tests {
             panic!("Unexpected state: {state:?}");
         };
         assert_eq!(packets, expected);
+        let starts: Vec<_> = packets
+            .iter()
+            .map(|detail| detail.start_time().unwrap())
+            .collect();
+        assert_eq!(
+            starts,
+            [
+                time!(4:00),
+                time!(5:00),
+                time!(6:00),
+                time!(7:00),
+                time!(19:00)
+            ]
+        );
+        assert!(packets
+            .iter()
+            .all(|detail| detail.date().unwrap() == date!(2023 - 08 - 13)));
+    }
+
+    /// The units are pinned by what a walk looks like, a stride near three
+    /// quarters of a meter and about 0.05 kilocalories a step. Reading the
+    /// distance as feet would make the stride 22cm
+    #[test]
+    fn units_of_a_walk() {
+        let walk = SportDetail {
+            year: 2023,
+            month: 8,
+            day: 13,
+            time_index: 20,
+            calories: 63260,
+            steps: 1194,
+            distance: 873,
+        };
+        let stride = walk.meters().0 / f32::from(walk.steps);
+        assert!((0.6..0.9).contains(&stride), "stride {stride}");
+        let per_step = walk.kilocalories().0 / f32::from(walk.steps);
+        assert!((0.03..0.07).contains(&per_step), "per step {per_step}");
+        assert_eq!(walk.kilocalories(), Kilocalories(63.26));
+        assert!((walk.meters().feet() - 2864.17).abs() < 0.01);
+        assert!((walk.meters().miles() - 0.5425).abs() < 0.0001);
+    }
+
+    #[test]
+    fn new_calorie_protocol_does_not_saturate() {
+        let packet = [
+            67, 0x23, 0x08, 0x13, 0x10, 0, 1, 0xff, 0xff, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        let mut state = SportDetailState::Initial {
+            new_cal_proto: true,
+            total: 1,
+        };
+        state.step(&packet).unwrap();
+        let packets = complete(state);
+        assert_eq!(packets[0].calories, 655_350);
+        assert_eq!(packets[0].kilocalories(), Kilocalories(655.35));
+    }
+
+    #[test]
+    fn slot_times() {
+        let slot = |time_index| SportDetail {
+            year: 2024,
+            month: 10,
+            day: 15,
+            time_index,
+            ..Default::default()
+        };
+        assert_eq!(slot(0).start_time().unwrap(), time!(0:00));
+        assert_eq!(slot(95).start_time().unwrap(), time!(23:45));
+        assert_eq!(
+            slot(95).timestamp(offset!(-5)).unwrap(),
+            datetime!(2024-10-15 23:45 -5)
+        );
+        let err = slot(96).start_time().unwrap_err();
+        assert!(
+            matches!(err, Error::InvalidTimeIndex(96)),
+            "unexpected error {err:?}"
+        );
+        assert!(slot(255).timestamp(offset!(UTC)).is_err());
+    }
+
+    #[test]
+    fn invalid_date() {
+        let detail = SportDetail {
+            year: 2023,
+            month: 2,
+            day: 29,
+            ..Default::default()
+        };
+        let err = detail.date().unwrap_err();
+        assert!(
+            matches!(
+                err,
+                Error::InvalidDate {
+                    year: 2023,
+                    month: 2,
+                    day: 29
+                }
+            ),
+            "unexpected error {err:?}"
+        );
+        let detail = SportDetail {
+            month: 13,
+            ..detail
+        };
+        assert!(detail.timestamp(offset!(UTC)).is_err());
+    }
+
+    #[test]
+    fn short_packet_is_parse_error() {
+        let err = SportDetail::try_from(&[0x24, 0x10][..]).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                Error::PacketParse {
+                    kind: PacketKind::SportDetail,
+                    ..
+                }
+            ),
+            "unexpected error {err:?}"
+        );
+        assert!(!err.is_connection_error());
     }
 
     #[test]
@@ -1,61 +1,148 @@
-use crate::Result;
+use std::time::Duration;
+
+use time::{Date, PrimitiveDateTime};
+
+use crate::{
+    util::{check_len, DurationExt as _},
+    Error, PacketKind, Result,
+};
+
+/// The first 3 bytes of a stress packet are a header and the last is a checksum
+const MIN_PACKET_LEN: usize = 4;
+/// Used when a stress reply doesn't report how far apart its readings are
+const DEFAULT_MINUTES_APART: u8 = 30;
+
+/// A day of stress readings
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StressData {
+    /// The day that was requested
+    pub date: Date,
+    /// How many minutes apart each reading is, 0 if the ring didn't report it
+    pub minutes_apart: u8,
+    pub readings: Vec<StressReading>,
+}
+
+/// A single stress reading, `index` counts intervals from midnight
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct StressReading {
+    pub index: u16,
+    /// `None` when the ring didn't take a reading in this interval
+    pub value: Option<u8>,
+}
+
+impl StressData {
+    pub(crate) fn new(date: Date, minutes_apart: u8, measurements: Vec<u8>) -> Self {
+        let readings = measurements
+            .into_iter()
+            .zip(0u16..)
+            .map(|(value, index)| StressReading {
+                index,
+                value: (value != 0).then_some(value),
+            })
+            .collect();
+        Self {
+            date,
+            minutes_apart,
+            readings,
+        }
+    }
+
+    /// The time between readings
+    pub fn interval(&self) -> Duration {
+        let minutes = if self.minutes_apart == 0 {
+            DEFAULT_MINUTES_APART
+        } else {
+            self.minutes_apart
+        };
+        Duration::minutes(minutes.into())
+    }
+
+    /// Each reading paired with the time it was taken, readings that would
+    /// fall on the following day are dropped
+    pub fn samples(&self) -> impl Iterator<Item = (PrimitiveDateTime, Option<u8>)> + '_ {
+        let start = self.date.midnight();
+        let interval = self.interval();
+        self.readings
+            .iter()
+            .map(move |reading| (start + interval * u32::from(reading.index), reading.value))
+            .take_while(move |(when, _)| when.date() == start.date())
+    }
+}
 
 #[derive(Debug)]
 pub enum StressState {
     Length {
         length: u8,
-        minutes_appart: u8,
+        minutes_apart: u8,
     },
     Receiving {
         target_length: u8,
         measurements: Vec<u8>,
-        minutes_appart: u8,
+        minutes_apart: u8,
     },
     Complete {
         measurements: Vec<u8>,
-        minutes_appart: u8,
+        minutes_apart: u8,
     },
 }
 
 impl StressState {
     pub fn new(packet: &[u8]) -> Result<Self> {
+        check_len(packet, MIN_PACKET_LEN)?;
         if packet[0] != 55 {
-            return Err(format!("Error parsing stress state {packet:?}").into());
+            return Err(Error::parse(
+                PacketKind::Stress,
+                packet,
+                "Error parsing stress state",
+            ));
         }
         if packet[1] == 255 {
             return Ok(Self::Complete {
                 measurements: Vec::new(),
-                minutes_appart: 0,
+                minutes_apart: 0,
             });
         }
         if packet[1] != 0 {
-            return Err(format!(
-                "unexpected initial stress state expected index 1 to be 0 {packet:?}"
-            )
-            .into());
+            return Err(Error::parse(
+                PacketKind::Stress,
+                packet,
+                "unexpected initial stress state expected index 1 to be 0",
+            ));
         }
-        let length = packet[2] - 1;
-        let minutes_appart = packet[3];
+        let length = packet[2].checked_sub(1).ok_or_else(|| {
+            Error::parse(
+                PacketKind::Stress,
+                packet,
+                "stress packet count must be at least 1",
+            )
+        })?;
+        let minutes_apart = packet[3];
         Ok(Self::Length {
             length,
-            minutes_appart,
+            minutes_apart,
         })
     }
 
     pub fn step(&mut self, packet: &[u8]) -> Result {
+        check_len(packet, MIN_PACKET_LEN)?;
         if packet[0] != 55 {
-            return Err(format!("Invalid stress state packet: {packet:?}").into());
+            return Err(Error::parse(
+                PacketKind::Stress,
+                packet,
+                "Invalid stress state packet",
+            ));
         }
         *self = match self {
             Self::Length {
                 length,
-                minutes_appart,
+                minutes_apart,
             } => {
                 if packet[1] == 0 {
                     log::debug!("empty from Length");
                     Self::Complete {
                         measurements: Vec::new(),
-                        minutes_appart: *minutes_appart,
+                        minutes_apart: *minutes_apart,
                     }
                 } else {
                     log::debug!("more after length");
@@ -64,33 +151,74 @@ impl StressState {
                     Self::Receiving {
                         target_length: *length,
                         measurements,
-                        minutes_appart: *minutes_appart,
+                        minutes_apart: *minutes_apart,
                     }
                 }
             }
             Self::Receiving {
                 target_length,
                 measurements,
-                minutes_appart,
+                minutes_apart,
             } => {
                 if packet[1] == 1 {
                     measurements.extend_from_slice(&packet[3..packet.len() - 1]);
                     return Ok(());
                 } else {
                     measurements.extend_from_slice(&packet[2..packet.len() - 1]);
-                    if *dbg!(target_length) == dbg!(packet[1]) {
+                    if *target_length == packet[1] {
                         let measurements = std::mem::take(measurements);
                         Self::Complete {
                             measurements,
-                            minutes_appart: *minutes_appart,
+                            minutes_apart: *minutes_apart,
                         }
                     } else {
                         return Ok(());
                     }
                 }
             }
-            Self::Complete { .. } => return Err(format!("Step after complete: {self:?}").into()),
+            Self::Complete { .. } => {
+                return Err(Error::parse(
+                    PacketKind::Stress,
+                    packet,
+                    format!("Step after complete: {self:?}"),
+                ))
+            }
         };
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use time::macros::{date, datetime};
+
+    use super::*;
+
+    #[test]
+    fn samples_expand_from_midnight() {
+        let mut measurements = vec![0u8; 48];
+        measurements[1] = 20;
+        measurements[47] = 40;
+        let data = StressData::new(date!(2024 - 11 - 27), 30, measurements);
+        let samples: Vec<_> = data.samples().collect();
+        assert_eq!(samples.len(), 48);
+        assert_eq!(samples[0], (datetime!(2024-11-27 0:00), None));
+        assert_eq!(samples[1], (datetime!(2024-11-27 0:30), Some(20)));
+        assert_eq!(samples[47], (datetime!(2024-11-27 23:30), Some(40)));
+    }
+
+    #[test]
+    fn samples_stop_at_end_of_day() {
+        let data = StressData::new(date!(2024 - 11 - 27), 60, vec![10; 30]);
+        let samples: Vec<_> = data.samples().collect();
+        assert_eq!(samples.len(), 24);
+        assert_eq!(samples[23].0, datetime!(2024-11-27 23:00));
+    }
+
+    #[test]
+    fn missing_interval_uses_default() {
+        let data = StressData::new(date!(2024 - 11 - 27), 0, vec![0, 15]);
+        let samples: Vec<_> = data.samples().collect();
+        assert_eq!(samples[1], (datetime!(2024-11-27 0:30), Some(15)));
+    }
+}
@@ -1,65 +1,85 @@
-use bleasy::{Device, ScanConfig};
-use futures::{Stream, StreamExt};
-use std::{pin::Pin, time::Duration};
-
-type Result<T = (), E = Box<dyn std::error::Error>> = std::result::Result<T, E>;
+pub type Result<T = (), E = Error> = std::result::Result<T, E>;
 
+pub mod analysis;
+pub mod capture;
 pub mod client;
 mod constants;
+#[cfg(feature = "ble")]
+mod discovery;
+mod error;
+pub mod gatt_names;
 pub mod incoming_messages;
+#[cfg(feature = "ble")]
+pub mod manager;
+#[cfg(all(feature = "ble", any(test, feature = "mock")))]
+pub mod mock;
+pub mod preferences;
+#[cfg(feature = "ble")]
+mod request_queue;
+#[cfg(feature = "ble")]
+pub mod transport;
+pub mod units;
 mod util;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
+#[cfg(feature = "ble")]
 pub use crate::{
     client::Client,
+    discovery::{
+        discover, discover_by_name, discover_rings, discover_with, sorted_by_rssi, DiscoveredRing,
+        ScanOptions,
+    },
+    manager::{RingId, RingManager},
+};
+pub use crate::{
+    error::{Error, PacketKind},
     incoming_messages::{
         big_data::{self, SleepStage},
         heart_rate, sport_detail, stress,
     },
-    util::DurationExt,
+    util::{date_range, DurationExt},
 };
 
+#[cfg(feature = "ble")]
 pub use bleasy::BDAddr;
 
-pub async fn discover(
-    all: bool,
-    force_disconnect: bool,
-) -> Result<Pin<Box<dyn Stream<Item = Device>>>> {
-    log::trace!("discover({all}, {force_disconnect})");
-    let mut config = ScanConfig::default().force_disconnect(force_disconnect);
+#[cfg(test)]
+mod tests {
+    use std::any::TypeId;
 
-    if !all {
-        config = config.filter_by_name(|n| {
-            crate::constants::DEVICE_NAME_PREFIXES
-                .iter()
-                .any(|p| n.starts_with(*p))
-        });
-    }
-    discover_(config).await
-}
-
-pub async fn discover_by_name(name: String) -> Result<Pin<Box<dyn Stream<Item = Device>>>> {
-    log::trace!("discover_by_name: `{name}`");
-    let config = ScanConfig::default().filter_by_name(move |n| n == name);
-    discover_(config).await
-}
+    use super::*;
 
-async fn discover_(mut config: ScanConfig) -> Result<Pin<Box<dyn Stream<Item = Device>>>> {
-    let mut scanner = bleasy::Scanner::new();
-    if let Some(max_op_secs) = std::env::var("COLE_MINE_MAX_TIMEOUT_SECS")
-        .ok()
-        .and_then(|a| a.parse::<u64>().ok())
-    {
-        log::debug!("Scanning for {max_op_secs} seconds");
-        config = config.stop_after_timeout(Duration::from_secs(max_op_secs))
-    }
-    log::trace!("starting scan");
-    scanner.start(config).await?;
-    Ok(async_stream::stream! {
-        let mut stream = scanner.device_stream();
-        while let Some(dev) = stream.next().await {
-            log::debug!("Stream returned device");
-            yield dev;
-        }
+    #[test]
+    fn top_level_modules_are_reexports() {
+        use incoming_messages as im;
+        assert_eq!(
+            TypeId::of::<stress::StressState>(),
+            TypeId::of::<im::stress::StressState>()
+        );
+        assert_eq!(
+            TypeId::of::<stress::StressData>(),
+            TypeId::of::<im::stress::StressData>()
+        );
+        assert_eq!(
+            TypeId::of::<heart_rate::HeartRateState>(),
+            TypeId::of::<im::heart_rate::HeartRateState>()
+        );
+        assert_eq!(
+            TypeId::of::<heart_rate::HeartRate>(),
+            TypeId::of::<im::heart_rate::HeartRate>()
+        );
+        assert_eq!(
+            TypeId::of::<sport_detail::SportDetailState>(),
+            TypeId::of::<im::sport_detail::SportDetailState>()
+        );
+        assert_eq!(
+            TypeId::of::<sport_detail::SportDetail>(),
+            TypeId::of::<im::sport_detail::SportDetail>()
+        );
+        assert_eq!(
+            TypeId::of::<big_data::BigDataState>(),
+            TypeId::of::<im::big_data::BigDataState>()
+        );
     }
-    .boxed_local())
 }
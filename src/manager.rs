@@ -0,0 +1,333 @@
+//! Working with several rings from one process, only available with the `ble`
+//! feature
+//!
+//! A [`RingManager`] finds every ring it was given in a single scan and then
+//! connects to them a few at a time, most adapters can only hold one or two
+//! connections at once
+
+use std::{collections::BTreeMap, fmt, future::Future, time::Duration};
+
+use bleasy::{BDAddr, Device};
+use futures::{Stream, StreamExt};
+
+use crate::{client::ClientBuilder, Client, Error, Result, ScanOptions};
+
+/// How long the discovery scan looks for the rings unless configured otherwise
+const DEFAULT_SCAN_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// A ring to look for, by its address or the name it advertises
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RingId {
+    Address(BDAddr),
+    Name(String),
+}
+
+impl RingId {
+    fn matches(&self, address: BDAddr, name: Option<&str>) -> bool {
+        match self {
+            Self::Address(addr) => *addr == address,
+            Self::Name(n) => name == Some(n.as_str()),
+        }
+    }
+}
+
+impl From<BDAddr> for RingId {
+    fn from(value: BDAddr) -> Self {
+        Self::Address(value)
+    }
+}
+
+impl fmt::Display for RingId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Address(addr) => addr.fmt(f),
+            Self::Name(name) => f.write_str(name),
+        }
+    }
+}
+
+/// Finds a set of rings with one scan and runs a task with a client for each
+///
+/// ```no_run
+/// # async fn sync(rings: Vec<cole_mine::BDAddr>) -> cole_mine::Result {
+/// use cole_mine::manager::RingManager;
+///
+/// let manager = RingManager::new(rings);
+/// let results = manager
+///     .for_each_ring(|client| async move {
+///         let battery = client.battery().await?;
+///         Ok::<_, cole_mine::Error>(battery.level)
+///     })
+///     .await?;
+/// for (ring, result) in results {
+///     match result {
+///         Ok(level) => println!("{ring}: {level}%"),
+///         Err(e) => println!("{ring}: {e}"),
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct RingManager {
+    rings: Vec<RingId>,
+    concurrency: usize,
+    scan_timeout: Duration,
+    builder: ClientBuilder,
+}
+
+impl RingManager {
+    pub fn new<I>(rings: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<RingId>,
+    {
+        let mut rings: Vec<RingId> = rings.into_iter().map(Into::into).collect();
+        rings.sort();
+        rings.dedup();
+        Self {
+            rings,
+            concurrency: 1,
+            scan_timeout: DEFAULT_SCAN_TIMEOUT,
+            builder: ClientBuilder::default(),
+        }
+    }
+
+    /// How many rings to be connected to at once, defaults to 1 since most
+    /// adapters only handle one or two connections
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// How long the discovery scan looks for rings, it stops early once every
+    /// ring was found
+    pub fn scan_timeout(mut self, timeout: Duration) -> Self {
+        self.scan_timeout = timeout;
+        self
+    }
+
+    /// Connect to each ring with the retries and timeouts of `builder`
+    pub fn client_builder(mut self, builder: ClientBuilder) -> Self {
+        self.builder = builder;
+        self
+    }
+
+    /// The rings this manager looks for
+    pub fn rings(&self) -> &[RingId] {
+        &self.rings
+    }
+
+    /// Scan once for every ring, the ones that weren't found are missing from
+    /// the map
+    pub async fn discover(&self) -> Result<BTreeMap<RingId, Device>> {
+        let options = ScanOptions::builder()
+            .timeout(self.scan_timeout)
+            .name_prefixes(Vec::new())
+            .build();
+        let stream = crate::discover_with(options).await?;
+        // only look the name up when a ring is still expected by its name
+        let by_name = self.rings.iter().any(|id| matches!(id, RingId::Name(_)));
+        let devices = stream.then(|device| async move {
+            let name = if by_name {
+                device.local_name().await
+            } else {
+                None
+            };
+            (device.address(), name, device)
+        });
+        Ok(collect_matches(&self.rings, devices).await)
+    }
+
+    /// Connect to every ring and call `cb` with its client, at most
+    /// `concurrency` at a time
+    ///
+    /// Each ring gets its own result, a ring that wasn't found gets
+    /// `Error::DeviceNotFound` and one failing doesn't stop the others. Only
+    /// an error starting the scan fails them all
+    pub async fn for_each_ring<F, G, T, E>(&self, cb: F) -> Result<BTreeMap<RingId, Result<T, E>>>
+    where
+        F: Fn(Client) -> G,
+        G: Future<Output = Result<T, E>>,
+        E: From<Error>,
+    {
+        let mut found = self.discover().await?;
+        let devices = self
+            .rings
+            .iter()
+            .map(|id| (id.clone(), found.remove(id).ok_or(Error::DeviceNotFound)));
+        let cb = &cb;
+        let ret = run_bounded(devices, self.concurrency, |device| async move {
+            let mut client = self
+                .builder
+                .build_with_device(device.clone())
+                .await
+                .map_err(E::from)?;
+            client.connect().await.map_err(E::from)?;
+            let ret = cb(client).await;
+            if let Err(e) = device.disconnect().await {
+                log::warn!("failed to disconnect from {}: {e}", device.address());
+            }
+            ret
+        })
+        .await;
+        Ok(ret)
+    }
+}
+
+/// Drain `devices` until each of `rings` matched one, keeping the first match
+/// for each
+async fn collect_matches<D>(
+    rings: &[RingId],
+    devices: impl Stream<Item = (BDAddr, Option<String>, D)>,
+) -> BTreeMap<RingId, D> {
+    let mut ret = BTreeMap::new();
+    if rings.is_empty() {
+        return ret;
+    }
+    futures::pin_mut!(devices);
+    while let Some((address, name, device)) = devices.next().await {
+        let Some(id) = rings
+            .iter()
+            .find(|id| !ret.contains_key(*id) && id.matches(address, name.as_deref()))
+        else {
+            continue;
+        };
+        log::debug!("found {id} at {address}");
+        ret.insert(id.clone(), device);
+        if ret.len() == rings.len() {
+            break;
+        }
+    }
+    ret
+}
+
+/// Call `run` for each found device, at most `concurrency` at a time, the
+/// devices that weren't found keep their error
+async fn run_bounded<K, D, F, G, T, E>(
+    devices: impl IntoIterator<Item = (K, Result<D>)>,
+    concurrency: usize,
+    run: F,
+) -> BTreeMap<K, Result<T, E>>
+where
+    K: Ord,
+    F: Fn(D) -> G,
+    G: Future<Output = Result<T, E>>,
+    E: From<Error>,
+{
+    let run = &run;
+    futures::stream::iter(devices)
+        .map(|(key, device)| async move {
+            let ret = match device {
+                Ok(device) => run(device).await,
+                Err(e) => Err(e.into()),
+            };
+            (key, ret)
+        })
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    fn addr(last: u8) -> BDAddr {
+        BDAddr::from([0, 0, 0, 0, 0, last])
+    }
+
+    /// Run `count` rings that each take a moment, returning the most that
+    /// were running at once
+    async fn most_at_once(count: u8, concurrency: usize) -> usize {
+        let running = AtomicUsize::new(0);
+        let most = AtomicUsize::new(0);
+        let devices = (0..count).map(|i| (i, Ok(i)));
+        let results = run_bounded(devices, concurrency, |i| {
+            let (running, most) = (&running, &most);
+            async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                most.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                Ok::<_, Error>(i)
+            }
+        })
+        .await;
+        assert_eq!(results.len(), usize::from(count));
+        assert!(results.iter().all(|(k, v)| v.as_ref().ok() == Some(k)));
+        most.into_inner()
+    }
+
+    #[tokio::test]
+    async fn runs_one_at_a_time_by_default() {
+        let manager = RingManager::new([addr(1), addr(2)]);
+        assert_eq!(manager.concurrency, 1);
+        assert_eq!(most_at_once(4, manager.concurrency).await, 1);
+        assert_eq!(RingManager::new([addr(1)]).concurrency(0).concurrency, 1);
+    }
+
+    #[tokio::test]
+    async fn bounds_concurrency() {
+        assert_eq!(most_at_once(5, 2).await, 2);
+        assert_eq!(most_at_once(2, 8).await, 2);
+    }
+
+    #[tokio::test]
+    async fn failures_stay_with_their_ring() {
+        let devices = [
+            (addr(1), Ok(1)),
+            (addr(2), Err(Error::DeviceNotFound)),
+            (addr(3), Ok(3)),
+            (addr(4), Ok(4)),
+        ];
+        let results = run_bounded(devices, 1, |i| async move {
+            if i == 3 {
+                return Err(Error::Timeout);
+            }
+            Ok(i * 10)
+        })
+        .await;
+        assert!(matches!(results[&addr(1)], Ok(10)));
+        assert!(matches!(results[&addr(2)], Err(Error::DeviceNotFound)));
+        assert!(matches!(results[&addr(3)], Err(Error::Timeout)));
+        assert!(matches!(results[&addr(4)], Ok(40)));
+    }
+
+    #[tokio::test]
+    async fn one_scan_matches_every_ring() {
+        let rings = RingManager::new([
+            RingId::Name("R02_0002".to_string()),
+            RingId::Address(addr(1)),
+            RingId::Address(addr(9)),
+        ])
+        .rings;
+        let seen = AtomicUsize::new(0);
+        let devices = futures::stream::iter([
+            (addr(5), Some("R02_0005".to_string()), 5),
+            (addr(1), Some("R02_0001".to_string()), 1),
+            (addr(2), Some("R02_0002".to_string()), 2),
+            (addr(1), Some("R02_0001".to_string()), 10),
+        ])
+        .inspect(|_| {
+            seen.fetch_add(1, Ordering::SeqCst);
+        });
+        let found = collect_matches(&rings, devices).await;
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[&RingId::Address(addr(1))], 1);
+        assert_eq!(found[&RingId::Name("R02_0002".to_string())], 2);
+        assert_eq!(seen.into_inner(), 4);
+
+        // the scan stops once every ring has been found
+        let seen = AtomicUsize::new(0);
+        let devices =
+            futures::stream::iter([(addr(1), None, 1), (addr(2), None, 2)]).inspect(|_| {
+                seen.fetch_add(1, Ordering::SeqCst);
+            });
+        let found = collect_matches(&[RingId::Address(addr(1))], devices).await;
+        assert_eq!(found.len(), 1);
+        assert_eq!(seen.into_inner(), 1);
+    }
+}
@@ -0,0 +1,153 @@
+//! A [`Transport`] for testing a [`Client`](crate::Client) without a ring
+//!
+//! Only available with the `mock` feature
+
+use std::sync::{Arc, Mutex};
+
+use futures::{channel::mpsc, future::BoxFuture, FutureExt};
+
+use crate::{
+    capture::Channel,
+    incoming_messages::{PacketStream, RawPacket},
+    transport::Transport,
+    Error, Result,
+};
+
+type Responder = dyn Fn(Channel, &[u8]) -> Vec<RawPacket> + Send + Sync;
+
+/// Records the commands written to it and delivers injected packets
+///
+/// Clones share the same state, so a test can keep one clone and hand another
+/// to the client
+#[derive(Clone, Default)]
+pub struct MockTransport {
+    inner: Arc<Mutex<MockState>>,
+}
+
+#[derive(Default)]
+struct MockState {
+    written: Vec<(Channel, Vec<u8>)>,
+    packets: Option<mpsc::UnboundedSender<RawPacket>>,
+    /// Packets injected before anything subscribed
+    queued: Vec<RawPacket>,
+    responder: Option<Arc<Responder>>,
+    disconnects: usize,
+    reconnects: usize,
+    /// How many of the next reconnects fail
+    failing_reconnects: usize,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reply to every command written with the packets `responder` returns
+    pub fn with_responder(
+        self,
+        responder: impl Fn(Channel, &[u8]) -> Vec<RawPacket> + Send + Sync + 'static,
+    ) -> Self {
+        self.lock().responder = Some(Arc::new(responder));
+        self
+    }
+
+    /// Deliver `packet` as if the ring had sent it
+    pub fn push(&self, packet: RawPacket) {
+        let mut state = self.lock();
+        state.deliver(packet);
+    }
+
+    /// Every command written so far and the channel it was written to
+    pub fn written(&self) -> Vec<(Channel, Vec<u8>)> {
+        self.lock().written.clone()
+    }
+
+    /// How many times the client disconnected
+    pub fn disconnects(&self) -> usize {
+        self.lock().disconnects
+    }
+
+    /// End the packet stream as if the ring had dropped the connection, packets
+    /// pushed afterwards arrive once the client subscribes again
+    pub fn drop_connection(&self) {
+        self.lock().packets = None;
+    }
+
+    /// Make the next `count` reconnects fail
+    pub fn fail_reconnects(&self, count: usize) {
+        self.lock().failing_reconnects = count;
+    }
+
+    /// How many times the client reconnected, including failed attempts
+    pub fn reconnects(&self) -> usize {
+        self.lock().reconnects
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MockState> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self, chan: Channel, bytes: &[u8]) -> Result {
+        let mut state = self.lock();
+        state.written.push((chan, bytes.to_vec()));
+        if let Some(responder) = state.responder.clone() {
+            for packet in responder(chan, bytes) {
+                state.deliver(packet);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl MockState {
+    fn deliver(&mut self, packet: RawPacket) {
+        let packet = match &self.packets {
+            Some(tx) => match tx.unbounded_send(packet) {
+                Ok(()) => return,
+                Err(e) => e.into_inner(),
+            },
+            None => packet,
+        };
+        self.queued.push(packet);
+    }
+}
+
+impl Transport for MockTransport {
+    fn write_uart<'a>(&'a self, bytes: &'a [u8]) -> BoxFuture<'a, Result> {
+        std::future::ready(self.write(Channel::Uart, bytes)).boxed()
+    }
+
+    fn write_v2<'a>(&'a self, bytes: &'a [u8]) -> BoxFuture<'a, Result> {
+        std::future::ready(self.write(Channel::V2, bytes)).boxed()
+    }
+
+    fn subscribe(&self) -> BoxFuture<'_, Result<PacketStream>> {
+        let (tx, rx) = mpsc::unbounded();
+        let mut state = self.lock();
+        for packet in state.queued.drain(..) {
+            let _ = tx.unbounded_send(packet);
+        }
+        state.packets = Some(tx);
+        let stream: PacketStream = Box::pin(rx);
+        std::future::ready(Ok(stream)).boxed()
+    }
+
+    fn disconnect(&self) -> BoxFuture<'_, Result> {
+        let mut state = self.lock();
+        state.packets = None;
+        state.disconnects += 1;
+        std::future::ready(Ok(())).boxed()
+    }
+
+    fn reconnect(&self) -> BoxFuture<'_, Result> {
+        let mut state = self.lock();
+        state.reconnects += 1;
+        let ret = if state.failing_reconnects > 0 {
+            state.failing_reconnects -= 1;
+            Err(Error::NotConnected)
+        } else {
+            Ok(())
+        };
+        std::future::ready(ret).boxed()
+    }
+}
@@ -0,0 +1,229 @@
+//! The user preferences the vendor app keeps on the ring, each stored under a
+//! one byte key with [`Command::WritePreference`]
+
+use crate::{client::Command, constants};
+
+/// The key a preference is stored under
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PreferenceKey {
+    TimeFormat,
+    Units,
+    WearHand,
+    RaiseToWake,
+    Unknown(u8),
+}
+
+impl PreferenceKey {
+    /// Every key this crate can decode the value of
+    pub const KNOWN: [Self; 4] = [
+        Self::TimeFormat,
+        Self::Units,
+        Self::WearHand,
+        Self::RaiseToWake,
+    ];
+
+    /// The name lode and the logs use for this key, `None` for unknown keys
+    pub fn name(self) -> Option<&'static str> {
+        Some(match self {
+            Self::TimeFormat => "time-format",
+            Self::Units => "units",
+            Self::WearHand => "wear-hand",
+            Self::RaiseToWake => "raise-to-wake",
+            Self::Unknown(_) => return None,
+        })
+    }
+
+    /// The command that reads this preference
+    pub fn read(self) -> Command {
+        Command::ReadPreference { key: self.into() }
+    }
+
+    /// The command that removes this preference, the ring falls back to its
+    /// default
+    pub fn delete(self) -> Command {
+        Command::DeletePreference { key: self.into() }
+    }
+}
+
+impl From<u8> for PreferenceKey {
+    fn from(key: u8) -> Self {
+        match key {
+            constants::PREF_KEY_TIME_FORMAT => Self::TimeFormat,
+            constants::PREF_KEY_UNITS => Self::Units,
+            constants::PREF_KEY_WEAR_HAND => Self::WearHand,
+            constants::PREF_KEY_RAISE_TO_WAKE => Self::RaiseToWake,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
+impl From<PreferenceKey> for u8 {
+    fn from(key: PreferenceKey) -> u8 {
+        match key {
+            PreferenceKey::TimeFormat => constants::PREF_KEY_TIME_FORMAT,
+            PreferenceKey::Units => constants::PREF_KEY_UNITS,
+            PreferenceKey::WearHand => constants::PREF_KEY_WEAR_HAND,
+            PreferenceKey::RaiseToWake => constants::PREF_KEY_RAISE_TO_WAKE,
+            PreferenceKey::Unknown(key) => key,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TimeFormat {
+    TwentyFourHour = 0,
+    TwelveHour = 1,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Units {
+    Metric = 0,
+    Imperial = 1,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Hand {
+    Left = 0,
+    Right = 1,
+}
+
+/// A preference value decoded for its key
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(tag = "key", content = "value", rename_all = "camelCase")]
+pub enum Preference {
+    TimeFormat(TimeFormat),
+    Units(Units),
+    WearHand(Hand),
+    /// If the screen turns on when the ring is raised
+    RaiseToWake(bool),
+    /// A key this crate doesn't know, or a value it can't decode for a key it
+    /// does, kept as the bytes the ring sent
+    Unknown {
+        key: u8,
+        value: Vec<u8>,
+    },
+}
+
+impl Preference {
+    /// Decode the value stored under `key`, `value` may have its trailing zero
+    /// padding removed so an empty value decodes the same as `[0]`
+    pub fn decode(key: u8, value: &[u8]) -> Self {
+        let decoded = match value {
+            [] | [_] => {
+                let byte = value.first().copied().unwrap_or(0);
+                match (PreferenceKey::from(key), byte) {
+                    (PreferenceKey::TimeFormat, 0) => {
+                        Some(Self::TimeFormat(TimeFormat::TwentyFourHour))
+                    }
+                    (PreferenceKey::TimeFormat, 1) => {
+                        Some(Self::TimeFormat(TimeFormat::TwelveHour))
+                    }
+                    (PreferenceKey::Units, 0) => Some(Self::Units(Units::Metric)),
+                    (PreferenceKey::Units, 1) => Some(Self::Units(Units::Imperial)),
+                    (PreferenceKey::WearHand, 0) => Some(Self::WearHand(Hand::Left)),
+                    (PreferenceKey::WearHand, 1) => Some(Self::WearHand(Hand::Right)),
+                    (PreferenceKey::RaiseToWake, 0 | 1) => Some(Self::RaiseToWake(byte == 1)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        decoded.unwrap_or_else(|| Self::Unknown {
+            key,
+            value: value.to_vec(),
+        })
+    }
+
+    pub fn key(&self) -> PreferenceKey {
+        match self {
+            Self::TimeFormat(_) => PreferenceKey::TimeFormat,
+            Self::Units(_) => PreferenceKey::Units,
+            Self::WearHand(_) => PreferenceKey::WearHand,
+            Self::RaiseToWake(_) => PreferenceKey::RaiseToWake,
+            Self::Unknown { key, .. } => PreferenceKey::from(*key),
+        }
+    }
+
+    /// The bytes stored under [`Preference::key`]
+    pub fn value(&self) -> Vec<u8> {
+        match self {
+            Self::TimeFormat(format) => vec![*format as u8],
+            Self::Units(units) => vec![*units as u8],
+            Self::WearHand(hand) => vec![*hand as u8],
+            Self::RaiseToWake(enabled) => vec![u8::from(*enabled)],
+            Self::Unknown { value, .. } => value.clone(),
+        }
+    }
+
+    /// The command that stores this preference
+    pub fn write(&self) -> Command {
+        Command::WritePreference {
+            key: self.key().into(),
+            value: self.value(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_round_trip() {
+        for key in 0..=u8::MAX {
+            assert_eq!(u8::from(PreferenceKey::from(key)), key);
+        }
+        for key in PreferenceKey::KNOWN {
+            assert!(key.name().is_some(), "{key:?}");
+            assert!(!matches!(
+                PreferenceKey::from(u8::from(key)),
+                PreferenceKey::Unknown(_)
+            ));
+        }
+    }
+
+    #[test]
+    fn values_round_trip() {
+        for pref in [
+            Preference::TimeFormat(TimeFormat::TwentyFourHour),
+            Preference::TimeFormat(TimeFormat::TwelveHour),
+            Preference::Units(Units::Metric),
+            Preference::Units(Units::Imperial),
+            Preference::WearHand(Hand::Left),
+            Preference::WearHand(Hand::Right),
+            Preference::RaiseToWake(false),
+            Preference::RaiseToWake(true),
+            Preference::Unknown {
+                key: 0x7f,
+                value: vec![1, 2, 3],
+            },
+        ] {
+            let key = u8::from(pref.key());
+            assert_eq!(Preference::decode(key, &pref.value()), pref);
+        }
+    }
+
+    #[test]
+    fn padding_and_unexpected_values() {
+        let key = constants::PREF_KEY_RAISE_TO_WAKE;
+        assert_eq!(Preference::decode(key, &[]), Preference::RaiseToWake(false));
+        assert_eq!(
+            Preference::decode(key, &[2]),
+            Preference::Unknown {
+                key,
+                value: vec![2]
+            }
+        );
+        assert_eq!(
+            Preference::decode(key, &[1, 1]),
+            Preference::Unknown {
+                key,
+                value: vec![1, 1]
+            }
+        );
+    }
+}
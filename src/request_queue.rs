@@ -0,0 +1,351 @@
+use std::{future::Future, time::Duration};
+
+use tokio::sync::{broadcast, Mutex, MutexGuard};
+
+use crate::{
+    capture::Channel,
+    client::Command,
+    constants,
+    incoming_messages::{ClientReceiver, CommandReply},
+    util::local_today,
+    Error, Result,
+};
+
+/// How long a queued request waits for its reply unless configured otherwise
+pub(crate) const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// How many notifications are held for slow subscribers before the oldest are dropped
+const NOTIFICATION_CAPACITY: usize = 64;
+
+/// Serializes requests to a ring so each caller gets the reply to its own command
+///
+/// The receiver is held for the whole of a request, from sending the command until
+/// its reply arrives or times out. Tokio's mutex is fair so requests are sent in the
+/// order they were made. Replies that arrive while a request is waiting but don't
+/// answer it are published to the notification channel instead.
+pub(crate) struct RequestQueue {
+    rx: Mutex<Option<ClientReceiver>>,
+    notifications: broadcast::Sender<CommandReply>,
+    timeout: Duration,
+}
+
+impl Default for RequestQueue {
+    fn default() -> Self {
+        Self::new(DEFAULT_REQUEST_TIMEOUT)
+    }
+}
+
+impl RequestQueue {
+    /// A queue whose requests wait up to `timeout` for their replies
+    pub(crate) fn new(timeout: Duration) -> Self {
+        Self {
+            rx: Mutex::new(None),
+            notifications: broadcast::channel(NOTIFICATION_CAPACITY).0,
+            timeout,
+        }
+    }
+
+    /// The receiver, for callers that already have exclusive access
+    pub(crate) fn receiver_mut(&mut self) -> &mut Option<ClientReceiver> {
+        self.rx.get_mut()
+    }
+
+    /// Wait for the requests ahead of this one to finish
+    pub(crate) async fn lock(&self) -> MutexGuard<'_, Option<ClientReceiver>> {
+        self.rx.lock().await
+    }
+
+    pub(crate) fn notifications(&self) -> broadcast::Receiver<CommandReply> {
+        self.notifications.subscribe()
+    }
+
+    /// Send `command` with `write` once every earlier request has finished, and
+    /// wait for the reply with the same command byte
+    ///
+    /// `connect` is used to create the receiver if there isn't one yet, and
+    /// `reconnect` to replace it once the ring has dropped the connection. If that
+    /// happens while waiting for the reply, `command` is sent again after
+    /// reconnecting unless it is one the ring drops the connection for, like a
+    /// reboot, which is `NotConnected` instead. Multi-packet replies are assembled by the receiver so they
+    /// count as a single reply.
+    pub(crate) async fn request<C, CF, R, RF, W, WF>(
+        &self,
+        command: Command,
+        connect: C,
+        reconnect: R,
+        write: W,
+    ) -> Result<CommandReply>
+    where
+        C: FnOnce() -> CF,
+        CF: Future<Output = Result<ClientReceiver>>,
+        R: Fn() -> RF,
+        RF: Future<Output = Result<ClientReceiver>>,
+        W: Fn(Channel, [u8; 16]) -> WF,
+        WF: Future<Output = Result>,
+    {
+        let mut guard = self.lock().await;
+        if guard.is_none() {
+            *guard = Some(connect().await?);
+        } else if guard.as_ref().is_some_and(ClientReceiver::is_closed) {
+            *guard = Some(reconnect().await?);
+        }
+        let Some(rx) = guard.as_mut() else {
+            return Err(Error::NotConnected);
+        };
+        match self.exchange(rx, command.clone(), &write).await {
+            // the reply was lost with the connection
+            Err(Error::NotConnected) if !command.drops_connection() => {
+                let rx = guard.insert(reconnect().await?);
+                self.exchange(rx, command, &write).await
+            }
+            ret => ret,
+        }
+    }
+
+    /// Send `command` with `write` and wait for its reply, `NotConnected` if the
+    /// packet stream ends first
+    async fn exchange<W, WF>(
+        &self,
+        rx: &mut ClientReceiver,
+        command: Command,
+        write: &W,
+    ) -> Result<CommandReply>
+    where
+        W: Fn(Channel, [u8; 16]) -> WF,
+        WF: Future<Output = Result>,
+    {
+        let (chan, bytes) = prepare(command, Some(&mut *rx))?;
+        write(chan, bytes).await?;
+        let expected = bytes[0];
+        tokio::time::timeout(self.timeout, async {
+            while let Some(reply) = rx.next().await {
+                if reply.command() == Some(expected) {
+                    return Ok(reply);
+                }
+                log::debug!("publishing reply unrelated to request {expected:#04x}: {reply:?}");
+                // an error only means nobody is subscribed
+                let _ = self.notifications.send(reply);
+            }
+            Err(Error::NotConnected)
+        })
+        .await?
+    }
+}
+
+/// Serialize `command` and work out which characteristic it is written to, the
+/// receiver is told about the command and records it if it is capturing
+pub(crate) fn prepare(
+    command: Command,
+    rx: Option<&mut ClientReceiver>,
+) -> Result<(Channel, [u8; 16])> {
+    let day = match command {
+        Command::ReadStress { day_offset } => {
+            Some(local_today() - time::Duration::days(day_offset.into()))
+        }
+        _ => None,
+    };
+    let big_data = matches!(command, Command::SyncSleep | Command::SyncOxygen);
+    let bytes: [u8; 16] = command.try_into()?;
+    log::trace!("serialized: {bytes:?}");
+    let chan = if bytes[0] == constants::CMD_BIG_DATA_V2 || bytes[0] == constants::CMD_NOTIFICATION
+    {
+        Channel::V2
+    } else {
+        Channel::Uart
+    };
+    match rx {
+        Some(rx) => {
+            if let Some(day) = day {
+                rx.expect_stress_day(day);
+            }
+            if big_data {
+                rx.expect_big_data(local_today());
+            }
+            rx.record_sent(chan, &bytes);
+        }
+        None if day.is_some() => {
+            log::warn!("stress requested before connecting, reply will use today")
+        }
+        None => {}
+    }
+    Ok((chan, bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use futures::channel::mpsc;
+
+    use super::*;
+    use crate::{incoming_messages::RawPacket, util::checksum};
+
+    fn packet(bytes: &[u8]) -> Vec<u8> {
+        let mut ret = bytes.to_vec();
+        ret.resize(constants::UART_PACKET_LEN, 0);
+        ret[15] = checksum(&ret);
+        ret
+    }
+
+    /// A fake ring that answers battery and goal requests after `delay`, sending
+    /// an unsolicited notification before each answer
+    struct MockRing {
+        packets: mpsc::UnboundedSender<RawPacket>,
+        written: std::sync::Mutex<Vec<u8>>,
+        delay: Duration,
+    }
+
+    impl MockRing {
+        fn new(delay: Duration) -> (Arc<Self>, RequestQueue) {
+            let (packets, stream) = mpsc::unbounded();
+            let queue = RequestQueue::default();
+            *queue.rx.try_lock().unwrap() = Some(ClientReceiver::from_stream(Box::pin(stream)));
+            let ring = Arc::new(Self {
+                packets,
+                written: Default::default(),
+                delay,
+            });
+            (ring, queue)
+        }
+
+        async fn write(self: Arc<Self>, bytes: [u8; 16]) -> Result {
+            self.written.lock().unwrap().push(bytes[0]);
+            tokio::spawn(async move {
+                tokio::time::sleep(self.delay).await;
+                let notification = packet(&[
+                    constants::CMD_NOTIFICATION,
+                    constants::NOTIFICATION_BATTERY_LEVEL,
+                    42,
+                ]);
+                self.packets
+                    .unbounded_send(RawPacket::Uart(notification))
+                    .unwrap();
+                let reply = match bytes[0] {
+                    constants::CMD_BATTERY => packet(&[constants::CMD_BATTERY, 70, 0]),
+                    constants::CMD_GOALS => {
+                        packet(&[constants::CMD_GOALS, constants::PREF_READ, 16, 39])
+                    }
+                    _ => return,
+                };
+                self.packets.unbounded_send(RawPacket::Uart(reply)).unwrap();
+            });
+            Ok(())
+        }
+    }
+
+    async fn request(
+        ring: &Arc<MockRing>,
+        queue: &RequestQueue,
+        command: Command,
+    ) -> Result<CommandReply> {
+        queue
+            .request(
+                command,
+                || async { Err(Error::NotConnected) },
+                || async { Err(Error::NotConnected) },
+                |_, bytes| ring.clone().write(bytes),
+            )
+            .await
+    }
+
+    #[tokio::test]
+    async fn concurrent_requests_get_their_own_replies() {
+        let (ring, queue) = MockRing::new(Duration::from_millis(20));
+        let queue = Arc::new(queue);
+        let mut notifications = queue.notifications();
+        let goals = tokio::spawn({
+            let (ring, queue) = (ring.clone(), queue.clone());
+            async move { request(&ring, &queue, Command::GetGoals).await }
+        });
+        tokio::task::yield_now().await;
+        let battery = request(&ring, &queue, Command::BatteryInfo).await.unwrap();
+        assert_eq!(
+            battery,
+            CommandReply::BatteryInfo {
+                level: 70,
+                charging: false
+            }
+        );
+        let goals = goals.await.unwrap().unwrap();
+        assert_eq!(
+            goals,
+            CommandReply::Goals {
+                steps: 10_000,
+                calories: 0,
+                distance: 0
+            }
+        );
+        assert_eq!(
+            *ring.written.lock().unwrap(),
+            vec![constants::CMD_GOALS, constants::CMD_BATTERY]
+        );
+        for _ in 0..2 {
+            assert!(matches!(
+                notifications.recv().await.unwrap(),
+                CommandReply::Notification(_)
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn next_request_waits_for_reply() {
+        let (ring, queue) = MockRing::new(Duration::from_millis(50));
+        let queue = Arc::new(queue);
+        let first = tokio::spawn({
+            let (ring, queue) = (ring.clone(), queue.clone());
+            async move { request(&ring, &queue, Command::BatteryInfo).await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let second = tokio::spawn({
+            let (ring, queue) = (ring.clone(), queue.clone());
+            async move { request(&ring, &queue, Command::BatteryInfo).await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(ring.written.lock().unwrap().len(), 1);
+        first.await.unwrap().unwrap();
+        second.await.unwrap().unwrap();
+        assert_eq!(ring.written.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn unanswered_request_times_out() {
+        let (ring, mut queue) = MockRing::new(Duration::from_millis(1));
+        queue.timeout = Duration::from_millis(50);
+        let err = request(&ring, &queue, Command::BlinkTwice)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Timeout), "{err:?}");
+        // the queue is free for the next request
+        request(&ring, &queue, Command::BatteryInfo).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn multi_packet_reply_is_one_request() {
+        let (packets, stream) = mpsc::unbounded();
+        let queue = RequestQueue::default();
+        *queue.rx.try_lock().unwrap() = Some(ClientReceiver::from_stream(Box::pin(stream)));
+        let reply = queue
+            .request(
+                Command::ReadStress { day_offset: 0 },
+                || async { Err(Error::NotConnected) },
+                || async { Err(Error::NotConnected) },
+                |_, _| async {
+                    for bytes in [
+                        &[constants::CMD_SYNC_STRESS, 0, 3, 30][..],
+                        &[constants::CMD_SYNC_STRESS, 1, 20],
+                        &[constants::CMD_SYNC_STRESS, 2, 40],
+                    ] {
+                        packets
+                            .unbounded_send(RawPacket::Uart(packet(bytes)))
+                            .unwrap();
+                    }
+                    Ok(())
+                },
+            )
+            .await
+            .unwrap();
+        let CommandReply::Stress(stress) = reply else {
+            panic!("unexpected reply {reply:?}");
+        };
+        assert_eq!(stress.date, local_today());
+    }
+}
@@ -0,0 +1,162 @@
+//! How a [`Client`](crate::Client) exchanges bytes with a ring
+//!
+//! [`BleTransport`] talks to a real device, the `mock` feature adds
+//! [`MockTransport`](crate::mock::MockTransport) for testing without one
+
+use std::sync::Mutex;
+
+use bleasy::{Characteristic, Device};
+use futures::{future::BoxFuture, FutureExt};
+
+use crate::{
+    constants, gatt_names,
+    incoming_messages::{subscribe_device, PacketStream},
+    Error, Result,
+};
+
+/// Writes commands to a ring and subscribes to its replies
+pub trait Transport: Send + Sync {
+    /// Write a command to the uart characteristic
+    fn write_uart<'a>(&'a self, bytes: &'a [u8]) -> BoxFuture<'a, Result>;
+    /// Write a command to the v2 characteristic, used for big data and notifications
+    fn write_v2<'a>(&'a self, bytes: &'a [u8]) -> BoxFuture<'a, Result>;
+    /// Start receiving the packets sent on both characteristics
+    fn subscribe(&self) -> BoxFuture<'_, Result<PacketStream>>;
+    fn disconnect(&self) -> BoxFuture<'_, Result>;
+    /// Re-establish a connection the ring dropped, `subscribe` is called after
+    /// this succeeds
+    fn reconnect(&self) -> BoxFuture<'_, Result> {
+        std::future::ready(Ok(())).boxed()
+    }
+    /// The bluetooth device behind this transport, if there is one
+    fn device(&self) -> Option<&Device> {
+        None
+    }
+}
+
+/// A transport over a bluetooth connection to a ring
+pub struct BleTransport {
+    device: Device,
+    /// The uart and v2 command characteristics, replaced when reconnecting
+    tx: Mutex<(Characteristic, Characteristic)>,
+    /// The characteristics to unsubscribe from when disconnecting
+    subscribed: Mutex<Vec<Characteristic>>,
+}
+
+impl BleTransport {
+    /// Look up the characteristics commands are written to on `device`
+    pub async fn new(device: Device) -> Result<Self> {
+        let tx = find_tx_characteristics(&device).await?;
+        Ok(Self {
+            device,
+            tx: Mutex::new(tx),
+            subscribed: Mutex::new(Vec::new()),
+        })
+    }
+}
+
+impl Transport for BleTransport {
+    fn write_uart<'a>(&'a self, bytes: &'a [u8]) -> BoxFuture<'a, Result> {
+        let tx = self.tx.lock().unwrap_or_else(|e| e.into_inner()).0.clone();
+        async move { Ok(tx.write_command(bytes).await?) }.boxed()
+    }
+
+    fn write_v2<'a>(&'a self, bytes: &'a [u8]) -> BoxFuture<'a, Result> {
+        let tx2 = self.tx.lock().unwrap_or_else(|e| e.into_inner()).1.clone();
+        async move { Ok(tx2.write_command(bytes).await?) }.boxed()
+    }
+
+    fn subscribe(&self) -> BoxFuture<'_, Result<PacketStream>> {
+        async move {
+            let (stream, charas) = subscribe_device(&self.device).await?;
+            self.subscribed
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .extend(charas);
+            Ok(stream)
+        }
+        .boxed()
+    }
+
+    fn disconnect(&self) -> BoxFuture<'_, Result> {
+        async move {
+            self.device.disconnect().await?;
+            let charas =
+                std::mem::take(&mut *self.subscribed.lock().unwrap_or_else(|e| e.into_inner()));
+            for ch in charas {
+                ch.unsubscribe().await?;
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn reconnect(&self) -> BoxFuture<'_, Result> {
+        async move {
+            // looking up the services connects the device again
+            let tx = find_tx_characteristics(&self.device).await?;
+            *self.tx.lock().unwrap_or_else(|e| e.into_inner()) = tx;
+            // the subscriptions ended with the connection that made them
+            self.subscribed
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .clear();
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn device(&self) -> Option<&Device> {
+        Some(&self.device)
+    }
+}
+
+async fn find_tx_characteristics(device: &Device) -> Result<(Characteristic, Characteristic)> {
+    let mut one = None;
+    let mut two = None;
+    let services = device.services().await?;
+    // what was searched, named, so a missing characteristic can be explained
+    let mut found = Vec::with_capacity(services.len());
+    'services: for service in services {
+        if one.is_some() && two.is_some() {
+            break;
+        }
+        found.push(gatt_names::describe_service(service.uuid()));
+        if service.uuid() == constants::UART_SERVICE_UUID {
+            for ch in service.characteristics() {
+                if ch.uuid() == constants::UART_RX_CHAR_UUID {
+                    one = Some(ch);
+                    continue 'services;
+                }
+                found.push(format!(
+                    "  {}",
+                    gatt_names::describe_characteristic(ch.uuid())
+                ));
+            }
+        }
+        if service.uuid() == constants::CHARACTERISTIC_SERVICE_V2 {
+            for ch in service.characteristics() {
+                if ch.uuid() == constants::CHARACTERISTIC_COMMAND {
+                    two = Some(ch);
+                    continue 'services;
+                }
+                found.push(format!(
+                    "  {}",
+                    gatt_names::describe_characteristic(ch.uuid())
+                ));
+            }
+        }
+    }
+    let missing = match (one, two) {
+        (Some(one), Some(two)) => return Ok((one, two)),
+        (Some(_), None) => constants::CHARACTERISTIC_COMMAND,
+        (None, _) => constants::UART_RX_CHAR_UUID,
+    };
+    log::debug!(
+        "{} is missing {}, found:\n{}",
+        device.address(),
+        gatt_names::describe_characteristic(missing),
+        found.join("\n")
+    );
+    Err(Error::CharacteristicMissing { uuid: missing })
+}
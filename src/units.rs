@@ -0,0 +1,67 @@
+//! Distances and energies with their unit in the type, so the units the ring
+//! reports only have to be known where its replies are parsed
+
+use serde::{Deserialize, Serialize};
+
+/// How many feet are in a meter
+pub const FEET_PER_METER: f32 = 3.28084;
+/// How many meters are in a mile
+pub const METERS_PER_MILE: f32 = 1609.344;
+/// How many kilojoules are in a kilocalorie
+pub const KILOJOULES_PER_KILOCALORIE: f32 = 4.184;
+
+/// A distance in meters
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct Meters(pub f32);
+
+impl Meters {
+    pub fn kilometers(self) -> f32 {
+        self.0 / 1000.0
+    }
+
+    pub fn feet(self) -> f32 {
+        self.0 * FEET_PER_METER
+    }
+
+    pub fn miles(self) -> f32 {
+        self.0 / METERS_PER_MILE
+    }
+}
+
+/// Energy in kilocalories, the "calories" shown on food labels and by the
+/// ring's app
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct Kilocalories(pub f32);
+
+impl Kilocalories {
+    pub fn kilojoules(self) -> f32 {
+        self.0 * KILOJOULES_PER_KILOCALORIE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[track_caller]
+    fn assert_close(found: f32, expected: f32) {
+        assert!(
+            (found - expected).abs() < 0.01,
+            "expected {expected} found {found}"
+        );
+    }
+
+    #[test]
+    fn distances() {
+        assert_close(Meters(1000.0).kilometers(), 1.0);
+        assert_close(Meters(1.0).feet(), 3.28);
+        assert_close(Meters(1609.344).miles(), 1.0);
+        assert_close(Meters(1609.344).feet(), 5280.0);
+    }
+
+    #[test]
+    fn energy() {
+        assert_close(Kilocalories(1.0).kilojoules(), 4.18);
+        assert_close(Kilocalories(250.0).kilojoules(), 1046.0);
+    }
+}
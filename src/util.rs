@@ -1,5 +1,27 @@
 use std::time::Duration;
 
+use time::{Date, OffsetDateTime, UtcOffset};
+
+use crate::{Error, PacketKind, Result};
+
+/// The current date in the local timezone, falling back to UTC if the
+/// local offset can't be determined
+pub(crate) fn local_today() -> Date {
+    OffsetDateTime::now_local()
+        .unwrap_or_else(|_| OffsetDateTime::now_utc())
+        .date()
+}
+
+/// The local timezone's offset, falling back to UTC if it can't be determined
+pub(crate) fn local_offset() -> UtcOffset {
+    UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC)
+}
+
+/// Every date from `start` through `end`, empty if `end` is before `start`
+pub fn date_range(start: Date, end: Date) -> impl Iterator<Item = Date> {
+    std::iter::successors(Some(start), |day| day.next_day()).take_while(move |day| *day <= end)
+}
+
 pub fn try_u16_from_le_slice(slice: &[u8]) -> Option<u16> {
     let mut bytes = [0u8; 2];
     bytes.copy_from_slice(slice.get(0..2)?);
@@ -13,10 +35,76 @@ pub fn try_u16_from_iter(slice: &mut dyn Iterator<Item = u8>) -> Option<u16> {
     Some(u16::from_le_bytes(bytes))
 }
 
+pub(crate) fn checksum(packet: &[u8]) -> u8 {
+    let sum: u32 = packet.iter().copied().map(|v| v as u32).sum();
+    let trunc = sum & 255;
+    trunc as u8
+}
+
+/// The CRC-16/MODBUS of `bytes`, which big data replies carry for their
+/// payload
+pub(crate) fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc = 0xffffu16;
+    for &b in bytes {
+        crc ^= u16::from(b);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xa001
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+/// Check that `packet` is at least `expected` bytes long before indexing into it
+pub(crate) fn check_len(packet: &[u8], expected: usize) -> Result {
+    if packet.len() < expected {
+        return Err(Error::PacketLength {
+            command: packet.first().copied(),
+            length: packet.len(),
+            expected,
+        });
+    }
+    Ok(())
+}
+
+/// Check that the last byte of `packet` is the checksum of the bytes before it
+pub(crate) fn verify_checksum(packet: &[u8]) -> Result {
+    let Some((&found, body)) = packet.split_last() else {
+        return Err(Error::parse(PacketKind::Uart, packet, "empty packet"));
+    };
+    let expected = checksum(body);
+    if found != expected {
+        return Err(Error::Checksum {
+            bytes: packet.to_vec(),
+            expected,
+        });
+    }
+    Ok(())
+}
+
+/// `bytes` without the zeros the ring pads the rest of a packet with
+pub(crate) fn trim_padding(bytes: &[u8]) -> &[u8] {
+    let len = bytes.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+    &bytes[..len]
+}
+
 pub trait DurationExt {
     fn minutes(value: u64) -> Duration;
     fn hours(value: u64) -> Duration;
     fn days(value: u64) -> Duration;
+    fn weeks(value: u64) -> Duration;
+    /// Fractional minutes rounded to the nearest second, like the 2.5 minute
+    /// stress interval some firmwares report. Negative minutes and NaN are
+    /// zero and too many saturate at `u64::MAX` seconds
+    fn minutes_f32(value: f32) -> Duration;
+    /// `hours`, `minutes` and `seconds` added together, saturating at
+    /// `u64::MAX` seconds instead of overflowing
+    fn hms(hours: u64, minutes: u64, seconds: u64) -> Duration;
+    /// Like `days` but an error instead of overflowing
+    fn checked_days(value: u64) -> Result<Duration>;
 }
 
 impl DurationExt for Duration {
@@ -31,4 +119,150 @@ impl DurationExt for Duration {
     fn days(value: u64) -> Duration {
         Duration::hours(value * 24)
     }
+
+    fn weeks(value: u64) -> Duration {
+        Duration::days(value * 7)
+    }
+
+    fn minutes_f32(value: f32) -> Duration {
+        // float to int casts saturate and turn NaN into 0
+        Duration::from_secs((f64::from(value) * 60.0).round() as u64)
+    }
+
+    fn hms(hours: u64, minutes: u64, seconds: u64) -> Duration {
+        let secs = hours
+            .saturating_mul(60 * 60)
+            .saturating_add(minutes.saturating_mul(60))
+            .saturating_add(seconds);
+        Duration::from_secs(secs)
+    }
+
+    fn checked_days(value: u64) -> Result<Duration> {
+        value
+            .checked_mul(24 * 60 * 60)
+            .map(Duration::from_secs)
+            .ok_or(Error::DurationOverflow { days: value })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::date;
+
+    #[test]
+    fn crc16_modbus() {
+        assert_eq!(crc16(b"123456789"), 0x4b37);
+        assert_eq!(crc16(&[]), 0xffff);
+    }
+
+    #[test]
+    fn whole_units() {
+        assert_eq!(Duration::minutes(5), Duration::from_secs(300));
+        assert_eq!(Duration::hours(2), Duration::from_secs(7_200));
+        assert_eq!(Duration::days(1), Duration::from_secs(86_400));
+        assert_eq!(Duration::weeks(2), Duration::days(14));
+        assert_eq!(Duration::hms(1, 30, 15), Duration::from_secs(5_415));
+    }
+
+    #[test]
+    fn minutes_f32_rounding() {
+        assert_eq!(Duration::minutes_f32(2.5), Duration::from_secs(150));
+        // 0.1 isn't exact as a float but is still 6 seconds
+        assert_eq!(Duration::minutes_f32(0.1), Duration::from_secs(6));
+        // half seconds round away from zero
+        assert_eq!(Duration::minutes_f32(0.125), Duration::from_secs(8));
+        assert_eq!(Duration::minutes_f32(0.0083), Duration::ZERO);
+        assert_eq!(Duration::minutes_f32(-3.0), Duration::ZERO);
+        assert_eq!(Duration::minutes_f32(f32::NAN), Duration::ZERO);
+        assert_eq!(
+            Duration::minutes_f32(f32::INFINITY),
+            Duration::from_secs(u64::MAX)
+        );
+        assert_eq!(
+            Duration::minutes_f32(f32::MAX),
+            Duration::from_secs(u64::MAX)
+        );
+    }
+
+    #[test]
+    fn hms_saturates() {
+        assert_eq!(Duration::hms(u64::MAX, 0, 0), Duration::from_secs(u64::MAX));
+        assert_eq!(
+            Duration::hms(0, u64::MAX / 60, u64::MAX),
+            Duration::from_secs(u64::MAX)
+        );
+        assert_eq!(Duration::hms(0, 0, u64::MAX), Duration::from_secs(u64::MAX));
+    }
+
+    #[test]
+    fn checked_days_overflow() {
+        let max = u64::MAX / 86_400;
+        assert_eq!(
+            Duration::checked_days(max).unwrap(),
+            Duration::from_secs(max * 86_400)
+        );
+        assert!(matches!(
+            Duration::checked_days(max + 1),
+            Err(Error::DurationOverflow { days }) if days == max + 1
+        ));
+        assert_eq!(Duration::checked_days(0).unwrap(), Duration::ZERO);
+        assert_eq!(Duration::checked_days(3).unwrap(), Duration::days(3));
+    }
+
+    #[test]
+    fn date_range_inclusive() {
+        let days: Vec<_> = date_range(date!(2024 - 11 - 18), date!(2024 - 11 - 20)).collect();
+        assert_eq!(
+            days,
+            [
+                date!(2024 - 11 - 18),
+                date!(2024 - 11 - 19),
+                date!(2024 - 11 - 20)
+            ]
+        );
+    }
+
+    #[test]
+    fn date_range_single_day() {
+        let days: Vec<_> = date_range(date!(2024 - 11 - 18), date!(2024 - 11 - 18)).collect();
+        assert_eq!(days, [date!(2024 - 11 - 18)]);
+    }
+
+    #[test]
+    fn date_range_end_before_start() {
+        assert_eq!(
+            date_range(date!(2024 - 11 - 20), date!(2024 - 11 - 18)).count(),
+            0
+        );
+    }
+
+    #[test]
+    fn date_range_month_boundary() {
+        let days: Vec<_> = date_range(date!(2024 - 02 - 28), date!(2024 - 03 - 01)).collect();
+        assert_eq!(
+            days,
+            [
+                date!(2024 - 02 - 28),
+                date!(2024 - 02 - 29),
+                date!(2024 - 03 - 01)
+            ]
+        );
+        let days: Vec<_> = date_range(date!(2023 - 02 - 28), date!(2023 - 03 - 01)).collect();
+        assert_eq!(days, [date!(2023 - 02 - 28), date!(2023 - 03 - 01)]);
+    }
+
+    #[test]
+    fn date_range_year_boundary() {
+        let days: Vec<_> = date_range(date!(2024 - 12 - 30), date!(2025 - 01 - 02)).collect();
+        assert_eq!(
+            days,
+            [
+                date!(2024 - 12 - 30),
+                date!(2024 - 12 - 31),
+                date!(2025 - 01 - 01),
+                date!(2025 - 01 - 02)
+            ]
+        );
+    }
 }
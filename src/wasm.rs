@@ -0,0 +1,55 @@
+//! Decoding captured packets from javascript, only available with the `wasm`
+//! feature
+//!
+//! Build with `wasm-pack build --no-default-features --features wasm`
+
+use js_sys::Uint8Array;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    constants,
+    incoming_messages::{framer::PacketFramer, CommandReply, PacketParser, RawPacket},
+    PacketKind,
+};
+
+/// Parse `packets`, each the bytes of one notification from the ring, into the
+/// replies they make up
+///
+/// Returns an array of the serialized replies, packets that fail to parse are
+/// skipped
+#[wasm_bindgen(js_name = parsePackets)]
+pub fn parse_packets(packets: Vec<Uint8Array>) -> Result<JsValue, JsValue> {
+    let replies = parse(packets.iter().map(Uint8Array::to_vec));
+    Ok(serde_wasm_bindgen::to_value(&replies)?)
+}
+
+/// Parse notifications that weren't recorded with the characteristic they
+/// arrived on, big data replies are told apart by their command byte
+pub fn parse(packets: impl IntoIterator<Item = Vec<u8>>) -> Vec<CommandReply> {
+    let mut parser = PacketParser::default();
+    let mut framer = PacketFramer::default();
+    let mut replies = Vec::new();
+    for bytes in packets {
+        let big_data = matches!(
+            parser.progress(),
+            Some(progress) if matches!(progress.kind, PacketKind::Sleep | PacketKind::Oxygen)
+        );
+        let frames = if big_data || bytes.first() == Some(&constants::CMD_BIG_DATA_V2) {
+            vec![RawPacket::V2(bytes)]
+        } else {
+            framer
+                .push(&bytes)
+                .into_iter()
+                .map(RawPacket::Uart)
+                .collect()
+        };
+        for packet in frames {
+            match parser.handle_packet(&packet) {
+                Ok(Some(reply)) => replies.push(reply),
+                Ok(None) => {}
+                Err(e) => log::debug!("skipping unparsable packet: {e}"),
+            }
+        }
+    }
+    replies
+}
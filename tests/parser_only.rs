@@ -0,0 +1,98 @@
+//! The parsing and command encoding that has to keep working without the `ble`
+//! feature, run with `cargo test --no-default-features`
+
+use cole_mine::{
+    client::Command,
+    incoming_messages::{ClientReceiver, CommandReply, PacketParser, RawPacket, SyncProgress},
+    PacketKind,
+};
+
+fn packet(bytes: &[u8]) -> Vec<u8> {
+    let mut ret = bytes.to_vec();
+    ret.resize(16, 0);
+    ret[15] = ret[..15].iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+    ret
+}
+
+#[test]
+fn commands_encode_and_decode() {
+    for command in [
+        Command::BatteryInfo,
+        Command::ReadHeartRate {
+            timestamp: 1_732_060_800,
+        },
+        Command::SetPhoneName("lode".to_string()),
+    ] {
+        let bytes: [u8; 16] = command.clone().try_into().unwrap();
+        assert_eq!(Command::try_from(&bytes).unwrap(), command);
+    }
+}
+
+#[test]
+fn parser_assembles_multi_packet_replies() {
+    let timestamp = 1_732_060_800u32.to_le_bytes();
+    let mut parser = PacketParser::default();
+    let reply = parser
+        .handle_packet(&RawPacket::Uart(packet(&[3, 80, 1])))
+        .unwrap();
+    assert_eq!(
+        reply,
+        Some(CommandReply::BatteryInfo {
+            level: 80,
+            charging: true
+        })
+    );
+    for bytes in [
+        &[21, 0, 3, 5][..],
+        &[
+            21,
+            1,
+            timestamp[0],
+            timestamp[1],
+            timestamp[2],
+            timestamp[3],
+            60,
+        ],
+    ] {
+        assert_eq!(
+            parser
+                .handle_packet(&RawPacket::Uart(packet(bytes)))
+                .unwrap(),
+            None
+        );
+    }
+    assert_eq!(
+        parser.progress(),
+        Some(SyncProgress {
+            kind: PacketKind::HeartRate,
+            received: 1,
+            expected: 2
+        })
+    );
+    let reply = parser
+        .handle_packet(&RawPacket::Uart(packet(&[21, 2, 61])))
+        .unwrap();
+    let Some(CommandReply::HeartRate(hr)) = reply else {
+        panic!("expected heart rate, found {reply:?}");
+    };
+    assert_eq!(hr.rates[0], 60);
+    assert_eq!(parser.progress(), None);
+}
+
+#[tokio::test]
+async fn receiver_reads_captured_packets() {
+    let packets = [
+        RawPacket::Uart(packet(&[3, 80])),
+        RawPacket::Uart(packet(&[3, 81])),
+    ];
+    let mut rx = ClientReceiver::from_stream(Box::pin(futures::stream::iter(packets)));
+    assert!(matches!(
+        rx.next().await,
+        Some(CommandReply::BatteryInfo { level: 80, .. })
+    ));
+    assert!(matches!(
+        rx.next().await,
+        Some(CommandReply::BatteryInfo { level: 81, .. })
+    ));
+    assert_eq!(rx.next().await, None);
+}
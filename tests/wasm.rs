@@ -0,0 +1,52 @@
+//! Run with `wasm-pack test --node -- --no-default-features --features wasm`
+#![cfg(all(target_arch = "wasm32", feature = "wasm"))]
+
+use cole_mine::incoming_messages::CommandReply;
+use js_sys::Uint8Array;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+/// A full day of heart rate data as captured from a ring, with a battery reply
+/// in the middle
+const PACKETS: [&[u8; 16]; 25] = [
+    b"\x15\x00\x18\x05\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x002",
+    b"\x15\x01\x80\xad\xb6f\x00\x00\x00\x00\x00\x00\x00\x00\x00_",
+    b"\x15\x02\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x17",
+    b"\x15\x03\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x18",
+    b"\x15\x04\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x19",
+    b"\x15\x05\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x1a",
+    b"\x15\x06\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x1b",
+    b"\x15\x07\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x1c",
+    b"\x15\x08\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x1d",
+    b"\x15\t\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x1e",
+    b"\x15\n\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x1f",
+    b"\x15\x0b\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00 ",
+    b"\x03P\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00S",
+    b"\x15\x0c\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00!",
+    b"\x15\r\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\"",
+    b"\x15\x0e\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00#",
+    b"\x15\x0f\x00\x00Y\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00}",
+    b"\x15\x10\x00k\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x90",
+    b"\x15\x11`\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00k\xf1",
+    b"\x15\x12\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00'",
+    b"\x15\x13\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00P\x00\x00x",
+    b"\x15\x14\x00\x00\x00\x00\x00\x00\x00\x00\x00F\x00\x00\x00o",
+    b"\x15\x15\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00*",
+    b"\x15\x16\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00+",
+    b"\x15\x17\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00,",
+];
+
+#[wasm_bindgen_test]
+fn parses_heart_rate_sync() {
+    let packets = PACKETS.iter().map(|p| Uint8Array::from(&p[..])).collect();
+    let replies = cole_mine::wasm::parse_packets(packets).unwrap();
+    let replies: Vec<CommandReply> = serde_wasm_bindgen::from_value(replies).unwrap();
+    let [CommandReply::BatteryInfo { level, charging }, CommandReply::HeartRate(hr)] =
+        replies.as_slice()
+    else {
+        panic!("unexpected replies {replies:?}");
+    };
+    assert_eq!((*level, *charging), (80, false));
+    assert_eq!(hr.range, 5);
+    assert!(hr.gaps.is_empty());
+    assert_eq!(hr.rates.iter().filter(|rate| **rate != 0).count(), 6);
+}